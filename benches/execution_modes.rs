@@ -0,0 +1,73 @@
+//! Compares the throughput of the two M6502 execution modes, by running Klaus Dormann's functional test.
+
+use std::fs;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+use aemula::chips::m6502::{Bus, ExecutionMode, M6502, M6502Options, M6502Variant};
+
+struct Ram {
+    data: Vec<u8>,
+}
+
+impl Bus for Ram {
+    fn read(&mut self, address: u16) -> u8 {
+        self.data[address as usize]
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        self.data[address as usize] = value;
+    }
+
+    fn peek(&self, address: u16) -> u8 {
+        self.data[address as usize]
+    }
+}
+
+/// Runs the functional test until it traps, and returns the number of cycles that it took.
+fn run_functional_test(program: &[u8], variant: M6502Variant, mode: ExecutionMode) -> u64 {
+    let mut ram = Ram { data: program.to_vec() };
+
+    let mut cpu = M6502::new_with_options(M6502Options {
+        variant,
+        ..Default::default()
+    });
+    cpu.set_res(false);
+    cpu.set_res(true);
+
+    let mut cycles = 0;
+    loop {
+        let pc = cpu.pc.to_u16();
+        cycles += match mode {
+            ExecutionMode::Cycle => cpu.step_instruction(&mut ram),
+            ExecutionMode::Instruction => cpu.execute_instruction(&mut ram),
+        } as u64;
+        if cpu.pc.to_u16() == pc {
+            assert_eq!(0x3399, pc, "Functional test failed");
+            return cycles;
+        }
+    }
+}
+
+fn execution_modes(c: &mut Criterion) {
+    let mut program = fs::read("test_assets/chips/mos6502/6502_functional_test.bin").unwrap();
+
+    // Patch the test start address into the RESET vector.
+    program[0xFFFC] = 0x00;
+    program[0xFFFD] = 0x04;
+
+    for variant in [M6502Variant::Nmos6502, M6502Variant::Cmos65C02] {
+        let mut group = c.benchmark_group(format!("{:?}", variant));
+        group.sample_size(10);
+        group.throughput(Throughput::Elements(run_functional_test(&program, variant, ExecutionMode::Cycle)));
+
+        for mode in [ExecutionMode::Cycle, ExecutionMode::Instruction] {
+            group.bench_function(format!("{:?}", mode), |b| b.iter(|| run_functional_test(&program, variant, mode)));
+        }
+
+        group.finish();
+    }
+}
+
+criterion_group!(benches, execution_modes);
+criterion_main!(benches);
@@ -8,6 +8,7 @@ enum AddressingMode {
     None,
     Accumulator,
     Immediate,
+    Relative,
     ZeroPage,
     ZeroPageX,
     ZeroPageY,
@@ -19,6 +20,13 @@ enum AddressingMode {
     Indirect,
     JSR,
     Invalid,
+
+    // 65C02 only
+    ZeroPageIndirect,
+    AbsoluteIndexedIndirect,
+    ZeroPageRelative,
+    SingleCycle,
+    AbsoluteEightCycles,
 }
 
 impl AddressingMode {
@@ -27,6 +35,7 @@ impl AddressingMode {
             AddressingMode::None => "",
             AddressingMode::Accumulator => "",
             AddressingMode::Immediate => "#",
+            AddressingMode::Relative => "rel",
             AddressingMode::ZeroPage => "zp",
             AddressingMode::ZeroPageX => "zp,X",
             AddressingMode::ZeroPageY => "zp,Y",
@@ -38,6 +47,47 @@ impl AddressingMode {
             AddressingMode::Indirect => "ind",
             AddressingMode::JSR => "",
             AddressingMode::Invalid => "invalid",
+            AddressingMode::ZeroPageIndirect => "(zp)",
+            AddressingMode::AbsoluteIndexedIndirect => "(abs,X)",
+            AddressingMode::ZeroPageRelative => "zp,rel",
+            AddressingMode::SingleCycle => "",
+            AddressingMode::AbsoluteEightCycles => "abs",
+        }
+    }
+
+    /// Name of the matching variant of the public `m6502::AddressingMode`,
+    /// which only describes how the operand is written, not how it is timed.
+    fn public_name(&self) -> &'static str {
+        match self {
+            AddressingMode::None | AddressingMode::SingleCycle | AddressingMode::Invalid => "Implied",
+            AddressingMode::Accumulator => "Accumulator",
+            AddressingMode::Immediate => "Immediate",
+            AddressingMode::Relative => "Relative",
+            AddressingMode::ZeroPage => "ZeroPage",
+            AddressingMode::ZeroPageX => "ZeroPageX",
+            AddressingMode::ZeroPageY => "ZeroPageY",
+            AddressingMode::Absolute | AddressingMode::JSR | AddressingMode::AbsoluteEightCycles => "Absolute",
+            AddressingMode::AbsoluteX => "AbsoluteX",
+            AddressingMode::AbsoluteY => "AbsoluteY",
+            AddressingMode::IndexedIndirectX => "IndexedIndirectX",
+            AddressingMode::IndirectIndexedY => "IndirectIndexedY",
+            AddressingMode::Indirect => "Indirect",
+            AddressingMode::ZeroPageIndirect => "ZeroPageIndirect",
+            AddressingMode::AbsoluteIndexedIndirect => "AbsoluteIndexedIndirect",
+            AddressingMode::ZeroPageRelative => "ZeroPageRelative",
+        }
+    }
+
+    /// Number of bytes taken by the opcode and its operand.
+    fn length(&self) -> u8 {
+        match self {
+            AddressingMode::None | AddressingMode::Accumulator | AddressingMode::SingleCycle | AddressingMode::Invalid => 1,
+            AddressingMode::Immediate | AddressingMode::Relative |
+            AddressingMode::ZeroPage | AddressingMode::ZeroPageX | AddressingMode::ZeroPageY |
+            AddressingMode::IndexedIndirectX | AddressingMode::IndirectIndexedY | AddressingMode::ZeroPageIndirect => 2,
+            AddressingMode::Absolute | AddressingMode::AbsoluteX | AddressingMode::AbsoluteY |
+            AddressingMode::Indirect | AddressingMode::JSR | AddressingMode::AbsoluteIndexedIndirect |
+            AddressingMode::ZeroPageRelative | AddressingMode::AbsoluteEightCycles => 3,
         }
     }
 }
@@ -52,6 +102,25 @@ enum MemoryAccess {
 
 struct Instruction(u8, &'static str, AddressingMode, MemoryAccess);
 
+impl Instruction {
+    /// Undocumented NMOS opcodes, and the unused 65C02 opcodes that act as NOPs.
+    fn is_official(&self) -> bool {
+        match self.1 {
+            "NOP" => self.0 == 0xEA,
+            "SBC" => self.0 != 0xEB,
+            "ANC" | "ANE" | "ARR" | "ASR" | "DCP" | "ISB" | "JAM" | "LAS" | "LAX" | "LXA" |
+            "RLA" | "RRA" | "SAX" | "SBX" | "SHA" | "SHS" | "SHX" | "SHY" | "SLO" | "SRE" => false,
+            _ => true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Variant {
+    Nmos,
+    Cmos,
+}
+
 static INSTRUCTIONS: [Instruction; 256] = [
     // Interrupt, jump, subroutine
     Instruction(0x00, "BRK", AddressingMode::None,             MemoryAccess::None),
@@ -63,7 +132,7 @@ static INSTRUCTIONS: [Instruction; 256] = [
 
     // Flags
     Instruction(0x18, "CLC", AddressingMode::None,             MemoryAccess::None),
-    Instruction(0x38, "SLC", AddressingMode::None,             MemoryAccess::None),
+    Instruction(0x38, "SEC", AddressingMode::None,             MemoryAccess::None),
     Instruction(0x58, "CLI", AddressingMode::None,             MemoryAccess::None),
     Instruction(0x78, "SEI", AddressingMode::None,             MemoryAccess::None),
     Instruction(0xB8, "CLV", AddressingMode::None,             MemoryAccess::None),
@@ -71,14 +140,14 @@ static INSTRUCTIONS: [Instruction; 256] = [
     Instruction(0xF8, "SED", AddressingMode::None,             MemoryAccess::None),
 
     // Branch
-    Instruction(0x10, "BPL", AddressingMode::Immediate,        MemoryAccess::None),
-    Instruction(0x30, "BMI", AddressingMode::Immediate,        MemoryAccess::None),
-    Instruction(0x50, "BVC", AddressingMode::Immediate,        MemoryAccess::None),
-    Instruction(0x70, "BVS", AddressingMode::Immediate,        MemoryAccess::None),
-    Instruction(0x90, "BCC", AddressingMode::Immediate,        MemoryAccess::None),
-    Instruction(0xB0, "BCS", AddressingMode::Immediate,        MemoryAccess::None),
-    Instruction(0xD0, "BNE", AddressingMode::Immediate,        MemoryAccess::None),
-    Instruction(0xF0, "BEQ", AddressingMode::Immediate,        MemoryAccess::None),
+    Instruction(0x10, "BPL", AddressingMode::Relative,         MemoryAccess::None),
+    Instruction(0x30, "BMI", AddressingMode::Relative,         MemoryAccess::None),
+    Instruction(0x50, "BVC", AddressingMode::Relative,         MemoryAccess::None),
+    Instruction(0x70, "BVS", AddressingMode::Relative,         MemoryAccess::None),
+    Instruction(0x90, "BCC", AddressingMode::Relative,         MemoryAccess::None),
+    Instruction(0xB0, "BCS", AddressingMode::Relative,         MemoryAccess::None),
+    Instruction(0xD0, "BNE", AddressingMode::Relative,         MemoryAccess::None),
+    Instruction(0xF0, "BEQ", AddressingMode::Relative,         MemoryAccess::None),
 
     // Stack
     Instruction(0x08, "PHP", AddressingMode::None,             MemoryAccess::Write),
@@ -406,14 +475,354 @@ static INSTRUCTIONS: [Instruction; 256] = [
     Instruction(0x94, "STY", AddressingMode::ZeroPageX,        MemoryAccess::Write),    
 ];
 
+/// Rockwell / WDC 65C02. The NMOS undocumented opcodes are gone, and every unused opcode is a NOP.
+static CMOS_INSTRUCTIONS: [Instruction; 256] = [
+    // Interrupt, jump, subroutine
+    Instruction(0x00, "BRK", AddressingMode::None,                     MemoryAccess::None),
+    Instruction(0x20, "JSR", AddressingMode::JSR,                      MemoryAccess::None),
+    Instruction(0x40, "RTI", AddressingMode::None,                     MemoryAccess::None),
+    Instruction(0x60, "RTS", AddressingMode::None,                     MemoryAccess::None),
+    Instruction(0x4C, "JMP", AddressingMode::Absolute,                 MemoryAccess::None),
+    Instruction(0x6C, "JMP", AddressingMode::Indirect,                 MemoryAccess::None),
+    Instruction(0x7C, "JMP", AddressingMode::AbsoluteIndexedIndirect,  MemoryAccess::None),
+
+    // Flags
+    Instruction(0x18, "CLC", AddressingMode::None,                     MemoryAccess::None),
+    Instruction(0x38, "SEC", AddressingMode::None,                     MemoryAccess::None),
+    Instruction(0x58, "CLI", AddressingMode::None,                     MemoryAccess::None),
+    Instruction(0x78, "SEI", AddressingMode::None,                     MemoryAccess::None),
+    Instruction(0xB8, "CLV", AddressingMode::None,                     MemoryAccess::None),
+    Instruction(0xD8, "CLD", AddressingMode::None,                     MemoryAccess::None),
+    Instruction(0xF8, "SED", AddressingMode::None,                     MemoryAccess::None),
+
+    // Branch
+    Instruction(0x10, "BPL", AddressingMode::Relative,                 MemoryAccess::None),
+    Instruction(0x30, "BMI", AddressingMode::Relative,                 MemoryAccess::None),
+    Instruction(0x50, "BVC", AddressingMode::Relative,                 MemoryAccess::None),
+    Instruction(0x70, "BVS", AddressingMode::Relative,                 MemoryAccess::None),
+    Instruction(0x80, "BRA", AddressingMode::Relative,                 MemoryAccess::None),
+    Instruction(0x90, "BCC", AddressingMode::Relative,                 MemoryAccess::None),
+    Instruction(0xB0, "BCS", AddressingMode::Relative,                 MemoryAccess::None),
+    Instruction(0xD0, "BNE", AddressingMode::Relative,                 MemoryAccess::None),
+    Instruction(0xF0, "BEQ", AddressingMode::Relative,                 MemoryAccess::None),
+
+    // Stack
+    Instruction(0x08, "PHP", AddressingMode::None,                     MemoryAccess::Write),
+    Instruction(0x28, "PLP", AddressingMode::None,                     MemoryAccess::None),
+    Instruction(0x48, "PHA", AddressingMode::None,                     MemoryAccess::Write),
+    Instruction(0x68, "PLA", AddressingMode::None,                     MemoryAccess::None),
+    Instruction(0x5A, "PHY", AddressingMode::None,                     MemoryAccess::Write),
+    Instruction(0x7A, "PLY", AddressingMode::None,                     MemoryAccess::None),
+    Instruction(0xDA, "PHX", AddressingMode::None,                     MemoryAccess::Write),
+    Instruction(0xFA, "PLX", AddressingMode::None,                     MemoryAccess::None),
+
+    // Implied arithmetic
+    Instruction(0x88, "DEY", AddressingMode::None,                     MemoryAccess::None),
+    Instruction(0xCA, "DEX", AddressingMode::None,                     MemoryAccess::None),
+    Instruction(0xC8, "INY", AddressingMode::None,                     MemoryAccess::None),
+    Instruction(0xE8, "INX", AddressingMode::None,                     MemoryAccess::None),
+
+    // Transfer
+    Instruction(0x8A, "TXA", AddressingMode::None,                     MemoryAccess::None),
+    Instruction(0x9A, "TXS", AddressingMode::None,                     MemoryAccess::None),
+    Instruction(0x98, "TYA", AddressingMode::None,                     MemoryAccess::None),
+    Instruction(0xA8, "TAY", AddressingMode::None,                     MemoryAccess::None),
+    Instruction(0xAA, "TAX", AddressingMode::None,                     MemoryAccess::None),
+    Instruction(0xBA, "TSX", AddressingMode::None,                     MemoryAccess::None),
+
+    // ADC
+    Instruction(0x61, "ADC", AddressingMode::IndexedIndirectX,         MemoryAccess::Read),
+    Instruction(0x65, "ADC", AddressingMode::ZeroPage,                 MemoryAccess::Read),
+    Instruction(0x69, "ADC", AddressingMode::Immediate,                MemoryAccess::None),
+    Instruction(0x6D, "ADC", AddressingMode::Absolute,                 MemoryAccess::Read),
+    Instruction(0x71, "ADC", AddressingMode::IndirectIndexedY,         MemoryAccess::Read),
+    Instruction(0x72, "ADC", AddressingMode::ZeroPageIndirect,         MemoryAccess::Read),
+    Instruction(0x75, "ADC", AddressingMode::ZeroPageX,                MemoryAccess::Read),
+    Instruction(0x79, "ADC", AddressingMode::AbsoluteY,                MemoryAccess::Read),
+    Instruction(0x7D, "ADC", AddressingMode::AbsoluteX,                MemoryAccess::Read),
+
+    // AND
+    Instruction(0x21, "AND", AddressingMode::IndexedIndirectX,         MemoryAccess::Read),
+    Instruction(0x25, "AND", AddressingMode::ZeroPage,                 MemoryAccess::Read),
+    Instruction(0x29, "AND", AddressingMode::Immediate,                MemoryAccess::None),
+    Instruction(0x2D, "AND", AddressingMode::Absolute,                 MemoryAccess::Read),
+    Instruction(0x31, "AND", AddressingMode::IndirectIndexedY,         MemoryAccess::Read),
+    Instruction(0x32, "AND", AddressingMode::ZeroPageIndirect,         MemoryAccess::Read),
+    Instruction(0x35, "AND", AddressingMode::ZeroPageX,                MemoryAccess::Read),
+    Instruction(0x39, "AND", AddressingMode::AbsoluteY,                MemoryAccess::Read),
+    Instruction(0x3D, "AND", AddressingMode::AbsoluteX,                MemoryAccess::Read),
+
+    // ASL
+    Instruction(0x06, "ASL", AddressingMode::ZeroPage,                 MemoryAccess::ReadWrite),
+    Instruction(0x0A, "ASL", AddressingMode::Accumulator,              MemoryAccess::None),
+    Instruction(0x0E, "ASL", AddressingMode::Absolute,                 MemoryAccess::ReadWrite),
+    Instruction(0x16, "ASL", AddressingMode::ZeroPageX,                MemoryAccess::ReadWrite),
+    Instruction(0x1E, "ASL", AddressingMode::AbsoluteX,                MemoryAccess::ReadWrite),
+
+    // BBR (Rockwell / WDC)
+    Instruction(0x0F, "BBR0", AddressingMode::ZeroPageRelative,         MemoryAccess::None),
+    Instruction(0x1F, "BBR1", AddressingMode::ZeroPageRelative,         MemoryAccess::None),
+    Instruction(0x2F, "BBR2", AddressingMode::ZeroPageRelative,         MemoryAccess::None),
+    Instruction(0x3F, "BBR3", AddressingMode::ZeroPageRelative,         MemoryAccess::None),
+    Instruction(0x4F, "BBR4", AddressingMode::ZeroPageRelative,         MemoryAccess::None),
+    Instruction(0x5F, "BBR5", AddressingMode::ZeroPageRelative,         MemoryAccess::None),
+    Instruction(0x6F, "BBR6", AddressingMode::ZeroPageRelative,         MemoryAccess::None),
+    Instruction(0x7F, "BBR7", AddressingMode::ZeroPageRelative,         MemoryAccess::None),
+
+    // BBS (Rockwell / WDC)
+    Instruction(0x8F, "BBS0", AddressingMode::ZeroPageRelative,         MemoryAccess::None),
+    Instruction(0x9F, "BBS1", AddressingMode::ZeroPageRelative,         MemoryAccess::None),
+    Instruction(0xAF, "BBS2", AddressingMode::ZeroPageRelative,         MemoryAccess::None),
+    Instruction(0xBF, "BBS3", AddressingMode::ZeroPageRelative,         MemoryAccess::None),
+    Instruction(0xCF, "BBS4", AddressingMode::ZeroPageRelative,         MemoryAccess::None),
+    Instruction(0xDF, "BBS5", AddressingMode::ZeroPageRelative,         MemoryAccess::None),
+    Instruction(0xEF, "BBS6", AddressingMode::ZeroPageRelative,         MemoryAccess::None),
+    Instruction(0xFF, "BBS7", AddressingMode::ZeroPageRelative,         MemoryAccess::None),
+
+    // BIT
+    Instruction(0x24, "BIT", AddressingMode::ZeroPage,                 MemoryAccess::Read),
+    Instruction(0x2C, "BIT", AddressingMode::Absolute,                 MemoryAccess::Read),
+    Instruction(0x34, "BIT", AddressingMode::ZeroPageX,                MemoryAccess::Read),
+    Instruction(0x3C, "BIT", AddressingMode::AbsoluteX,                MemoryAccess::Read),
+    Instruction(0x89, "BIT", AddressingMode::Immediate,                MemoryAccess::None),
+
+    // CMP
+    Instruction(0xC1, "CMP", AddressingMode::IndexedIndirectX,         MemoryAccess::Read),
+    Instruction(0xC5, "CMP", AddressingMode::ZeroPage,                 MemoryAccess::Read),
+    Instruction(0xC9, "CMP", AddressingMode::Immediate,                MemoryAccess::None),
+    Instruction(0xCD, "CMP", AddressingMode::Absolute,                 MemoryAccess::Read),
+    Instruction(0xD1, "CMP", AddressingMode::IndirectIndexedY,         MemoryAccess::Read),
+    Instruction(0xD2, "CMP", AddressingMode::ZeroPageIndirect,         MemoryAccess::Read),
+    Instruction(0xD5, "CMP", AddressingMode::ZeroPageX,                MemoryAccess::Read),
+    Instruction(0xD9, "CMP", AddressingMode::AbsoluteY,                MemoryAccess::Read),
+    Instruction(0xDD, "CMP", AddressingMode::AbsoluteX,                MemoryAccess::Read),
+
+    // CPX
+    Instruction(0xE0, "CPX", AddressingMode::Immediate,                MemoryAccess::None),
+    Instruction(0xE4, "CPX", AddressingMode::ZeroPage,                 MemoryAccess::Read),
+    Instruction(0xEC, "CPX", AddressingMode::Absolute,                 MemoryAccess::Read),
+
+    // CPY
+    Instruction(0xC0, "CPY", AddressingMode::Immediate,                MemoryAccess::None),
+    Instruction(0xC4, "CPY", AddressingMode::ZeroPage,                 MemoryAccess::Read),
+    Instruction(0xCC, "CPY", AddressingMode::Absolute,                 MemoryAccess::Read),
+
+    // DEC
+    Instruction(0x3A, "DEC", AddressingMode::Accumulator,              MemoryAccess::None),
+    Instruction(0xC6, "DEC", AddressingMode::ZeroPage,                 MemoryAccess::ReadWrite),
+    Instruction(0xCE, "DEC", AddressingMode::Absolute,                 MemoryAccess::ReadWrite),
+    Instruction(0xD6, "DEC", AddressingMode::ZeroPageX,                MemoryAccess::ReadWrite),
+    Instruction(0xDE, "DEC", AddressingMode::AbsoluteX,                MemoryAccess::ReadWrite),
+
+    // EOR
+    Instruction(0x41, "EOR", AddressingMode::IndexedIndirectX,         MemoryAccess::Read),
+    Instruction(0x45, "EOR", AddressingMode::ZeroPage,                 MemoryAccess::Read),
+    Instruction(0x49, "EOR", AddressingMode::Immediate,                MemoryAccess::None),
+    Instruction(0x4D, "EOR", AddressingMode::Absolute,                 MemoryAccess::Read),
+    Instruction(0x51, "EOR", AddressingMode::IndirectIndexedY,         MemoryAccess::Read),
+    Instruction(0x52, "EOR", AddressingMode::ZeroPageIndirect,         MemoryAccess::Read),
+    Instruction(0x55, "EOR", AddressingMode::ZeroPageX,                MemoryAccess::Read),
+    Instruction(0x59, "EOR", AddressingMode::AbsoluteY,                MemoryAccess::Read),
+    Instruction(0x5D, "EOR", AddressingMode::AbsoluteX,                MemoryAccess::Read),
+
+    // INC
+    Instruction(0x1A, "INC", AddressingMode::Accumulator,              MemoryAccess::None),
+    Instruction(0xE6, "INC", AddressingMode::ZeroPage,                 MemoryAccess::ReadWrite),
+    Instruction(0xEE, "INC", AddressingMode::Absolute,                 MemoryAccess::ReadWrite),
+    Instruction(0xF6, "INC", AddressingMode::ZeroPageX,                MemoryAccess::ReadWrite),
+    Instruction(0xFE, "INC", AddressingMode::AbsoluteX,                MemoryAccess::ReadWrite),
+
+    // LDA
+    Instruction(0xA1, "LDA", AddressingMode::IndexedIndirectX,         MemoryAccess::Read),
+    Instruction(0xA5, "LDA", AddressingMode::ZeroPage,                 MemoryAccess::Read),
+    Instruction(0xA9, "LDA", AddressingMode::Immediate,                MemoryAccess::None),
+    Instruction(0xAD, "LDA", AddressingMode::Absolute,                 MemoryAccess::Read),
+    Instruction(0xB1, "LDA", AddressingMode::IndirectIndexedY,         MemoryAccess::Read),
+    Instruction(0xB2, "LDA", AddressingMode::ZeroPageIndirect,         MemoryAccess::Read),
+    Instruction(0xB5, "LDA", AddressingMode::ZeroPageX,                MemoryAccess::Read),
+    Instruction(0xB9, "LDA", AddressingMode::AbsoluteY,                MemoryAccess::Read),
+    Instruction(0xBD, "LDA", AddressingMode::AbsoluteX,                MemoryAccess::Read),
+
+    // LDX
+    Instruction(0xA2, "LDX", AddressingMode::Immediate,                MemoryAccess::None),
+    Instruction(0xA6, "LDX", AddressingMode::ZeroPage,                 MemoryAccess::Read),
+    Instruction(0xAE, "LDX", AddressingMode::Absolute,                 MemoryAccess::Read),
+    Instruction(0xB6, "LDX", AddressingMode::ZeroPageY,                MemoryAccess::Read),
+    Instruction(0xBE, "LDX", AddressingMode::AbsoluteY,                MemoryAccess::Read),
+
+    // LDY
+    Instruction(0xA0, "LDY", AddressingMode::Immediate,                MemoryAccess::None),
+    Instruction(0xA4, "LDY", AddressingMode::ZeroPage,                 MemoryAccess::Read),
+    Instruction(0xAC, "LDY", AddressingMode::Absolute,                 MemoryAccess::Read),
+    Instruction(0xB4, "LDY", AddressingMode::ZeroPageX,                MemoryAccess::Read),
+    Instruction(0xBC, "LDY", AddressingMode::AbsoluteX,                MemoryAccess::Read),
+
+    // LSR
+    Instruction(0x46, "LSR", AddressingMode::ZeroPage,                 MemoryAccess::ReadWrite),
+    Instruction(0x4A, "LSR", AddressingMode::Accumulator,              MemoryAccess::None),
+    Instruction(0x4E, "LSR", AddressingMode::Absolute,                 MemoryAccess::ReadWrite),
+    Instruction(0x56, "LSR", AddressingMode::ZeroPageX,                MemoryAccess::ReadWrite),
+    Instruction(0x5E, "LSR", AddressingMode::AbsoluteX,                MemoryAccess::ReadWrite),
+
+    // NOP (unused opcodes are defined as NOPs of various lengths)
+    Instruction(0x02, "NOP", AddressingMode::Immediate,                MemoryAccess::None),
+    Instruction(0x03, "NOP", AddressingMode::SingleCycle,              MemoryAccess::None),
+    Instruction(0x0B, "NOP", AddressingMode::SingleCycle,              MemoryAccess::None),
+    Instruction(0x13, "NOP", AddressingMode::SingleCycle,              MemoryAccess::None),
+    Instruction(0x1B, "NOP", AddressingMode::SingleCycle,              MemoryAccess::None),
+    Instruction(0x22, "NOP", AddressingMode::Immediate,                MemoryAccess::None),
+    Instruction(0x23, "NOP", AddressingMode::SingleCycle,              MemoryAccess::None),
+    Instruction(0x2B, "NOP", AddressingMode::SingleCycle,              MemoryAccess::None),
+    Instruction(0x33, "NOP", AddressingMode::SingleCycle,              MemoryAccess::None),
+    Instruction(0x3B, "NOP", AddressingMode::SingleCycle,              MemoryAccess::None),
+    Instruction(0x42, "NOP", AddressingMode::Immediate,                MemoryAccess::None),
+    Instruction(0x43, "NOP", AddressingMode::SingleCycle,              MemoryAccess::None),
+    Instruction(0x44, "NOP", AddressingMode::ZeroPage,                 MemoryAccess::Read),
+    Instruction(0x4B, "NOP", AddressingMode::SingleCycle,              MemoryAccess::None),
+    Instruction(0x53, "NOP", AddressingMode::SingleCycle,              MemoryAccess::None),
+    Instruction(0x54, "NOP", AddressingMode::ZeroPageX,                MemoryAccess::Read),
+    Instruction(0x5B, "NOP", AddressingMode::SingleCycle,              MemoryAccess::None),
+    Instruction(0x5C, "NOP", AddressingMode::AbsoluteEightCycles,      MemoryAccess::None),
+    Instruction(0x62, "NOP", AddressingMode::Immediate,                MemoryAccess::None),
+    Instruction(0x63, "NOP", AddressingMode::SingleCycle,              MemoryAccess::None),
+    Instruction(0x6B, "NOP", AddressingMode::SingleCycle,              MemoryAccess::None),
+    Instruction(0x73, "NOP", AddressingMode::SingleCycle,              MemoryAccess::None),
+    Instruction(0x7B, "NOP", AddressingMode::SingleCycle,              MemoryAccess::None),
+    Instruction(0x82, "NOP", AddressingMode::Immediate,                MemoryAccess::None),
+    Instruction(0x83, "NOP", AddressingMode::SingleCycle,              MemoryAccess::None),
+    Instruction(0x8B, "NOP", AddressingMode::SingleCycle,              MemoryAccess::None),
+    Instruction(0x93, "NOP", AddressingMode::SingleCycle,              MemoryAccess::None),
+    Instruction(0x9B, "NOP", AddressingMode::SingleCycle,              MemoryAccess::None),
+    Instruction(0xA3, "NOP", AddressingMode::SingleCycle,              MemoryAccess::None),
+    Instruction(0xAB, "NOP", AddressingMode::SingleCycle,              MemoryAccess::None),
+    Instruction(0xB3, "NOP", AddressingMode::SingleCycle,              MemoryAccess::None),
+    Instruction(0xBB, "NOP", AddressingMode::SingleCycle,              MemoryAccess::None),
+    Instruction(0xC2, "NOP", AddressingMode::Immediate,                MemoryAccess::None),
+    Instruction(0xC3, "NOP", AddressingMode::SingleCycle,              MemoryAccess::None),
+    Instruction(0xD3, "NOP", AddressingMode::SingleCycle,              MemoryAccess::None),
+    Instruction(0xD4, "NOP", AddressingMode::ZeroPageX,                MemoryAccess::Read),
+    Instruction(0xDC, "NOP", AddressingMode::Absolute,                 MemoryAccess::Read),
+    Instruction(0xE2, "NOP", AddressingMode::Immediate,                MemoryAccess::None),
+    Instruction(0xE3, "NOP", AddressingMode::SingleCycle,              MemoryAccess::None),
+    Instruction(0xEA, "NOP", AddressingMode::None,                     MemoryAccess::None),
+    Instruction(0xEB, "NOP", AddressingMode::SingleCycle,              MemoryAccess::None),
+    Instruction(0xF3, "NOP", AddressingMode::SingleCycle,              MemoryAccess::None),
+    Instruction(0xF4, "NOP", AddressingMode::ZeroPageX,                MemoryAccess::Read),
+    Instruction(0xFB, "NOP", AddressingMode::SingleCycle,              MemoryAccess::None),
+    Instruction(0xFC, "NOP", AddressingMode::Absolute,                 MemoryAccess::Read),
+
+    // ORA
+    Instruction(0x01, "ORA", AddressingMode::IndexedIndirectX,         MemoryAccess::Read),
+    Instruction(0x05, "ORA", AddressingMode::ZeroPage,                 MemoryAccess::Read),
+    Instruction(0x09, "ORA", AddressingMode::Immediate,                MemoryAccess::None),
+    Instruction(0x0D, "ORA", AddressingMode::Absolute,                 MemoryAccess::Read),
+    Instruction(0x11, "ORA", AddressingMode::IndirectIndexedY,         MemoryAccess::Read),
+    Instruction(0x12, "ORA", AddressingMode::ZeroPageIndirect,         MemoryAccess::Read),
+    Instruction(0x15, "ORA", AddressingMode::ZeroPageX,                MemoryAccess::Read),
+    Instruction(0x19, "ORA", AddressingMode::AbsoluteY,                MemoryAccess::Read),
+    Instruction(0x1D, "ORA", AddressingMode::AbsoluteX,                MemoryAccess::Read),
+
+    // RMB (Rockwell / WDC)
+    Instruction(0x07, "RMB0", AddressingMode::ZeroPage,                 MemoryAccess::ReadWrite),
+    Instruction(0x17, "RMB1", AddressingMode::ZeroPage,                 MemoryAccess::ReadWrite),
+    Instruction(0x27, "RMB2", AddressingMode::ZeroPage,                 MemoryAccess::ReadWrite),
+    Instruction(0x37, "RMB3", AddressingMode::ZeroPage,                 MemoryAccess::ReadWrite),
+    Instruction(0x47, "RMB4", AddressingMode::ZeroPage,                 MemoryAccess::ReadWrite),
+    Instruction(0x57, "RMB5", AddressingMode::ZeroPage,                 MemoryAccess::ReadWrite),
+    Instruction(0x67, "RMB6", AddressingMode::ZeroPage,                 MemoryAccess::ReadWrite),
+    Instruction(0x77, "RMB7", AddressingMode::ZeroPage,                 MemoryAccess::ReadWrite),
+
+    // ROL
+    Instruction(0x26, "ROL", AddressingMode::ZeroPage,                 MemoryAccess::ReadWrite),
+    Instruction(0x2A, "ROL", AddressingMode::Accumulator,              MemoryAccess::None),
+    Instruction(0x2E, "ROL", AddressingMode::Absolute,                 MemoryAccess::ReadWrite),
+    Instruction(0x36, "ROL", AddressingMode::ZeroPageX,                MemoryAccess::ReadWrite),
+    Instruction(0x3E, "ROL", AddressingMode::AbsoluteX,                MemoryAccess::ReadWrite),
+
+    // ROR
+    Instruction(0x66, "ROR", AddressingMode::ZeroPage,                 MemoryAccess::ReadWrite),
+    Instruction(0x6A, "ROR", AddressingMode::Accumulator,              MemoryAccess::None),
+    Instruction(0x6E, "ROR", AddressingMode::Absolute,                 MemoryAccess::ReadWrite),
+    Instruction(0x76, "ROR", AddressingMode::ZeroPageX,                MemoryAccess::ReadWrite),
+    Instruction(0x7E, "ROR", AddressingMode::AbsoluteX,                MemoryAccess::ReadWrite),
+
+    // SBC
+    Instruction(0xE1, "SBC", AddressingMode::IndexedIndirectX,         MemoryAccess::Read),
+    Instruction(0xE5, "SBC", AddressingMode::ZeroPage,                 MemoryAccess::Read),
+    Instruction(0xE9, "SBC", AddressingMode::Immediate,                MemoryAccess::None),
+    Instruction(0xED, "SBC", AddressingMode::Absolute,                 MemoryAccess::Read),
+    Instruction(0xF1, "SBC", AddressingMode::IndirectIndexedY,         MemoryAccess::Read),
+    Instruction(0xF2, "SBC", AddressingMode::ZeroPageIndirect,         MemoryAccess::Read),
+    Instruction(0xF5, "SBC", AddressingMode::ZeroPageX,                MemoryAccess::Read),
+    Instruction(0xF9, "SBC", AddressingMode::AbsoluteY,                MemoryAccess::Read),
+    Instruction(0xFD, "SBC", AddressingMode::AbsoluteX,                MemoryAccess::Read),
+
+    // SMB (Rockwell / WDC)
+    Instruction(0x87, "SMB0", AddressingMode::ZeroPage,                 MemoryAccess::ReadWrite),
+    Instruction(0x97, "SMB1", AddressingMode::ZeroPage,                 MemoryAccess::ReadWrite),
+    Instruction(0xA7, "SMB2", AddressingMode::ZeroPage,                 MemoryAccess::ReadWrite),
+    Instruction(0xB7, "SMB3", AddressingMode::ZeroPage,                 MemoryAccess::ReadWrite),
+    Instruction(0xC7, "SMB4", AddressingMode::ZeroPage,                 MemoryAccess::ReadWrite),
+    Instruction(0xD7, "SMB5", AddressingMode::ZeroPage,                 MemoryAccess::ReadWrite),
+    Instruction(0xE7, "SMB6", AddressingMode::ZeroPage,                 MemoryAccess::ReadWrite),
+    Instruction(0xF7, "SMB7", AddressingMode::ZeroPage,                 MemoryAccess::ReadWrite),
+
+    // STA
+    Instruction(0x81, "STA", AddressingMode::IndexedIndirectX,         MemoryAccess::Write),
+    Instruction(0x85, "STA", AddressingMode::ZeroPage,                 MemoryAccess::Write),
+    Instruction(0x8D, "STA", AddressingMode::Absolute,                 MemoryAccess::Write),
+    Instruction(0x91, "STA", AddressingMode::IndirectIndexedY,         MemoryAccess::Write),
+    Instruction(0x92, "STA", AddressingMode::ZeroPageIndirect,         MemoryAccess::Write),
+    Instruction(0x95, "STA", AddressingMode::ZeroPageX,                MemoryAccess::Write),
+    Instruction(0x99, "STA", AddressingMode::AbsoluteY,                MemoryAccess::Write),
+    Instruction(0x9D, "STA", AddressingMode::AbsoluteX,                MemoryAccess::Write),
+
+    // STP (WDC)
+    Instruction(0xDB, "STP", AddressingMode::None,                     MemoryAccess::None),
+
+    // STX
+    Instruction(0x86, "STX", AddressingMode::ZeroPage,                 MemoryAccess::Write),
+    Instruction(0x8E, "STX", AddressingMode::Absolute,                 MemoryAccess::Write),
+    Instruction(0x96, "STX", AddressingMode::ZeroPageY,                MemoryAccess::Write),
+
+    // STY
+    Instruction(0x84, "STY", AddressingMode::ZeroPage,                 MemoryAccess::Write),
+    Instruction(0x8C, "STY", AddressingMode::Absolute,                 MemoryAccess::Write),
+    Instruction(0x94, "STY", AddressingMode::ZeroPageX,                MemoryAccess::Write),
+
+    // STZ
+    Instruction(0x64, "STZ", AddressingMode::ZeroPage,                 MemoryAccess::Write),
+    Instruction(0x74, "STZ", AddressingMode::ZeroPageX,                MemoryAccess::Write),
+    Instruction(0x9C, "STZ", AddressingMode::Absolute,                 MemoryAccess::Write),
+    Instruction(0x9E, "STZ", AddressingMode::AbsoluteX,                MemoryAccess::Write),
+
+    // TRB
+    Instruction(0x14, "TRB", AddressingMode::ZeroPage,                 MemoryAccess::ReadWrite),
+    Instruction(0x1C, "TRB", AddressingMode::Absolute,                 MemoryAccess::ReadWrite),
+
+    // TSB
+    Instruction(0x04, "TSB", AddressingMode::ZeroPage,                 MemoryAccess::ReadWrite),
+    Instruction(0x0C, "TSB", AddressingMode::Absolute,                 MemoryAccess::ReadWrite),
+
+    // WAI (WDC)
+    Instruction(0xCB, "WAI", AddressingMode::None,                     MemoryAccess::None),
+];
+
 struct InstructionCodeBuilder {
+    variant: Variant,
     lines: Vec<String>,
+
+    /// Number of lines that are only executed some of the time,
+    /// such as page boundary crossings and taken branches.
+    optional_cycles: usize,
 }
 
 impl InstructionCodeBuilder {
-    fn new() -> InstructionCodeBuilder {
+    fn new(variant: Variant) -> InstructionCodeBuilder {
         InstructionCodeBuilder {
+            variant,
             lines: Vec::with_capacity(8),
+            optional_cycles: 0,
         }
     }
 
@@ -463,6 +872,11 @@ impl InstructionCodeBuilder {
                 self.add("self.pla_1();");
                 self.add("self.pla_2();");
             },
+            "PLX" | "PLY" => {
+                self.add("self.pla_0();");
+                self.add("self.pla_1();");
+                self.add_string(format!("self.{}();", mnemonic.to_lowercase()));
+            },
             "PLP" => {
                 self.add("self.plp_0();");
                 self.add("self.plp_1();");
@@ -482,42 +896,79 @@ impl InstructionCodeBuilder {
                 self.add("self.rts_3();");
                 self.add("");
             },
-            "CLC" | "CLD" | "CLI" | "CLV" | "SED" | "SEI" | "SLC" |
+            "CLC" | "CLD" | "CLI" | "CLV" | "SEC" | "SED" | "SEI" |
             "DEX" | "DEY" | "INX" | "INY" | 
             "TAX" | "TAY" | "TSX" | "TXA" | "TXS" | "TYA" => {
                 self.add_string(format!("self.{}();", mnemonic.to_lowercase()))
             },
 
+            "WAI" | "STP" => {
+                self.add_string(format!("self.{}();", mnemonic.to_lowercase()));
+                self.add("");
+            },
+
+            // Push. The first cycle is a dummy read of the next byte.
+            "PHA" | "PHP" | "PHX" | "PHY" => {
+                self.add_string(format!("self.{}();", mnemonic.to_lowercase()));
+            },
+
             // Write memory
-            "PHA" | "PHP" | "SAX" | "SHA" | "SHX" | "SHY" | "SHS" | "STA" | "STX" | "STY" => {
+            "SAX" | "SHA" | "SHX" | "SHY" | "SHS" | "STA" | "STX" | "STY" | "STZ" => {
                 self.modify_previous_string(format!("self.{}();", mnemonic.to_lowercase()));
             },
 
+            // The 65C02 takes an extra cycle to produce valid flags in decimal mode.
+            "ADC" | "SBC" if self.variant == Variant::Cmos => {
+                self.add_string(format!("self.{}();", mnemonic.to_lowercase()));
+                self.modify_previous("self.fetch_next_instruction_unless_decimal();");
+                self.add("");
+                self.optional_cycles += 1;
+            },
+
+            // BIT immediate only affects the Z flag.
+            "BIT" if addressing_mode == &AddressingMode::Immediate => {
+                self.add("self.bit_immediate();");
+            },
+
             "ADC" | "AND" | "BIT" | "CMP" | "CPX" | "CPY" | "EOR" | "LAX" | "LDA" | "LDX" | "LDY" | "ORA" | "SBC" |
             "ANC" | "ANE" | "ARR" | "ASR" | "LAS" | "LXA" | "SBX" => {
                 self.add_string(format!("self.{}();", mnemonic.to_lowercase()))
             },
 
             // Accumulator
-            "ASL" | "LSR" | "ROL" | "ROR" if addressing_mode == &AddressingMode::Accumulator => {
+            "ASL" | "DEC" | "INC" | "LSR" | "ROL" | "ROR" if addressing_mode == &AddressingMode::Accumulator => {
                 self.add_string(format!("self.{}a();", mnemonic.to_lowercase()));
             },
 
             // Read / modify / write memory
-            "ASL" | "DEC" | "DCP" | "INC" | "ISB" | "LSR" | "RLA" | "ROL" | "ROR" | "RRA" | "SLO" | "SRE" => {
-                self.add("self.rmw_cycle();");
+            "ASL" | "DEC" | "DCP" | "INC" | "ISB" | "LSR" | "RLA" | "ROL" | "ROR" | "RRA" | "SLO" | "SRE" | "TRB" | "TSB" => {
+                self.add_rmw_cycle();
                 self.add_string(format!("self.{}();", mnemonic.to_lowercase()));
             },
+            "RMB0" | "RMB1" | "RMB2" | "RMB3" | "RMB4" | "RMB5" | "RMB6" | "RMB7" |
+            "SMB0" | "SMB1" | "SMB2" | "SMB3" | "SMB4" | "SMB5" | "SMB6" | "SMB7" => {
+                self.add_rmw_cycle();
+                self.add_string(format!("self.{}({});", mnemonic[..3].to_lowercase(), &mnemonic[3..]));
+            },
 
             // Branch
-            "BCC" | "BCS" | "BEQ" | "BMI" | "BNE" | "BPL" | "BVC" | "BVS" => {
+            "BCC" | "BCS" | "BEQ" | "BMI" | "BNE" | "BPL" | "BRA" | "BVC" | "BVS" => {
                 self.add_string(format!("self.branch_0_{}();", mnemonic.to_lowercase()));
                 self.add("self.branch_1();");
                 self.add("self.branch_2();");
+                // BRA is always taken, so only the page crossing cycle is optional.
+                self.optional_cycles += if mnemonic == "BRA" { 1 } else { 2 };
+            },
+            "BBR0" | "BBR1" | "BBR2" | "BBR3" | "BBR4" | "BBR5" | "BBR6" | "BBR7" |
+            "BBS0" | "BBS1" | "BBS2" | "BBS3" | "BBS4" | "BBS5" | "BBS6" | "BBS7" => {
+                self.add_string(format!("self.branch_0_{}({});", mnemonic[..3].to_lowercase(), &mnemonic[3..]));
+                self.add("self.branch_1();");
+                self.add("self.branch_2();");
+                self.optional_cycles += 2;
             },
 
-            // Invalid
-            "JAM" => {},
+            // Invalid. The CPU is jammed until RESET, so there is no fetch.
+            "JAM" => self.add("self.jam();"),
 
             "NOP" => self.add(""),
             
@@ -525,12 +976,42 @@ impl InstructionCodeBuilder {
         }
     }
 
+    /// The NMOS 6502 writes the unmodified value back during the extra cycle
+    /// of a read / modify / write instruction. The 65C02 reads it again instead.
+    fn add_rmw_cycle(&mut self) {
+        match self.variant {
+            Variant::Nmos => self.add("self.rmw_cycle();"),
+            Variant::Cmos => self.add("self.rmw_cycle_cmos();"),
+        }
+    }
+
+    fn encode_absolute_indexed(&mut self, instruction: &Instruction, index_register: &str) {
+        // The 65C02 can also skip the page boundary cycle for shifts and rotates.
+        let can_skip_cycle = match instruction.3 {
+            MemoryAccess::Read => true,
+            MemoryAccess::ReadWrite => self.variant == Variant::Cmos && matches!(instruction.1, "ASL" | "LSR" | "ROL" | "ROR"),
+            _ => false,
+        };
+
+        self.add("self.addressing_mode_absolute_indexed_cycle_0();");
+        self.add("self.addressing_mode_absolute_indexed_cycle_1();");
+        match self.variant {
+            Variant::Nmos => self.add_string(format!("self.addressing_mode_absolute_indexed_cycle_2({});", index_register)),
+            Variant::Cmos => self.add_string(format!("self.addressing_mode_absolute_indexed_cycle_2_cmos({});", index_register)),
+        }
+        if can_skip_cycle {
+            self.modify_previous_string(format!("self.addressing_mode_absolute_indexed_cycle_2_read({});", index_register));
+            self.optional_cycles += 1;
+        }
+        self.add_string(format!("self.addressing_mode_absolute_indexed_cycle_3({});", index_register));
+    }
+
     fn encode_addressing_mode(&mut self, instruction: &Instruction) {
         match instruction.2 {
             AddressingMode::None | AddressingMode::Accumulator => {
                 self.add("self.addressing_mode_none_cycle_0();");
             },
-            AddressingMode::Immediate => {
+            AddressingMode::Immediate | AddressingMode::Relative => {
                 self.add("self.addressing_mode_immediate_cycle_0();");
             },
             AddressingMode::ZeroPage => {
@@ -552,24 +1033,8 @@ impl InstructionCodeBuilder {
                 self.add("self.addressing_mode_absolute_cycle_1();");
                 self.add("self.addressing_mode_absolute_cycle_2();");
             },
-            AddressingMode::AbsoluteX => {
-                self.add("self.addressing_mode_absolute_indexed_cycle_0();");
-                self.add("self.addressing_mode_absolute_indexed_cycle_1();");
-                self.add("self.addressing_mode_absolute_indexed_cycle_2(self.x);");
-                if instruction.3 == MemoryAccess::Read {
-                    self.modify_previous("self.addressing_mode_absolute_indexed_cycle_2_read(self.x);");
-                }
-                self.add("self.addressing_mode_absolute_indexed_cycle_3(self.x);");
-            },
-            AddressingMode::AbsoluteY => {
-                self.add("self.addressing_mode_absolute_indexed_cycle_0();");
-                self.add("self.addressing_mode_absolute_indexed_cycle_1();");
-                self.add("self.addressing_mode_absolute_indexed_cycle_2(self.y);");
-                if instruction.3 == MemoryAccess::Read {
-                    self.modify_previous("self.addressing_mode_absolute_indexed_cycle_2_read(self.y);");
-                }
-                self.add("self.addressing_mode_absolute_indexed_cycle_3(self.y);");
-            },
+            AddressingMode::AbsoluteX => self.encode_absolute_indexed(instruction, "self.x"),
+            AddressingMode::AbsoluteY => self.encode_absolute_indexed(instruction, "self.y"),
             AddressingMode::IndexedIndirectX => {
                 self.add("self.addressing_mode_indexed_indirect_x_cycle_0();");
                 self.add("self.addressing_mode_indexed_indirect_x_cycle_1();");
@@ -581,12 +1046,25 @@ impl InstructionCodeBuilder {
                 self.add("self.addressing_mode_indirect_indexed_y_cycle_0();");
                 self.add("self.addressing_mode_indirect_indexed_y_cycle_1();");
                 self.add("self.addressing_mode_indirect_indexed_y_cycle_2();");
-                self.add("self.addressing_mode_indirect_indexed_y_cycle_3();");
+                match self.variant {
+                    Variant::Nmos => self.add("self.addressing_mode_indirect_indexed_y_cycle_3();"),
+                    Variant::Cmos => self.add("self.addressing_mode_indirect_indexed_y_cycle_3_cmos();"),
+                }
                 if instruction.3 == MemoryAccess::Read {
                     self.modify_previous("self.addressing_mode_indirect_indexed_y_cycle_3_read();");
+                    self.optional_cycles += 1;
                 }
                 self.add("self.addressing_mode_indirect_indexed_y_cycle_4();");
             },
+            // The 65C02 fixes the page wrapping bug in JMP (abs), at the cost of an extra cycle.
+            AddressingMode::Indirect if self.variant == Variant::Cmos => {
+                self.add("self.addressing_mode_absolute_indexed_indirect_cycle_0();");
+                self.add("self.addressing_mode_absolute_indexed_indirect_cycle_1();");
+                self.add("self.addressing_mode_absolute_indexed_indirect_cycle_2();");
+                self.add("self.addressing_mode_absolute_indexed_indirect_cycle_3(0);");
+                self.add("self.addressing_mode_absolute_indexed_indirect_cycle_4();");
+                self.add("self.addressing_mode_absolute_indexed_indirect_cycle_5();");
+            },
             AddressingMode::Indirect => {
                 self.add("self.addressing_mode_indirect_cycle_0();");
                 self.add("self.addressing_mode_indirect_cycle_1();");
@@ -597,7 +1075,35 @@ impl InstructionCodeBuilder {
             AddressingMode::JSR => (),
             AddressingMode::Invalid => {
                 self.add("self.addressing_mode_invalid_cycle_0();");
-                self.add("self.addressing_mode_invalid_cycle_1();");
+            },
+            AddressingMode::ZeroPageIndirect => {
+                self.add("self.addressing_mode_zero_page_indirect_cycle_0();");
+                self.add("self.addressing_mode_zero_page_indirect_cycle_1();");
+                self.add("self.addressing_mode_zero_page_indirect_cycle_2();");
+                self.add("self.addressing_mode_zero_page_indirect_cycle_3();");
+            },
+            AddressingMode::AbsoluteIndexedIndirect => {
+                self.add("self.addressing_mode_absolute_indexed_indirect_cycle_0();");
+                self.add("self.addressing_mode_absolute_indexed_indirect_cycle_1();");
+                self.add("self.addressing_mode_absolute_indexed_indirect_cycle_2();");
+                self.add("self.addressing_mode_absolute_indexed_indirect_cycle_3(self.x);");
+                self.add("self.addressing_mode_absolute_indexed_indirect_cycle_4();");
+                self.add("self.addressing_mode_absolute_indexed_indirect_cycle_5();");
+            },
+            AddressingMode::ZeroPageRelative => {
+                self.add("self.addressing_mode_zero_page_cycle_0();");
+                self.add("self.addressing_mode_zero_page_cycle_1();");
+                self.add("self.addressing_mode_zero_page_relative_cycle_2();");
+                self.add("self.addressing_mode_zero_page_relative_cycle_3();");
+            },
+            AddressingMode::SingleCycle => (),
+            AddressingMode::AbsoluteEightCycles => {
+                self.add("self.addressing_mode_absolute_cycle_0();");
+                self.add("self.addressing_mode_absolute_cycle_1();");
+                self.add("self.addressing_mode_absolute_cycle_2();");
+                for _ in 0..4 {
+                    self.add("self.idle_cycle();");
+                }
             },
         }
     }
@@ -606,40 +1112,47 @@ impl InstructionCodeBuilder {
 struct InstructionCode {
     comment: String,
     lines: Vec<String>,
+
+    /// Cycles taken when no page boundary is crossed, and no branch is taken.
+    base_cycles: usize,
 }
 
 impl InstructionCode {
-    fn from_instruction(instruction: &Instruction) -> InstructionCode {
+    fn from_instruction(instruction: &Instruction, variant: Variant) -> InstructionCode {
         let comment = format!("{0} {1}", instruction.1, instruction.2.as_string());
     
-        let mut code_builder = InstructionCodeBuilder::new();
+        let mut code_builder = InstructionCodeBuilder::new(variant);
         code_builder.encode_addressing_mode(instruction);
 
         code_builder.encode_operation(instruction.1, &instruction.2);
     
-        match instruction.3 {
-            MemoryAccess::None | MemoryAccess::Read => code_builder.modify_previous("self.fetch_next_instruction();"),
-            _ => code_builder.add("self.fetch_next_instruction();"),
+        // JAM never finishes, so it doesn't fetch the next instruction.
+        if instruction.1 != "JAM" {
+            match instruction.3 {
+                MemoryAccess::None | MemoryAccess::Read => code_builder.modify_previous("self.fetch_next_instruction();"),
+                _ => code_builder.add("self.fetch_next_instruction();"),
+            }
         }
     
         InstructionCode {
             comment,
+            base_cycles: code_builder.lines.len() - code_builder.optional_cycles,
             lines: code_builder.lines,
         }
     }
 }
 
-fn main() -> Result<(), std::io::Error> {
+fn write_instructions(file_name: &str, instructions: &[Instruction; 256], variant: Variant) -> Result<(), std::io::Error> {
     let out_dir = env::var_os("OUT_DIR").unwrap();
-    let dest_path = Path::new(&out_dir).join("mos6502_instructions.generated.rs");
+    let dest_path = Path::new(&out_dir).join(file_name);
     let mut buffer = File::create(&dest_path)?;
 
     write!(buffer, "// This is a generated file. Do not modify.\n")?;
     write!(buffer, "\n")?;
     write!(buffer, "match (self.ir, self.tr) {{\n")?;
 
-    for instruction in INSTRUCTIONS.iter() {
-        let instruction_code = InstructionCode::from_instruction(instruction);
+    for instruction in instructions.iter() {
+        let instruction_code = InstructionCode::from_instruction(instruction, variant);
 
         write!(buffer, "    // {}\n", instruction_code.comment)?;
 
@@ -652,11 +1165,1081 @@ fn main() -> Result<(), std::io::Error> {
         write!(buffer, "\n")?;
     }
 
-    write!(buffer, "    _ => todo!(\"Unimplemented opcode 0x{{:02X}} timing {{}}\", self.ir, self.tr)")?;
+    write!(buffer, "    _ => unreachable!(\"Invalid timing {{}} for opcode 0x{{:02X}}\", self.tr, self.ir)")?;
 
     write!(buffer, "}}\n")?;
 
+    Ok(())
+}
+
+/// Generates one match arm per opcode for `M6502::execute_instruction`, which runs a whole
+/// instruction at once. Each arm evaluates to the instruction's cycle count, not including
+/// page crossings, taken branches or decimal mode, which the arm adds to `extra_cycles`.
+struct FastInstructionCode {
+    variant: Variant,
+    statements: Vec<String>,
+}
+
+impl FastInstructionCode {
+    fn from_instruction(instruction: &Instruction, variant: Variant) -> String {
+        let mut code = FastInstructionCode {
+            variant,
+            statements: Vec::with_capacity(4),
+        };
+
+        code.encode_address(instruction);
+        code.encode_operation(instruction);
+
+        // Some NOPs read their operand, but never use the address.
+        if instruction.1 == "NOP" && instruction.3 == MemoryAccess::None {
+            for statement in code.statements.iter_mut() {
+                *statement = statement.replace("let address =", "let _ =");
+            }
+        }
+
+        let base_cycles = InstructionCode::from_instruction(instruction, variant).base_cycles;
+        let cycles = match instruction.1 {
+            // BRA's taken cycle is already included in its base cycles.
+            "BRA" => base_cycles - 1,
+
+            // WAI and STP halt before their last cycle, which fetches the next opcode.
+            "WAI" | "STP" => base_cycles - 1,
+
+            _ => base_cycles,
+        };
+
+        code.statements.push(cycles.to_string());
+        code.statements.join(" ")
+    }
+
+    fn add(&mut self, text: &str) {
+        self.statements.push(text.to_string());
+    }
+
+    /// Reads the operand bytes, and leaves the effective address in `address`.
+    fn encode_address(&mut self, instruction: &Instruction) {
+        // The 65C02 can also skip the page boundary cycle for shifts and rotates.
+        let can_skip_cycle = match instruction.3 {
+            MemoryAccess::Read => true,
+            MemoryAccess::ReadWrite => self.variant == Variant::Cmos && matches!(instruction.1, "ASL" | "LSR" | "ROL" | "ROR"),
+            _ => false,
+        };
+
+        match instruction.2 {
+            AddressingMode::ZeroPage | AddressingMode::ZeroPageRelative => self.add("let address = self.fast_zero_page(bus);"),
+            AddressingMode::ZeroPageX => self.add("let address = self.fast_zero_page_indexed(bus, self.x);"),
+            AddressingMode::ZeroPageY => self.add("let address = self.fast_zero_page_indexed(bus, self.y);"),
+            AddressingMode::Absolute | AddressingMode::AbsoluteEightCycles => self.add("let address = self.fast_absolute(bus);"),
+            AddressingMode::AbsoluteX | AddressingMode::AbsoluteY | AddressingMode::IndirectIndexedY => {
+                let function = match instruction.2 {
+                    AddressingMode::AbsoluteX => "self.fast_absolute_indexed(bus, self.x)",
+                    AddressingMode::AbsoluteY => "self.fast_absolute_indexed(bus, self.y)",
+                    _ => "self.fast_indirect_indexed_y(bus)",
+                };
+                if can_skip_cycle {
+                    self.add(&format!("let (address, page_crossed) = {};", function));
+                    self.add("extra_cycles += page_crossed as u32;");
+                } else {
+                    self.add(&format!("let (address, _) = {};", function));
+                }
+            },
+            AddressingMode::IndexedIndirectX => self.add("let address = self.fast_indexed_indirect_x(bus);"),
+            AddressingMode::ZeroPageIndirect => self.add("let address = self.fast_zero_page_indirect(bus);"),
+            AddressingMode::Indirect => match self.variant {
+                Variant::Nmos => self.add("let address = self.fast_indirect(bus);"),
+                Variant::Cmos => self.add("let address = self.fast_indirect_cmos(bus);"),
+            },
+            AddressingMode::AbsoluteIndexedIndirect => self.add("let address = self.fast_absolute_indexed_indirect(bus);"),
+
+            // Immediate operands are read by the operation. Branches and JSR read their own operands.
+            AddressingMode::None | AddressingMode::Accumulator | AddressingMode::Immediate | AddressingMode::Relative |
+            AddressingMode::JSR | AddressingMode::Invalid | AddressingMode::SingleCycle => (),
+        }
+    }
+
+    fn encode_operation(&mut self, instruction: &Instruction) {
+        let mnemonic = instruction.1;
+        let addressing_mode = &instruction.2;
+        let name = mnemonic.to_lowercase();
+
+        match mnemonic {
+            "BRK" => self.add("self.fast_brk(bus);"),
+            "JMP" => self.add("self.pc = SplitRegister16::from_u16(address);"),
+            "JSR" => self.add("self.fast_jsr(bus);"),
+            "RTI" => self.add("self.fast_rti(bus);"),
+            "RTS" => self.add("self.fast_rts(bus);"),
+
+            "PHA" => self.add("self.fast_push(bus, self.a);"),
+            "PHP" => self.add("self.fast_push(bus, self.p.as_u8(true));"),
+            "PHX" => self.add("self.fast_push(bus, self.x);"),
+            "PHY" => self.add("self.fast_push(bus, self.y);"),
+            "PLA" | "PLP" => {
+                self.add("self.data = self.fast_pull(bus);");
+                self.add(&format!("self.{}_2();", name));
+            },
+            "PLX" | "PLY" => {
+                self.add("self.data = self.fast_pull(bus);");
+                self.add(&format!("self.{}();", name));
+            },
+
+            "CLC" | "CLD" | "CLI" | "CLV" | "SEC" | "SED" | "SEI" |
+            "DEX" | "DEY" | "INX" | "INY" |
+            "TAX" | "TAY" | "TSX" | "TXA" | "TXS" | "TYA" |
+            "WAI" | "STP" | "JAM" => self.add(&format!("self.{}();", name)),
+
+            // SHA, SHX, SHY and SHS use the high byte of the address.
+            "SHA" | "SHX" | "SHY" | "SHS" => {
+                self.add("self.address_hi = (address >> 8) as u8;");
+                self.add(&format!("self.{}();", name));
+                self.add("bus.write(address, self.data);");
+            },
+            "SAX" | "STA" | "STX" | "STY" | "STZ" => {
+                self.add(&format!("self.{}();", name));
+                self.add("bus.write(address, self.data);");
+            },
+
+            "BIT" if addressing_mode == &AddressingMode::Immediate => {
+                self.add("self.data = self.fast_read_pc(bus);");
+                self.add("self.bit_immediate();");
+            },
+            "ADC" | "AND" | "BIT" | "CMP" | "CPX" | "CPY" | "EOR" | "LAX" | "LDA" | "LDX" | "LDY" | "ORA" | "SBC" |
+            "ANC" | "ANE" | "ARR" | "ASR" | "LAS" | "LXA" | "SBX" => {
+                self.encode_read_operand(addressing_mode);
+                self.add(&format!("self.{}();", name));
+
+                // The 65C02 takes an extra cycle to produce valid flags in decimal mode.
+                if self.variant == Variant::Cmos && matches!(mnemonic, "ADC" | "SBC") {
+                    self.add("extra_cycles += (self.p.d && self.bcd_enabled) as u32;");
+                }
+            },
+
+            "ASL" | "DEC" | "INC" | "LSR" | "ROL" | "ROR" if addressing_mode == &AddressingMode::Accumulator => {
+                self.add(&format!("self.{}a();", name));
+            },
+            "ASL" | "DEC" | "DCP" | "INC" | "ISB" | "LSR" | "RLA" | "ROL" | "ROR" | "RRA" | "SLO" | "SRE" | "TRB" | "TSB" => {
+                self.add("self.ad.lo = bus.read(address);");
+                self.add(&format!("self.{}();", name));
+                self.add("bus.write(address, self.data);");
+            },
+            "RMB0" | "RMB1" | "RMB2" | "RMB3" | "RMB4" | "RMB5" | "RMB6" | "RMB7" |
+            "SMB0" | "SMB1" | "SMB2" | "SMB3" | "SMB4" | "SMB5" | "SMB6" | "SMB7" => {
+                self.add("self.ad.lo = bus.read(address);");
+                self.add(&format!("self.{}({});", &name[..3], &name[3..]));
+                self.add("bus.write(address, self.data);");
+            },
+
+            "BCC" | "BCS" | "BEQ" | "BMI" | "BNE" | "BPL" | "BRA" | "BVC" | "BVS" => {
+                let condition = match mnemonic {
+                    "BCC" => "!self.p.c",
+                    "BCS" => "self.p.c",
+                    "BEQ" => "self.p.z",
+                    "BMI" => "self.p.n",
+                    "BNE" => "!self.p.z",
+                    "BPL" => "!self.p.n",
+                    "BVC" => "!self.p.v",
+                    "BVS" => "self.p.v",
+                    _ => "true",
+                };
+                self.encode_branch(condition);
+            },
+            "BBR0" | "BBR1" | "BBR2" | "BBR3" | "BBR4" | "BBR5" | "BBR6" | "BBR7" |
+            "BBS0" | "BBS1" | "BBS2" | "BBS3" | "BBS4" | "BBS5" | "BBS6" | "BBS7" => {
+                let comparison = if mnemonic.starts_with("BBR") { "==" } else { "!=" };
+                let mask = 1 << mnemonic[3..].parse::<u8>().unwrap();
+                self.add(&format!("let taken = bus.read(address) & 0x{:02X} {} 0;", mask, comparison));
+                self.encode_branch("taken");
+            },
+
+            "NOP" => match (addressing_mode, &instruction.3) {
+                (AddressingMode::Immediate, _) => self.add("self.fast_read_pc(bus);"),
+                (_, MemoryAccess::Read) => self.add("self.data = bus.read(address);"),
+                _ => (),
+            },
+
+            _ => unreachable!("Unexpected mnemonic {}", mnemonic)
+        }
+    }
+
+    fn encode_read_operand(&mut self, addressing_mode: &AddressingMode) {
+        if addressing_mode == &AddressingMode::Immediate {
+            self.add("self.data = self.fast_read_pc(bus);");
+        } else {
+            self.add("self.data = bus.read(address);");
+        }
+    }
+
+    fn encode_branch(&mut self, condition: &str) {
+        self.add(&format!("extra_cycles += self.fast_branch(bus, {});", condition));
+    }
+}
+
+fn write_fast_instructions(file_name: &str, instructions: &[Instruction; 256], variant: Variant) -> Result<(), std::io::Error> {
+    let out_dir = env::var_os("OUT_DIR").unwrap();
+    let dest_path = Path::new(&out_dir).join(file_name);
+    let mut buffer = File::create(&dest_path)?;
+
+    writeln!(buffer, "// This is a generated file. Do not modify.")?;
+    writeln!(buffer)?;
+    writeln!(buffer, "match self.ir {{")?;
+
+    for instruction in instructions.iter() {
+        writeln!(buffer, "    // {} {}", instruction.1, instruction.2.as_string())?;
+        writeln!(buffer, "    0x{:02X} => {{ {} }},", instruction.0, FastInstructionCode::from_instruction(instruction, variant))?;
+    }
+
+    writeln!(buffer, "}}")?;
+
+    Ok(())
+}
+
+fn write_opcode_table(buffer: &mut File, name: &str, instructions: &[Instruction; 256], variant: Variant) -> Result<(), std::io::Error> {
+    let mut sorted_instructions: Vec<&Instruction> = instructions.iter().collect();
+    sorted_instructions.sort_by_key(|instruction| instruction.0);
+
+    writeln!(buffer, "pub static {}: [Opcode; 256] = [", name)?;
+
+    for (opcode, instruction) in sorted_instructions.iter().enumerate() {
+        assert_eq!(opcode, instruction.0 as usize, "Missing or duplicate opcode in {}", name);
+
+        let instruction_code = InstructionCode::from_instruction(instruction, variant);
+
+        writeln!(
+            buffer,
+            "    Opcode {{ mnemonic: \"{}\", addressing_mode: AddressingMode::{}, length: {}, cycles: {}, official: {} }}, // 0x{:02X}",
+            instruction.1,
+            instruction.2.public_name(),
+            instruction.2.length(),
+            instruction_code.base_cycles,
+            instruction.is_official(),
+            instruction.0)?;
+    }
+
+    writeln!(buffer, "];")?;
+
+    Ok(())
+}
+
+fn write_opcode_tables(file_name: &str) -> Result<(), std::io::Error> {
+    let out_dir = env::var_os("OUT_DIR").unwrap();
+    let dest_path = Path::new(&out_dir).join(file_name);
+    let mut buffer = File::create(&dest_path)?;
+
+    writeln!(buffer, "// This is a generated file. Do not modify.")?;
+    writeln!(buffer)?;
+    write_opcode_table(&mut buffer, "NMOS_OPCODES", &INSTRUCTIONS, Variant::Nmos)?;
+    writeln!(buffer)?;
+    write_opcode_table(&mut buffer, "CMOS_OPCODES", &CMOS_INSTRUCTIONS, Variant::Cmos)?;
+
+    Ok(())
+}
+
+//////////////////////////////////////////
+// WDC 65C816
+//////////////////////////////////////////
+
+#[derive(PartialEq)]
+enum W65C816AddressingMode {
+    Implied,
+    Accumulator,
+    Immediate,
+    Stack,
+    Relative,
+    RelativeLong,
+    BlockMove,
+    Absolute,
+    AbsoluteX,
+    AbsoluteY,
+    AbsoluteLong,
+    AbsoluteLongX,
+    AbsoluteIndirect,
+    AbsoluteIndirectLong,
+    AbsoluteIndexedIndirect,
+    Direct,
+    DirectX,
+    DirectY,
+    DirectIndirect,
+    DirectIndirectLong,
+    DirectIndexedIndirect,
+    DirectIndirectIndexed,
+    DirectIndirectLongIndexed,
+    StackRelative,
+    StackRelativeIndirectIndexed,
+}
+
+impl W65C816AddressingMode {
+    fn as_string(&self) -> &'static str {
+        match self {
+            W65C816AddressingMode::Implied | W65C816AddressingMode::Stack => "",
+            W65C816AddressingMode::Accumulator => "A",
+            W65C816AddressingMode::Immediate => "#",
+            W65C816AddressingMode::Relative => "rel",
+            W65C816AddressingMode::RelativeLong => "rl",
+            W65C816AddressingMode::BlockMove => "xyc",
+            W65C816AddressingMode::Absolute => "a",
+            W65C816AddressingMode::AbsoluteX => "a,x",
+            W65C816AddressingMode::AbsoluteY => "a,y",
+            W65C816AddressingMode::AbsoluteLong => "al",
+            W65C816AddressingMode::AbsoluteLongX => "al,x",
+            W65C816AddressingMode::AbsoluteIndirect => "(a)",
+            W65C816AddressingMode::AbsoluteIndirectLong => "[a]",
+            W65C816AddressingMode::AbsoluteIndexedIndirect => "(a,x)",
+            W65C816AddressingMode::Direct => "d",
+            W65C816AddressingMode::DirectX => "d,x",
+            W65C816AddressingMode::DirectY => "d,y",
+            W65C816AddressingMode::DirectIndirect => "(d)",
+            W65C816AddressingMode::DirectIndirectLong => "[d]",
+            W65C816AddressingMode::DirectIndexedIndirect => "(d,x)",
+            W65C816AddressingMode::DirectIndirectIndexed => "(d),y",
+            W65C816AddressingMode::DirectIndirectLongIndexed => "[d],y",
+            W65C816AddressingMode::StackRelative => "d,s",
+            W65C816AddressingMode::StackRelativeIndirectIndexed => "(d,s),y",
+        }
+    }
+}
+
+struct W65C816Instruction(u8, &'static str, W65C816AddressingMode, MemoryAccess);
+
+static W65C816_INSTRUCTIONS: [W65C816Instruction; 256] = [
+    W65C816Instruction(0x00, "BRK", W65C816AddressingMode::Stack,                        MemoryAccess::None),
+    W65C816Instruction(0x01, "ORA", W65C816AddressingMode::DirectIndexedIndirect,        MemoryAccess::Read),
+    W65C816Instruction(0x02, "COP", W65C816AddressingMode::Stack,                        MemoryAccess::None),
+    W65C816Instruction(0x03, "ORA", W65C816AddressingMode::StackRelative,                MemoryAccess::Read),
+    W65C816Instruction(0x04, "TSB", W65C816AddressingMode::Direct,                       MemoryAccess::ReadWrite),
+    W65C816Instruction(0x05, "ORA", W65C816AddressingMode::Direct,                       MemoryAccess::Read),
+    W65C816Instruction(0x06, "ASL", W65C816AddressingMode::Direct,                       MemoryAccess::ReadWrite),
+    W65C816Instruction(0x07, "ORA", W65C816AddressingMode::DirectIndirectLong,           MemoryAccess::Read),
+    W65C816Instruction(0x08, "PHP", W65C816AddressingMode::Stack,                        MemoryAccess::Write),
+    W65C816Instruction(0x09, "ORA", W65C816AddressingMode::Immediate,                    MemoryAccess::None),
+    W65C816Instruction(0x0A, "ASL", W65C816AddressingMode::Accumulator,                  MemoryAccess::None),
+    W65C816Instruction(0x0B, "PHD", W65C816AddressingMode::Stack,                        MemoryAccess::Write),
+    W65C816Instruction(0x0C, "TSB", W65C816AddressingMode::Absolute,                     MemoryAccess::ReadWrite),
+    W65C816Instruction(0x0D, "ORA", W65C816AddressingMode::Absolute,                     MemoryAccess::Read),
+    W65C816Instruction(0x0E, "ASL", W65C816AddressingMode::Absolute,                     MemoryAccess::ReadWrite),
+    W65C816Instruction(0x0F, "ORA", W65C816AddressingMode::AbsoluteLong,                 MemoryAccess::Read),
+
+    W65C816Instruction(0x10, "BPL", W65C816AddressingMode::Relative,                     MemoryAccess::None),
+    W65C816Instruction(0x11, "ORA", W65C816AddressingMode::DirectIndirectIndexed,        MemoryAccess::Read),
+    W65C816Instruction(0x12, "ORA", W65C816AddressingMode::DirectIndirect,               MemoryAccess::Read),
+    W65C816Instruction(0x13, "ORA", W65C816AddressingMode::StackRelativeIndirectIndexed, MemoryAccess::Read),
+    W65C816Instruction(0x14, "TRB", W65C816AddressingMode::Direct,                       MemoryAccess::ReadWrite),
+    W65C816Instruction(0x15, "ORA", W65C816AddressingMode::DirectX,                      MemoryAccess::Read),
+    W65C816Instruction(0x16, "ASL", W65C816AddressingMode::DirectX,                      MemoryAccess::ReadWrite),
+    W65C816Instruction(0x17, "ORA", W65C816AddressingMode::DirectIndirectLongIndexed,    MemoryAccess::Read),
+    W65C816Instruction(0x18, "CLC", W65C816AddressingMode::Implied,                      MemoryAccess::None),
+    W65C816Instruction(0x19, "ORA", W65C816AddressingMode::AbsoluteY,                    MemoryAccess::Read),
+    W65C816Instruction(0x1A, "INC", W65C816AddressingMode::Accumulator,                  MemoryAccess::None),
+    W65C816Instruction(0x1B, "TCS", W65C816AddressingMode::Implied,                      MemoryAccess::None),
+    W65C816Instruction(0x1C, "TRB", W65C816AddressingMode::Absolute,                     MemoryAccess::ReadWrite),
+    W65C816Instruction(0x1D, "ORA", W65C816AddressingMode::AbsoluteX,                    MemoryAccess::Read),
+    W65C816Instruction(0x1E, "ASL", W65C816AddressingMode::AbsoluteX,                    MemoryAccess::ReadWrite),
+    W65C816Instruction(0x1F, "ORA", W65C816AddressingMode::AbsoluteLongX,                MemoryAccess::Read),
+
+    W65C816Instruction(0x20, "JSR", W65C816AddressingMode::Absolute,                     MemoryAccess::None),
+    W65C816Instruction(0x21, "AND", W65C816AddressingMode::DirectIndexedIndirect,        MemoryAccess::Read),
+    W65C816Instruction(0x22, "JSL", W65C816AddressingMode::AbsoluteLong,                 MemoryAccess::None),
+    W65C816Instruction(0x23, "AND", W65C816AddressingMode::StackRelative,                MemoryAccess::Read),
+    W65C816Instruction(0x24, "BIT", W65C816AddressingMode::Direct,                       MemoryAccess::Read),
+    W65C816Instruction(0x25, "AND", W65C816AddressingMode::Direct,                       MemoryAccess::Read),
+    W65C816Instruction(0x26, "ROL", W65C816AddressingMode::Direct,                       MemoryAccess::ReadWrite),
+    W65C816Instruction(0x27, "AND", W65C816AddressingMode::DirectIndirectLong,           MemoryAccess::Read),
+    W65C816Instruction(0x28, "PLP", W65C816AddressingMode::Stack,                        MemoryAccess::None),
+    W65C816Instruction(0x29, "AND", W65C816AddressingMode::Immediate,                    MemoryAccess::None),
+    W65C816Instruction(0x2A, "ROL", W65C816AddressingMode::Accumulator,                  MemoryAccess::None),
+    W65C816Instruction(0x2B, "PLD", W65C816AddressingMode::Stack,                        MemoryAccess::None),
+    W65C816Instruction(0x2C, "BIT", W65C816AddressingMode::Absolute,                     MemoryAccess::Read),
+    W65C816Instruction(0x2D, "AND", W65C816AddressingMode::Absolute,                     MemoryAccess::Read),
+    W65C816Instruction(0x2E, "ROL", W65C816AddressingMode::Absolute,                     MemoryAccess::ReadWrite),
+    W65C816Instruction(0x2F, "AND", W65C816AddressingMode::AbsoluteLong,                 MemoryAccess::Read),
+
+    W65C816Instruction(0x30, "BMI", W65C816AddressingMode::Relative,                     MemoryAccess::None),
+    W65C816Instruction(0x31, "AND", W65C816AddressingMode::DirectIndirectIndexed,        MemoryAccess::Read),
+    W65C816Instruction(0x32, "AND", W65C816AddressingMode::DirectIndirect,               MemoryAccess::Read),
+    W65C816Instruction(0x33, "AND", W65C816AddressingMode::StackRelativeIndirectIndexed, MemoryAccess::Read),
+    W65C816Instruction(0x34, "BIT", W65C816AddressingMode::DirectX,                      MemoryAccess::Read),
+    W65C816Instruction(0x35, "AND", W65C816AddressingMode::DirectX,                      MemoryAccess::Read),
+    W65C816Instruction(0x36, "ROL", W65C816AddressingMode::DirectX,                      MemoryAccess::ReadWrite),
+    W65C816Instruction(0x37, "AND", W65C816AddressingMode::DirectIndirectLongIndexed,    MemoryAccess::Read),
+    W65C816Instruction(0x38, "SEC", W65C816AddressingMode::Implied,                      MemoryAccess::None),
+    W65C816Instruction(0x39, "AND", W65C816AddressingMode::AbsoluteY,                    MemoryAccess::Read),
+    W65C816Instruction(0x3A, "DEC", W65C816AddressingMode::Accumulator,                  MemoryAccess::None),
+    W65C816Instruction(0x3B, "TSC", W65C816AddressingMode::Implied,                      MemoryAccess::None),
+    W65C816Instruction(0x3C, "BIT", W65C816AddressingMode::AbsoluteX,                    MemoryAccess::Read),
+    W65C816Instruction(0x3D, "AND", W65C816AddressingMode::AbsoluteX,                    MemoryAccess::Read),
+    W65C816Instruction(0x3E, "ROL", W65C816AddressingMode::AbsoluteX,                    MemoryAccess::ReadWrite),
+    W65C816Instruction(0x3F, "AND", W65C816AddressingMode::AbsoluteLongX,                MemoryAccess::Read),
+
+    W65C816Instruction(0x40, "RTI", W65C816AddressingMode::Stack,                        MemoryAccess::None),
+    W65C816Instruction(0x41, "EOR", W65C816AddressingMode::DirectIndexedIndirect,        MemoryAccess::Read),
+    W65C816Instruction(0x42, "WDM", W65C816AddressingMode::Immediate,                    MemoryAccess::None),
+    W65C816Instruction(0x43, "EOR", W65C816AddressingMode::StackRelative,                MemoryAccess::Read),
+    W65C816Instruction(0x44, "MVP", W65C816AddressingMode::BlockMove,                    MemoryAccess::None),
+    W65C816Instruction(0x45, "EOR", W65C816AddressingMode::Direct,                       MemoryAccess::Read),
+    W65C816Instruction(0x46, "LSR", W65C816AddressingMode::Direct,                       MemoryAccess::ReadWrite),
+    W65C816Instruction(0x47, "EOR", W65C816AddressingMode::DirectIndirectLong,           MemoryAccess::Read),
+    W65C816Instruction(0x48, "PHA", W65C816AddressingMode::Stack,                        MemoryAccess::Write),
+    W65C816Instruction(0x49, "EOR", W65C816AddressingMode::Immediate,                    MemoryAccess::None),
+    W65C816Instruction(0x4A, "LSR", W65C816AddressingMode::Accumulator,                  MemoryAccess::None),
+    W65C816Instruction(0x4B, "PHK", W65C816AddressingMode::Stack,                        MemoryAccess::Write),
+    W65C816Instruction(0x4C, "JMP", W65C816AddressingMode::Absolute,                     MemoryAccess::None),
+    W65C816Instruction(0x4D, "EOR", W65C816AddressingMode::Absolute,                     MemoryAccess::Read),
+    W65C816Instruction(0x4E, "LSR", W65C816AddressingMode::Absolute,                     MemoryAccess::ReadWrite),
+    W65C816Instruction(0x4F, "EOR", W65C816AddressingMode::AbsoluteLong,                 MemoryAccess::Read),
+
+    W65C816Instruction(0x50, "BVC", W65C816AddressingMode::Relative,                     MemoryAccess::None),
+    W65C816Instruction(0x51, "EOR", W65C816AddressingMode::DirectIndirectIndexed,        MemoryAccess::Read),
+    W65C816Instruction(0x52, "EOR", W65C816AddressingMode::DirectIndirect,               MemoryAccess::Read),
+    W65C816Instruction(0x53, "EOR", W65C816AddressingMode::StackRelativeIndirectIndexed, MemoryAccess::Read),
+    W65C816Instruction(0x54, "MVN", W65C816AddressingMode::BlockMove,                    MemoryAccess::None),
+    W65C816Instruction(0x55, "EOR", W65C816AddressingMode::DirectX,                      MemoryAccess::Read),
+    W65C816Instruction(0x56, "LSR", W65C816AddressingMode::DirectX,                      MemoryAccess::ReadWrite),
+    W65C816Instruction(0x57, "EOR", W65C816AddressingMode::DirectIndirectLongIndexed,    MemoryAccess::Read),
+    W65C816Instruction(0x58, "CLI", W65C816AddressingMode::Implied,                      MemoryAccess::None),
+    W65C816Instruction(0x59, "EOR", W65C816AddressingMode::AbsoluteY,                    MemoryAccess::Read),
+    W65C816Instruction(0x5A, "PHY", W65C816AddressingMode::Stack,                        MemoryAccess::Write),
+    W65C816Instruction(0x5B, "TCD", W65C816AddressingMode::Implied,                      MemoryAccess::None),
+    W65C816Instruction(0x5C, "JML", W65C816AddressingMode::AbsoluteLong,                 MemoryAccess::None),
+    W65C816Instruction(0x5D, "EOR", W65C816AddressingMode::AbsoluteX,                    MemoryAccess::Read),
+    W65C816Instruction(0x5E, "LSR", W65C816AddressingMode::AbsoluteX,                    MemoryAccess::ReadWrite),
+    W65C816Instruction(0x5F, "EOR", W65C816AddressingMode::AbsoluteLongX,                MemoryAccess::Read),
+
+    W65C816Instruction(0x60, "RTS", W65C816AddressingMode::Stack,                        MemoryAccess::None),
+    W65C816Instruction(0x61, "ADC", W65C816AddressingMode::DirectIndexedIndirect,        MemoryAccess::Read),
+    W65C816Instruction(0x62, "PER", W65C816AddressingMode::RelativeLong,                 MemoryAccess::Write),
+    W65C816Instruction(0x63, "ADC", W65C816AddressingMode::StackRelative,                MemoryAccess::Read),
+    W65C816Instruction(0x64, "STZ", W65C816AddressingMode::Direct,                       MemoryAccess::Write),
+    W65C816Instruction(0x65, "ADC", W65C816AddressingMode::Direct,                       MemoryAccess::Read),
+    W65C816Instruction(0x66, "ROR", W65C816AddressingMode::Direct,                       MemoryAccess::ReadWrite),
+    W65C816Instruction(0x67, "ADC", W65C816AddressingMode::DirectIndirectLong,           MemoryAccess::Read),
+    W65C816Instruction(0x68, "PLA", W65C816AddressingMode::Stack,                        MemoryAccess::None),
+    W65C816Instruction(0x69, "ADC", W65C816AddressingMode::Immediate,                    MemoryAccess::None),
+    W65C816Instruction(0x6A, "ROR", W65C816AddressingMode::Accumulator,                  MemoryAccess::None),
+    W65C816Instruction(0x6B, "RTL", W65C816AddressingMode::Stack,                        MemoryAccess::None),
+    W65C816Instruction(0x6C, "JMP", W65C816AddressingMode::AbsoluteIndirect,             MemoryAccess::None),
+    W65C816Instruction(0x6D, "ADC", W65C816AddressingMode::Absolute,                     MemoryAccess::Read),
+    W65C816Instruction(0x6E, "ROR", W65C816AddressingMode::Absolute,                     MemoryAccess::ReadWrite),
+    W65C816Instruction(0x6F, "ADC", W65C816AddressingMode::AbsoluteLong,                 MemoryAccess::Read),
+
+    W65C816Instruction(0x70, "BVS", W65C816AddressingMode::Relative,                     MemoryAccess::None),
+    W65C816Instruction(0x71, "ADC", W65C816AddressingMode::DirectIndirectIndexed,        MemoryAccess::Read),
+    W65C816Instruction(0x72, "ADC", W65C816AddressingMode::DirectIndirect,               MemoryAccess::Read),
+    W65C816Instruction(0x73, "ADC", W65C816AddressingMode::StackRelativeIndirectIndexed, MemoryAccess::Read),
+    W65C816Instruction(0x74, "STZ", W65C816AddressingMode::DirectX,                      MemoryAccess::Write),
+    W65C816Instruction(0x75, "ADC", W65C816AddressingMode::DirectX,                      MemoryAccess::Read),
+    W65C816Instruction(0x76, "ROR", W65C816AddressingMode::DirectX,                      MemoryAccess::ReadWrite),
+    W65C816Instruction(0x77, "ADC", W65C816AddressingMode::DirectIndirectLongIndexed,    MemoryAccess::Read),
+    W65C816Instruction(0x78, "SEI", W65C816AddressingMode::Implied,                      MemoryAccess::None),
+    W65C816Instruction(0x79, "ADC", W65C816AddressingMode::AbsoluteY,                    MemoryAccess::Read),
+    W65C816Instruction(0x7A, "PLY", W65C816AddressingMode::Stack,                        MemoryAccess::None),
+    W65C816Instruction(0x7B, "TDC", W65C816AddressingMode::Implied,                      MemoryAccess::None),
+    W65C816Instruction(0x7C, "JMP", W65C816AddressingMode::AbsoluteIndexedIndirect,      MemoryAccess::None),
+    W65C816Instruction(0x7D, "ADC", W65C816AddressingMode::AbsoluteX,                    MemoryAccess::Read),
+    W65C816Instruction(0x7E, "ROR", W65C816AddressingMode::AbsoluteX,                    MemoryAccess::ReadWrite),
+    W65C816Instruction(0x7F, "ADC", W65C816AddressingMode::AbsoluteLongX,                MemoryAccess::Read),
+
+    W65C816Instruction(0x80, "BRA", W65C816AddressingMode::Relative,                     MemoryAccess::None),
+    W65C816Instruction(0x81, "STA", W65C816AddressingMode::DirectIndexedIndirect,        MemoryAccess::Write),
+    W65C816Instruction(0x82, "BRL", W65C816AddressingMode::RelativeLong,                 MemoryAccess::None),
+    W65C816Instruction(0x83, "STA", W65C816AddressingMode::StackRelative,                MemoryAccess::Write),
+    W65C816Instruction(0x84, "STY", W65C816AddressingMode::Direct,                       MemoryAccess::Write),
+    W65C816Instruction(0x85, "STA", W65C816AddressingMode::Direct,                       MemoryAccess::Write),
+    W65C816Instruction(0x86, "STX", W65C816AddressingMode::Direct,                       MemoryAccess::Write),
+    W65C816Instruction(0x87, "STA", W65C816AddressingMode::DirectIndirectLong,           MemoryAccess::Write),
+    W65C816Instruction(0x88, "DEY", W65C816AddressingMode::Implied,                      MemoryAccess::None),
+    W65C816Instruction(0x89, "BIT", W65C816AddressingMode::Immediate,                    MemoryAccess::None),
+    W65C816Instruction(0x8A, "TXA", W65C816AddressingMode::Implied,                      MemoryAccess::None),
+    W65C816Instruction(0x8B, "PHB", W65C816AddressingMode::Stack,                        MemoryAccess::Write),
+    W65C816Instruction(0x8C, "STY", W65C816AddressingMode::Absolute,                     MemoryAccess::Write),
+    W65C816Instruction(0x8D, "STA", W65C816AddressingMode::Absolute,                     MemoryAccess::Write),
+    W65C816Instruction(0x8E, "STX", W65C816AddressingMode::Absolute,                     MemoryAccess::Write),
+    W65C816Instruction(0x8F, "STA", W65C816AddressingMode::AbsoluteLong,                 MemoryAccess::Write),
+
+    W65C816Instruction(0x90, "BCC", W65C816AddressingMode::Relative,                     MemoryAccess::None),
+    W65C816Instruction(0x91, "STA", W65C816AddressingMode::DirectIndirectIndexed,        MemoryAccess::Write),
+    W65C816Instruction(0x92, "STA", W65C816AddressingMode::DirectIndirect,               MemoryAccess::Write),
+    W65C816Instruction(0x93, "STA", W65C816AddressingMode::StackRelativeIndirectIndexed, MemoryAccess::Write),
+    W65C816Instruction(0x94, "STY", W65C816AddressingMode::DirectX,                      MemoryAccess::Write),
+    W65C816Instruction(0x95, "STA", W65C816AddressingMode::DirectX,                      MemoryAccess::Write),
+    W65C816Instruction(0x96, "STX", W65C816AddressingMode::DirectY,                      MemoryAccess::Write),
+    W65C816Instruction(0x97, "STA", W65C816AddressingMode::DirectIndirectLongIndexed,    MemoryAccess::Write),
+    W65C816Instruction(0x98, "TYA", W65C816AddressingMode::Implied,                      MemoryAccess::None),
+    W65C816Instruction(0x99, "STA", W65C816AddressingMode::AbsoluteY,                    MemoryAccess::Write),
+    W65C816Instruction(0x9A, "TXS", W65C816AddressingMode::Implied,                      MemoryAccess::None),
+    W65C816Instruction(0x9B, "TXY", W65C816AddressingMode::Implied,                      MemoryAccess::None),
+    W65C816Instruction(0x9C, "STZ", W65C816AddressingMode::Absolute,                     MemoryAccess::Write),
+    W65C816Instruction(0x9D, "STA", W65C816AddressingMode::AbsoluteX,                    MemoryAccess::Write),
+    W65C816Instruction(0x9E, "STZ", W65C816AddressingMode::AbsoluteX,                    MemoryAccess::Write),
+    W65C816Instruction(0x9F, "STA", W65C816AddressingMode::AbsoluteLongX,                MemoryAccess::Write),
+
+    W65C816Instruction(0xA0, "LDY", W65C816AddressingMode::Immediate,                    MemoryAccess::None),
+    W65C816Instruction(0xA1, "LDA", W65C816AddressingMode::DirectIndexedIndirect,        MemoryAccess::Read),
+    W65C816Instruction(0xA2, "LDX", W65C816AddressingMode::Immediate,                    MemoryAccess::None),
+    W65C816Instruction(0xA3, "LDA", W65C816AddressingMode::StackRelative,                MemoryAccess::Read),
+    W65C816Instruction(0xA4, "LDY", W65C816AddressingMode::Direct,                       MemoryAccess::Read),
+    W65C816Instruction(0xA5, "LDA", W65C816AddressingMode::Direct,                       MemoryAccess::Read),
+    W65C816Instruction(0xA6, "LDX", W65C816AddressingMode::Direct,                       MemoryAccess::Read),
+    W65C816Instruction(0xA7, "LDA", W65C816AddressingMode::DirectIndirectLong,           MemoryAccess::Read),
+    W65C816Instruction(0xA8, "TAY", W65C816AddressingMode::Implied,                      MemoryAccess::None),
+    W65C816Instruction(0xA9, "LDA", W65C816AddressingMode::Immediate,                    MemoryAccess::None),
+    W65C816Instruction(0xAA, "TAX", W65C816AddressingMode::Implied,                      MemoryAccess::None),
+    W65C816Instruction(0xAB, "PLB", W65C816AddressingMode::Stack,                        MemoryAccess::None),
+    W65C816Instruction(0xAC, "LDY", W65C816AddressingMode::Absolute,                     MemoryAccess::Read),
+    W65C816Instruction(0xAD, "LDA", W65C816AddressingMode::Absolute,                     MemoryAccess::Read),
+    W65C816Instruction(0xAE, "LDX", W65C816AddressingMode::Absolute,                     MemoryAccess::Read),
+    W65C816Instruction(0xAF, "LDA", W65C816AddressingMode::AbsoluteLong,                 MemoryAccess::Read),
+
+    W65C816Instruction(0xB0, "BCS", W65C816AddressingMode::Relative,                     MemoryAccess::None),
+    W65C816Instruction(0xB1, "LDA", W65C816AddressingMode::DirectIndirectIndexed,        MemoryAccess::Read),
+    W65C816Instruction(0xB2, "LDA", W65C816AddressingMode::DirectIndirect,               MemoryAccess::Read),
+    W65C816Instruction(0xB3, "LDA", W65C816AddressingMode::StackRelativeIndirectIndexed, MemoryAccess::Read),
+    W65C816Instruction(0xB4, "LDY", W65C816AddressingMode::DirectX,                      MemoryAccess::Read),
+    W65C816Instruction(0xB5, "LDA", W65C816AddressingMode::DirectX,                      MemoryAccess::Read),
+    W65C816Instruction(0xB6, "LDX", W65C816AddressingMode::DirectY,                      MemoryAccess::Read),
+    W65C816Instruction(0xB7, "LDA", W65C816AddressingMode::DirectIndirectLongIndexed,    MemoryAccess::Read),
+    W65C816Instruction(0xB8, "CLV", W65C816AddressingMode::Implied,                      MemoryAccess::None),
+    W65C816Instruction(0xB9, "LDA", W65C816AddressingMode::AbsoluteY,                    MemoryAccess::Read),
+    W65C816Instruction(0xBA, "TSX", W65C816AddressingMode::Implied,                      MemoryAccess::None),
+    W65C816Instruction(0xBB, "TYX", W65C816AddressingMode::Implied,                      MemoryAccess::None),
+    W65C816Instruction(0xBC, "LDY", W65C816AddressingMode::AbsoluteX,                    MemoryAccess::Read),
+    W65C816Instruction(0xBD, "LDA", W65C816AddressingMode::AbsoluteX,                    MemoryAccess::Read),
+    W65C816Instruction(0xBE, "LDX", W65C816AddressingMode::AbsoluteY,                    MemoryAccess::Read),
+    W65C816Instruction(0xBF, "LDA", W65C816AddressingMode::AbsoluteLongX,                MemoryAccess::Read),
+
+    W65C816Instruction(0xC0, "CPY", W65C816AddressingMode::Immediate,                    MemoryAccess::None),
+    W65C816Instruction(0xC1, "CMP", W65C816AddressingMode::DirectIndexedIndirect,        MemoryAccess::Read),
+    W65C816Instruction(0xC2, "REP", W65C816AddressingMode::Immediate,                    MemoryAccess::None),
+    W65C816Instruction(0xC3, "CMP", W65C816AddressingMode::StackRelative,                MemoryAccess::Read),
+    W65C816Instruction(0xC4, "CPY", W65C816AddressingMode::Direct,                       MemoryAccess::Read),
+    W65C816Instruction(0xC5, "CMP", W65C816AddressingMode::Direct,                       MemoryAccess::Read),
+    W65C816Instruction(0xC6, "DEC", W65C816AddressingMode::Direct,                       MemoryAccess::ReadWrite),
+    W65C816Instruction(0xC7, "CMP", W65C816AddressingMode::DirectIndirectLong,           MemoryAccess::Read),
+    W65C816Instruction(0xC8, "INY", W65C816AddressingMode::Implied,                      MemoryAccess::None),
+    W65C816Instruction(0xC9, "CMP", W65C816AddressingMode::Immediate,                    MemoryAccess::None),
+    W65C816Instruction(0xCA, "DEX", W65C816AddressingMode::Implied,                      MemoryAccess::None),
+    W65C816Instruction(0xCB, "WAI", W65C816AddressingMode::Implied,                      MemoryAccess::None),
+    W65C816Instruction(0xCC, "CPY", W65C816AddressingMode::Absolute,                     MemoryAccess::Read),
+    W65C816Instruction(0xCD, "CMP", W65C816AddressingMode::Absolute,                     MemoryAccess::Read),
+    W65C816Instruction(0xCE, "DEC", W65C816AddressingMode::Absolute,                     MemoryAccess::ReadWrite),
+    W65C816Instruction(0xCF, "CMP", W65C816AddressingMode::AbsoluteLong,                 MemoryAccess::Read),
+
+    W65C816Instruction(0xD0, "BNE", W65C816AddressingMode::Relative,                     MemoryAccess::None),
+    W65C816Instruction(0xD1, "CMP", W65C816AddressingMode::DirectIndirectIndexed,        MemoryAccess::Read),
+    W65C816Instruction(0xD2, "CMP", W65C816AddressingMode::DirectIndirect,               MemoryAccess::Read),
+    W65C816Instruction(0xD3, "CMP", W65C816AddressingMode::StackRelativeIndirectIndexed, MemoryAccess::Read),
+    W65C816Instruction(0xD4, "PEI", W65C816AddressingMode::DirectIndirect,               MemoryAccess::Write),
+    W65C816Instruction(0xD5, "CMP", W65C816AddressingMode::DirectX,                      MemoryAccess::Read),
+    W65C816Instruction(0xD6, "DEC", W65C816AddressingMode::DirectX,                      MemoryAccess::ReadWrite),
+    W65C816Instruction(0xD7, "CMP", W65C816AddressingMode::DirectIndirectLongIndexed,    MemoryAccess::Read),
+    W65C816Instruction(0xD8, "CLD", W65C816AddressingMode::Implied,                      MemoryAccess::None),
+    W65C816Instruction(0xD9, "CMP", W65C816AddressingMode::AbsoluteY,                    MemoryAccess::Read),
+    W65C816Instruction(0xDA, "PHX", W65C816AddressingMode::Stack,                        MemoryAccess::Write),
+    W65C816Instruction(0xDB, "STP", W65C816AddressingMode::Implied,                      MemoryAccess::None),
+    W65C816Instruction(0xDC, "JML", W65C816AddressingMode::AbsoluteIndirectLong,         MemoryAccess::None),
+    W65C816Instruction(0xDD, "CMP", W65C816AddressingMode::AbsoluteX,                    MemoryAccess::Read),
+    W65C816Instruction(0xDE, "DEC", W65C816AddressingMode::AbsoluteX,                    MemoryAccess::ReadWrite),
+    W65C816Instruction(0xDF, "CMP", W65C816AddressingMode::AbsoluteLongX,                MemoryAccess::Read),
+
+    W65C816Instruction(0xE0, "CPX", W65C816AddressingMode::Immediate,                    MemoryAccess::None),
+    W65C816Instruction(0xE1, "SBC", W65C816AddressingMode::DirectIndexedIndirect,        MemoryAccess::Read),
+    W65C816Instruction(0xE2, "SEP", W65C816AddressingMode::Immediate,                    MemoryAccess::None),
+    W65C816Instruction(0xE3, "SBC", W65C816AddressingMode::StackRelative,                MemoryAccess::Read),
+    W65C816Instruction(0xE4, "CPX", W65C816AddressingMode::Direct,                       MemoryAccess::Read),
+    W65C816Instruction(0xE5, "SBC", W65C816AddressingMode::Direct,                       MemoryAccess::Read),
+    W65C816Instruction(0xE6, "INC", W65C816AddressingMode::Direct,                       MemoryAccess::ReadWrite),
+    W65C816Instruction(0xE7, "SBC", W65C816AddressingMode::DirectIndirectLong,           MemoryAccess::Read),
+    W65C816Instruction(0xE8, "INX", W65C816AddressingMode::Implied,                      MemoryAccess::None),
+    W65C816Instruction(0xE9, "SBC", W65C816AddressingMode::Immediate,                    MemoryAccess::None),
+    W65C816Instruction(0xEA, "NOP", W65C816AddressingMode::Implied,                      MemoryAccess::None),
+    W65C816Instruction(0xEB, "XBA", W65C816AddressingMode::Implied,                      MemoryAccess::None),
+    W65C816Instruction(0xEC, "CPX", W65C816AddressingMode::Absolute,                     MemoryAccess::Read),
+    W65C816Instruction(0xED, "SBC", W65C816AddressingMode::Absolute,                     MemoryAccess::Read),
+    W65C816Instruction(0xEE, "INC", W65C816AddressingMode::Absolute,                     MemoryAccess::ReadWrite),
+    W65C816Instruction(0xEF, "SBC", W65C816AddressingMode::AbsoluteLong,                 MemoryAccess::Read),
+
+    W65C816Instruction(0xF0, "BEQ", W65C816AddressingMode::Relative,                     MemoryAccess::None),
+    W65C816Instruction(0xF1, "SBC", W65C816AddressingMode::DirectIndirectIndexed,        MemoryAccess::Read),
+    W65C816Instruction(0xF2, "SBC", W65C816AddressingMode::DirectIndirect,               MemoryAccess::Read),
+    W65C816Instruction(0xF3, "SBC", W65C816AddressingMode::StackRelativeIndirectIndexed, MemoryAccess::Read),
+    W65C816Instruction(0xF4, "PEA", W65C816AddressingMode::Absolute,                     MemoryAccess::Write),
+    W65C816Instruction(0xF5, "SBC", W65C816AddressingMode::DirectX,                      MemoryAccess::Read),
+    W65C816Instruction(0xF6, "INC", W65C816AddressingMode::DirectX,                      MemoryAccess::ReadWrite),
+    W65C816Instruction(0xF7, "SBC", W65C816AddressingMode::DirectIndirectLongIndexed,    MemoryAccess::Read),
+    W65C816Instruction(0xF8, "SED", W65C816AddressingMode::Implied,                      MemoryAccess::None),
+    W65C816Instruction(0xF9, "SBC", W65C816AddressingMode::AbsoluteY,                    MemoryAccess::Read),
+    W65C816Instruction(0xFA, "PLX", W65C816AddressingMode::Stack,                        MemoryAccess::None),
+    W65C816Instruction(0xFB, "XCE", W65C816AddressingMode::Implied,                      MemoryAccess::None),
+    W65C816Instruction(0xFC, "JSR", W65C816AddressingMode::AbsoluteIndexedIndirect,      MemoryAccess::None),
+    W65C816Instruction(0xFD, "SBC", W65C816AddressingMode::AbsoluteX,                    MemoryAccess::Read),
+    W65C816Instruction(0xFE, "INC", W65C816AddressingMode::AbsoluteX,                    MemoryAccess::ReadWrite),
+    W65C816Instruction(0xFF, "SBC", W65C816AddressingMode::AbsoluteLongX,                MemoryAccess::Read),
+];
+
+/// One bus cycle of a 65C816 instruction. `setup` runs at the end of the previous cycle, and
+/// puts the address and RW for this cycle on the pins. `process` runs at the end of this
+/// cycle, once the data pins hold what was read. Cycles with a `condition` are skipped,
+/// along with their `process`, when it is false.
+struct BusCycle {
+    condition: Option<String>,
+    setup: String,
+    process: String,
+}
+
+struct W65C816CodeBuilder {
+    cycles: Vec<BusCycle>,
+}
+
+impl W65C816CodeBuilder {
+    fn new() -> W65C816CodeBuilder {
+        W65C816CodeBuilder {
+            cycles: Vec::with_capacity(8),
+        }
+    }
+
+    fn add(&mut self, setup: &str, process: &str) {
+        self.cycles.push(BusCycle {
+            condition: None,
+            setup: setup.to_string(),
+            process: process.to_string(),
+        });
+    }
+
+    fn add_if(&mut self, condition: &str, setup: &str, process: &str) {
+        self.cycles.push(BusCycle {
+            condition: Some(condition.to_string()),
+            setup: setup.to_string(),
+            process: process.to_string(),
+        });
+    }
+
+    /// Width condition for the high byte of an operand: the M flag for the
+    /// accumulator and memory, and the X flag for the index registers.
+    fn sixteen_bit_condition(mnemonic: &str) -> &'static str {
+        match mnemonic {
+            "LDX" | "LDY" | "STX" | "STY" | "CPX" | "CPY" | "PHX" | "PHY" | "PLX" | "PLY" => "!self.p.x",
+            _ => "!self.p.m",
+        }
+    }
+
+    fn encode(&mut self, instruction: &W65C816Instruction) -> String {
+        let mnemonic = instruction.1;
+        let operation = mnemonic.to_lowercase();
+        let wide = Self::sixteen_bit_condition(mnemonic);
+
+        match mnemonic {
+            "BRK" | "COP" => {
+                self.add("self.interrupt_signature();", "");
+                self.add_if("!self.e", "self.push_low(self.pbr as u16);", "");
+                self.add("self.push_high(self.pc);", "");
+                self.add("self.push_low(self.pc);", "");
+                self.add("self.push_status();", "");
+                self.add("self.read_vector_low();", "self.aa_lo();");
+                self.add("self.read_vector_high();", "self.aa_hi();");
+                "self.finish_interrupt();".to_string()
+            },
+            "JSR" if instruction.2 == W65C816AddressingMode::Absolute => {
+                self.add("self.read_pc();", "self.aa_lo();");
+                self.add("self.read_pc();", "self.aa_hi();");
+                self.add("self.io();", "");
+                self.add("self.push_high(self.pc.wrapping_sub(1));", "");
+                self.add("self.push_low(self.pc.wrapping_sub(1));", "");
+                "self.jmp();".to_string()
+            },
+            "JSR" => {
+                self.add("self.read_pc();", "self.aa_lo();");
+                self.add("self.push_high(self.pc);", "");
+                self.add("self.push_low(self.pc);", "");
+                self.add("self.read_pc();", "self.aa_hi(); self.set_program_pointer(self.x);");
+                self.add("self.io();", "");
+                self.add("self.read_ea();", "self.aa_lo();");
+                self.add("self.read_ea_high();", "self.aa_hi();");
+                "self.jmp();".to_string()
+            },
+            "JSL" => {
+                self.add("self.read_pc();", "self.aa_lo();");
+                self.add("self.read_pc();", "self.aa_hi();");
+                self.add("self.push_low(self.pbr as u16);", "");
+                self.add("self.io_stack();", "");
+                self.add("self.read_pc();", "self.aa_bank();");
+                self.add("self.push_high(self.pc.wrapping_sub(1));", "");
+                self.add("self.push_low(self.pc.wrapping_sub(1));", "");
+                "self.jml();".to_string()
+            },
+            "JMP" | "JML" => {
+                self.add("self.read_pc();", "self.aa_lo();");
+                match instruction.2 {
+                    W65C816AddressingMode::Absolute => {
+                        self.add("self.read_pc();", "self.aa_hi();");
+                        "self.jmp();".to_string()
+                    },
+                    W65C816AddressingMode::AbsoluteLong => {
+                        self.add("self.read_pc();", "self.aa_hi();");
+                        self.add("self.read_pc();", "self.aa_bank();");
+                        "self.jml();".to_string()
+                    },
+                    W65C816AddressingMode::AbsoluteIndirect => {
+                        self.add("self.read_pc();", "self.aa_hi(); self.set_pointer();");
+                        self.add("self.read_ea();", "self.aa_lo();");
+                        self.add("self.read_ea_high();", "self.aa_hi();");
+                        "self.jmp();".to_string()
+                    },
+                    W65C816AddressingMode::AbsoluteIndirectLong => {
+                        self.add("self.read_pc();", "self.aa_hi(); self.set_pointer();");
+                        self.add("self.read_ea();", "self.aa_lo();");
+                        self.add("self.read_ea_high();", "self.aa_hi();");
+                        self.add("self.read_ea_bank();", "self.aa_bank();");
+                        "self.jml();".to_string()
+                    },
+                    W65C816AddressingMode::AbsoluteIndexedIndirect => {
+                        self.add("self.read_pc();", "self.aa_hi(); self.set_program_pointer(self.x);");
+                        self.add("self.io();", "");
+                        self.add("self.read_ea();", "self.aa_lo();");
+                        self.add("self.read_ea_high();", "self.aa_hi();");
+                        "self.jmp();".to_string()
+                    },
+                    _ => unreachable!("Unexpected addressing mode for {}", mnemonic),
+                }
+            },
+            "RTS" | "RTL" | "RTI" => {
+                self.add("self.io();", "");
+                self.add("self.io();", "");
+                if mnemonic == "RTI" {
+                    self.add("self.pull();", "self.value_lo(); self.plp();");
+                }
+                self.add("self.pull();", "self.aa_lo();");
+                self.add("self.pull();", "self.aa_hi();");
+                match mnemonic {
+                    "RTS" => self.add("self.io_stack();", ""),
+                    "RTL" => self.add("self.pull();", "self.aa_bank();"),
+                    _ => self.add_if("!self.e", "self.pull();", "self.aa_bank();"),
+                }
+                format!("self.{}();", operation)
+            },
+            "PHA" | "PHB" | "PHD" | "PHK" | "PHP" | "PHX" | "PHY" => {
+                let source = match mnemonic {
+                    "PHA" => "self.a",
+                    "PHB" => "self.dbr as u16",
+                    "PHD" => "self.d",
+                    "PHK" => "self.pbr as u16",
+                    "PHP" => "self.p.as_u8() as u16",
+                    "PHX" => "self.x",
+                    _ => "self.y",
+                };
+                self.add("self.io();", "");
+                match mnemonic {
+                    "PHD" => self.add(&format!("self.push_high({});", source), ""),
+                    "PHB" | "PHK" | "PHP" => (),
+                    _ => self.add_if(wide, &format!("self.push_high({});", source), ""),
+                }
+                self.add(&format!("self.push_low({});", source), "");
+                String::new()
+            },
+            "PLA" | "PLB" | "PLD" | "PLP" | "PLX" | "PLY" => {
+                self.add("self.io();", "");
+                self.add("self.io();", "");
+                self.add("self.pull();", "self.value_lo();");
+                match mnemonic {
+                    "PLD" => self.add("self.pull();", "self.value_hi();"),
+                    "PLB" | "PLP" => (),
+                    _ => self.add_if(wide, "self.pull();", "self.value_hi();"),
+                }
+                format!("self.{}();", operation)
+            },
+            "PEA" | "PEI" | "PER" => {
+                match mnemonic {
+                    "PEA" => {
+                        self.add("self.read_pc();", "self.aa_lo();");
+                        self.add("self.read_pc();", "self.aa_hi();");
+                    },
+                    "PEI" => {
+                        self.add("self.read_pc();", "self.direct_address(0);");
+                        self.add_if("self.direct_page_cycle_needed()", "self.io();", "");
+                        self.add("self.read_ea();", "self.aa_lo();");
+                        self.add("self.read_ea_high();", "self.aa_hi();");
+                    },
+                    _ => {
+                        self.add("self.read_pc();", "self.aa_lo();");
+                        self.add("self.read_pc();", "self.aa_hi();");
+                        self.add("self.io();", "self.per();");
+                    },
+                }
+                self.add("self.push_high(self.aa);", "");
+                self.add("self.push_low(self.aa);", "");
+                String::new()
+            },
+            "BCC" | "BCS" | "BEQ" | "BMI" | "BNE" | "BPL" | "BRA" | "BVC" | "BVS" => {
+                let taken = match mnemonic {
+                    "BCC" => Some("!self.p.c"),
+                    "BCS" => Some("self.p.c"),
+                    "BEQ" => Some("self.p.z"),
+                    "BMI" => Some("self.p.n"),
+                    "BNE" => Some("!self.p.z"),
+                    "BPL" => Some("!self.p.n"),
+                    "BVC" => Some("!self.p.v"),
+                    "BVS" => Some("self.p.v"),
+                    _ => None,
+                };
+                self.add("self.read_pc();", "self.branch_offset();");
+                match taken {
+                    Some(condition) => self.add_if(condition, "self.io();", "self.branch();"),
+                    None => self.add("self.io();", "self.branch();"),
+                }
+                // Only emulation mode takes an extra cycle when the branch crosses a page.
+                self.add_if("self.e && self.page_crossed", "self.io();", "");
+                String::new()
+            },
+            "BRL" => {
+                self.add("self.read_pc();", "self.aa_lo();");
+                self.add("self.read_pc();", "self.aa_hi();");
+                self.add("self.io();", "");
+                "self.brl();".to_string()
+            },
+            "MVN" | "MVP" => {
+                self.add("self.read_pc();", "self.block_move_destination();");
+                self.add("self.read_pc();", "self.aa_bank();");
+                self.add("self.read_block_source();", "self.value_lo();");
+                self.add("self.write_block_destination();", "");
+                self.add("self.io();", "");
+                self.add("self.io();", "");
+                format!("self.{}();", operation)
+            },
+            "REP" | "SEP" => {
+                self.add("self.read_pc();", "self.value_lo();");
+                self.add("self.io();", "");
+                format!("self.{}();", operation)
+            },
+            "WDM" => {
+                self.add("self.read_pc();", "");
+                String::new()
+            },
+            "XBA" => {
+                self.add("self.io();", "");
+                self.add("self.io();", "");
+                "self.xba();".to_string()
+            },
+            "WAI" | "STP" => {
+                self.add("self.io();", "");
+                self.add(&format!("self.io(); self.{}();", operation), "");
+                String::new()
+            },
+            _ => self.encode_generic(instruction),
+        }
+    }
+
+    /// Loads, stores, arithmetic and read / modify / write instructions, which
+    /// only differ in how they form the effective address.
+    fn encode_generic(&mut self, instruction: &W65C816Instruction) -> String {
+        let mnemonic = instruction.1;
+        let operation = mnemonic.to_lowercase();
+        let wide = Self::sixteen_bit_condition(mnemonic);
+
+        // Reads only take the indexing cycle when they cross a page, or the index registers are 16 bits wide.
+        let index_condition = "self.index_cycle_needed()";
+        let add_index_cycle = |builder: &mut Self, setup: &str| {
+            if instruction.3 == MemoryAccess::Read {
+                builder.add_if(index_condition, setup, "");
+            } else {
+                builder.add(setup, "");
+            }
+        };
+        let direct_page_condition = "self.direct_page_cycle_needed()";
+
+        match instruction.2 {
+            W65C816AddressingMode::Implied => {
+                self.add("self.io();", "");
+                return format!("self.{}();", operation);
+            },
+            W65C816AddressingMode::Accumulator => {
+                self.add("self.io();", "");
+                return format!("self.{}a();", operation);
+            },
+            W65C816AddressingMode::Immediate => {
+                self.add("self.read_pc();", "self.value_lo();");
+                self.add_if(wide, "self.read_pc();", "self.value_hi();");
+                return match mnemonic {
+                    "BIT" => "self.bit_immediate();".to_string(),
+                    _ => format!("self.{}();", operation),
+                };
+            },
+            W65C816AddressingMode::Absolute => {
+                self.add("self.read_pc();", "self.aa_lo();");
+                self.add("self.read_pc();", "self.aa_hi(); self.set_effective_address(self.dbr, 0);");
+            },
+            W65C816AddressingMode::AbsoluteX | W65C816AddressingMode::AbsoluteY => {
+                let index = if instruction.2 == W65C816AddressingMode::AbsoluteX { "self.x" } else { "self.y" };
+                self.add("self.read_pc();", "self.aa_lo();");
+                self.add("self.read_pc();", &format!("self.aa_hi(); self.set_effective_address(self.dbr, {});", index));
+                add_index_cycle(self, "self.io_uncarried();");
+            },
+            W65C816AddressingMode::AbsoluteLong | W65C816AddressingMode::AbsoluteLongX => {
+                let index = if instruction.2 == W65C816AddressingMode::AbsoluteLongX { "self.x" } else { "0" };
+                self.add("self.read_pc();", "self.aa_lo();");
+                self.add("self.read_pc();", "self.aa_hi();");
+                self.add("self.read_pc();", &format!("self.aa_bank(); self.set_effective_address(self.aa_bank, {});", index));
+            },
+            W65C816AddressingMode::Direct => {
+                self.add("self.read_pc();", "self.direct_address(0);");
+                self.add_if(direct_page_condition, "self.io();", "");
+            },
+            W65C816AddressingMode::DirectX | W65C816AddressingMode::DirectY => {
+                let index = if instruction.2 == W65C816AddressingMode::DirectX { "self.x" } else { "self.y" };
+                self.add("self.read_pc();", &format!("self.direct_address({});", index));
+                self.add_if(direct_page_condition, "self.io();", "");
+                self.add("self.io();", "");
+            },
+            W65C816AddressingMode::DirectIndirect => {
+                self.add("self.read_pc();", "self.direct_address(0);");
+                self.add_if(direct_page_condition, "self.io();", "");
+                self.add("self.read_ea();", "self.aa_lo();");
+                self.add("self.read_ea_high();", "self.aa_hi(); self.set_effective_address(self.dbr, 0);");
+            },
+            W65C816AddressingMode::DirectIndexedIndirect => {
+                self.add("self.read_pc();", "self.direct_address(self.x);");
+                self.add_if(direct_page_condition, "self.io();", "");
+                self.add("self.io();", "");
+                self.add("self.read_ea();", "self.aa_lo();");
+                self.add("self.read_ea_high();", "self.aa_hi(); self.set_effective_address(self.dbr, 0);");
+            },
+            W65C816AddressingMode::DirectIndirectIndexed => {
+                self.add("self.read_pc();", "self.direct_address(0);");
+                self.add_if(direct_page_condition, "self.io();", "");
+                self.add("self.read_ea();", "self.aa_lo();");
+                self.add("self.read_ea_high();", "self.aa_hi(); self.set_effective_address(self.dbr, self.y);");
+                add_index_cycle(self, "self.io_uncarried();");
+            },
+            W65C816AddressingMode::DirectIndirectLong | W65C816AddressingMode::DirectIndirectLongIndexed => {
+                let index = if instruction.2 == W65C816AddressingMode::DirectIndirectLongIndexed { "self.y" } else { "0" };
+                self.add("self.read_pc();", "self.direct_long_address();");
+                self.add_if(direct_page_condition, "self.io();", "");
+                self.add("self.read_ea();", "self.aa_lo();");
+                self.add("self.read_ea_high();", "self.aa_hi();");
+                self.add("self.read_ea_bank();", &format!("self.aa_bank(); self.set_effective_address(self.aa_bank, {});", index));
+            },
+            W65C816AddressingMode::StackRelative => {
+                self.add("self.read_pc();", "self.stack_relative_address();");
+                self.add("self.io();", "");
+            },
+            W65C816AddressingMode::StackRelativeIndirectIndexed => {
+                self.add("self.read_pc();", "self.stack_relative_address();");
+                self.add("self.io();", "");
+                self.add("self.read_ea();", "self.aa_lo();");
+                self.add("self.read_ea_high();", "self.aa_hi(); self.set_effective_address(self.dbr, self.y);");
+                self.add("self.io();", "");
+            },
+            _ => unreachable!("Unexpected addressing mode for {}", mnemonic),
+        }
+
+        match instruction.3 {
+            MemoryAccess::Read => {
+                self.add("self.read_ea();", "self.value_lo();");
+                self.add_if(wide, "self.read_ea_high();", "self.value_hi();");
+                format!("self.{}();", operation)
+            },
+            MemoryAccess::Write => {
+                self.add(&format!("self.{}(); self.write_ea();", operation), "");
+                self.add_if(wide, "self.write_ea_high();", "");
+                String::new()
+            },
+            // The high byte is written first, so that the low byte is written last as on the 6502.
+            MemoryAccess::ReadWrite => {
+                self.add("self.read_ea(); self.lock();", "self.value_lo();");
+                self.add_if(wide, "self.read_ea_high(); self.lock();", "self.value_hi();");
+                self.add("self.modify_cycle();", &format!("self.{}();", operation));
+                self.add_if(wide, "self.write_ea_high(); self.lock();", "");
+                self.add("self.write_ea(); self.lock();", "");
+                String::new()
+            },
+            MemoryAccess::None => unreachable!("Missing memory access for {}", mnemonic),
+        }
+    }
+
+    /// Setup for the bus cycle at `index`, including any cycles that follow it when it is skipped.
+    fn setup_chain(&self, index: usize) -> String {
+        let cycle = &self.cycles[index];
+        match &cycle.condition {
+            Some(condition) => format!(
+                "if {} {{ {} }} else {{ self.tr += 1; {} }}",
+                condition,
+                cycle.setup,
+                self.setup_chain(index + 1)),
+            None => cycle.setup.clone(),
+        }
+    }
+}
+
+struct W65C816InstructionCode {
+    comment: String,
+    lines: Vec<String>,
+}
+
+impl W65C816InstructionCode {
+    fn from_instruction(instruction: &W65C816Instruction) -> W65C816InstructionCode {
+        let comment = format!("{0} {1}", instruction.1, instruction.2.as_string());
+
+        let mut code_builder = W65C816CodeBuilder::new();
+        let operation = code_builder.encode(instruction);
+
+        // The last cycle of every instruction fetches the next opcode.
+        let fetch = if operation.is_empty() {
+            "self.fetch_next_instruction();".to_string()
+        } else {
+            format!("{} self.fetch_next_instruction();", operation)
+        };
+        code_builder.cycles.push(BusCycle { condition: None, setup: fetch, process: String::new() });
+
+        // Line k finishes bus cycle k (counting the opcode fetch as cycle 0), and sets up the next one.
+        let lines = (0..code_builder.cycles.len())
+            .map(|index| {
+                let process = if index == 0 { "" } else { code_builder.cycles[index - 1].process.as_str() };
+                let setup = code_builder.setup_chain(index);
+                if process.is_empty() { setup } else { format!("{} {}", process, setup) }
+            })
+            .collect();
+
+        W65C816InstructionCode {
+            comment,
+            lines,
+        }
+    }
+}
+
+fn write_w65c816_instructions(file_name: &str) -> Result<(), std::io::Error> {
+    let out_dir = env::var_os("OUT_DIR").unwrap();
+    let dest_path = Path::new(&out_dir).join(file_name);
+    let mut buffer = File::create(&dest_path)?;
+
+    writeln!(buffer, "// This is a generated file. Do not modify.")?;
+    writeln!(buffer)?;
+    writeln!(buffer, "match (self.ir, self.tr) {{")?;
+
+    for (opcode, instruction) in W65C816_INSTRUCTIONS.iter().enumerate() {
+        assert_eq!(opcode, instruction.0 as usize, "Missing or duplicate opcode in W65C816_INSTRUCTIONS");
+
+        let instruction_code = W65C816InstructionCode::from_instruction(instruction);
+
+        writeln!(buffer, "    // {}", instruction_code.comment)?;
+
+        for (index, line) in instruction_code.lines.iter().enumerate() {
+            writeln!(buffer, "    (0x{:02X}, {}) => {{ {} }},", instruction.0, index, line)?;
+        }
+
+        writeln!(buffer)?;
+    }
+
+    write!(buffer, "    _ => unreachable!(\"Invalid timing {{}} for opcode 0x{{:02X}}\", self.tr, self.ir)")?;
+
+    writeln!(buffer, "}}")?;
+
+    Ok(())
+}
+
+fn main() -> Result<(), std::io::Error> {
+    write_instructions("mos6502_instructions.generated.rs", &INSTRUCTIONS, Variant::Nmos)?;
+    write_instructions("wdc65c02_instructions.generated.rs", &CMOS_INSTRUCTIONS, Variant::Cmos)?;
+    write_fast_instructions("mos6502_fast_instructions.generated.rs", &INSTRUCTIONS, Variant::Nmos)?;
+    write_fast_instructions("wdc65c02_fast_instructions.generated.rs", &CMOS_INSTRUCTIONS, Variant::Cmos)?;
+    write_opcode_tables("m6502_opcodes.generated.rs")?;
+    write_w65c816_instructions("w65c816_instructions.generated.rs")?;
+
     println!("cargo:rerun-if-changed=build.rs");
 
     Ok(())
-}
\ No newline at end of file
+}
@@ -21,6 +21,16 @@ impl M6502 {
         self.address_lo = self.ad.lo.wrapping_add(index_register_value);
     }
 
+    /// On the 65C02, if adding the index crosses a page, the dummy read in this cycle
+    /// is from the last operand byte, rather than from an address on the wrong page.
+    pub(crate) fn addressing_mode_absolute_indexed_cycle_2_cmos(&mut self, index_register_value: u8) {
+        self.ad.hi = self.data;
+        if self.ad.wrapping_add(index_register_value).hi == self.ad.hi {
+            self.address_hi = self.ad.hi;
+            self.address_lo = self.ad.lo.wrapping_add(index_register_value);
+        }
+    }
+
     /// If, when the index register is added to BAL (the low byte of the base address),
     /// the resulting address is on the same page, then we skip the next cycle.
     /// 
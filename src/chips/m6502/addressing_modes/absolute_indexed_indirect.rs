@@ -0,0 +1,40 @@
+use super::super::M6502;
+
+// (abs,X) addressing, only used by JMP on the 65C02. The 65C02's JMP (abs)
+// also uses this, with an index of 0, because it shares the same timing.
+impl M6502 {
+    /// Read low byte of base address.
+    pub(crate) fn addressing_mode_absolute_indexed_indirect_cycle_0(&mut self) {
+        self.set_address(self.pc);
+        self.pc = self.pc.wrapping_add(1);
+    }
+
+    /// Read high byte of base address.
+    pub(crate) fn addressing_mode_absolute_indexed_indirect_cycle_1(&mut self) {
+        self.set_address(self.pc);
+        self.pc = self.pc.wrapping_add(1);
+        self.ad.lo = self.data;
+    }
+
+    /// Internal operation. The high byte of the base address is read again.
+    pub(crate) fn addressing_mode_absolute_indexed_indirect_cycle_2(&mut self) {
+        self.ad.hi = self.data;
+    }
+
+    /// Read low byte of pointer stored at (base address + index).
+    pub(crate) fn addressing_mode_absolute_indexed_indirect_cycle_3(&mut self, index_register_value: u8) {
+        self.ad = self.ad.wrapping_add(index_register_value);
+        self.set_address(self.ad);
+    }
+
+    /// Read high byte of pointer. Unlike the NMOS JMP (abs), this correctly crosses pages.
+    pub(crate) fn addressing_mode_absolute_indexed_indirect_cycle_4(&mut self) {
+        self.set_address(self.ad.wrapping_add(1));
+        self.ad.lo = self.data;
+    }
+
+    pub(crate) fn addressing_mode_absolute_indexed_indirect_cycle_5(&mut self) {
+        self.address_hi = self.data;
+        self.address_lo = self.ad.lo;
+    }
+}
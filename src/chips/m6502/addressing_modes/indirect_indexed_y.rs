@@ -23,6 +23,16 @@ impl M6502 {
         self.address_lo = self.ad.lo.wrapping_add(self.y);
     }
 
+    /// On the 65C02, if adding Y crosses a page, the dummy read in this cycle
+    /// is from the pointer's high byte again, rather than from an address on the wrong page.
+    pub(crate) fn addressing_mode_indirect_indexed_y_cycle_3_cmos(&mut self) {
+        self.ad.hi = self.data;
+        if self.ad.wrapping_add(self.y).hi == self.ad.hi {
+            self.address_hi = self.ad.hi;
+            self.address_lo = self.ad.lo.wrapping_add(self.y);
+        }
+    }
+
     pub(crate) fn addressing_mode_indirect_indexed_y_cycle_3_read(&mut self) {
         let without_carry = self.ad.hi;
         let with_carry = self.ad.wrapping_add(self.y).hi;
@@ -1,5 +1,6 @@
 mod absolute;
 mod absolute_indexed;
+mod absolute_indexed_indirect;
 mod immediate;
 mod indexed_indirect_x;
 mod indirect;
@@ -7,4 +8,6 @@ mod indirect_indexed_y;
 mod invalid;
 mod none;
 mod zero_page;
-mod zero_page_indexed;
\ No newline at end of file
+mod zero_page_indexed;
+mod zero_page_indirect;
+mod zero_page_relative;
\ No newline at end of file
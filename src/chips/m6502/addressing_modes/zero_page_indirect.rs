@@ -0,0 +1,27 @@
+use super::super::M6502;
+
+// (zp) addressing, only available on the 65C02.
+impl M6502 {
+    pub(crate) fn addressing_mode_zero_page_indirect_cycle_0(&mut self) {
+        self.set_address(self.pc);
+        self.pc = self.pc.wrapping_add(1);
+    }
+
+    /// Read low byte of pointer stored in zero page.
+    pub(crate) fn addressing_mode_zero_page_indirect_cycle_1(&mut self) {
+        self.ad.hi = 0;
+        self.ad.lo = self.data;
+        self.set_address(self.ad);
+    }
+
+    /// Read high byte of pointer. This wraps around within zero page.
+    pub(crate) fn addressing_mode_zero_page_indirect_cycle_2(&mut self) {
+        self.address_lo = self.ad.lo.wrapping_add(1);
+        self.ad.lo = self.data;
+    }
+
+    pub(crate) fn addressing_mode_zero_page_indirect_cycle_3(&mut self) {
+        self.address_hi = self.data;
+        self.address_lo = self.ad.lo;
+    }
+}
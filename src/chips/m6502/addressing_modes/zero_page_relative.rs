@@ -0,0 +1,16 @@
+use super::super::M6502;
+
+// zp,rel addressing, used by BBR and BBS on the 65C02.
+// Cycles 0 and 1 are the same as zero page addressing.
+impl M6502 {
+    /// Keep the zero page value for the branch test. The zero page address is read again.
+    pub(crate) fn addressing_mode_zero_page_relative_cycle_2(&mut self) {
+        self.ad.lo = self.data;
+    }
+
+    /// Read branch offset, increment PC.
+    pub(crate) fn addressing_mode_zero_page_relative_cycle_3(&mut self) {
+        self.set_address(self.pc);
+        self.pc = self.pc.wrapping_add(1);
+    }
+}
@@ -0,0 +1,900 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use super::{opcodes, AddressingMode, M6502Variant};
+
+pub struct AssemblerOptions {
+    pub variant: M6502Variant,
+
+    /// Allows undocumented NMOS opcodes, and the unused 65C02 opcodes, to be assembled.
+    pub unofficial_opcodes: bool,
+}
+
+impl Default for AssemblerOptions {
+    fn default() -> Self {
+        Self {
+            variant: M6502Variant::Nmos6502,
+            unofficial_opcodes: false,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct AssemblerError {
+    /// Line number in the source, starting from 1.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AssemblerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AssemblerError {}
+
+/// A run of bytes that starts at a given address. A new segment is started by each `.org`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Segment {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+}
+
+pub struct Program {
+    pub segments: Vec<Segment>,
+    labels: HashMap<String, u16>,
+}
+
+impl Program {
+    /// Returns the address of a label, or the value of a constant.
+    pub fn label(&self, name: &str) -> Option<u16> {
+        self.labels.get(name).copied()
+    }
+
+    /// Copies each segment into memory at its address.
+    pub fn load_into(&self, memory: &mut [u8]) {
+        for segment in &self.segments {
+            let start = segment.address as usize;
+            memory[start..(start + segment.bytes.len())].copy_from_slice(&segment.bytes);
+        }
+    }
+}
+
+/// Assembles source for the NMOS 6502, using only official opcodes.
+///
+/// Each line can have a label (`loop:`), an instruction or directive, and a comment (`; ...`).
+/// Supported directives are `.org`, `.byte` (which also takes strings) and `.word`,
+/// and constants can be defined with `name = expression`. `* = expression` is the same as `.org`.
+///
+/// Numbers can be written as `$FF`, `%1010`, `255` or `'A'`, and combined with `+ - * / % & | ^ << >>`,
+/// parentheses, and unary `-`, `~`, `<` (low byte) and `>` (high byte). `*` is the current address.
+/// Hex numbers with more than two digits, such as `$0012`, always use absolute addressing.
+/// An operand that is entirely wrapped in parentheses is indirect.
+pub fn assemble(source: &str) -> Result<Program, AssemblerError> {
+    assemble_with_options(source, AssemblerOptions::default())
+}
+
+pub fn assemble_with_options(source: &str, options: AssemblerOptions) -> Result<Program, AssemblerError> {
+    let lines = source
+        .lines()
+        .enumerate()
+        .map(|(index, text)| parse_line(text).map_err(|message| AssemblerError { line: index + 1, message }))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let assembler = Assembler {
+        options,
+        labels: HashMap::new(),
+    };
+    assembler.assemble(&lines)
+}
+
+//////////////////////////////////////////
+// Expressions
+//////////////////////////////////////////
+
+#[derive(Clone, Debug)]
+enum Expression {
+    /// The flag is set for hex numbers with more than two digits.
+    Number(i64, bool),
+    Label(String),
+    CurrentAddress,
+    Unary(char, Box<Expression>),
+    Binary(&'static str, Box<Expression>, Box<Expression>),
+}
+
+impl Expression {
+    /// Evaluates the expression. Returns `Ok(None)` if it uses a label that isn't defined yet.
+    fn evaluate(&self, labels: &HashMap<String, u16>, address: u16) -> Result<Option<i64>, String> {
+        Ok(match self {
+            Expression::Number(value, _) => Some(*value),
+            Expression::Label(name) => labels.get(name).map(|value| *value as i64),
+            Expression::CurrentAddress => Some(address as i64),
+            Expression::Unary(operator, operand) => operand.evaluate(labels, address)?.map(|value| match operator {
+                '-' => value.wrapping_neg(),
+                '~' => !value,
+                '<' => value & 0xFF,
+                '>' => (value >> 8) & 0xFF,
+                _ => unreachable!(),
+            }),
+            Expression::Binary(operator, left, right) => {
+                let (left, right) = match (left.evaluate(labels, address)?, right.evaluate(labels, address)?) {
+                    (Some(left), Some(right)) => (left, right),
+                    _ => return Ok(None),
+                };
+                Some(match *operator {
+                    "+" => left.wrapping_add(right),
+                    "-" => left.wrapping_sub(right),
+                    "*" => left.wrapping_mul(right),
+                    "/" | "%" if right == 0 => return Err("Division by zero".to_string()),
+                    "/" => left.wrapping_div(right),
+                    "%" => left.wrapping_rem(right),
+                    "&" => left & right,
+                    "|" => left | right,
+                    "^" => left ^ right,
+                    "<<" => left << (right & 63),
+                    ">>" => left >> (right & 63),
+                    _ => unreachable!(),
+                })
+            },
+        })
+    }
+
+    fn is_word_literal(&self) -> bool {
+        matches!(self, Expression::Number(_, true))
+    }
+}
+
+struct ExpressionParser<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl<'a> ExpressionParser<'a> {
+    fn parse(text: &str) -> Result<Expression, String> {
+        let mut parser = ExpressionParser { text, position: 0 };
+        let expression = parser.parse_binary(0)?;
+        parser.skip_whitespace();
+        if parser.position != text.len() {
+            return Err(format!("Unexpected '{}' in expression", &text[parser.position..]));
+        }
+        Ok(expression)
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.position..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    /// Binary operators, from lowest to highest precedence.
+    const OPERATORS: [&'static [&'static str]; 6] = [
+        &["|"],
+        &["^"],
+        &["&"],
+        &["<<", ">>"],
+        &["+", "-"],
+        &["*", "/", "%"],
+    ];
+
+    fn parse_binary(&mut self, level: usize) -> Result<Expression, String> {
+        if level == Self::OPERATORS.len() {
+            return self.parse_unary();
+        }
+
+        let mut left = self.parse_binary(level + 1)?;
+        loop {
+            self.skip_whitespace();
+            let operator = Self::OPERATORS[level].iter().find(|operator| self.rest().starts_with(**operator));
+            match operator {
+                Some(operator) => {
+                    self.position += operator.len();
+                    let right = self.parse_binary(level + 1)?;
+                    left = Expression::Binary(operator, Box::new(left), Box::new(right));
+                },
+                None => return Ok(left),
+            }
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Expression, String> {
+        self.skip_whitespace();
+        match self.rest().chars().next() {
+            Some(operator @ ('-' | '~' | '<' | '>')) => {
+                self.position += 1;
+                Ok(Expression::Unary(operator, Box::new(self.parse_unary()?)))
+            },
+            _ => self.parse_primary(),
+        }
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> &'a str {
+        let rest = self.rest();
+        let length = rest.find(|c: char| !predicate(c)).unwrap_or(rest.len());
+        self.position += length;
+        &rest[..length]
+    }
+
+    fn parse_number(digits: &str, radix: u32) -> Result<i64, String> {
+        i64::from_str_radix(digits, radix).map_err(|_| format!("Invalid number '{}'", digits))
+    }
+
+    fn parse_primary(&mut self) -> Result<Expression, String> {
+        let c = match self.rest().chars().next() {
+            Some(c) => c,
+            None => return Err("Expected an expression".to_string()),
+        };
+
+        match c {
+            '(' => {
+                self.position += 1;
+                let expression = self.parse_binary(0)?;
+                self.skip_whitespace();
+                if !self.rest().starts_with(')') {
+                    return Err("Expected ')'".to_string());
+                }
+                self.position += 1;
+                Ok(expression)
+            },
+            '*' => {
+                self.position += 1;
+                Ok(Expression::CurrentAddress)
+            },
+            '$' => {
+                self.position += 1;
+                let digits = self.take_while(|c| c.is_ascii_hexdigit());
+                Ok(Expression::Number(Self::parse_number(digits, 16)?, digits.len() > 2))
+            },
+            '%' => {
+                self.position += 1;
+                let digits = self.take_while(|c| c == '0' || c == '1');
+                Ok(Expression::Number(Self::parse_number(digits, 2)?, false))
+            },
+            '\'' => {
+                let mut chars = self.rest().chars();
+                match (chars.nth(1), chars.next()) {
+                    (Some(value), Some('\'')) if value.is_ascii() => {
+                        self.position += 3;
+                        Ok(Expression::Number(value as i64, false))
+                    },
+                    _ => Err("Invalid character literal".to_string()),
+                }
+            },
+            '0'..='9' => {
+                let digits = self.take_while(|c| c.is_ascii_digit());
+                Ok(Expression::Number(Self::parse_number(digits, 10)?, false))
+            },
+            _ if is_identifier_start(c) => {
+                let name = self.take_while(is_identifier_char);
+                Ok(Expression::Label(name.to_string()))
+            },
+            _ => Err(format!("Unexpected '{}' in expression", self.rest())),
+        }
+    }
+}
+
+fn is_identifier_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn is_identifier(text: &str) -> bool {
+    text.starts_with(is_identifier_start) && text.chars().all(is_identifier_char)
+}
+
+//////////////////////////////////////////
+// Statements
+//////////////////////////////////////////
+
+/// An operand, as written. The addressing mode is chosen later, once label values are known.
+#[derive(Clone, Debug)]
+enum Operand {
+    None,
+    Accumulator,
+    Immediate(Expression),
+    Direct(Expression),
+    DirectX(Expression),
+    DirectY(Expression),
+    Indirect(Expression),
+    IndirectX(Expression),
+    IndirectY(Expression),
+    Pair(Expression, Expression),
+}
+
+#[derive(Clone, Debug)]
+enum DataItem {
+    Expression(Expression),
+    String(Vec<u8>),
+}
+
+#[derive(Clone, Debug)]
+enum Statement {
+    Org(Expression),
+    Constant(String, Expression),
+    Bytes(Vec<DataItem>),
+    Words(Vec<Expression>),
+    Instruction(String, Operand),
+}
+
+struct Line {
+    label: Option<String>,
+    statement: Option<Statement>,
+}
+
+/// Splits on commas that aren't inside parentheses or quotes.
+fn split_top_level(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut quote = None;
+    let mut start = 0;
+
+    for (index, c) in text.char_indices() {
+        match (quote, c) {
+            (Some(q), _) if c == q => quote = None,
+            (Some(_), _) => {},
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') => depth -= 1,
+            (None, ',') if depth == 0 => {
+                parts.push(text[start..index].trim());
+                start = index + 1;
+            },
+            _ => {},
+        }
+    }
+
+    parts.push(text[start..].trim());
+    parts
+}
+
+/// Removes a trailing comment, ignoring semicolons inside quotes.
+fn strip_comment(text: &str) -> &str {
+    let mut quote = None;
+    for (index, c) in text.char_indices() {
+        match (quote, c) {
+            (Some(q), _) if c == q => quote = None,
+            (Some(_), _) => {},
+            (None, '"' | '\'') => quote = Some(c),
+            (None, ';') => return &text[..index],
+            _ => {},
+        }
+    }
+    text
+}
+
+fn parse_operand(text: &str) -> Result<Operand, String> {
+    if text.is_empty() {
+        return Ok(Operand::None);
+    }
+    if text.eq_ignore_ascii_case("A") {
+        return Ok(Operand::Accumulator);
+    }
+    if let Some(value) = text.strip_prefix('#') {
+        return Ok(Operand::Immediate(ExpressionParser::parse(value)?));
+    }
+
+    // Indirect operands are entirely wrapped in parentheses, optionally followed by ",Y".
+    if text.starts_with('(') {
+        let mut depth = 0;
+        let close = text.char_indices().find(|(_, c)| {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {},
+            }
+            depth == 0
+        });
+
+        if let Some((close, _)) = close {
+            let inner = &text[1..close];
+            let rest = text[(close + 1)..].trim();
+            let parts = split_top_level(inner);
+
+            if rest.is_empty() && parts.len() == 1 {
+                return Ok(Operand::Indirect(ExpressionParser::parse(inner)?));
+            }
+            if rest.is_empty() && parts.len() == 2 && parts[1].eq_ignore_ascii_case("X") {
+                return Ok(Operand::IndirectX(ExpressionParser::parse(parts[0])?));
+            }
+            if parts.len() == 1 && rest.strip_prefix(',').is_some_and(|index| index.trim().eq_ignore_ascii_case("Y")) {
+                return Ok(Operand::IndirectY(ExpressionParser::parse(inner)?));
+            }
+        }
+    }
+
+    let parts = split_top_level(text);
+    match parts.as_slice() {
+        [value] => Ok(Operand::Direct(ExpressionParser::parse(value)?)),
+        [value, index] if index.eq_ignore_ascii_case("X") => Ok(Operand::DirectX(ExpressionParser::parse(value)?)),
+        [value, index] if index.eq_ignore_ascii_case("Y") => Ok(Operand::DirectY(ExpressionParser::parse(value)?)),
+        [first, second] => Ok(Operand::Pair(ExpressionParser::parse(first)?, ExpressionParser::parse(second)?)),
+        _ => Err(format!("Invalid operand '{}'", text)),
+    }
+}
+
+fn parse_data_items(text: &str) -> Result<Vec<DataItem>, String> {
+    split_top_level(text)
+        .into_iter()
+        .map(|item| {
+            if item.len() >= 2 && item.starts_with('"') && item.ends_with('"') {
+                Ok(DataItem::String(item[1..(item.len() - 1)].bytes().collect()))
+            } else {
+                Ok(DataItem::Expression(ExpressionParser::parse(item)?))
+            }
+        })
+        .collect()
+}
+
+fn parse_line(text: &str) -> Result<Line, String> {
+    let mut text = strip_comment(text).trim();
+
+    // Constant definitions, including "* = address".
+    if let Some((name, value)) = text.split_once('=') {
+        let name = name.trim();
+        if name == "*" {
+            return Ok(Line { label: None, statement: Some(Statement::Org(ExpressionParser::parse(value)?)) });
+        }
+        if is_identifier(name) {
+            return Ok(Line { label: None, statement: Some(Statement::Constant(name.to_string(), ExpressionParser::parse(value)?)) });
+        }
+    }
+
+    let mut label = None;
+    if let Some((name, rest)) = text.split_once(':') {
+        if is_identifier(name.trim()) {
+            label = Some(name.trim().to_string());
+            text = rest.trim();
+        }
+    }
+
+    if text.is_empty() {
+        return Ok(Line { label, statement: None });
+    }
+
+    let (keyword, operand) = match text.find(char::is_whitespace) {
+        Some(index) => (&text[..index], text[index..].trim()),
+        None => (text, ""),
+    };
+
+    let statement = match keyword.to_ascii_lowercase().as_str() {
+        ".org" => Statement::Org(ExpressionParser::parse(operand)?),
+        ".byte" => Statement::Bytes(parse_data_items(operand)?),
+        ".word" => Statement::Words(split_top_level(operand).into_iter().map(ExpressionParser::parse).collect::<Result<_, _>>()?),
+        directive if directive.starts_with('.') => return Err(format!("Unknown directive '{}'", keyword)),
+        _ => Statement::Instruction(keyword.to_ascii_uppercase(), parse_operand(operand)?),
+    };
+
+    Ok(Line { label, statement: Some(statement) })
+}
+
+//////////////////////////////////////////
+// Assembly
+//////////////////////////////////////////
+
+struct Assembler {
+    options: AssemblerOptions,
+    labels: HashMap<String, u16>,
+}
+
+impl Assembler {
+    fn find_opcode(&self, mnemonic: &str, addressing_mode: AddressingMode) -> Option<u8> {
+        let table = opcodes(self.options.variant);
+        let matches = |official_only: bool| {
+            (0..=255u8).find(|opcode| {
+                let metadata = &table[*opcode as usize];
+                metadata.mnemonic == mnemonic
+                    && metadata.addressing_mode == addressing_mode
+                    && (metadata.official || !official_only)
+            })
+        };
+
+        // Prefer the official encoding, such as $E9 instead of $EB for SBC #.
+        matches(true).or_else(|| if self.options.unofficial_opcodes { matches(false) } else { None })
+    }
+
+    fn has_mode(&self, mnemonic: &str, addressing_mode: AddressingMode) -> bool {
+        self.find_opcode(mnemonic, addressing_mode).is_some()
+    }
+
+    /// Picks the addressing mode for an instruction. Zero page is used if the operand's value
+    /// is already known to fit in a byte. Forward references always use absolute addressing.
+    fn choose_addressing_mode(&self, mnemonic: &str, operand: &Operand, address: u16) -> Result<AddressingMode, String> {
+        let table = opcodes(self.options.variant);
+        if !table.iter().any(|opcode| opcode.mnemonic == mnemonic) {
+            return Err(format!("Unknown instruction '{}'", mnemonic));
+        }
+        if !table.iter().any(|opcode| opcode.mnemonic == mnemonic && (opcode.official || self.options.unofficial_opcodes)) {
+            return Err(format!("'{}' is an unofficial opcode, and unofficial opcodes are not enabled", mnemonic));
+        }
+
+        let fits_zero_page = |expression: &Expression| -> Result<bool, String> {
+            Ok(!expression.is_word_literal() && matches!(expression.evaluate(&self.labels, address)?, Some(0..=0xFF)))
+        };
+        let zero_page_or_absolute = |expression: &Expression, zero_page, absolute| -> Result<AddressingMode, String> {
+            if self.has_mode(mnemonic, zero_page) && (fits_zero_page(expression)? || !self.has_mode(mnemonic, absolute)) {
+                Ok(zero_page)
+            } else {
+                Ok(absolute)
+            }
+        };
+        let first_available = |modes: &[AddressingMode]| {
+            modes.iter().copied().find(|mode| self.has_mode(mnemonic, *mode)).unwrap_or(modes[0])
+        };
+
+        let addressing_mode = match operand {
+            Operand::None => first_available(&[AddressingMode::Implied, AddressingMode::Accumulator]),
+            Operand::Accumulator => AddressingMode::Accumulator,
+            Operand::Immediate(_) => AddressingMode::Immediate,
+            Operand::Direct(_) if self.has_mode(mnemonic, AddressingMode::Relative) => AddressingMode::Relative,
+            Operand::Direct(value) => zero_page_or_absolute(value, AddressingMode::ZeroPage, AddressingMode::Absolute)?,
+            Operand::DirectX(value) => zero_page_or_absolute(value, AddressingMode::ZeroPageX, AddressingMode::AbsoluteX)?,
+            Operand::DirectY(value) => zero_page_or_absolute(value, AddressingMode::ZeroPageY, AddressingMode::AbsoluteY)?,
+            Operand::Indirect(_) => first_available(&[AddressingMode::Indirect, AddressingMode::ZeroPageIndirect]),
+            Operand::IndirectX(_) => first_available(&[AddressingMode::IndexedIndirectX, AddressingMode::AbsoluteIndexedIndirect]),
+            Operand::IndirectY(_) => AddressingMode::IndirectIndexedY,
+            Operand::Pair(_, _) => AddressingMode::ZeroPageRelative,
+        };
+
+        if !self.has_mode(mnemonic, addressing_mode) {
+            return Err(format!("{:?} addressing is not available for '{}'", addressing_mode, mnemonic));
+        }
+
+        Ok(addressing_mode)
+    }
+
+    fn evaluate(&self, expression: &Expression, address: u16) -> Result<i64, String> {
+        match expression.evaluate(&self.labels, address)? {
+            Some(value) => Ok(value),
+            None => Err("Undefined label".to_string()),
+        }
+    }
+
+    fn evaluate_byte(&self, expression: &Expression, address: u16) -> Result<u8, String> {
+        match self.evaluate(expression, address)? {
+            value @ -0x80..=0xFF => Ok(value as u8),
+            value => Err(format!("Value {} does not fit in a byte", value)),
+        }
+    }
+
+    fn evaluate_word(&self, expression: &Expression, address: u16) -> Result<u16, String> {
+        match self.evaluate(expression, address)? {
+            value @ 0..=0xFFFF => Ok(value as u16),
+            value => Err(format!("Value {} does not fit in a word", value)),
+        }
+    }
+
+    fn evaluate_branch_offset(&self, expression: &Expression, address: u16, next_address: u16) -> Result<u8, String> {
+        let offset = self.evaluate(expression, address)? - next_address as i64;
+        match offset {
+            -0x80..=0x7F => Ok(offset as u8),
+            _ => Err(format!("Branch target is out of range ({} bytes away)", offset)),
+        }
+    }
+
+    fn statement_length(&self, statement: &Statement, addressing_mode: Option<AddressingMode>) -> usize {
+        match statement {
+            Statement::Org(_) | Statement::Constant(_, _) => 0,
+            Statement::Bytes(items) => items.iter().map(|item| match item {
+                DataItem::Expression(_) => 1,
+                DataItem::String(bytes) => bytes.len(),
+            }).sum(),
+            Statement::Words(values) => values.len() * 2,
+            Statement::Instruction(_, _) => 1 + addressing_mode.unwrap().operand_length() as usize,
+        }
+    }
+
+    fn encode_instruction(&self, mnemonic: &str, operand: &Operand, addressing_mode: AddressingMode, address: u16) -> Result<Vec<u8>, String> {
+        let mut bytes = vec![self.find_opcode(mnemonic, addressing_mode).unwrap()];
+
+        match (operand, addressing_mode.operand_length()) {
+            (Operand::None | Operand::Accumulator, _) => {},
+            (Operand::Direct(value), _) if addressing_mode == AddressingMode::Relative => {
+                bytes.push(self.evaluate_branch_offset(value, address, address.wrapping_add(2))?);
+            },
+            (Operand::Pair(zero_page, target), _) => {
+                bytes.push(self.evaluate_byte(zero_page, address)?);
+                bytes.push(self.evaluate_branch_offset(target, address, address.wrapping_add(3))?);
+            },
+            (Operand::Immediate(value), _) => bytes.push(self.evaluate_byte(value, address)?),
+            (Operand::Direct(value) | Operand::DirectX(value) | Operand::DirectY(value) |
+             Operand::Indirect(value) | Operand::IndirectX(value) | Operand::IndirectY(value), 1) => {
+                match self.evaluate(value, address)? {
+                    value @ 0..=0xFF => bytes.push(value as u8),
+                    value => return Err(format!("Zero page address {} is out of range", value)),
+                }
+            },
+            (Operand::Direct(value) | Operand::DirectX(value) | Operand::DirectY(value) |
+             Operand::Indirect(value) | Operand::IndirectX(value), _) => {
+                bytes.extend_from_slice(&self.evaluate_word(value, address)?.to_le_bytes());
+            },
+            _ => unreachable!(),
+        }
+
+        Ok(bytes)
+    }
+
+    fn define_label(&mut self, name: &str, value: i64) -> Result<(), String> {
+        if self.labels.contains_key(name) {
+            return Err(format!("'{}' is already defined", name));
+        }
+        if !(0..=0xFFFF).contains(&value) {
+            return Err(format!("Value {} of '{}' does not fit in a word", value, name));
+        }
+        self.labels.insert(name.to_string(), value as u16);
+        Ok(())
+    }
+
+    fn assemble(mut self, lines: &[Line]) -> Result<Program, AssemblerError> {
+        // First pass: work out addresses of labels, and which addressing mode each instruction uses.
+        let mut addressing_modes = vec![None; lines.len()];
+        let mut address = 0u16;
+
+        for (index, line) in lines.iter().enumerate() {
+            let error = |message| AssemblerError { line: index + 1, message };
+
+            if let Some(label) = &line.label {
+                self.define_label(label, address as i64).map_err(error)?;
+            }
+
+            match &line.statement {
+                Some(Statement::Org(value)) => address = self.evaluate_word(value, address).map_err(error)?,
+                Some(Statement::Constant(name, value)) => {
+                    let value = self.evaluate(value, address).map_err(error)?;
+                    self.define_label(name, value).map_err(error)?;
+                },
+                Some(Statement::Instruction(mnemonic, operand)) => {
+                    addressing_modes[index] = Some(self.choose_addressing_mode(mnemonic, operand, address).map_err(error)?);
+                },
+                _ => {},
+            }
+
+            if let Some(statement) = &line.statement {
+                address = address.wrapping_add(self.statement_length(statement, addressing_modes[index]) as u16);
+            }
+        }
+
+        // Second pass: all labels are now known, so generate the bytes.
+        let mut segments: Vec<Segment> = Vec::new();
+        let mut address = 0u16;
+
+        for (index, line) in lines.iter().enumerate() {
+            let error = |message| AssemblerError { line: index + 1, message };
+
+            let bytes = match &line.statement {
+                None | Some(Statement::Constant(_, _)) => continue,
+                Some(Statement::Org(value)) => {
+                    address = self.evaluate_word(value, address).map_err(error)?;
+                    continue;
+                },
+                Some(Statement::Bytes(items)) => {
+                    let mut bytes = Vec::new();
+                    for item in items {
+                        match item {
+                            DataItem::Expression(value) => bytes.push(self.evaluate_byte(value, address).map_err(error)?),
+                            DataItem::String(string) => bytes.extend_from_slice(string),
+                        }
+                    }
+                    bytes
+                },
+                Some(Statement::Words(values)) => {
+                    let mut bytes = Vec::new();
+                    for value in values {
+                        bytes.extend_from_slice(&self.evaluate_word(value, address).map_err(error)?.to_le_bytes());
+                    }
+                    bytes
+                },
+                Some(Statement::Instruction(mnemonic, operand)) => {
+                    self.encode_instruction(mnemonic, operand, addressing_modes[index].unwrap(), address).map_err(error)?
+                },
+            };
+
+            match segments.last_mut() {
+                Some(segment) if segment.address.wrapping_add(segment.bytes.len() as u16) == address => {
+                    segment.bytes.extend_from_slice(&bytes);
+                },
+                _ => segments.push(Segment { address, bytes: bytes.clone() }),
+            }
+            address = address.wrapping_add(bytes.len() as u16);
+        }
+
+        Ok(Program {
+            segments,
+            labels: self.labels,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::disassemble_with_variant;
+
+    fn assemble_bytes(source: &str) -> Vec<u8> {
+        let program = assemble(source).unwrap();
+        assert_eq!(1, program.segments.len());
+        program.segments[0].bytes.clone()
+    }
+
+    fn assemble_error(source: &str) -> AssemblerError {
+        match assemble(source) {
+            Ok(_) => panic!("Expected an error"),
+            Err(error) => error,
+        }
+    }
+
+    #[test]
+    fn assembles_addressing_modes() {
+        let source = "
+            .org $C000
+            CLC
+            ASL
+            ROL A
+            LDA #$12
+            LDA $34
+            LDA $34,X
+            LDX $34,Y
+            LDA $1234
+            LDA $0034
+            LDA $1234,X
+            LDA $1234,Y
+            LDA $34,Y
+            LDA ($80,X)
+            LDA ($80),Y
+            JMP ($1234)
+            JSR $C000
+        ";
+
+        assert_eq!(
+            vec![
+                0x18, 0x0A, 0x2A, 0xA9, 0x12, 0xA5, 0x34, 0xB5, 0x34, 0xB6, 0x34,
+                0xAD, 0x34, 0x12, 0xAD, 0x34, 0x00, 0xBD, 0x34, 0x12, 0xB9, 0x34, 0x12,
+                0xB9, 0x34, 0x00, 0xA1, 0x80, 0xB1, 0x80, 0x6C, 0x34, 0x12, 0x20, 0x00, 0xC0,
+            ],
+            assemble_bytes(source));
+    }
+
+    #[test]
+    fn labels_and_branches() {
+        let source = "
+                .org $0400
+            start:
+                LDX #0
+            loop: INX
+                BNE loop
+                BEQ end     ; Forward reference
+                JMP start
+            end:
+                JMP end
+        ";
+        let program = assemble(source).unwrap();
+
+        assert_eq!(Some(0x0400), program.label("start"));
+        assert_eq!(Some(0x040A), program.label("end"));
+        assert_eq!(
+            vec![0xA2, 0x00, 0xE8, 0xD0, 0xFD, 0xF0, 0x03, 0x4C, 0x00, 0x04, 0x4C, 0x0A, 0x04],
+            program.segments[0].bytes);
+    }
+
+    #[test]
+    fn forward_references_use_absolute_addressing() {
+        let source = "
+            LDA value
+            value = $12
+            LDA value
+        ";
+        assert_eq!(vec![0xAD, 0x12, 0x00, 0xA5, 0x12], assemble_bytes(source));
+    }
+
+    #[test]
+    fn expressions() {
+        let source = "
+            .org $1000
+            table = $2345
+            size = 3 * (2 + 1)
+            LDA #<table
+            LDX #>table
+            LDY #size - 1
+            .byte %1010, 'A', -1, 1 << 4 | 1, ~0 & $FF
+            .word table + 1, *
+            .byte \"HI; there\"
+        ";
+        assert_eq!(
+            vec![
+                0xA9, 0x45, 0xA2, 0x23, 0xA0, 0x08,
+                0x0A, 0x41, 0xFF, 0x11, 0xFF,
+                0x46, 0x23, 0x0B, 0x10,
+                b'H', b'I', b';', b' ', b't', b'h', b'e', b'r', b'e',
+            ],
+            assemble_bytes(source));
+    }
+
+    #[test]
+    fn org_starts_new_segment() {
+        let program = assemble("
+            .org $0400
+            NOP
+            * = $FFFC
+            .word $0400
+        ").unwrap();
+
+        assert_eq!(
+            vec![
+                Segment { address: 0x0400, bytes: vec![0xEA] },
+                Segment { address: 0xFFFC, bytes: vec![0x00, 0x04] },
+            ],
+            program.segments);
+
+        let mut memory = vec![0; 0x10000];
+        program.load_into(&mut memory);
+        assert_eq!(0xEA, memory[0x0400]);
+        assert_eq!(0x04, memory[0xFFFD]);
+    }
+
+    #[test]
+    fn unofficial_opcodes_must_be_enabled() {
+        assert_eq!(
+            "line 1: 'LAX' is an unofficial opcode, and unofficial opcodes are not enabled",
+            assemble_error("LAX $12").to_string());
+
+        let options = AssemblerOptions { unofficial_opcodes: true, ..Default::default() };
+        let program = assemble_with_options("LAX $12\nSBC #1\nNOP #1", options).unwrap();
+        assert_eq!(vec![0xA7, 0x12, 0xE9, 0x01, 0x80, 0x01], program.segments[0].bytes);
+    }
+
+    #[test]
+    fn cmos_instructions() {
+        let options = AssemblerOptions { variant: M6502Variant::Cmos65C02, ..Default::default() };
+        let program = assemble_with_options("
+            .org $0200
+            start:
+            LDA ($12)
+            JMP ($1234,X)
+            JMP ($1234)
+            INC
+            STZ $12
+            BBR3 $12, start
+            BRA start
+        ", options).unwrap();
+
+        assert_eq!(
+            vec![0xB2, 0x12, 0x7C, 0x34, 0x12, 0x6C, 0x34, 0x12, 0x1A, 0x64, 0x12, 0x3F, 0x12, 0xF2, 0x80, 0xF0],
+            program.segments[0].bytes);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(AssemblerError { line: 2, message: "Unknown instruction 'FOO'".to_string() }, assemble_error("NOP\nFOO"));
+        assert_eq!("line 1: Undefined label", assemble_error("JMP nowhere").to_string());
+        assert_eq!("line 2: 'x' is already defined", assemble_error("x: NOP\nx: NOP").to_string());
+        assert_eq!("line 1: Unknown directive '.foo'", assemble_error(".foo 1").to_string());
+        assert_eq!("line 1: Indirect addressing is not available for 'LDA'", assemble_error("LDA ($1234)").to_string());
+        assert_eq!("line 1: Value 256 does not fit in a byte", assemble_error("LDA #256").to_string());
+        assert_eq!("line 1: Branch target is out of range (128 bytes away)", assemble_error("BNE end\n.org $0082\nend:").to_string());
+    }
+
+    /// Every instruction that the disassembler produces should assemble back to the same bytes.
+    #[test]
+    fn round_trips_through_disassembler() {
+        for variant in [M6502Variant::Nmos6502, M6502Variant::Cmos65C02] {
+            for (opcode, metadata) in opcodes(variant).iter().enumerate() {
+                if !metadata.official {
+                    continue;
+                }
+
+                let bytes = [opcode as u8, 0x34, 0x12];
+                let instruction = &disassemble_with_variant(variant, &bytes, 0x8000)[0];
+                let source = format!(".org $8000\n{}", instruction);
+
+                let options = AssemblerOptions { variant, ..Default::default() };
+                let program = assemble_with_options(&source, options).unwrap();
+                assert_eq!(instruction.bytes, program.segments[0].bytes, "{}", source);
+            }
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use super::M6502;
+
+/// Memory and I/O seen by the CPU when it is driven by [`M6502::step_cycle`],
+/// [`M6502::step_instruction`] or [`M6502::execute_instruction`].
+///
+/// These methods take care of the pin plumbing (PHI0, address bus, RW and data bus),
+/// so that a harness only needs to say what lives at each address.
+pub trait Bus {
+    /// Called for every read cycle. The returned value is placed on the data bus.
+    fn read(&mut self, address: u16) -> u8;
+
+    /// Called for every write cycle, with the value the CPU put on the data bus.
+    fn write(&mut self, address: u16, value: u8);
+
+    /// Returns the value at an address without any side effects.
+    /// Used by debugging tools, which must not disturb I/O registers.
+    fn peek(&self, address: u16) -> u8;
+
+    /// Called instead of [`Bus::read`] for each cycle that DMA, requested with [`M6502::request_dma`],
+    /// has taken from the CPU. The CPU's address is still on the pins, for devices that read from it
+    /// while the CPU is halted.
+    fn dma_cycle(&mut self, _cpu: &M6502) {}
+
+    /// Called once at the end of every cycle, after the read or write has happened.
+    /// [`M6502::execute_instruction`] only calls it after fetching the next opcode.
+    fn on_cycle(&mut self, _cpu: &M6502) {}
+}
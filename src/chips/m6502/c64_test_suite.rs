@@ -0,0 +1,149 @@
+//! A harness for Wolfgang Lorenz's C64 test suite. Each test is a C64 program, which only needs
+//! a few KERNAL routines to print its results and load the next test, so those are stubbed out.
+
+use std::{fs, path::Path};
+
+use super::{assemble, Bus, M6502};
+
+const BIN_PATH: &str = "test_assets/chips/mos6502/c64_test_suite/bin";
+
+fn petscii_to_ascii(character: u8) -> String {
+    match character {
+        147 => "\n------------\n".to_string(), // Clear
+        14 => "".to_string(), // Toggle lowercase/uppercase character set
+        0xC1..=0xDA => ((character - 0xC1 + 65) as char).to_string(),
+        0x41..=0x5A => ((character - 0x41 + 97) as char).to_string(),
+        _ => (character as char).to_string()
+    }
+}
+
+pub(crate) struct C64Bus {
+    pub ram: [u8; 0x10000],
+
+    /// Everything that the tests have printed, which is shown if one of them fails.
+    pub log: String,
+
+    /// Set when the running test asks the "KERNAL" to load the next test.
+    pub next_test_filename: Option<String>,
+}
+
+impl C64Bus {
+    /// Loads a test, and the KERNAL stubs and vectors that it needs.
+    pub fn new(filename: &str) -> Self {
+        let mut ram = [0; 0x10000];
+
+        // Load test data.
+        // First two bytes contain starting address.
+        let path = Path::new(BIN_PATH).join(filename);
+        let test_data = fs::read(path).unwrap();
+        let start_address = (test_data[0] as usize) | ((test_data[1] as usize) << 8);
+        ram[start_address..(start_address + test_data.len() - 2)].copy_from_slice(&test_data[2..]);
+
+        // Initialize some memory locations.
+        ram[0x0002] = 0x00;
+        ram[0xA002] = 0x00;
+        ram[0xA003] = 0x80;
+        ram[0xFFFE] = 0x48;
+        ram[0xFFFF] = 0xFF;
+        ram[0x01FE] = 0xFF;
+        ram[0x01FF] = 0x7F;
+
+        // Install KERNAL "IRQ handler" and stub routines.
+        let kernal = assemble("
+                .org $FF48
+                PHA
+                TXA
+                PHA
+                TYA
+                PHA
+                TSX
+                LDA $0104,X     ; Check B flag in pushed status register
+                AND #$10
+                BEQ irq
+                JMP ($0316)     ; BRK vector
+            irq:
+                JMP ($0314)     ; IRQ vector
+
+                .org $FFD2      ; CHROUT
+                RTS
+
+                .org $E16F      ; Load
+                NOP
+
+                .org $FFE4      ; GETIN
+                LDA #3
+                RTS
+        ").unwrap();
+        kernal.load_into(&mut ram);
+
+        // Initialize RESET vector.
+        ram[0xFFFC] = 0x01;
+        ram[0xFFFD] = 0x08;
+
+        Self {
+            ram,
+            log: String::new(),
+            next_test_filename: None,
+        }
+    }
+
+    /// Runs the test a cycle at a time with `step_cycle`, until it asks to load the next test,
+    /// and returns the name of that test. Panics if the test fails.
+    pub fn run(&mut self, mut step_cycle: impl FnMut(&mut Self)) -> String {
+        while self.next_test_filename.is_none() {
+            step_cycle(self);
+        }
+        self.next_test_filename.take().unwrap()
+    }
+}
+
+impl Bus for C64Bus {
+    fn read(&mut self, address: u16) -> u8 {
+        self.ram[address as usize]
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        self.ram[address as usize] = value;
+    }
+
+    fn peek(&self, address: u16) -> u8 {
+        self.ram[address as usize]
+    }
+
+    fn on_cycle(&mut self, cpu: &M6502) {
+        if !cpu.rw {
+            return;
+        }
+
+        match cpu.get_address() {
+            0xFFD2 => { // Print character
+                if cpu.a == 13 {
+                    self.log.push('\n');
+                } else {
+                    self.log += &petscii_to_ascii(cpu.a);
+                }
+                self.ram[0x030C] = 0x00;
+            },
+            0xE16F => { // Load
+                let filename_address = self.ram[0xBB] as usize | ((self.ram[0xBC] as usize) << 8);
+                let filename_len = self.ram[0xB7] as usize;
+                let mut filename = String::new();
+                for i in 0..filename_len {
+                    filename += &petscii_to_ascii(self.ram[filename_address + i]);
+                }
+                self.next_test_filename = Some(filename);
+            },
+            0x8000 | 0xA474 => { // Exit
+                panic!("Test failed:\n{}", self.log);
+            },
+            _ => {}
+        }
+    }
+}
+
+/// Sets up the registers the way the KERNAL leaves them when it starts a program.
+/// Call this after RESET.
+pub(crate) fn setup_registers(cpu: &mut M6502) {
+    cpu.sp = 0xFD;
+    cpu.p.i = true;
+}
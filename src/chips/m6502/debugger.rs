@@ -0,0 +1,386 @@
+use std::ops::RangeInclusive;
+
+use super::{Bus, BrkFlags, M6502, RunState};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Register {
+    A,
+    X,
+    Y,
+    SP,
+    P,
+    PC,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    LessThan,
+    GreaterThan,
+}
+
+/// Compares a register with a value, such as `X == $10`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Condition {
+    pub register: Register,
+    pub comparison: Comparison,
+    pub value: u16,
+}
+
+impl Condition {
+    pub fn new(register: Register, comparison: Comparison, value: u16) -> Self {
+        Self { register, comparison, value }
+    }
+
+    pub fn is_met(&self, cpu: &M6502) -> bool {
+        let register = match self.register {
+            Register::A => cpu.a as u16,
+            Register::X => cpu.x as u16,
+            Register::Y => cpu.y as u16,
+            Register::SP => cpu.sp as u16,
+            Register::P => cpu.p.as_u8(false) as u16,
+            Register::PC => cpu.pc.to_u16(),
+        };
+
+        match self.comparison {
+            Comparison::Equal => register == self.value,
+            Comparison::NotEqual => register != self.value,
+            Comparison::LessThan => register < self.value,
+            Comparison::GreaterThan => register > self.value,
+        }
+    }
+}
+
+/// Stops when an instruction is about to execute. The registers are checked
+/// at the opcode fetch, so they hold the values from the previous instruction.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Breakpoint {
+    /// If `None`, every instruction is checked against the condition.
+    pub address: Option<u16>,
+    pub condition: Option<Condition>,
+}
+
+impl Breakpoint {
+    pub fn at(address: u16) -> Self {
+        Self { address: Some(address), condition: None }
+    }
+
+    pub fn at_if(address: u16, condition: Condition) -> Self {
+        Self { address: Some(address), condition: Some(condition) }
+    }
+
+    pub fn when(condition: Condition) -> Self {
+        Self { address: None, condition: Some(condition) }
+    }
+
+    fn is_hit(&self, cpu: &M6502) -> bool {
+        self.address.is_none_or(|address| address == cpu.pc.to_u16())
+            && self.condition.is_none_or(|condition| condition.is_met(cpu))
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Access {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+/// Stops on any bus cycle that accesses an address in the range.
+/// This includes the dummy reads and writes that some instructions do.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Watchpoint {
+    pub addresses: RangeInclusive<u16>,
+    pub access: Access,
+}
+
+impl Watchpoint {
+    pub fn new(addresses: RangeInclusive<u16>, access: Access) -> Self {
+        Self { addresses, access }
+    }
+
+    fn is_hit(&self, address: u16, rw: bool) -> bool {
+        let access_matches = match self.access {
+            Access::Read => rw,
+            Access::Write => !rw,
+            Access::ReadWrite => true,
+        };
+        access_matches && self.addresses.contains(&address)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Interrupt {
+    Irq,
+    Nmi,
+    Reset,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum StopReason {
+    /// The opcode for the instruction at this address has just been fetched.
+    Breakpoint(Breakpoint),
+
+    /// The CPU has just read or written a watched address. `access` is either `Read` or `Write`.
+    Watchpoint { address: u16, access: Access, value: u8 },
+
+    /// The CPU has just started handling an interrupt, instead of the instruction at PC.
+    Interrupt(Interrupt),
+
+    /// The CPU was stopped by STP or JAM, and will stay that way until RESET.
+    Halted,
+
+    /// The maximum number of cycles passed to [`Debugger::run`] has been reached.
+    CycleLimit,
+}
+
+/// Breakpoints and watchpoints for an [`M6502`].
+///
+/// Use [`Debugger::run`] to step a CPU with a [`Bus`] until something is hit.
+/// Systems that drive the CPU through its pins can instead call
+/// [`Debugger::check`] at the end of every cycle.
+#[derive(Clone, Default, Debug)]
+pub struct Debugger {
+    pub breakpoints: Vec<Breakpoint>,
+    pub watchpoints: Vec<Watchpoint>,
+    pub break_on_interrupt: bool,
+
+    /// Whether the previous cycle was a read. RDY and DMA only pause the CPU on a read, which is then
+    /// repeated every cycle until it is released. What it hit has already been reported by then.
+    previous_rw: bool,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the reason to stop after the cycle that has just run, if there is one.
+    pub fn check(&mut self, cpu: &M6502) -> Option<StopReason> {
+        let paused = (!cpu.rdy || cpu.is_dma_cycle()) && self.previous_rw;
+        self.previous_rw = cpu.rw;
+
+        let address = cpu.get_address();
+
+        if !paused && self.watchpoints.iter().any(|watchpoint| watchpoint.is_hit(address, cpu.rw)) {
+            return Some(StopReason::Watchpoint {
+                address,
+                access: if cpu.rw { Access::Read } else { Access::Write },
+                value: cpu.data,
+            });
+        }
+
+        if cpu.sync && !paused {
+            if let Some(breakpoint) = self.breakpoints.iter().find(|breakpoint| breakpoint.is_hit(cpu)) {
+                return Some(StopReason::Breakpoint(*breakpoint));
+            }
+        }
+
+        // Interrupts are recognised in the cycle after the opcode fetch, which is the first cycle of the BRK sequence.
+        if self.break_on_interrupt && !paused && cpu.tr == 1 && cpu.brk_flags != BrkFlags::NONE {
+            let interrupt = if cpu.brk_flags.contains(BrkFlags::RESET) {
+                Interrupt::Reset
+            } else if cpu.brk_flags.contains(BrkFlags::NMI) {
+                Interrupt::Nmi
+            } else {
+                Interrupt::Irq
+            };
+            return Some(StopReason::Interrupt(interrupt));
+        }
+
+        if matches!(cpu.run_state, RunState::Stopped | RunState::Jammed) {
+            return Some(StopReason::Halted);
+        }
+
+        None
+    }
+
+    /// Runs the CPU one cycle at a time until there is a reason to stop,
+    /// or until `max_cycles` cycles have run.
+    pub fn run(&mut self, cpu: &mut M6502, bus: &mut impl Bus, max_cycles: u64) -> StopReason {
+        for _ in 0..max_cycles {
+            cpu.step_cycle(bus);
+
+            if let Some(reason) = self.check(cpu) {
+                return reason;
+            }
+        }
+
+        StopReason::CycleLimit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{assemble, Program};
+    use crate::chips::test_bus::Ram;
+
+    fn setup() -> (M6502, Ram, Program) {
+        let program = assemble("
+                .org $0400
+                CLI
+                LDX #0
+            loop:
+                INX
+                STX $10
+                LDA $20
+                JMP loop
+
+            irq:
+                RTI
+
+                .org $FFFC
+                .word $0400, irq
+        ").unwrap();
+
+        let mut ram = Ram::new();
+        program.load_into(&mut ram.data);
+
+        let mut cpu = M6502::new();
+        cpu.set_res(false);
+        cpu.set_res(true);
+        (cpu, ram, program)
+    }
+
+    #[test]
+    fn breakpoint() {
+        let (mut cpu, mut ram, program) = setup();
+        let loop_address = program.label("loop").unwrap();
+        let mut debugger = Debugger::new();
+        debugger.breakpoints.push(Breakpoint::at(loop_address));
+
+        assert_eq!(StopReason::Breakpoint(Breakpoint::at(loop_address)), debugger.run(&mut cpu, &mut ram, 1000));
+        assert_eq!(loop_address, cpu.pc.to_u16());
+        assert!(cpu.sync());
+        assert_eq!(0, cpu.x);
+
+        // Running again continues past the breakpoint, and stops at the next loop.
+        assert_eq!(StopReason::Breakpoint(Breakpoint::at(loop_address)), debugger.run(&mut cpu, &mut ram, 1000));
+        assert_eq!(1, cpu.x);
+
+        debugger.breakpoints.clear();
+        assert_eq!(StopReason::CycleLimit, debugger.run(&mut cpu, &mut ram, 1000));
+    }
+
+    #[test]
+    fn conditional_breakpoint() {
+        let (mut cpu, mut ram, program) = setup();
+        let loop_address = program.label("loop").unwrap();
+        let condition = Condition::new(Register::X, Comparison::Equal, 5);
+        let mut debugger = Debugger::new();
+        debugger.breakpoints.push(Breakpoint::at_if(loop_address, condition));
+
+        assert_eq!(StopReason::Breakpoint(Breakpoint::at_if(loop_address, condition)), debugger.run(&mut cpu, &mut ram, 1000));
+        assert_eq!(loop_address, cpu.pc.to_u16());
+        assert_eq!(5, cpu.x);
+
+        let condition = Condition::new(Register::A, Comparison::GreaterThan, 0x7F);
+        debugger.breakpoints = vec![Breakpoint::when(condition)];
+        ram.data[0x20] = 0x80;
+        assert_eq!(StopReason::Breakpoint(Breakpoint::when(condition)), debugger.run(&mut cpu, &mut ram, 1000));
+        assert_eq!(0x80, cpu.a);
+        assert_eq!(loop_address + 5, cpu.pc.to_u16());
+    }
+
+    #[test]
+    fn watchpoints() {
+        let (mut cpu, mut ram, _) = setup();
+        let mut debugger = Debugger::new();
+        debugger.watchpoints.push(Watchpoint::new(0x10..=0x1F, Access::Write));
+
+        assert_eq!(StopReason::Watchpoint { address: 0x10, access: Access::Write, value: 1 }, debugger.run(&mut cpu, &mut ram, 1000));
+        assert!(!cpu.rw);
+        assert_eq!(0x10, cpu.get_address());
+
+        debugger.watchpoints = vec![Watchpoint::new(0x20..=0x20, Access::Read)];
+        ram.data[0x20] = 0x42;
+        assert_eq!(StopReason::Watchpoint { address: 0x20, access: Access::Read, value: 0x42 }, debugger.run(&mut cpu, &mut ram, 1000));
+        assert!(cpu.rw);
+    }
+
+    #[test]
+    fn break_on_interrupt() {
+        let (mut cpu, mut ram, _) = setup();
+        let mut debugger = Debugger::new();
+        debugger.break_on_interrupt = true;
+
+        assert_eq!(StopReason::Interrupt(Interrupt::Reset), debugger.run(&mut cpu, &mut ram, 1000));
+        assert_eq!(StopReason::CycleLimit, debugger.run(&mut cpu, &mut ram, 100));
+
+        cpu.set_irq(false);
+        assert_eq!(StopReason::Interrupt(Interrupt::Irq), debugger.run(&mut cpu, &mut ram, 1000));
+        cpu.set_irq(true);
+        assert_eq!(StopReason::CycleLimit, debugger.run(&mut cpu, &mut ram, 100));
+
+        cpu.set_nmi(false);
+        assert_eq!(StopReason::Interrupt(Interrupt::Nmi), debugger.run(&mut cpu, &mut ram, 1000));
+    }
+
+    #[test]
+    fn break_on_interrupt_once_while_rdy_stalls() {
+        let (mut cpu, mut ram, _) = setup();
+        let mut debugger = Debugger::new();
+        debugger.break_on_interrupt = true;
+        assert_eq!(StopReason::Interrupt(Interrupt::Reset), debugger.run(&mut cpu, &mut ram, 1000));
+        debugger.run(&mut cpu, &mut ram, 100);
+
+        // Hold the CPU in the first cycle of the IRQ sequence.
+        cpu.set_irq(false);
+        assert_eq!(StopReason::Interrupt(Interrupt::Irq), debugger.run(&mut cpu, &mut ram, 1000));
+        cpu.set_irq(true);
+        cpu.set_rdy(false);
+        assert_eq!(StopReason::CycleLimit, debugger.run(&mut cpu, &mut ram, 10));
+        assert_eq!(1, cpu.tr);
+
+        cpu.set_rdy(true);
+        assert_eq!(StopReason::CycleLimit, debugger.run(&mut cpu, &mut ram, 100));
+    }
+
+    #[test]
+    fn breakpoint_once_while_rdy_stalls() {
+        let (mut cpu, mut ram, program) = setup();
+        let loop_address = program.label("loop").unwrap();
+        let mut debugger = Debugger::new();
+        debugger.breakpoints.push(Breakpoint::at(loop_address));
+        assert_eq!(StopReason::Breakpoint(Breakpoint::at(loop_address)), debugger.run(&mut cpu, &mut ram, 1000));
+
+        // Hold the CPU in the opcode fetch, as the 2600 does after STA WSYNC.
+        cpu.set_rdy(false);
+        for _ in 0..5 {
+            assert_eq!(StopReason::CycleLimit, debugger.run(&mut cpu, &mut ram, 1));
+        }
+        assert!(cpu.sync());
+
+        cpu.set_rdy(true);
+        assert_eq!(StopReason::Breakpoint(Breakpoint::at(loop_address)), debugger.run(&mut cpu, &mut ram, 1000));
+        assert_eq!(1, cpu.x);
+    }
+
+    #[test]
+    fn watchpoint_once_while_rdy_stalls() {
+        let (mut cpu, mut ram, _) = setup();
+        let mut debugger = Debugger::new();
+        debugger.watchpoints.push(Watchpoint::new(0x20..=0x20, Access::Read));
+        assert_eq!(StopReason::Watchpoint { address: 0x20, access: Access::Read, value: 0 }, debugger.run(&mut cpu, &mut ram, 1000));
+
+        cpu.set_rdy(false);
+        assert_eq!(StopReason::CycleLimit, debugger.run(&mut cpu, &mut ram, 5));
+        assert_eq!(0x20, cpu.get_address());
+
+        // Releasing RDY finishes the read that was already reported, so the next stop is on the next loop.
+        cpu.set_rdy(true);
+        assert_eq!(StopReason::Watchpoint { address: 0x20, access: Access::Read, value: 0 }, debugger.run(&mut cpu, &mut ram, 1000));
+        assert_eq!(2, cpu.x);
+    }
+
+    #[test]
+    fn halted() {
+        let (mut cpu, mut ram, program) = setup();
+        ram.data[program.label("loop").unwrap() as usize] = 0x02; // JAM
+
+        assert_eq!(StopReason::Halted, Debugger::new().run(&mut cpu, &mut ram, 1000));
+        assert!(cpu.is_jammed());
+    }
+}
@@ -0,0 +1,191 @@
+use std::fmt;
+
+use super::{opcodes, AddressingMode, M6502Variant, Opcode};
+
+/// A single instruction, as produced by [`disassemble`] or [`disassemble_instruction`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DisassembledInstruction {
+    pub address: u16,
+
+    /// The opcode, followed by any operand bytes.
+    pub bytes: Vec<u8>,
+
+    pub opcode: Opcode,
+
+    /// The operand as it would be written in assembly source, such as `($12),Y`.
+    /// Branch targets are shown as absolute addresses.
+    pub operand: String,
+}
+
+impl fmt::Display for DisassembledInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.operand.is_empty() {
+            write!(f, "{}", self.opcode.mnemonic)
+        } else {
+            write!(f, "{} {}", self.opcode.mnemonic, self.operand)
+        }
+    }
+}
+
+fn branch_target(next_address: u16, offset: u8) -> u16 {
+    next_address.wrapping_add(offset as i8 as u16)
+}
+
+fn format_operand(addressing_mode: AddressingMode, address: u16, bytes: &[u8]) -> String {
+    let byte = || bytes[1];
+    let word = || u16::from_le_bytes([bytes[1], bytes[2]]);
+
+    match addressing_mode {
+        AddressingMode::Implied => String::new(),
+        AddressingMode::Accumulator => "A".to_string(),
+        AddressingMode::Immediate => format!("#${:02X}", byte()),
+        AddressingMode::Relative => format!("${:04X}", branch_target(address.wrapping_add(2), byte())),
+        AddressingMode::ZeroPage => format!("${:02X}", byte()),
+        AddressingMode::ZeroPageX => format!("${:02X},X", byte()),
+        AddressingMode::ZeroPageY => format!("${:02X},Y", byte()),
+        AddressingMode::Absolute => format!("${:04X}", word()),
+        AddressingMode::AbsoluteX => format!("${:04X},X", word()),
+        AddressingMode::AbsoluteY => format!("${:04X},Y", word()),
+        AddressingMode::IndexedIndirectX => format!("(${:02X},X)", byte()),
+        AddressingMode::IndirectIndexedY => format!("(${:02X}),Y", byte()),
+        AddressingMode::Indirect => format!("(${:04X})", word()),
+        AddressingMode::ZeroPageIndirect => format!("(${:02X})", byte()),
+        AddressingMode::AbsoluteIndexedIndirect => format!("(${:04X},X)", word()),
+        AddressingMode::ZeroPageRelative => format!("${:02X},${:04X}", byte(), branch_target(address.wrapping_add(3), bytes[2])),
+    }
+}
+
+/// Disassembles the instruction at the given address, using `read` to fetch its bytes.
+/// Debuggers and tracers can pass a function that calls [`super::Bus::peek`].
+pub fn disassemble_instruction(variant: M6502Variant, address: u16, read: impl Fn(u16) -> u8) -> DisassembledInstruction {
+    let opcode = opcodes(variant)[read(address) as usize];
+
+    let bytes: Vec<u8> = (0..opcode.length as u16)
+        .map(|offset| read(address.wrapping_add(offset)))
+        .collect();
+
+    DisassembledInstruction {
+        address,
+        operand: format_operand(opcode.addressing_mode, address, &bytes),
+        bytes,
+        opcode,
+    }
+}
+
+/// Disassembles NMOS 6502 machine code that is loaded at the given address.
+/// An instruction that is cut short by the end of `bytes` is left out.
+pub fn disassemble(bytes: &[u8], address: u16) -> Vec<DisassembledInstruction> {
+    disassemble_with_variant(M6502Variant::Nmos6502, bytes, address)
+}
+
+/// Disassembles machine code for the given variant that is loaded at the given address.
+/// An instruction that is cut short by the end of `bytes` is left out.
+pub fn disassemble_with_variant(variant: M6502Variant, bytes: &[u8], address: u16) -> Vec<DisassembledInstruction> {
+    let table = opcodes(variant);
+
+    let mut result = Vec::new();
+    let mut offset = 0;
+
+    while offset < bytes.len() {
+        let length = table[bytes[offset] as usize].length as usize;
+        if offset + length > bytes.len() {
+            break;
+        }
+
+        let instruction_address = address.wrapping_add(offset as u16);
+        result.push(disassemble_instruction(variant, instruction_address, |a| {
+            bytes[a.wrapping_sub(instruction_address) as usize + offset]
+        }));
+
+        offset += length;
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn disassemble_to_strings(variant: M6502Variant, bytes: &[u8], address: u16) -> Vec<String> {
+        disassemble_with_variant(variant, bytes, address)
+            .iter()
+            .map(|instruction| format!("{:04X} {}", instruction.address, instruction))
+            .collect()
+    }
+
+    #[test]
+    fn disassembles_nmos_addressing_modes() {
+        let bytes = [
+            0x18,               // CLC
+            0x0A,               // ASL A
+            0xA9, 0x12,         // LDA #$12
+            0xD0, 0xFC,         // BNE $C002
+            0xA5, 0x34,         // LDA $34
+            0xB5, 0x34,         // LDA $34,X
+            0xB6, 0x34,         // LDX $34,Y
+            0x4C, 0xF5, 0xC5,   // JMP $C5F5
+            0xBD, 0x00, 0x02,   // LDA $0200,X
+            0xB9, 0x00, 0x02,   // LDA $0200,Y
+            0xA1, 0x80,         // LDA ($80,X)
+            0xB1, 0x80,         // LDA ($80),Y
+            0x6C, 0xFF, 0x02,   // JMP ($02FF)
+            0x20, 0x00, 0xC0,   // JSR $C000
+            0xA7, 0x10,         // LAX $10
+        ];
+
+        assert_eq!(
+            vec![
+                "C000 CLC", "C001 ASL A", "C002 LDA #$12", "C004 BNE $C002",
+                "C006 LDA $34", "C008 LDA $34,X", "C00A LDX $34,Y", "C00C JMP $C5F5",
+                "C00F LDA $0200,X", "C012 LDA $0200,Y", "C015 LDA ($80,X)", "C017 LDA ($80),Y",
+                "C019 JMP ($02FF)", "C01C JSR $C000", "C01F LAX $10",
+            ],
+            disassemble_to_strings(M6502Variant::Nmos6502, &bytes, 0xC000));
+    }
+
+    #[test]
+    fn disassembles_cmos_addressing_modes() {
+        let bytes = [
+            0xB2, 0x80,         // LDA ($80)
+            0x7C, 0x00, 0x10,   // JMP ($1000,X)
+            0x8F, 0x12, 0x7F,   // BBS0 $12,$0087
+            0x80, 0x80,         // BRA $FF8A
+            0x1A,               // INC A
+            0x03,               // NOP
+        ];
+
+        assert_eq!(
+            vec![
+                "0000 LDA ($80)", "0002 JMP ($1000,X)", "0005 BBS0 $12,$0087",
+                "0008 BRA $FF8A", "000A INC A", "000B NOP",
+            ],
+            disassemble_to_strings(M6502Variant::Cmos65C02, &bytes, 0x0000));
+    }
+
+    #[test]
+    fn leaves_out_incomplete_instruction() {
+        let instructions = disassemble(&[0xEA, 0x4C, 0x00], 0x1000);
+
+        assert_eq!(1, instructions.len());
+        assert_eq!(vec![0xEA], instructions[0].bytes);
+    }
+
+    #[test]
+    fn opcode_tables() {
+        let nmos = opcodes(M6502Variant::Nmos6502);
+        assert_eq!(151, nmos.iter().filter(|opcode| opcode.official).count());
+        assert_eq!(Opcode { mnemonic: "LDA", addressing_mode: AddressingMode::AbsoluteX, length: 3, cycles: 4, official: true }, nmos[0xBD]);
+        assert_eq!(Opcode { mnemonic: "SLO", addressing_mode: AddressingMode::IndirectIndexedY, length: 2, cycles: 8, official: false }, nmos[0x13]);
+
+        let cmos = opcodes(M6502Variant::Cmos65C02);
+        assert_eq!(Opcode { mnemonic: "JMP", addressing_mode: AddressingMode::Indirect, length: 3, cycles: 6, official: true }, cmos[0x6C]);
+        assert_eq!(Opcode { mnemonic: "NOP", addressing_mode: AddressingMode::Absolute, length: 3, cycles: 8, official: false }, cmos[0x5C]);
+
+        for table in [nmos, cmos] {
+            for opcode in table.iter() {
+                assert_eq!(opcode.length, 1 + opcode.addressing_mode.operand_length(), "{:?}", opcode);
+            }
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use super::{Bus, BrkFlags, M6502, M6502Variant, RunState};
+use super::registers::SplitRegister16;
+
+/// How a system drives its CPU.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ExecutionMode {
+    /// One cycle at a time with [`M6502::step_cycle`], with every bus access at the right time.
+    Cycle,
+
+    /// One instruction at a time with [`M6502::execute_instruction`]. Much faster, for when
+    /// nothing needs to see the bus activity within an instruction.
+    Instruction,
+}
+
+// CLI, SEI and PLP change the I flag after interrupts have been polled,
+// so the new value only affects the next instruction.
+const CLI: u8 = 0x58;
+const SEI: u8 = 0x78;
+const PLP: u8 = 0x28;
+
+impl M6502 {
+    /// Runs a whole instruction, or interrupt sequence, without going through the pins,
+    /// and then fetches the next opcode. Returns the number of cycles it would have taken.
+    ///
+    /// Registers, flags, memory and cycle counts end up the same as for [`M6502::step_instruction`],
+    /// and the two can be mixed freely. But only the reads and writes that an instruction needs are
+    /// made, rather than every dummy read and write, and [`Bus::on_cycle`] is only called once,
+    /// after the next opcode has been fetched. RDY is ignored.
+    ///
+    /// If the CPU is part way through an instruction, has been halted, or has DMA cycles to give up,
+    /// this runs cycle by cycle instead.
+    pub fn execute_instruction(&mut self, bus: &mut impl Bus) -> u32 {
+        // Waiting for an interrupt, a RESET, or DMA, is left to the cycle by cycle path.
+        if !self.sync || self.is_halted() || self.dma_cycles > 0 {
+            return self.step_instruction(bus);
+        }
+
+        self.ir = self.data;
+        self.sync = false;
+
+        if self.irq_pipeline & 0b100 != 0 {
+            self.brk_flags |= BrkFlags::IRQ;
+        }
+        if self.nmi_pipeline & 0xFFFC != 0 {
+            self.brk_flags |= BrkFlags::NMI;
+        }
+        self.irq_pipeline = 0;
+        self.nmi_pipeline &= 0b11;
+
+        let i_before = self.p.i;
+        let mut extra_cycles = 0;
+
+        let cycles = if self.brk_flags != BrkFlags::NONE {
+            self.ir = 0;
+            self.fast_brk(bus);
+            7
+        } else {
+            self.pc = self.pc.wrapping_add(1);
+            match self.variant {
+                M6502Variant::Nmos6502 => include!(concat!(env!("OUT_DIR"), "/mos6502_fast_instructions.generated.rs")),
+                M6502Variant::Cmos65C02 => include!(concat!(env!("OUT_DIR"), "/wdc65c02_fast_instructions.generated.rs")),
+            }
+        };
+
+        // Leave the interrupt pipelines as if the instruction had been run cycle by cycle.
+        // The pins can't change part way through, so a taken branch never delays an interrupt.
+        let i_at_poll = if matches!(self.ir, CLI | SEI | PLP) { i_before } else { self.p.i };
+        if !self.irq && !i_at_poll {
+            self.irq_pipeline = 0b110;
+        }
+        self.nmi_pipeline <<= 2;
+
+        if self.run_state == RunState::Running {
+            self.fast_fetch(bus);
+        } else {
+            // WAI's last cycle, which fetches the next opcode, will run cycle by cycle.
+            self.tr = cycles as u8;
+        }
+
+        cycles + extra_cycles
+    }
+
+    fn fast_fetch(&mut self, bus: &mut impl Bus) {
+        self.fetch_next_instruction();
+        self.rw = true;
+        self.data = bus.read(self.pc.to_u16());
+        bus.on_cycle(self);
+    }
+
+    fn fast_read_pc(&mut self, bus: &mut impl Bus) -> u8 {
+        let value = bus.read(self.pc.to_u16());
+        self.pc = self.pc.wrapping_add(1);
+        value
+    }
+
+    fn fast_read_pc_u16(&mut self, bus: &mut impl Bus) -> u16 {
+        u16::from_le_bytes([self.fast_read_pc(bus), self.fast_read_pc(bus)])
+    }
+
+    /// Reads a pointer from zero page. The high byte wraps around within zero page.
+    fn fast_read_zero_page_u16(&mut self, bus: &mut impl Bus, address: u8) -> u16 {
+        u16::from_le_bytes([bus.read(address as u16), bus.read(address.wrapping_add(1) as u16)])
+    }
+
+    fn fast_push(&mut self, bus: &mut impl Bus, value: u8) {
+        bus.write(0x0100 | self.sp as u16, value);
+        self.sp = self.sp.wrapping_sub(1);
+    }
+
+    fn fast_pull(&mut self, bus: &mut impl Bus) -> u8 {
+        self.sp = self.sp.wrapping_add(1);
+        bus.read(0x0100 | self.sp as u16)
+    }
+
+    fn fast_zero_page(&mut self, bus: &mut impl Bus) -> u16 {
+        self.fast_read_pc(bus) as u16
+    }
+
+    fn fast_zero_page_indexed(&mut self, bus: &mut impl Bus, index_register_value: u8) -> u16 {
+        self.fast_read_pc(bus).wrapping_add(index_register_value) as u16
+    }
+
+    fn fast_absolute(&mut self, bus: &mut impl Bus) -> u16 {
+        self.fast_read_pc_u16(bus)
+    }
+
+    /// Returns the address, and whether adding the index crossed a page.
+    fn fast_absolute_indexed(&mut self, bus: &mut impl Bus, index_register_value: u8) -> (u16, bool) {
+        let base = self.fast_read_pc_u16(bus);
+        let address = base.wrapping_add(index_register_value as u16);
+        (address, (base ^ address) & 0xFF00 != 0)
+    }
+
+    fn fast_indexed_indirect_x(&mut self, bus: &mut impl Bus) -> u16 {
+        let pointer = self.fast_read_pc(bus).wrapping_add(self.x);
+        self.fast_read_zero_page_u16(bus, pointer)
+    }
+
+    /// Returns the address, and whether adding Y crossed a page.
+    fn fast_indirect_indexed_y(&mut self, bus: &mut impl Bus) -> (u16, bool) {
+        let pointer = self.fast_read_pc(bus);
+        let base = self.fast_read_zero_page_u16(bus, pointer);
+        let address = base.wrapping_add(self.y as u16);
+        (address, (base ^ address) & 0xFF00 != 0)
+    }
+
+    fn fast_zero_page_indirect(&mut self, bus: &mut impl Bus) -> u16 {
+        let pointer = self.fast_read_pc(bus);
+        self.fast_read_zero_page_u16(bus, pointer)
+    }
+
+    /// JMP (abs) on the NMOS 6502 doesn't carry into the high byte of the pointer,
+    /// so the high byte of the target comes from the start of the same page.
+    fn fast_indirect(&mut self, bus: &mut impl Bus) -> u16 {
+        let pointer = self.fast_read_pc_u16(bus);
+        let pointer_hi = (pointer & 0xFF00) | (pointer.wrapping_add(1) & 0x00FF);
+        u16::from_le_bytes([bus.read(pointer), bus.read(pointer_hi)])
+    }
+
+    fn fast_indirect_cmos(&mut self, bus: &mut impl Bus) -> u16 {
+        let pointer = self.fast_read_pc_u16(bus);
+        u16::from_le_bytes([bus.read(pointer), bus.read(pointer.wrapping_add(1))])
+    }
+
+    fn fast_absolute_indexed_indirect(&mut self, bus: &mut impl Bus) -> u16 {
+        let pointer = self.fast_read_pc_u16(bus).wrapping_add(self.x as u16);
+        u16::from_le_bytes([bus.read(pointer), bus.read(pointer.wrapping_add(1))])
+    }
+
+    /// Reads the offset and branches if `taken` is true. Returns the extra cycles
+    /// compared with a branch that isn't taken.
+    fn fast_branch(&mut self, bus: &mut impl Bus, taken: bool) -> u32 {
+        let offset = self.fast_read_pc(bus) as i8;
+        if !taken {
+            return 0;
+        }
+
+        let target = self.pc.wrapping_add_i8(offset);
+        let same_page = target.hi == self.pc.hi;
+        self.pc = target;
+        if same_page { 1 } else { 2 }
+    }
+
+    fn fast_jsr(&mut self, bus: &mut impl Bus) {
+        let lo = self.fast_read_pc(bus);
+        self.fast_push(bus, self.pc.hi);
+        self.fast_push(bus, self.pc.lo);
+        self.pc.hi = bus.read(self.pc.to_u16());
+        self.pc.lo = lo;
+    }
+
+    fn fast_rts(&mut self, bus: &mut impl Bus) {
+        self.pc.lo = self.fast_pull(bus);
+        self.pc.hi = self.fast_pull(bus);
+        self.pc = self.pc.wrapping_add(1);
+    }
+
+    fn fast_rti(&mut self, bus: &mut impl Bus) {
+        let p = self.fast_pull(bus);
+        self.p.set_from_u8(p);
+        self.pc.lo = self.fast_pull(bus);
+        self.pc.hi = self.fast_pull(bus);
+    }
+
+    /// BRK, IRQ, NMI and RESET, following the same steps as `brk_0` to `brk_5`.
+    fn fast_brk(&mut self, bus: &mut impl Bus) {
+        // BRK skips over its padding byte. IRQ and NMI return to the interrupted instruction.
+        if !self.brk_flags.intersects(BrkFlags::NMI | BrkFlags::IRQ) {
+            self.pc = self.pc.wrapping_add(1);
+        }
+
+        let vector = if self.brk_flags.contains(BrkFlags::RESET) {
+            // RESET goes through the motions of pushing, but doesn't write anything.
+            self.sp = self.sp.wrapping_sub(3);
+            0xFFFC
+        } else {
+            self.fast_push(bus, self.pc.hi);
+            self.fast_push(bus, self.pc.lo);
+            self.fast_push(bus, self.p.as_u8(self.brk_flags == BrkFlags::NONE));
+            if self.brk_flags.contains(BrkFlags::NMI) { 0xFFFA } else { 0xFFFE }
+        };
+
+        self.p.i = true;
+        if self.variant == M6502Variant::Cmos65C02 {
+            self.p.d = false;
+        }
+        self.brk_flags = BrkFlags::NONE;
+
+        self.pc = SplitRegister16::from_u16(u16::from_le_bytes([bus.read(vector), bus.read(vector + 1)]));
+    }
+}
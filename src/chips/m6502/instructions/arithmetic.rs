@@ -1,4 +1,4 @@
-use super::super::M6502;
+use super::super::{M6502, M6502Variant};
 
 impl M6502 {
     fn do_adc_binary(&mut self, value: u8) {
@@ -40,9 +40,33 @@ impl M6502 {
         self.a = (al & 0xF) | (ah << 4);
     }
 
+    /// 65C02 decimal mode. The carry and accumulator are the same as NMOS,
+    /// but N and Z are set from the decimal result.
+    fn do_adc_decimal_cmos(&mut self, value: u8) {
+        let carry = if self.p.c { 1 } else { 0 };
+        let mut al = (self.a & 0xF) as i16 + (value & 0xF) as i16 + carry;
+        if al >= 0xA {
+            al = ((al + 0x6) & 0xF) + 0x10;
+        }
+
+        // Overflow comes from the signed sum, before the high nibble is adjusted.
+        let signed = (self.a & 0xF0) as i8 as i16 + (value & 0xF0) as i8 as i16 + al;
+        self.p.v = !(-128..=127).contains(&signed);
+
+        let mut result = (self.a & 0xF0) as i16 + (value & 0xF0) as i16 + al;
+        if result >= 0xA0 {
+            result += 0x60;
+        }
+
+        self.p.c = result >= 0x100;
+        self.a = self.p.set_zero_negative_flags(result as u8);
+    }
+
     pub(crate) fn adc(&mut self) {
         if !self.p.d || !self.bcd_enabled {
             self.do_adc_binary(self.data);
+        } else if self.variant == M6502Variant::Cmos65C02 {
+            self.do_adc_decimal_cmos(self.data);
         } else {
             self.do_adc_decimal(self.data);
         }
@@ -71,20 +95,50 @@ impl M6502 {
         self.a = (al | (ah << 4)) as u8;
     }
 
+    /// 65C02 decimal mode. Carry and overflow are the same as in binary mode,
+    /// and N and Z are set from the decimal result.
+    fn do_sbc_decimal_cmos(&mut self, value: u8) {
+        let borrow = if self.p.c { 0 } else { 1 };
+        let al = (self.a & 0xF) as i16 - (value & 0xF) as i16 - borrow;
+        let mut result = self.a as i16 - value as i16 - borrow;
+        if result < 0 {
+            result -= 0x60;
+        }
+        if al < 0 {
+            result -= 0x6;
+        }
+
+        self.do_adc_binary(!value);
+        self.a = self.p.set_zero_negative_flags(result as u8);
+    }
+
     pub(crate) fn sbc(&mut self) {
         if !self.p.d || !self.bcd_enabled {
             let value = !self.data;
             self.do_adc_binary(value);
+        } else if self.variant == M6502Variant::Cmos65C02 {
+            self.do_sbc_decimal_cmos(self.data);
         } else {
             self.do_sbc_decimal(self.data);
         }
     }
 
+    /// On the 65C02, ADC and SBC take an extra cycle in decimal mode.
+    pub(crate) fn fetch_next_instruction_unless_decimal(&mut self) {
+        if !self.p.d || !self.bcd_enabled {
+            self.fetch_next_instruction();
+        }
+    }
+
     pub(crate) fn dec(&mut self) {
         self.data = self.p.set_zero_negative_flags(self.ad.lo.wrapping_sub(1));
         self.rw = false;
     }
 
+    pub(crate) fn deca(&mut self) {
+        self.a = self.p.set_zero_negative_flags(self.a.wrapping_sub(1));
+    }
+
     pub(crate) fn dex(&mut self) {
         self.x = self.p.set_zero_negative_flags(self.x.wrapping_sub(1));
     }
@@ -98,6 +152,10 @@ impl M6502 {
         self.rw = false;
     }
 
+    pub(crate) fn inca(&mut self) {
+        self.a = self.p.set_zero_negative_flags(self.a.wrapping_add(1));
+    }
+
     pub(crate) fn inx(&mut self) {
         self.x = self.p.set_zero_negative_flags(self.x.wrapping_add(1));
     }
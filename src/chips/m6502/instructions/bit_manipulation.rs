@@ -0,0 +1,25 @@
+use super::super::M6502;
+
+impl M6502 {
+    pub(crate) fn rmb(&mut self, bit: u8) {
+        self.data = self.ad.lo & !(1 << bit);
+        self.rw = false;
+    }
+
+    pub(crate) fn smb(&mut self, bit: u8) {
+        self.data = self.ad.lo | (1 << bit);
+        self.rw = false;
+    }
+
+    pub(crate) fn trb(&mut self) {
+        self.p.z = (self.a & self.ad.lo) == 0;
+        self.data = self.ad.lo & !self.a;
+        self.rw = false;
+    }
+
+    pub(crate) fn tsb(&mut self) {
+        self.p.z = (self.a & self.ad.lo) == 0;
+        self.data = self.ad.lo | self.a;
+        self.rw = false;
+    }
+}
\ No newline at end of file
@@ -34,6 +34,19 @@ impl M6502 {
         self.branch_0(self.p.n, false);
     }
 
+    pub(crate) fn branch_0_bra(&mut self) {
+        self.branch_0(true, true);
+    }
+
+    /// BBR tests a bit of the zero page value stored by zp,rel addressing.
+    pub(crate) fn branch_0_bbr(&mut self, bit: u8) {
+        self.branch_0(self.ad.lo & (1 << bit) != 0, false);
+    }
+
+    pub(crate) fn branch_0_bbs(&mut self, bit: u8) {
+        self.branch_0(self.ad.lo & (1 << bit) != 0, true);
+    }
+
     pub(crate) fn branch_0_bvc(&mut self) {
         self.branch_0(self.p.v, false);
     }
@@ -47,6 +60,12 @@ impl M6502 {
         self.address_lo = self.ad.lo;
         if self.ad.hi == self.pc.hi { // Are we branching to the same page?
             self.pc = self.ad;
+
+            // A taken branch that doesn't cross a page delays interrupts by one instruction.
+            // An NMI edge detected in this cycle is kept, because it would otherwise be lost.
+            self.irq_pipeline >>= 1;
+            self.nmi_pipeline = (self.nmi_pipeline >> 1) | (self.nmi_pipeline & 1);
+
             self.fetch_next_instruction();
         }
     }
@@ -5,4 +5,12 @@ impl M6502 {
         self.ad.lo = self.data;
         self.rw = false;
     }
+
+    /// The 65C02 reads the address again, instead of writing the unmodified value.
+    pub(crate) fn rmw_cycle_cmos(&mut self) {
+        self.ad.lo = self.data;
+    }
+
+    /// Does nothing. The address bus keeps the value from the previous cycle.
+    pub(crate) fn idle_cycle(&mut self) {}
 }
\ No newline at end of file
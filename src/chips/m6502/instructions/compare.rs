@@ -8,6 +8,11 @@ impl M6502 {
         self.p.n = (value & 0x80) == 0x80;
     }
 
+    /// BIT #imm (65C02) only affects the Z flag.
+    pub(crate) fn bit_immediate(&mut self) {
+        self.p.z = (self.a & self.data) == 0;
+    }
+
     fn compare(&mut self, register: u8) {
         self.p.set_zero_negative_flags(register.wrapping_sub(self.data));
         self.p.c = register >= self.data;
@@ -25,7 +25,7 @@ impl M6502 {
         self.p.i = true;
     }
 
-    pub(crate) fn slc(&mut self) {
+    pub(crate) fn sec(&mut self) {
         self.p.c = true;
     }
 }
\ No newline at end of file
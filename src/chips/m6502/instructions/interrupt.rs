@@ -1,8 +1,9 @@
-use super::super::{BrkFlags, M6502};
+use super::super::{BrkFlags, M6502, M6502Variant, RunState};
 
 impl M6502 {
     pub(crate) fn brk_0(&mut self) {
-        if !self.brk_flags.contains(BrkFlags::NMI | BrkFlags::IRQ) { 
+        // BRK skips over its padding byte. IRQ and NMI return to the interrupted instruction.
+        if !self.brk_flags.intersects(BrkFlags::NMI | BrkFlags::IRQ) {
             self.pc = self.pc.wrapping_add(1);
         }
         self.address_hi = 0x01;
@@ -34,6 +35,14 @@ impl M6502 {
             self.ad.lo = 0xFC;
         } else {
             self.rw = false; 
+
+            // An NMI that arrives before this point "hijacks" a BRK or IRQ,
+            // which then uses the NMI vector instead of its own.
+            if self.nmi_pipeline & 0xFFFC != 0 {
+                self.nmi_pipeline &= 0b11;
+                self.brk_flags |= BrkFlags::NMI;
+            }
+
             if self.brk_flags.contains(BrkFlags::NMI) {
                 self.ad.lo = 0xFA;
             } else {
@@ -47,6 +56,9 @@ impl M6502 {
         self.address_lo = self.ad.lo;
         self.ad.lo += 1;
         self.p.i = true; 
+        if self.variant == M6502Variant::Cmos65C02 {
+            self.p.d = false;
+        }
         self.brk_flags = BrkFlags::NONE;
     }
 
@@ -59,4 +71,14 @@ impl M6502 {
         self.pc.hi = self.data;
         self.pc.lo = self.ad.lo;
     }
+
+    /// WAI (65C02) stops the CPU until an interrupt arrives.
+    pub(crate) fn wai(&mut self) {
+        self.run_state = RunState::Waiting;
+    }
+
+    /// STP (65C02) stops the CPU until it is reset.
+    pub(crate) fn stp(&mut self) {
+        self.run_state = RunState::Stopped;
+    }
 }
\ No newline at end of file
@@ -1,4 +1,5 @@
 mod arithmetic;
+mod bit_manipulation;
 mod branch;
 mod common;
 mod compare;
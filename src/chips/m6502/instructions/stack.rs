@@ -17,6 +17,22 @@ impl M6502 {
         self.rw = false;
     }
 
+    pub(crate) fn phx(&mut self) {
+        self.address_hi = 0x01;
+        self.address_lo = self.sp;
+        self.sp = self.sp.wrapping_sub(1);
+        self.data = self.x;
+        self.rw = false;
+    }
+
+    pub(crate) fn phy(&mut self) {
+        self.address_hi = 0x01;
+        self.address_lo = self.sp;
+        self.sp = self.sp.wrapping_sub(1);
+        self.data = self.y;
+        self.rw = false;
+    }
+
     pub(crate) fn pla_0(&mut self) {
         self.address_hi = 0x01;
         self.address_lo = self.sp;
@@ -32,6 +48,15 @@ impl M6502 {
         self.a = self.p.set_zero_negative_flags(self.data);
     }
 
+    /// PLX and PLY share the first two cycles of PLA.
+    pub(crate) fn plx(&mut self) {
+        self.x = self.p.set_zero_negative_flags(self.data);
+    }
+
+    pub(crate) fn ply(&mut self) {
+        self.y = self.p.set_zero_negative_flags(self.data);
+    }
+
     pub(crate) fn plp_0(&mut self) {
         self.address_hi = 0x01;
         self.address_lo = self.sp;
@@ -20,4 +20,9 @@ impl M6502 {
         self.data = self.y;
         self.rw = false;
     }
+
+    pub(crate) fn stz(&mut self) {
+        self.data = 0;
+        self.rw = false;
+    }
 }
\ No newline at end of file
@@ -1,4 +1,4 @@
-use super::super::M6502;
+use super::super::{M6502, RunState};
 
 impl M6502 {
     pub(crate) fn anc(&mut self) {
@@ -74,6 +74,14 @@ impl M6502 {
         self.sbc();
     }
 
+    /// JAM (also known as KIL or HLT) locks up the CPU until RESET,
+    /// with the address bus stuck at $FFFF. Interrupts are ignored.
+    pub(crate) fn jam(&mut self) {
+        self.address_hi = 0xFF;
+        self.address_lo = 0xFF;
+        self.run_state = RunState::Jammed;
+    }
+
     pub(crate) fn las(&mut self) {
         self.a = self.data & self.sp;
         self.x = self.a;
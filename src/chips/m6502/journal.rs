@@ -0,0 +1,535 @@
+use std::collections::VecDeque;
+use std::ops::RangeInclusive;
+
+use super::{Bus, M6502, M6502State};
+use super::state::STATE_LENGTH;
+
+pub struct JournalOptions {
+    /// Number of instructions to keep. Older ones are forgotten. Must be at least 1.
+    pub capacity: usize,
+
+    /// Addresses that hold RAM. Writes anywhere are recorded, but only writes to RAM are undone
+    /// when stepping backwards, so that I/O registers aren't disturbed.
+    pub ram: Vec<RangeInclusive<u16>>,
+}
+
+impl Default for JournalOptions {
+    fn default() -> Self {
+        Self {
+            capacity: 1_000_000,
+            ram: vec![0x0000..=0xFFFF],
+        }
+    }
+}
+
+/// A write, and what it replaced.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct MemoryWrite {
+    pub address: u16,
+    pub old_value: u8,
+    pub new_value: u8,
+}
+
+/// The instruction that made a write.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct WriteRecord {
+    /// Address of the instruction.
+    pub pc: u16,
+
+    /// Number of the instruction, counting from the first one that the journal saw.
+    pub instruction: u64,
+
+    pub write: MemoryWrite,
+}
+
+/// Every this many instructions, the CPU's state is stored in full, rather than as changes.
+/// Stepping back decodes the state from the nearest of these keyframes.
+const KEYFRAME_INTERVAL: u64 = 256;
+
+/// An instruction, from its opcode fetch. The CPU's state at the fetch is stored as the bytes of
+/// [`M6502State::to_bytes`] that changed since the previous entry, or that aren't zero for a keyframe.
+/// The bytes, and the instruction's writes, follow on from the previous entry's.
+struct Entry {
+    pc: u16,
+
+    /// Bit N is set if byte N of the state is stored.
+    changed: u32,
+    keyframe: bool,
+    writes: u8,
+}
+
+/// Records each instruction that the CPU runs, so that it can be undone. For each one, this keeps
+/// the registers and flags that it changed, and the memory that it wrote.
+///
+/// Wrap the CPU's [`Bus`] in a [`JournaledBus`], which sees each write before it happens.
+/// This works with both [`M6502::step_cycle`] and [`M6502::execute_instruction`].
+///
+/// Only the CPU and RAM are rewound. Anything else in the system carries on from where it was.
+pub struct Journal {
+    options: JournalOptions,
+    entries: VecDeque<Entry>,
+    state_bytes: VecDeque<u8>,
+    writes: VecDeque<MemoryWrite>,
+
+    /// The state at the newest entry, which the next entry's changes are from.
+    last_state: [u8; STATE_LENGTH],
+
+    /// Number of the oldest instruction that is still in the journal.
+    first_instruction: u64,
+
+    previous_rw: bool,
+}
+
+impl Journal {
+    pub fn new() -> Self {
+        Journal::new_with_options(JournalOptions::default())
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `options.capacity` is 0.
+    pub fn new_with_options(options: JournalOptions) -> Self {
+        assert!(options.capacity > 0, "Journal capacity must be at least 1");
+
+        Self {
+            options,
+            entries: VecDeque::new(),
+            state_bytes: VecDeque::new(),
+            writes: VecDeque::new(),
+            last_state: [0; STATE_LENGTH],
+            first_instruction: 0,
+            previous_rw: false,
+        }
+    }
+
+    /// Number of instructions in the journal, including the one that is running.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.first_instruction += self.entries.len() as u64;
+        self.entries.clear();
+        self.state_bytes.clear();
+        self.writes.clear();
+    }
+
+    /// Called at the end of every cycle. An instruction starts at its opcode fetch,
+    /// unless the fetch is being repeated because RDY or DMA has paused the CPU.
+    fn record_cycle(&mut self, cpu: &M6502) {
+        let paused = (!cpu.rdy || cpu.is_dma_cycle()) && self.previous_rw;
+        self.previous_rw = cpu.rw;
+
+        if !cpu.sync || paused {
+            return;
+        }
+
+        if self.entries.len() == self.options.capacity {
+            self.forget_oldest();
+        }
+
+        let instruction = self.first_instruction + self.entries.len() as u64;
+        let keyframe = self.entries.is_empty() || instruction.is_multiple_of(KEYFRAME_INTERVAL);
+        let previous = if keyframe { [0; STATE_LENGTH] } else { self.last_state };
+
+        let state = cpu.save_state().to_bytes();
+        let mut changed = 0;
+        for (index, byte) in state.iter().enumerate() {
+            if *byte != previous[index] {
+                changed |= 1 << index;
+                self.state_bytes.push_back(*byte);
+            }
+        }
+        self.last_state.copy_from_slice(&state);
+
+        self.entries.push_back(Entry {
+            pc: cpu.get_address(),
+            changed,
+            keyframe,
+            writes: 0,
+        });
+    }
+
+    fn record_write(&mut self, write: MemoryWrite) {
+        // Writes before the first opcode fetch don't belong to an instruction that can be undone.
+        if let Some(entry) = self.entries.back_mut() {
+            entry.writes += 1;
+            self.writes.push_back(write);
+        }
+    }
+
+    /// Drops the oldest entry. The one after it becomes a keyframe, so that the oldest entry can always be decoded.
+    fn forget_oldest(&mut self) {
+        let next_state = (self.entries.len() > 1).then(|| self.decode_state(1));
+
+        let Some(oldest) = self.entries.pop_front() else {
+            return;
+        };
+        self.state_bytes.drain(..oldest.changed.count_ones() as usize);
+        self.writes.drain(..oldest.writes as usize);
+        self.first_instruction += 1;
+
+        let (Some(next), Some(state)) = (self.entries.front_mut(), next_state) else {
+            return;
+        };
+        if next.keyframe {
+            return;
+        }
+
+        self.state_bytes.drain(..next.changed.count_ones() as usize);
+        next.changed = 0;
+        for (index, byte) in state.iter().enumerate().rev() {
+            if *byte != 0 {
+                next.changed |= 1 << index;
+                self.state_bytes.push_front(*byte);
+            }
+        }
+        next.keyframe = true;
+    }
+
+    /// Drops the newest entry, which must not have any writes.
+    fn forget_newest(&mut self) {
+        let Some(newest) = self.entries.pop_back() else {
+            return;
+        };
+        self.state_bytes.truncate(self.state_bytes.len() - newest.changed.count_ones() as usize);
+        if !self.entries.is_empty() {
+            self.last_state = self.decode_state(self.entries.len() - 1);
+        }
+    }
+
+    /// Rebuilds the state at an entry, from the keyframe at or before it.
+    fn decode_state(&self, index: usize) -> [u8; STATE_LENGTH] {
+        let keyframe = (0..=index).rev().find(|i| self.entries[*i].keyframe).unwrap();
+        let stored = |entry: &Entry| entry.changed.count_ones() as usize;
+
+        // Count from whichever end of the journal is closer.
+        let mut position = if keyframe < self.entries.len() / 2 {
+            self.entries.range(..keyframe).map(stored).sum()
+        } else {
+            self.state_bytes.len() - self.entries.range(keyframe..).map(stored).sum::<usize>()
+        };
+
+        let mut state = [0; STATE_LENGTH];
+        for entry in self.entries.range(keyframe..=index) {
+            for (byte_index, byte) in state.iter_mut().enumerate() {
+                if entry.changed & (1 << byte_index) != 0 {
+                    *byte = self.state_bytes[position];
+                    position += 1;
+                }
+            }
+        }
+        state
+    }
+
+    /// Returns the most recent write to an address, and the instruction that made it.
+    pub fn last_write(&self, address: u16) -> Option<WriteRecord> {
+        let mut end = self.writes.len();
+        for (index, entry) in self.entries.iter().enumerate().rev() {
+            let start = end - entry.writes as usize;
+            if let Some(write) = self.writes.range(start..end).rev().find(|write| write.address == address) {
+                return Some(WriteRecord {
+                    pc: entry.pc,
+                    instruction: self.first_instruction + index as u64,
+                    write: *write,
+                });
+            }
+            end = start;
+        }
+
+        None
+    }
+
+    /// Puts the CPU back at the start of the previous instruction, and undoes its writes.
+    /// If the CPU is part way through an instruction, this goes back to the start of that one instead.
+    /// Returns false if there is nothing left in the journal to go back to.
+    pub fn step_back(&mut self, cpu: &mut M6502, bus: &mut impl Bus) -> bool {
+        // Already at the start of the newest instruction, so go back past it.
+        let at_start = self.entries.back().is_some_and(|entry| entry.writes == 0)
+            && self.last_state[..] == cpu.save_state().to_bytes()[..];
+        if at_start && self.entries.len() > 1 {
+            self.forget_newest();
+        } else if at_start {
+            return false;
+        }
+
+        let Some(entry) = self.entries.back_mut() else {
+            return false;
+        };
+
+        for _ in 0..entry.writes {
+            let write = self.writes.pop_back().unwrap();
+            if self.options.ram.iter().any(|range| range.contains(&write.address)) {
+                bus.write(write.address, write.old_value);
+            }
+        }
+        entry.writes = 0;
+
+        cpu.load_state(&M6502State::from_bytes(&self.last_state).unwrap());
+        self.previous_rw = true;
+        true
+    }
+
+    /// Steps back until the most recent write to the address has been undone, leaving the CPU
+    /// at the start of the instruction that made it. Returns that write, or `None` if the journal
+    /// doesn't have one, in which case nothing is undone.
+    pub fn run_back_to_write(&mut self, cpu: &mut M6502, bus: &mut impl Bus, address: u16) -> Option<WriteRecord> {
+        let record = self.last_write(address)?;
+        while self.first_instruction + self.entries.len() as u64 > record.instruction + 1 || self.entries.back()?.writes > 0 {
+            self.step_back(cpu, bus);
+        }
+        Some(record)
+    }
+}
+
+impl Default for Journal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A [`Bus`] that passes everything through to another bus, and records it in a [`Journal`].
+pub struct JournaledBus<'a, B: Bus> {
+    pub bus: &'a mut B,
+    pub journal: &'a mut Journal,
+}
+
+impl<B: Bus> Bus for JournaledBus<'_, B> {
+    fn read(&mut self, address: u16) -> u8 {
+        self.bus.read(address)
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        let old_value = self.bus.peek(address);
+        self.journal.record_write(MemoryWrite { address, old_value, new_value: value });
+        self.bus.write(address, value);
+    }
+
+    fn peek(&self, address: u16) -> u8 {
+        self.bus.peek(address)
+    }
+
+    fn dma_cycle(&mut self, cpu: &M6502) {
+        self.bus.dma_cycle(cpu);
+    }
+
+    fn on_cycle(&mut self, cpu: &M6502) {
+        self.bus.on_cycle(cpu);
+        self.journal.record_cycle(cpu);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{assemble, ExecutionMode, Program};
+    use crate::chips::test_bus::Ram;
+
+    fn setup() -> (M6502, Ram, Program) {
+        let program = assemble("
+                .org $0400
+                LDX #$00
+                LDA #$01
+            mode:
+                STA $0355
+                JSR count
+                LDA #$02
+            loop:
+                INX
+                INC $20,X
+                PHA
+                PLA
+                JMP loop
+
+            count:
+                INC $21
+                RTS
+
+                .org $FFFC
+                .word $0400
+        ").unwrap();
+
+        let mut ram = Ram::new();
+        program.load_into(&mut ram.data);
+
+        let mut cpu = M6502::new();
+        cpu.set_res(false);
+        cpu.set_res(true);
+        (cpu, ram, program)
+    }
+
+    fn step(cpu: &mut M6502, ram: &mut Ram, journal: &mut Journal, mode: ExecutionMode) {
+        let mut bus = JournaledBus { bus: ram, journal };
+        match mode {
+            ExecutionMode::Cycle => cpu.step_instruction(&mut bus),
+            ExecutionMode::Instruction => cpu.execute_instruction(&mut bus),
+        };
+    }
+
+    #[test]
+    fn steps_back_through_instructions() {
+        for mode in [ExecutionMode::Cycle, ExecutionMode::Instruction] {
+            let (mut cpu, mut ram, _) = setup();
+            let mut journal = Journal::new();
+
+            let mut history = vec![];
+            for _ in 0..50 {
+                step(&mut cpu, &mut ram, &mut journal, mode);
+                history.push((cpu.save_state(), ram.clone()));
+            }
+            assert_eq!(50, journal.len());
+
+            // The first entry is the instruction after RESET, which the CPU is already at the start of.
+            history.pop();
+            while let Some((state, expected_ram)) = history.pop() {
+                assert!(journal.step_back(&mut cpu, &mut ram), "{:?}", mode);
+                assert_eq!(state, cpu.save_state(), "{:?}", mode);
+                assert_eq!(expected_ram, ram, "{:?}", mode);
+            }
+            assert!(!journal.step_back(&mut cpu, &mut ram));
+            assert_eq!(1, journal.len());
+        }
+    }
+
+    #[test]
+    fn steps_back_to_start_of_current_instruction() {
+        let (mut cpu, mut ram, program) = setup();
+        let mut journal = Journal::new();
+        for _ in 0..4 {
+            step(&mut cpu, &mut ram, &mut journal, ExecutionMode::Cycle);
+        }
+        let state = cpu.save_state();
+
+        // Part way through the JSR, after it has pushed the return address.
+        for _ in 0..5 {
+            cpu.step_cycle(&mut JournaledBus { bus: &mut ram, journal: &mut journal });
+        }
+        assert_ne!(0, ram.data[0x01FD]);
+
+        assert!(journal.step_back(&mut cpu, &mut ram));
+        assert_eq!(state, cpu.save_state());
+        assert_eq!(program.label("mode").unwrap() + 3, cpu.get_address());
+        assert_eq!(0, ram.data[0x01FD]);
+
+        // Running forwards again gives the same result.
+        step(&mut cpu, &mut ram, &mut journal, ExecutionMode::Cycle);
+        assert_eq!(program.label("count").unwrap(), cpu.get_address());
+    }
+
+    #[test]
+    fn paused_fetch_is_one_instruction() {
+        let (mut cpu, mut ram, _) = setup();
+        let mut journal = Journal::new();
+        step(&mut cpu, &mut ram, &mut journal, ExecutionMode::Cycle);
+        assert_eq!(1, journal.len());
+
+        cpu.set_rdy(false);
+        for _ in 0..5 {
+            cpu.step_cycle(&mut JournaledBus { bus: &mut ram, journal: &mut journal });
+        }
+        cpu.set_rdy(true);
+        step(&mut cpu, &mut ram, &mut journal, ExecutionMode::Cycle);
+
+        assert_eq!(2, journal.len());
+    }
+
+    #[test]
+    fn finds_last_write() {
+        let (mut cpu, mut ram, program) = setup();
+        let mut journal = Journal::new();
+        for _ in 0..30 {
+            step(&mut cpu, &mut ram, &mut journal, ExecutionMode::Cycle);
+        }
+
+        let mode = program.label("mode").unwrap();
+        assert_eq!(Some(WriteRecord {
+            pc: mode,
+            instruction: 2,
+            write: MemoryWrite { address: 0x0355, old_value: 0x00, new_value: 0x01 },
+        }), journal.last_write(0x0355));
+        assert_eq!(None, journal.last_write(0x0356));
+
+        // The stack is written by JSR, and then by each PHA.
+        assert_eq!(program.label("loop").unwrap() + 3, journal.last_write(0x01FD).unwrap().pc);
+
+        let record = journal.run_back_to_write(&mut cpu, &mut ram, 0x0355).unwrap();
+        assert_eq!(mode, record.pc);
+        assert_eq!(mode, cpu.get_address());
+        assert!(cpu.sync());
+        assert_eq!(0x00, ram.data[0x0355]);
+        assert_eq!(0x00, ram.data[0x21]);
+        assert_eq!(None, journal.last_write(0x0355));
+        assert_eq!(3, journal.len());
+    }
+
+    #[test]
+    fn forgets_old_instructions() {
+        let (mut cpu, mut ram, _) = setup();
+        let mut journal = Journal::new_with_options(JournalOptions {
+            capacity: 3,
+            ..Default::default()
+        });
+        for _ in 0..10 {
+            step(&mut cpu, &mut ram, &mut journal, ExecutionMode::Cycle);
+        }
+        assert_eq!(3, journal.len());
+
+        assert!(journal.step_back(&mut cpu, &mut ram));
+        assert!(journal.step_back(&mut cpu, &mut ram));
+        assert!(!journal.step_back(&mut cpu, &mut ram));
+    }
+
+    #[test]
+    fn steps_back_past_keyframes() {
+        let (mut cpu, mut ram, _) = setup();
+        let capacity = KEYFRAME_INTERVAL as usize + 100;
+        let mut journal = Journal::new_with_options(JournalOptions {
+            capacity,
+            ..Default::default()
+        });
+
+        // Enough instructions for the oldest ones to be forgotten, so that a keyframe has to be made.
+        let mut history = vec![];
+        for _ in 0..(3 * KEYFRAME_INTERVAL) {
+            step(&mut cpu, &mut ram, &mut journal, ExecutionMode::Cycle);
+            history.push(cpu.save_state());
+        }
+        assert_eq!(capacity, journal.len());
+
+        history.pop();
+        for expected in history.iter().rev().take(capacity - 1) {
+            assert!(journal.step_back(&mut cpu, &mut ram));
+            assert_eq!(*expected, cpu.save_state());
+        }
+        assert!(!journal.step_back(&mut cpu, &mut ram));
+    }
+
+    #[test]
+    #[should_panic(expected = "capacity")]
+    fn rejects_zero_capacity() {
+        Journal::new_with_options(JournalOptions {
+            capacity: 0,
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn only_undoes_writes_to_ram() {
+        let (mut cpu, mut ram, _) = setup();
+        let mut journal = Journal::new_with_options(JournalOptions {
+            ram: vec![0x0000..=0x02FF],
+            ..Default::default()
+        });
+        for _ in 0..4 {
+            step(&mut cpu, &mut ram, &mut journal, ExecutionMode::Cycle);
+        }
+        assert_eq!(0x01, ram.data[0x0355]);
+
+        while journal.step_back(&mut cpu, &mut ram) {}
+        assert_eq!(0x01, ram.data[0x0355]);
+    }
+}
@@ -1,10 +1,37 @@
+mod assembler;
+mod bus;
+#[cfg(test)]
+pub(crate) mod c64_test_suite;
+mod debugger;
+mod disassembler;
+mod fast;
+mod journal;
+#[cfg(test)]
+mod netlist;
+mod opcodes;
 mod registers;
+mod sanitizer;
+#[cfg(test)]
+mod single_step_tests;
+mod state;
+mod tracer;
 mod status_register;
 mod addressing_modes;
 mod instructions;
 
 use aemula_macros::PinAccessors;
 
+pub use self::assembler::{assemble, assemble_with_options, AssemblerError, AssemblerOptions, Program, Segment};
+pub use self::bus::Bus;
+pub use self::debugger::{Access, Breakpoint, Comparison, Condition, Debugger, Interrupt, Register, StopReason, Watchpoint};
+pub use self::disassembler::{disassemble, disassemble_instruction, disassemble_with_variant, DisassembledInstruction};
+pub use self::fast::ExecutionMode;
+pub use self::journal::{Journal, JournaledBus, JournalOptions, MemoryWrite, WriteRecord};
+pub use self::opcodes::{opcodes, AddressingMode, Opcode};
+pub use self::sanitizer::{Finding, SanitizedBus, Sanitizer, SanitizerOptions, Violation};
+pub use self::state::{M6502State, StateError, M6502_STATE_VERSION};
+pub use self::tracer::{TraceLevel, Tracer, TracerOptions};
+
 use self::registers::SplitRegister16;
 use self::status_register::StatusRegister;
 
@@ -32,8 +59,14 @@ pub struct M6502 {
     #[pin(in)]
     rdy: bool,
 
-    pub irq: bool,
-    pub nmi: bool,
+    /// Interrupt request (active low). Level-triggered, and ignored while the I flag is set.
+    #[pin(in)]
+    irq: bool,
+
+    /// Non-maskable interrupt (active low). Edge-triggered on the high to low transition.
+    #[pin(in)]
+    #[handle(transition_hi_to_lo)]
+    nmi: bool,
 
     #[pin(out)]
     sync: bool,
@@ -92,19 +125,60 @@ pub struct M6502 {
     brk_flags: BrkFlags,
     ad: SplitRegister16,
 
+    /// Interrupt pipelines. Bit 0 is set in the cycle that an interrupt is detected,
+    /// and the pipelines are shifted left at the end of each cycle. This lets us
+    /// model the delay between an interrupt pin becoming active and the CPU acting on it.
+    irq_pipeline: u16,
+    nmi_pipeline: u16,
+
+    /// Cycles still wanted by DMA, and whether the current cycle was taken by DMA.
+    dma_cycles: u16,
+    dma_cycle: bool,
+
+    run_state: RunState,
+
     bcd_enabled: bool,
+    variant: M6502Variant,
+}
+
+/// Which member of the 6502 family to emulate.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum M6502Variant {
+    /// The original NMOS 6502, including its undocumented opcodes and bugs.
+    Nmos6502,
+
+    /// The CMOS 65C02, with the Rockwell and WDC instructions (including WAI and STP).
+    /// Unused opcodes are NOPs, JMP (abs) works across pages, decimal mode
+    /// sets N and Z correctly, and interrupts clear the D flag.
+    Cmos65C02,
 }
 
 pub struct M6502Options {
     pub bcd_enabled: bool,
+    pub variant: M6502Variant,
+}
+
+impl Default for M6502Options {
+    fn default() -> Self {
+        Self {
+            bcd_enabled: true,
+            variant: M6502Variant::Nmos6502,
+        }
+    }
+}
+
+/// Set by instructions that halt the CPU: WAI and STP on the 65C02, and JAM on the NMOS 6502.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum RunState {
+    Running,
+    Waiting,
+    Stopped,
+    Jammed,
 }
 
 impl M6502 {
     pub fn new() -> Self {
-        let options = M6502Options {
-            bcd_enabled: true,
-        };
-        M6502::new_with_options(options)
+        M6502::new_with_options(M6502Options::default())
     }
 
     pub fn new_with_options(options: M6502Options) -> Self {
@@ -113,8 +187,8 @@ impl M6502 {
             address_hi: 0,
             data: 0,
             rdy: true,
-            irq: false,
-            nmi: false,
+            irq: true,
+            nmi: true,
             sync: true,
             res: true,
             rw: true,
@@ -138,10 +212,23 @@ impl M6502 {
             brk_flags: BrkFlags::NONE,
             ad: SplitRegister16::new(),
 
+            irq_pipeline: 0,
+            nmi_pipeline: 0,
+
+            dma_cycles: 0,
+            dma_cycle: false,
+
+            run_state: RunState::Running,
+
             bcd_enabled: options.bcd_enabled,
+            variant: options.variant,
         }
     }
 
+    pub fn variant(&self) -> M6502Variant {
+        self.variant
+    }
+
     pub fn get_address(&self) -> u16 {
         u16::from_le_bytes([self.address_lo, self.address_hi])
     }
@@ -156,13 +243,85 @@ impl M6502 {
         self.sync = true;
     }
 
+    /// Runs a single clock cycle, and then performs the read or write
+    /// that the CPU requested on the given bus.
+    pub fn step_cycle(&mut self, bus: &mut impl Bus) {
+        self.set_phi0(true);
+        self.set_phi0(false);
+
+        let address = self.get_address();
+
+        if self.dma_cycle {
+            bus.dma_cycle(self);
+        } else if self.rw {
+            self.data = bus.read(address);
+        } else {
+            bus.write(address, self.data);
+        }
+
+        bus.on_cycle(self);
+    }
+
+    /// Runs clock cycles until the CPU is about to fetch the next opcode,
+    /// or has been halted by WAI, STP or JAM. Returns the number of cycles that were run.
+    /// DMA cycles that are wanted before the next opcode is fetched are counted as part of this instruction.
+    pub fn step_instruction(&mut self, bus: &mut impl Bus) -> u32 {
+        let mut cycles = 0;
+
+        loop {
+            self.step_cycle(bus);
+            cycles += 1;
+
+            if (self.sync && self.dma_cycles == 0) || self.is_halted() {
+                return cycles;
+            }
+        }
+    }
+
+    /// Asks for the bus for a number of cycles, on behalf of a DMA device. Requests add up.
+    ///
+    /// Like RDY, this only halts the CPU on a read cycle, so it can take up to three cycles of writes
+    /// before the transfer starts. For each cycle that the CPU is halted, [`Bus::dma_cycle`] is
+    /// called instead of [`Bus::read`], so that the device can drive the address and data buses.
+    /// The CPU then carries on where it left off.
+    pub fn request_dma(&mut self, cycles: u16) {
+        self.dma_cycles = self.dma_cycles.saturating_add(cycles);
+    }
+
+    /// Number of DMA cycles that have been requested, but not yet taken.
+    pub fn dma_cycles_remaining(&self) -> u16 {
+        self.dma_cycles
+    }
+
+    /// Returns true if the current cycle was taken by DMA, rather than the CPU.
+    /// Systems that drive the pins directly should leave the buses to the DMA device for this cycle.
+    pub fn is_dma_cycle(&self) -> bool {
+        self.dma_cycle
+    }
+
+    /// Returns true if the CPU has been halted by WAI, STP or JAM.
+    pub fn is_halted(&self) -> bool {
+        self.run_state != RunState::Running
+    }
+
+    /// Returns true if the CPU has executed one of the NMOS JAM opcodes.
+    /// It stays jammed, with the address bus stuck at $FFFF, until RESET.
+    pub fn is_jammed(&self) -> bool {
+        self.run_state == RunState::Jammed
+    }
+
     fn on_res_set(&mut self) {
         if !self.res {
             self.sync = true;
             self.brk_flags = BrkFlags::RESET;
+            self.run_state = RunState::Running;
         }
     }
 
+    fn on_nmi_transition_hi_to_lo(&mut self) {
+        self.nmi_pipeline |= 1;
+    }
+
     // How this is actually supposed to work is:
     // - PHI1 is when address lines change.
     // - PHI2 is when the data is transferred.
@@ -180,8 +339,26 @@ impl M6502 {
         // TODO: Set RW pin.
         // TODO: Set SYNC pin for an opcode fetch.
 
-        // A low RDY pin, combined with a read cycle, pauses the CPU.
-        if self.rdy || !self.rw {
+        // IRQ is sampled every cycle, even while the CPU is paused by RDY.
+        if !self.irq && !self.p.i {
+            self.irq_pipeline |= 1;
+        }
+
+        // WAI waits until an interrupt is about to be taken. An IRQ that is masked
+        // by the I flag also ends the wait, and execution continues without taking it.
+        if self.run_state == RunState::Waiting
+            && (self.irq_pipeline & 0b10 != 0 || self.nmi_pipeline & 0b10 != 0 || (!self.irq && self.p.i)) {
+            self.run_state = RunState::Running;
+        }
+
+        // A low RDY pin, combined with a read cycle, pauses the CPU. So does DMA.
+        self.dma_cycle = self.dma_cycles > 0 && self.rw;
+        if self.dma_cycle {
+            self.dma_cycles -= 1;
+        }
+        let paused = (!self.rdy || self.dma_cycle) && self.rw;
+
+        if !paused && self.run_state == RunState::Running {
             // If SYNC pin is set, this is the start of a new instruction.
             // We will have the new opcode in the DATA pins.
             if self.sync {
@@ -189,6 +366,17 @@ impl M6502 {
                 self.tr = 0;
                 self.sync = false;
 
+                // IRQ must have been active in the cycle before the last cycle of the previous instruction.
+                // NMI can have been triggered at any time up to that cycle.
+                if self.irq_pipeline & 0b100 != 0 {
+                    self.brk_flags |= BrkFlags::IRQ;
+                }
+                if self.nmi_pipeline & 0xFFFC != 0 {
+                    self.brk_flags |= BrkFlags::NMI;
+                }
+                self.irq_pipeline &= 0b11;
+                self.nmi_pipeline &= 0b11;
+
                 if self.brk_flags != BrkFlags::NONE {
                     self.ir = 0;
                     self.res = false;
@@ -200,302 +388,861 @@ impl M6502 {
             // Assume we're going to read.
             self.rw = true;
 
-            // Include generated file with actual instruction implementations.
-            include!(concat!(env!("OUT_DIR"), "/mos6502_instructions.generated.rs"));
+            // Include generated files with actual instruction implementations.
+            match self.variant {
+                M6502Variant::Nmos6502 => include!(concat!(env!("OUT_DIR"), "/mos6502_instructions.generated.rs")),
+                M6502Variant::Cmos65C02 => include!(concat!(env!("OUT_DIR"), "/wdc65c02_instructions.generated.rs")),
+            }
 
             // Increment timing register.
             self.tr += 1;
         }
-        
+
+        // A detected NMI is not forgotten while the CPU is paused by RDY.
+        if !paused {
+            self.nmi_pipeline <<= 1;
+        }
+
+        self.irq_pipeline <<= 1;
+
         self.phi2 = self.phi0;
         self.phi1 = !self.phi0;
     }
 }
 
+impl Default for M6502 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::{env, fs, fs::File, io::Write, path::Path};
+    use std::{env, fs, fs::File, path::Path};
     use file_diff::diff;
+    use test_case::test_case;
+    use super::c64_test_suite::{setup_registers, C64Bus};
+    use crate::chips::test_bus::Ram;
 
-    const ASSET_PATH:&'static str = "test_assets/chips/mos6502";
+    const ASSET_PATH: &str = "test_assets/chips/mos6502";
 
-    #[test]
-    fn all_suite_a() {
-        let path = Path::new(ASSET_PATH).join("AllSuiteA.bin");
-        let rom = fs::read(path).unwrap();
+    /// Runs until an instruction jumps or branches to itself.
+    fn run_until_trapped_with_mode(cpu: &mut M6502, bus: &mut impl Bus, mode: ExecutionMode) {
+        loop {
+            let pc = cpu.pc.to_u16();
+            match mode {
+                ExecutionMode::Cycle => cpu.step_instruction(bus),
+                ExecutionMode::Instruction => cpu.execute_instruction(bus),
+            };
+            if cpu.pc.to_u16() == pc {
+                return;
+            }
+        }
+    }
 
-        let mut ram = [0; 0x4000];
+    /// Sets up a CPU that has been reset, and RAM containing the given program at $0400.
+    /// The IRQ handler at $0600 increments $10, and the NMI handler at $0700 increments $11.
+    fn setup_interrupt_test(program: &[u8]) -> (M6502, Ram) {
+        setup_interrupt_test_for_variant(program, M6502Variant::Nmos6502)
+    }
 
-        let mut cpu = M6502::new();
+    fn setup_interrupt_test_for_variant(program: &[u8], variant: M6502Variant) -> (M6502, Ram) {
+        let mut ram = Ram::new();
+
+        ram.data[0x0400..(0x0400 + program.len())].copy_from_slice(program);
+
+        ram.data[0x0600..0x0603].copy_from_slice(&[0xE6, 0x10, 0x40]); // INC $10, RTI
+        ram.data[0x0700..0x0703].copy_from_slice(&[0xE6, 0x11, 0x40]); // INC $11, RTI
+
+        ram.data[0xFFFA..].copy_from_slice(&[0x00, 0x07, 0x00, 0x04, 0x00, 0x06]);
+
+        let mut cpu = M6502::new_with_options(M6502Options {
+            variant,
+            ..Default::default()
+        });
 
         cpu.set_res(false);
         cpu.set_res(true);
 
-        while cpu.pc.to_u16() != 0x45C2 {
-            cpu.set_phi0(true);
-            cpu.set_phi0(false);
+        // Run the RESET sequence.
+        cpu.step_instruction(&mut ram);
 
-            let address = cpu.get_address();
+        (cpu, ram)
+    }
 
-            if cpu.rw {
-                cpu.data = match address {
-                    0x0000..=0x3FFF => ram[address as usize],
-                    0x4000..=0xFFFF => rom[(address - 0x4000) as usize],
-                }
-            } else {
-                match address {
-                    0x0000..=0x3FFF => ram[address as usize] = cpu.data,
-                    _ => {},
-                }
-            }
+    /// Address that the most recent interrupt will return to, read from the stack.
+    fn interrupt_return_address(cpu: &M6502, ram: &Ram) -> u16 {
+        let sp = cpu.sp as usize;
+        u16::from_le_bytes([ram.data[0x0102 + sp], ram.data[0x0103 + sp]])
+    }
+
+    #[test]
+    fn irq_is_level_triggered() {
+        // CLI, then loop forever.
+        let (mut cpu, mut ram) = setup_interrupt_test(&[0x58, 0x4C, 0x01, 0x04]);
+
+        for _ in 0..10 {
+            cpu.step_instruction(&mut ram);
+        }
+        assert_eq!(0, ram.data[0x10]);
+
+        // While IRQ is held low, the handler runs again as soon as it returns.
+        cpu.set_irq(false);
+        while ram.data[0x10] < 3 {
+            cpu.step_instruction(&mut ram);
         }
 
-        assert_eq!(0xFF, ram[0x0210]);
+        cpu.set_irq(true);
+        for _ in 0..20 {
+            cpu.step_instruction(&mut ram);
+        }
+        assert_eq!(3, ram.data[0x10]);
     }
 
     #[test]
-    fn dormann_functional_test() {
-        let path = Path::new(ASSET_PATH).join("6502_functional_test.bin");
-        let mut ram = fs::read(path).unwrap();
-        assert_eq!(0x10000, ram.len());
+    fn irq_is_masked_by_i_flag() {
+        // SEI, then loop forever.
+        let (mut cpu, mut ram) = setup_interrupt_test(&[0x78, 0x4C, 0x01, 0x04]);
 
-        // Patch the test start address into the RESET vector.
-        ram[0xFFFC] = 0x00;
-        ram[0xFFFD] = 0x04;
+        cpu.set_irq(false);
+        for _ in 0..20 {
+            cpu.step_instruction(&mut ram);
+        }
 
-        let mut cpu = M6502::new();
+        assert_eq!(0, ram.data[0x10]);
+    }
 
-        cpu.set_res(false);
-        cpu.set_res(true);
+    #[test]
+    fn irq_is_delayed_by_cli() {
+        // CLI, LDA #1, STA $20, loop forever.
+        let (mut cpu, mut ram) = setup_interrupt_test(&[0x58, 0xA9, 0x01, 0x85, 0x20, 0x4C, 0x05, 0x04]);
+
+        // IRQ is already pending when CLI executes, but the instruction after CLI still runs first.
+        cpu.set_irq(false);
+        while cpu.pc.to_u16() != 0x0600 {
+            cpu.step_instruction(&mut ram);
+        }
+
+        assert_eq!(0x01, cpu.a);
+        assert_eq!(0x0403, interrupt_return_address(&cpu, &ram));
+        assert_eq!(0, ram.data[0x0101 + cpu.sp as usize] & 0x10); // B flag is clear
+    }
+
+    #[test]
+    fn taken_branch_delays_irq() {
+        // CLI, LDX #1, BNE +0, LDA #5, loop forever.
+        let (mut cpu, mut ram) = setup_interrupt_test(&[0x58, 0xA2, 0x01, 0xD0, 0x00, 0xA9, 0x05, 0x4C, 0x07, 0x04]);
 
-        while cpu.pc.to_u16() != 0x3399 && cpu.pc.to_u16() != 0xD0FE {
-            cpu.set_phi0(true);
-            cpu.set_phi0(false);
+        while cpu.pc.to_u16() != 0x0403 {
+            cpu.step_instruction(&mut ram);
+        }
+
+        // IRQ arrives during the second cycle of the branch. Any other instruction
+        // would have been interrupted straight after, but here LDA runs first.
+        cpu.step_cycle(&mut ram);
+        cpu.set_irq(false);
+        while cpu.pc.to_u16() != 0x0600 {
+            cpu.step_instruction(&mut ram);
+        }
+
+        assert_eq!(0x05, cpu.a);
+        assert_eq!(0x0407, interrupt_return_address(&cpu, &ram));
+    }
 
-            let address = cpu.get_address();
+    #[test_case(0 ; "first cycle")]
+    #[test_case(1 ; "second cycle")]
+    #[test_case(2 ; "last cycle")]
+    #[test_case(3 ; "next instruction")]
+    #[allow(clippy::unused_unit)]
+    fn taken_branch_keeps_nmi(cycles_into_branch: usize) {
+        // SEI, LDX #1, BNE +0, LDA #5, loop forever.
+        let (mut cpu, mut ram) = setup_interrupt_test(&[0x78, 0xA2, 0x01, 0xD0, 0x00, 0xA9, 0x05, 0x4C, 0x07, 0x04]);
+
+        while cpu.pc.to_u16() != 0x0403 {
+            cpu.step_instruction(&mut ram);
+        }
 
-            if cpu.rw {
-                cpu.data = ram[address as usize];
-            } else {
-                ram[address as usize] = cpu.data;
+        // However late in the branch the NMI arrives, it's delayed rather than lost.
+        for _ in 0..cycles_into_branch {
+            cpu.step_cycle(&mut ram);
+        }
+        cpu.set_nmi(false);
+        for _ in 0..10 {
+            cpu.step_instruction(&mut ram);
+        }
+
+        assert_eq!(1, ram.data[0x11]);
+    }
+
+    #[test]
+    fn nmi_is_edge_triggered() {
+        // SEI, then loop forever.
+        let (mut cpu, mut ram) = setup_interrupt_test(&[0x78, 0x4C, 0x01, 0x04]);
+
+        // NMI ignores the I flag, but only fires once while the pin is held low.
+        cpu.set_nmi(false);
+        for _ in 0..20 {
+            cpu.step_instruction(&mut ram);
+        }
+        assert_eq!(1, ram.data[0x11]);
+
+        cpu.set_nmi(true);
+        cpu.set_nmi(false);
+        for _ in 0..20 {
+            cpu.step_instruction(&mut ram);
+        }
+        assert_eq!(2, ram.data[0x11]);
+        assert_eq!(0, ram.data[0x10]);
+    }
+
+    #[test]
+    fn nmi_hijacks_brk() {
+        // BRK, padding byte, then loop forever.
+        let (mut cpu, mut ram) = setup_interrupt_test(&[0x00, 0x00, 0x4C, 0x02, 0x04]);
+
+        // NMI arrives while BRK is pushing the return address.
+        cpu.step_cycle(&mut ram);
+        cpu.set_nmi(false);
+        cpu.step_instruction(&mut ram);
+
+        assert_eq!(0x0700, cpu.pc.to_u16());
+        assert_eq!(0x0402, interrupt_return_address(&cpu, &ram));
+        assert_eq!(0x10, ram.data[0x0101 + cpu.sp as usize] & 0x10); // B flag is still set
+
+        for _ in 0..20 {
+            cpu.step_instruction(&mut ram);
+        }
+        assert_eq!(0, ram.data[0x10]);
+        assert_eq!(1, ram.data[0x11]);
+    }
+
+    /// Instructions to be placed one after another, each with the number of cycles it should take.
+    type TimedProgram<'a> = [(&'a [u8], u32)];
+
+    fn timed_program_bytes(program: &TimedProgram) -> Vec<u8> {
+        program.iter().flat_map(|(bytes, _)| bytes.iter().copied()).collect()
+    }
+
+    fn run_timed_program(cpu: &mut M6502, ram: &mut Ram, program: &TimedProgram) {
+        for (bytes, cycles) in program {
+            assert_eq!(*cycles, cpu.step_instruction(ram), "{:02X?}", bytes);
+        }
+    }
+
+    #[test]
+    fn push_has_dummy_read() {
+        let program: &TimedProgram = &[
+            (&[0xA9, 0x42], 2),             // LDA #$42
+            (&[0x48], 3),                   // PHA
+            (&[0x08], 3),                   // PHP
+            (&[0x68], 4),                   // PLA
+            (&[0x68], 4),                   // PLA
+        ];
+
+        for variant in [M6502Variant::Nmos6502, M6502Variant::Cmos65C02] {
+            let (mut cpu, mut ram) = setup_interrupt_test_for_variant(&timed_program_bytes(program), variant);
+            run_timed_program(&mut cpu, &mut ram, program);
+            assert_eq!(0x42, ram.data[0x01FD], "{:?}", variant);
+            assert_eq!(0x42, cpu.a, "{:?}", variant);
+        }
+    }
+
+    #[test]
+    fn cmos_instructions() {
+        let program: &TimedProgram = &[
+            (&[0xA9, 0x0F], 2),             // LDA #$0F
+            (&[0x85, 0x20], 3),             // STA $20
+            (&[0x64, 0x20], 3),             // STZ $20
+            (&[0xA9, 0x33], 2),             // LDA #$33
+            (&[0x04, 0x20], 5),             // TSB $20
+            (&[0xA9, 0x03], 2),             // LDA #$03
+            (&[0x14, 0x20], 5),             // TRB $20
+            (&[0x87, 0x20], 5),             // SMB0 $20
+            (&[0x57, 0x20], 5),             // RMB5 $20
+            (&[0xA2, 0x42], 2),             // LDX #$42
+            (&[0xDA], 3),                   // PHX
+            (&[0x7A], 4),                   // PLY
+            (&[0x1A], 2),                   // INC A
+            (&[0x89, 0x00], 2),             // BIT #$00
+            (&[0xB2, 0x30], 5),             // LDA ($30)
+            (&[0x9C, 0x00, 0x05], 4),       // STZ $0500
+            (&[0x9E, 0x00, 0x05], 5),       // STZ $0500,X
+            (&[0x80, 0x01, 0xFF], 3),       // BRA +1
+            (&[0x0F, 0x20, 0x7F], 5),       // BBR0 $20,+$7F (not taken)
+            (&[0x8F, 0x20, 0x01, 0xFF], 6), // BBS0 $20,+1 (taken)
+            (&[0x6C, 0xFF, 0x02], 6),       // JMP ($02FF)
+        ];
+        let (mut cpu, mut ram) = setup_interrupt_test_for_variant(&timed_program_bytes(program), M6502Variant::Cmos65C02);
+
+        ram.data[0x30..0x32].copy_from_slice(&[0x80, 0x03]);
+        ram.data[0x0380] = 0x99;
+        ram.data[0x0500] = 0xAA;
+        ram.data[0x0542] = 0xAA;
+
+        // The NMOS 6502 would read the high byte of the JMP target from $0200.
+        ram.data[0x0200] = 0xFF;
+        ram.data[0x02FF..0x0301].copy_from_slice(&[0x00, 0x08]);
+
+        ram.data[0x0800..0x0803].copy_from_slice(&[0x7C, 0x00, 0x09]); // JMP ($0900,X)
+        ram.data[0x0942..0x0944].copy_from_slice(&[0x00, 0x0A]);
+
+        run_timed_program(&mut cpu, &mut ram, &program[..14]);
+        assert_eq!(0x04, cpu.a);
+        assert_eq!(0x42, cpu.y);
+        assert_eq!(0x42, ram.data[0x01FD]);
+        assert_eq!(0x11, ram.data[0x20]);
+        assert!(cpu.p.z);
+
+        run_timed_program(&mut cpu, &mut ram, &program[14..]);
+        assert_eq!(0x99, cpu.a);
+        assert_eq!(0x00, ram.data[0x0500]);
+        assert_eq!(0x00, ram.data[0x0542]);
+        assert_eq!(0x0800, cpu.pc.to_u16());
+
+        assert_eq!(6, cpu.step_instruction(&mut ram));
+        assert_eq!(0x0A00, cpu.pc.to_u16());
+    }
+
+    #[test]
+    fn cmos_unused_opcodes_are_nops() {
+        let program: &TimedProgram = &[
+            (&[0x03], 1),
+            (&[0xFB], 1),
+            (&[0x02, 0xFF], 2),
+            (&[0x44, 0xFF], 3),
+            (&[0xF4, 0xFF], 4),
+            (&[0x5C, 0xFF, 0xFF], 8),
+            (&[0xDC, 0xFF, 0xFF], 4),
+        ];
+        let (mut cpu, mut ram) = setup_interrupt_test_for_variant(&timed_program_bytes(program), M6502Variant::Cmos65C02);
+
+        run_timed_program(&mut cpu, &mut ram, program);
+
+        assert_eq!(0x040E, cpu.pc.to_u16());
+        assert_eq!((0, 0, 0), (cpu.a, cpu.x, cpu.y));
+    }
+
+    #[test]
+    fn cmos_decimal_mode_sets_flags() {
+        let program: &TimedProgram = &[
+            (&[0xF8], 2),                   // SED
+            (&[0x18], 2),                   // CLC
+            (&[0xA9, 0x99], 2),             // LDA #$99
+            (&[0x69, 0x01], 3),             // ADC #$01
+        ];
+        let (mut cpu, mut ram) = setup_interrupt_test_for_variant(&timed_program_bytes(program), M6502Variant::Cmos65C02);
+        run_timed_program(&mut cpu, &mut ram, program);
+        assert_eq!(0x00, cpu.a);
+        assert!(cpu.p.c);
+        assert!(cpu.p.z);
+        assert!(!cpu.p.n);
+
+        let program: &TimedProgram = &[
+            (&[0xF8], 2),                   // SED
+            (&[0x38], 2),                   // SEC
+            (&[0xA9, 0x00], 2),             // LDA #$00
+            (&[0xE9, 0x01], 3),             // SBC #$01
+        ];
+        let (mut cpu, mut ram) = setup_interrupt_test_for_variant(&timed_program_bytes(program), M6502Variant::Cmos65C02);
+        run_timed_program(&mut cpu, &mut ram, program);
+        assert_eq!(0x99, cpu.a);
+        assert!(!cpu.p.c);
+        assert!(!cpu.p.z);
+        assert!(cpu.p.n);
+    }
+
+    #[test]
+    fn interrupts_clear_decimal_flag_on_cmos() {
+        // SED, BRK, padding byte.
+        for (variant, expected_d) in [(M6502Variant::Nmos6502, true), (M6502Variant::Cmos65C02, false)] {
+            let (mut cpu, mut ram) = setup_interrupt_test_for_variant(&[0xF8, 0x00, 0x00], variant);
+            while cpu.pc.to_u16() != 0x0600 {
+                cpu.step_instruction(&mut ram);
             }
+            assert_eq!(expected_d, cpu.p.d);
+        }
+    }
+
+    #[test]
+    fn cmos_wai_waits_for_irq() {
+        // CLI, WAI, LDA #5, loop forever.
+        let (mut cpu, mut ram) = setup_interrupt_test_for_variant(&[0x58, 0xCB, 0xA9, 0x05, 0x4C, 0x04, 0x04], M6502Variant::Cmos65C02);
+
+        for _ in 0..20 {
+            cpu.step_instruction(&mut ram);
         }
+        assert!(cpu.is_halted());
+        assert_eq!(0x0402, cpu.pc.to_u16());
 
-        assert_eq!(0x3399, cpu.pc.to_u16());
+        // The IRQ handler runs straight after WAI.
+        cpu.set_irq(false);
+        while cpu.pc.to_u16() != 0x0600 {
+            cpu.step_instruction(&mut ram);
+        }
+        assert!(!cpu.is_halted());
+        assert_eq!(0x00, cpu.a);
+        assert_eq!(0x0402, interrupt_return_address(&cpu, &ram));
+
+        cpu.set_irq(true);
+        for _ in 0..10 {
+            cpu.step_instruction(&mut ram);
+        }
+        assert_eq!(0x05, cpu.a);
+        assert_eq!(1, ram.data[0x10]);
     }
 
     #[test]
-    fn nes_test() -> Result<(), std::io::Error> {
-        let path = Path::new(ASSET_PATH).join("nestest.nes");
-        let mut cartridge_bytes = fs::read(path).unwrap();
-        let rom = &mut cartridge_bytes[16..(16+0x4000)];
+    fn cmos_wai_continues_after_masked_irq() {
+        // SEI, WAI, LDA #5, loop forever.
+        let (mut cpu, mut ram) = setup_interrupt_test_for_variant(&[0x78, 0xCB, 0xA9, 0x05, 0x4C, 0x04, 0x04], M6502Variant::Cmos65C02);
 
-        // Patch the test start address into the RESET vector.
-        rom[0x3FFC] = 0x00;
-        rom[0x3FFD] = 0xC0;
+        for _ in 0..20 {
+            cpu.step_instruction(&mut ram);
+        }
+        assert!(cpu.is_halted());
 
-        let mut ram = [0; 0x0800];
+        cpu.set_irq(false);
+        for _ in 0..10 {
+            cpu.step_instruction(&mut ram);
+        }
+        assert_eq!(0x05, cpu.a);
+        assert_eq!(0, ram.data[0x10]);
+    }
 
-        // APU and I/O registers - for the purposes of this test, treat them as RAM.
-        let mut apu = [0; 0x18];
+    #[test]
+    fn cmos_stp_stops_until_reset() {
+        // STP
+        let (mut cpu, mut ram) = setup_interrupt_test_for_variant(&[0xDB], M6502Variant::Cmos65C02);
 
-        let options = M6502Options {
-            bcd_enabled: false,
-        };
-        let mut cpu = M6502::new_with_options(options);
+        cpu.set_nmi(false);
+        for _ in 0..20 {
+            cpu.step_instruction(&mut ram);
+        }
+        assert!(cpu.is_halted());
+        assert_eq!(0x0401, cpu.pc.to_u16());
+        assert_eq!(0, ram.data[0x11]);
 
         cpu.set_res(false);
         cpu.set_res(true);
+        cpu.step_instruction(&mut ram);
+        assert!(!cpu.is_halted());
+        assert_eq!(0x0400, cpu.pc.to_u16());
+    }
 
-        let test_log_path = env::temp_dir().join("nestest_aemula.log");
-        let mut test_log_buffer = File::create(&test_log_path)?;
+    /// RAM that records the address on the pins for each DMA cycle.
+    struct DmaRam {
+        ram: Ram,
+        dma_addresses: Vec<u16>,
+    }
 
-        let mut cycles = 0;
-        let mut should_log = false;
-        while cpu.pc.to_u16() != 0xC66E {
-            cpu.set_phi0(true);
-            cpu.set_phi0(false);
+    impl Bus for DmaRam {
+        fn read(&mut self, address: u16) -> u8 {
+            self.ram.read(address)
+        }
 
-            cycles += 1;
+        fn write(&mut self, address: u16, value: u8) {
+            self.ram.write(address, value);
+        }
+
+        fn peek(&self, address: u16) -> u8 {
+            self.ram.peek(address)
+        }
+
+        fn dma_cycle(&mut self, cpu: &M6502) {
+            self.dma_addresses.push(cpu.get_address());
+        }
+    }
+
+    #[test]
+    fn dma_waits_for_read_cycle() {
+        // INC $20, LDA #5, loop forever.
+        let (mut cpu, ram) = setup_interrupt_test(&[0xE6, 0x20, 0xA9, 0x05, 0x4C, 0x04, 0x04]);
+        let mut bus = DmaRam { ram, dma_addresses: vec![] };
+
+        // DMA is requested before the two write cycles of INC, so the CPU is halted on the next opcode fetch.
+        for _ in 0..3 {
+            cpu.step_cycle(&mut bus);
+        }
+        cpu.request_dma(2);
+        cpu.request_dma(1);
+        assert_eq!(3, cpu.dma_cycles_remaining());
+
+        assert_eq!(5, cpu.step_instruction(&mut bus));
+        assert_eq!(vec![0x0402; 3], bus.dma_addresses);
+        assert_eq!(1, bus.ram.data[0x20]);
+        assert_eq!(0, cpu.dma_cycles_remaining());
+        assert!(cpu.is_dma_cycle());
+
+        assert_eq!(2, cpu.step_instruction(&mut bus));
+        assert_eq!(0x05, cpu.a);
+        assert!(!cpu.is_dma_cycle());
+    }
+
+    #[test]
+    fn fast_execution_waits_for_dma() {
+        // LDA #5, loop forever.
+        let (mut cpu, ram) = setup_interrupt_test(&[0xA9, 0x05, 0x4C, 0x02, 0x04]);
+        let mut bus = DmaRam { ram, dma_addresses: vec![] };
+
+        // The DMA cycles are run on their own, before the instruction.
+        cpu.request_dma(4);
+        assert_eq!(4, cpu.execute_instruction(&mut bus));
+        assert_eq!(vec![0x0400; 4], bus.dma_addresses);
+        assert_eq!(0x00, cpu.a);
+
+        assert_eq!(2, cpu.execute_instruction(&mut bus));
+        assert_eq!(0x05, cpu.a);
+    }
+
+    #[test]
+    fn jam_halts_until_reset() {
+        // LDA #5, JAM
+        let (mut cpu, mut ram) = setup_interrupt_test(&[0xA9, 0x05, 0x02]);
+
+        cpu.step_instruction(&mut ram);
+        assert!(!cpu.is_jammed());
+
+        assert_eq!(2, cpu.step_instruction(&mut ram));
+        assert!(cpu.is_jammed());
+
+        // Interrupts don't get it out of the jammed state.
+        cpu.set_irq(false);
+        cpu.set_nmi(false);
+        for _ in 0..20 {
+            assert_eq!(1, cpu.step_instruction(&mut ram));
+            assert_eq!(0xFFFF, cpu.get_address());
+            assert!(cpu.rw);
+        }
+        assert!(cpu.is_jammed());
+        assert_eq!(0x0403, cpu.pc.to_u16());
+        assert_eq!(0x05, cpu.a);
+        assert_eq!(0, ram.data[0x10]);
+        assert_eq!(0, ram.data[0x11]);
+
+        cpu.set_irq(true);
+        cpu.set_nmi(true);
+        cpu.set_res(false);
+        cpu.set_res(true);
+        cpu.step_instruction(&mut ram);
+        assert!(!cpu.is_jammed());
+        assert_eq!(0x0400, cpu.pc.to_u16());
+    }
 
-            if cycles == 7 {
-                should_log = true;
+    /// Runs a program from `setup_interrupt_test` instruction by instruction, both cycle by cycle
+    /// and with `execute_instruction`, and checks that they stay the same. Before each instruction,
+    /// `pins` gives the levels of IRQ and NMI.
+    fn assert_fast_execution_matches(program: &[u8], variant: M6502Variant, pins: impl Fn(usize) -> (bool, bool)) {
+        let (mut cpu, mut ram) = setup_interrupt_test_for_variant(program, variant);
+        let (mut fast_cpu, mut fast_ram) = setup_interrupt_test_for_variant(program, variant);
+
+        for step in 0..100 {
+            let (irq, nmi) = pins(step);
+            for cpu in [&mut cpu, &mut fast_cpu] {
+                cpu.set_irq(irq);
+                cpu.set_nmi(nmi);
             }
 
-            if should_log && cpu.sync {
-                write!(test_log_buffer,
-                    "{:04X}  A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} CPUC:{}\n", 
-                    cpu.pc.to_u16(),
-                    cpu.a,
-                    cpu.x,
-                    cpu.y,
-                    cpu.p.as_u8(false),
-                    cpu.sp,
-                    cycles - 7)?;
+            let expected_cycles = cpu.step_instruction(&mut ram);
+            let cycles = fast_cpu.execute_instruction(&mut fast_ram);
+
+            let registers = |cpu: &M6502| (cpu.pc.to_u16(), cpu.a, cpu.x, cpu.y, cpu.sp, cpu.p.as_u8(false), cpu.is_halted());
+            assert_eq!(registers(&cpu), registers(&fast_cpu), "Step {}", step);
+            assert_eq!(expected_cycles, cycles, "Step {}", step);
+            assert!(ram.data == fast_ram.data, "Step {}", step);
+        }
+    }
+
+    #[test]
+    fn fast_execution_handles_interrupts() {
+        // CLI, LDA #1, STA $20, loop forever. IRQ is held low from the start, and then NMI arrives.
+        assert_fast_execution_matches(&[0x58, 0xA9, 0x01, 0x85, 0x20, 0x4C, 0x05, 0x04], M6502Variant::Nmos6502,
+            |step| (step >= 30, !(50..60).contains(&step)));
+
+        // CLI, LDX #1, BNE +0, LDA #5, loop forever. IRQ arrives just before the taken branch.
+        assert_fast_execution_matches(&[0x58, 0xA2, 0x01, 0xD0, 0x00, 0xA9, 0x05, 0x4C, 0x07, 0x04], M6502Variant::Nmos6502,
+            |step| (!(2..=20).contains(&step), true));
+
+        // SED, BRK, padding byte, then loop forever.
+        assert_fast_execution_matches(&[0xF8, 0x00, 0x00, 0x4C, 0x03, 0x04], M6502Variant::Cmos65C02,
+            |_| (true, true));
+
+        // CLI, WAI, LDA #5, loop forever.
+        assert_fast_execution_matches(&[0x58, 0xCB, 0xA9, 0x05, 0x4C, 0x04, 0x04], M6502Variant::Cmos65C02,
+            |step| (!(20..22).contains(&step), true));
+
+        // LDA #5, JAM
+        assert_fast_execution_matches(&[0xA9, 0x05, 0x02], M6502Variant::Nmos6502,
+            |step| (step < 10, step < 10));
+    }
+
+    #[test]
+    fn fast_execution_matches_for_every_opcode() {
+        for variant in [M6502Variant::Nmos6502, M6502Variant::Cmos65C02] {
+            for opcode in 0..=0xFF {
+                // Operands and registers that cross pages, with memory full of different values.
+                let (mut cpu, mut ram) = setup_interrupt_test_for_variant(&[opcode, 0xE0, 0x12], variant);
+                for (address, value) in ram.data.iter_mut().enumerate().take(0x0600).skip(0x0403) {
+                    *value = (address as u8).wrapping_mul(37);
+                }
+                ram.data.copy_within(0x0500..0x0600, 0x0000);
+                let mut fast_ram = Ram::from_data(ram.data.clone());
+
+                (cpu.a, cpu.x, cpu.y, cpu.sp) = (0x9C, 0x35, 0xF2, 0x80);
+                cpu.p.set_from_u8(0xC3);
+                let mut fast_cpu = M6502::new_with_options(M6502Options { variant, ..Default::default() });
+                fast_cpu.load_state(&cpu.save_state());
+
+                for step in 0..2 {
+                    let expected_cycles = cpu.step_instruction(&mut ram);
+                    let cycles = fast_cpu.execute_instruction(&mut fast_ram);
+
+                    let registers = |cpu: &M6502| (cpu.pc.to_u16(), cpu.a, cpu.x, cpu.y, cpu.sp, cpu.p.as_u8(false), cpu.is_halted());
+                    let message = format!("{:?} {:02X} step {}", variant, opcode, step);
+                    assert_eq!(registers(&cpu), registers(&fast_cpu), "{}", message);
+                    assert_eq!(expected_cycles, cycles, "{}", message);
+                    assert!(ram.data == fast_ram.data, "{}", message);
+                }
             }
+        }
+    }
 
-            let address = cpu.get_address();
+    #[test]
+    fn opcode_table_cycles_match_cpu() {
+        for variant in [M6502Variant::Nmos6502, M6502Variant::Cmos65C02] {
+            for (opcode, metadata) in opcodes(variant).iter().enumerate() {
+                // Operands are all zero, so no page boundaries are crossed. Branches may or may not
+                // be taken, and some instructions halt the CPU, so those are skipped.
+                let skip = matches!(metadata.addressing_mode, AddressingMode::Relative | AddressingMode::ZeroPageRelative)
+                    || matches!(metadata.mnemonic, "JAM" | "STP" | "WAI");
+                if skip {
+                    continue;
+                }
 
-            if should_log {
-                write!(test_log_buffer, "      ")?;
+                let (mut cpu, mut ram) = setup_interrupt_test_for_variant(&[opcode as u8], variant);
+                assert_eq!(metadata.cycles as u32, cpu.step_instruction(&mut ram), "{:?} {:02X}", variant, opcode);
+                if !matches!(metadata.mnemonic, "BRK" | "JMP" | "JSR" | "RTI" | "RTS") {
+                    assert_eq!(0x0400 + metadata.length as u16, cpu.pc.to_u16(), "{:?} {:02X}", variant, opcode);
+                }
             }
-            if cpu.rw {
-                cpu.data = match address {
-                    0x0000..=0x1FFF => ram[(address & 0x07FF) as usize],
-                    0x4000..=0x4017 => apu[(address - 0x4000) as usize],
-                    0x8000..=0xFFFF => rom[((address - 0x8000) & 0x3FFF) as usize],
-                    _ => 0,
-                };
-                if should_log {
-                    write!(test_log_buffer, "READ      ${:04X} => ${:02X}\n", address, cpu.data)?;
+        }
+    }
+
+    #[test]
+    fn all_suite_a() {
+        struct AllSuiteABus {
+            ram: [u8; 0x4000],
+            rom: Vec<u8>,
+        }
+
+        impl Bus for AllSuiteABus {
+            fn read(&mut self, address: u16) -> u8 {
+                self.peek(address)
+            }
+
+            fn write(&mut self, address: u16, value: u8) {
+                if let 0x0000..=0x3FFF = address {
+                    self.ram[address as usize] = value;
                 }
-            } else {
+            }
+
+            fn peek(&self, address: u16) -> u8 {
                 match address {
-                    0x0000..=0x1FFF => ram[(address & 0x07FF) as usize] = cpu.data,
-                    0x4000..=0x4017 => apu[(address - 0x4000) as usize] = cpu.data,
-                    _ => {},
-                }
-                if should_log {
-                    write!(test_log_buffer, "WRITE     ${:04X} <= ${:02X}\n", address, cpu.data)?;
+                    0x0000..=0x3FFF => self.ram[address as usize],
+                    0x4000..=0xFFFF => self.rom[(address - 0x4000) as usize],
                 }
             }
         }
 
-        assert_eq!(0x00, ram[0x0002]);
-        assert_eq!(0x00, ram[0x0003]);
+        let path = Path::new(ASSET_PATH).join("AllSuiteA.bin");
+        let mut bus = AllSuiteABus {
+            ram: [0; 0x4000],
+            rom: fs::read(path).unwrap(),
+        };
+
+        let mut cpu = M6502::new();
+
+        cpu.set_res(false);
+        cpu.set_res(true);
 
-        diff("assets/nestest.log", test_log_path.to_str().unwrap());
+        while cpu.pc.to_u16() != 0x45C2 {
+            cpu.step_cycle(&mut bus);
+        }
 
-        Ok(())
+        assert_eq!(0xFF, bus.ram[0x0210]);
     }
 
     #[test]
-    fn c64_suite() {
-        fn petscii_to_ascii(character: u8) -> String {
-            match character {
-                147 => "\n------------\n".to_string(), // Clear
-                14 => "".to_string(), // Toggle lowercase/uppercase character set
-                0xC1..=0xDA => ((character - 0xC1 + 65) as char).to_string(),
-                0x41..=0x5A => ((character - 0x41 + 97) as char).to_string(),
-                _ => (character as char).to_string()
-            }
-        }
+    fn dormann_functional_test() {
+        run_dormann_functional_test(M6502Variant::Nmos6502, ExecutionMode::Cycle);
+    }
 
-        fn setup_test(filename: &String, ram: &mut [u8; 0x10000], cpu: &mut M6502) {
-            cpu.set_res(false);
-            cpu.set_res(true);
+    #[test]
+    fn dormann_functional_test_65c02() {
+        run_dormann_functional_test(M6502Variant::Cmos65C02, ExecutionMode::Cycle);
+    }
 
-            // Reset RAM.
-            for i in ram.iter_mut() {
-                *i = 0x00;
-            }
+    #[test]
+    fn dormann_functional_test_fast() {
+        run_dormann_functional_test(M6502Variant::Nmos6502, ExecutionMode::Instruction);
+    }
 
-            // Load test data.
-            // First two bytes contain starting address.
-            let path = Path::new(ASSET_PATH).join("c64_test_suite/bin").join(filename);
-            let test_data = fs::read(path).unwrap();
-            let start_address = (test_data[0] as usize) | ((test_data[1] as usize) << 8);
-            for i in 2..test_data.len() {
-                ram[start_address + i - 2] = test_data[i];
+    #[test]
+    fn dormann_functional_test_65c02_fast() {
+        run_dormann_functional_test(M6502Variant::Cmos65C02, ExecutionMode::Instruction);
+    }
+
+    fn run_dormann_functional_test(variant: M6502Variant, mode: ExecutionMode) {
+        let path = Path::new(ASSET_PATH).join("6502_functional_test.bin");
+        let mut ram = Ram::from_data(fs::read(path).unwrap());
+        assert_eq!(0x10000, ram.data.len());
+
+        // Patch the test start address into the RESET vector.
+        ram.data[0xFFFC] = 0x00;
+        ram.data[0xFFFD] = 0x04;
+
+        let mut cpu = M6502::new_with_options(M6502Options {
+            variant,
+            ..Default::default()
+        });
+
+        cpu.set_res(false);
+        cpu.set_res(true);
+
+        // Failed tests end up in an infinite loop, jumping to the same instruction.
+        // The whole suite has passed if that happens at $3399.
+        run_until_trapped_with_mode(&mut cpu, &mut ram, mode);
+
+        assert_eq!(0x3399, cpu.pc.to_u16());
+    }
+
+    struct NesTestBus {
+        ram: [u8; 0x0800],
+
+        // APU and I/O registers - for the purposes of this test, treat them as RAM.
+        apu: [u8; 0x18],
+
+        rom: Vec<u8>,
+    }
+
+    impl Bus for NesTestBus {
+        fn read(&mut self, address: u16) -> u8 {
+            self.peek(address)
+        }
+
+        fn write(&mut self, address: u16, value: u8) {
+            match address {
+                0x0000..=0x1FFF => self.ram[(address & 0x07FF) as usize] = value,
+                0x4000..=0x4017 => self.apu[(address - 0x4000) as usize] = value,
+                _ => {},
             }
+        }
 
-            // Initialize some memory locations.
-            ram[0x0002] = 0x00;
-            ram[0xA002] = 0x00;
-            ram[0xA003] = 0x80;
-            ram[0xFFFE] = 0x48;
-            ram[0xFFFF] = 0xFF;
-            ram[0x01FE] = 0xFF;
-            ram[0x01FF] = 0x7F;
-
-            // Install KERNAL "IRQ handler".
-            static IRQ_ROUTINE: [u8; 19] = [
-                0x48,             // PHA
-                0x8A,             // TXA
-                0x48,             // PHA
-                0x98,             // TYA
-                0x48,             // PHA
-                0xBA,             // TSX
-                0xBD, 0x04, 0x01, // LDA $0104,X
-                0x29, 0x10,       // AND #$10
-                0xF0, 0x03,       // BEQ $FF58
-                0x6C, 0x16, 0x03, // JMP ($0316)
-                0x6C, 0x14, 0x03, // JMP ($0314)
-            ];
-            for i in 0..IRQ_ROUTINE.len() {
-                ram[0xFF48 + i] = IRQ_ROUTINE[i];
+        fn peek(&self, address: u16) -> u8 {
+            match address {
+                0x0000..=0x1FFF => self.ram[(address & 0x07FF) as usize],
+                0x4000..=0x4017 => self.apu[(address - 0x4000) as usize],
+                0x8000..=0xFFFF => self.rom[((address - 0x8000) & 0x3FFF) as usize],
+                _ => 0,
             }
+        }
+    }
 
-            // Stub CHROUT routine.
-            ram[0xFFD2] = 0x60; // RTS
+    /// Loads nestest, and resets a CPU without decimal mode, like the NES's 2A03.
+    fn setup_nes_test() -> (M6502, NesTestBus) {
+        let path = Path::new(ASSET_PATH).join("nestest.nes");
+        let cartridge_bytes = fs::read(path).unwrap();
+        let mut rom = cartridge_bytes[16..(16+0x4000)].to_vec();
 
-            // Stub load routine.
-            ram[0xE16F] = 0xEA; // NOP
+        // Patch the test start address into the RESET vector.
+        rom[0x3FFC] = 0x00;
+        rom[0x3FFD] = 0xC0;
 
-            // Stub GETIN routine.
-            ram[0xFFE4] = 0xA9; // LDA #3
-            ram[0xFFE5] = 0x03;
-            ram[0xFFE6] = 0x60; // RTS
+        let bus = NesTestBus {
+            ram: [0; 0x0800],
+            apu: [0; 0x18],
+            rom,
+        };
 
-            // Initialize registers.
-            cpu.sp = 0xFD;
-            cpu.p.i = true;
-            
-            // Initialize RESET vector.
-            ram[0xFFFC] = 0x01;
-            ram[0xFFFD] = 0x08;
+        let options = M6502Options {
+            bcd_enabled: false,
+            ..Default::default()
+        };
+        let mut cpu = M6502::new_with_options(options);
+
+        cpu.set_res(false);
+        cpu.set_res(true);
+
+        (cpu, bus)
+    }
+
+    #[test]
+    fn nes_test() -> Result<(), std::io::Error> {
+        let (mut cpu, mut bus) = setup_nes_test();
+        let test_log_path = env::temp_dir().join("nestest_aemula.log");
+
+        // Skip the RESET sequence.
+        for _ in 0..6 {
+            cpu.step_cycle(&mut bus);
+        }
+
+        let options = TracerOptions {
+            level: TraceLevel::Cycle,
+            ..Default::default()
+        };
+        let mut tracer = Tracer::new_with_options(File::create(&test_log_path)?, options);
+
+        while cpu.pc.to_u16() != 0xC66E {
+            cpu.step_cycle(&mut bus);
+            tracer.trace(&cpu)?;
+        }
+
+        assert_eq!(0x00, bus.ram[0x0002]);
+        assert_eq!(0x00, bus.ram[0x0003]);
+
+        tracer.flush()?;
+        let expected_log_path = Path::new(ASSET_PATH).join("nestest.log");
+        assert!(diff(expected_log_path.to_str().unwrap(), test_log_path.to_str().unwrap()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn nes_test_fast() {
+        let (mut cpu, mut bus) = setup_nes_test();
+        let (mut fast_cpu, mut fast_bus) = setup_nes_test();
+
+        // Each instruction must leave the registers and memory as they are after running it cycle by cycle.
+        while cpu.pc.to_u16() != 0xC66E {
+            let expected_cycles = cpu.step_instruction(&mut bus);
+            let cycles = fast_cpu.execute_instruction(&mut fast_bus);
+
+            let registers = |cpu: &M6502| (cpu.pc.to_u16(), cpu.a, cpu.x, cpu.y, cpu.sp, cpu.p.as_u8(false), cpu.get_address(), cpu.data);
+            assert_eq!(registers(&cpu), registers(&fast_cpu), "After instruction at ${:04X}", cpu.pc.to_u16());
+            assert_eq!(expected_cycles, cycles, "Cycles for instruction at ${:04X}", cpu.pc.to_u16());
+            assert_eq!(bus.ram, fast_bus.ram);
         }
 
-        let mut ram: [u8; 0x10000] = [0; 0x10000];
-        let mut log = String::new();
+        assert_eq!(0x00, fast_bus.ram[0x0002]);
+        assert_eq!(0x00, fast_bus.ram[0x0003]);
+    }
+
+    #[test]
+    fn c64_suite() {
         let mut test_filename = " start".to_string();
 
         loop {
+            let mut bus = C64Bus::new(&test_filename);
             let mut cpu = M6502::new();
-            setup_test(&test_filename, &mut ram, &mut cpu);
-
-            loop {
-                cpu.set_phi0(true);
-                cpu.set_phi0(false);
-
-                let address = cpu.get_address();
-
-                if cpu.rw {
-                    match address {
-                        0xFFD2 => { // Print character
-                            if cpu.a == 13 {
-                                println!("{}", log);
-                                log.clear();
-                            } else {
-                                log += &petscii_to_ascii(cpu.a);
-                            }
-                            ram[0x030C] = 0x00;
-                        },
-                        0xE16F => { // Load
-                            let filename_address = ram[0xBB] as usize | ((ram[0xBC] as usize) << 8);
-                            let filename_len = ram[0xB7] as usize;
-                            test_filename.clear();
-                            for i in 0..filename_len {
-                                test_filename += &petscii_to_ascii(ram[filename_address + i]);
-                            }
-                            if test_filename == "trap17" {
-                                // All tests passed. Everything from trap17 onwards is C64-specific.
-                                return;
-                            }
-                            break; // Break to outer loop, and load next test.
-                        },
-                        0x8000 | 0xA474 => { // Exit
-                            println!("{}", log);
-                            panic!("Test failed");
-                        },
-                        _ => {}
-                    }
-                    cpu.data = ram[address as usize];
-                } else {
-                    ram[address as usize] = cpu.data;
-                }
+            cpu.set_res(false);
+            cpu.set_res(true);
+            setup_registers(&mut cpu);
+
+            test_filename = bus.run(|bus| cpu.step_cycle(bus));
+            if test_filename == "trap17" {
+                // All tests passed. trap17 onwards need the 6510's I/O port, and are run by the 6510's tests.
+                return;
             }
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,156 @@
+use super::super::Bus;
+use super::Netlist;
+
+/// Drives a 6502 netlist (such as visual6502's) through its pins, reading and writing memory through a [`Bus`].
+pub struct Netlist6502 {
+    netlist: Netlist,
+
+    clk0: usize,
+    res: usize,
+    rdy: usize,
+    so: usize,
+    irq: usize,
+    nmi: usize,
+    rw: usize,
+    sync: usize,
+    address: [usize; 16],
+    data: [usize; 8],
+
+    a: [usize; 8],
+    x: [usize; 8],
+    y: [usize; 8],
+    sp: [usize; 8],
+    /// Bits 4 and 5 aren't stored on the chip, so they may not have nodes.
+    p: [Option<usize>; 8],
+    pcl: [usize; 8],
+    pch: [usize; 8],
+}
+
+fn find_node(netlist: &Netlist, name: &str) -> Result<usize, String> {
+    netlist.node(name).ok_or_else(|| format!("Netlist has no node called '{}'", name))
+}
+
+fn find_nodes<const N: usize>(netlist: &Netlist, prefix: &str) -> Result<[usize; N], String> {
+    let mut nodes = [0; N];
+    for (bit, node) in nodes.iter_mut().enumerate() {
+        *node = find_node(netlist, &format!("{}{}", prefix, bit))?;
+    }
+    Ok(nodes)
+}
+
+impl Netlist6502 {
+    /// Wraps a netlist that uses the visual6502 node names for the pins and registers.
+    pub fn new(netlist: Netlist) -> Result<Self, String> {
+        Ok(Self {
+            clk0: find_node(&netlist, "clk0")?,
+            res: find_node(&netlist, "res")?,
+            rdy: find_node(&netlist, "rdy")?,
+            so: find_node(&netlist, "so")?,
+            irq: find_node(&netlist, "irq")?,
+            nmi: find_node(&netlist, "nmi")?,
+            rw: find_node(&netlist, "rw")?,
+            sync: find_node(&netlist, "sync")?,
+            address: find_nodes(&netlist, "ab")?,
+            data: find_nodes(&netlist, "db")?,
+
+            a: find_nodes(&netlist, "a")?,
+            x: find_nodes(&netlist, "x")?,
+            y: find_nodes(&netlist, "y")?,
+            sp: find_nodes(&netlist, "s")?,
+            p: std::array::from_fn(|bit| netlist.node(&format!("p{}", bit))),
+            pcl: find_nodes(&netlist, "pcl")?,
+            pch: find_nodes(&netlist, "pch")?,
+
+            netlist,
+        })
+    }
+
+    /// Powers up the chip and holds RESET low for a few cycles, in the same way as visual6502.
+    /// The RESET sequence starts when the chip is next clocked.
+    pub fn power_on(&mut self) {
+        self.netlist.reset();
+
+        self.netlist.set(self.res, false);
+        self.netlist.set(self.clk0, false);
+        self.netlist.set(self.rdy, true);
+        self.netlist.set(self.so, false);
+        self.netlist.set(self.irq, true);
+        self.netlist.set(self.nmi, true);
+
+        for _ in 0..8 {
+            self.netlist.set(self.clk0, true);
+            self.netlist.set(self.clk0, false);
+        }
+
+        self.netlist.set(self.res, true);
+    }
+
+    /// Toggles CLK0. On the falling edge, the CPU puts a new address on the bus, and
+    /// for a read cycle, memory is read onto the data bus. On the rising edge, a write
+    /// cycle writes the data bus to memory.
+    pub fn half_step(&mut self, bus: &mut impl Bus) {
+        if self.clk0() {
+            self.netlist.set(self.clk0, false);
+            if self.rw() {
+                let value = bus.read(self.address());
+                self.netlist.set_bits(&self.data, value as u32);
+            }
+        } else {
+            self.netlist.set(self.clk0, true);
+            self.handle_bus_write(bus);
+        }
+    }
+
+    fn handle_bus_write(&mut self, bus: &mut impl Bus) {
+        if !self.rw() {
+            bus.write(self.address(), self.data());
+        }
+    }
+
+    pub fn clk0(&self) -> bool {
+        self.netlist.is_high(self.clk0)
+    }
+
+    pub fn rw(&self) -> bool {
+        self.netlist.is_high(self.rw)
+    }
+
+    pub fn sync(&self) -> bool {
+        self.netlist.is_high(self.sync)
+    }
+
+    pub fn address(&self) -> u16 {
+        self.netlist.read_bits(&self.address) as u16
+    }
+
+    pub fn data(&self) -> u8 {
+        self.netlist.read_bits(&self.data) as u8
+    }
+
+    pub fn a(&self) -> u8 {
+        self.netlist.read_bits(&self.a) as u8
+    }
+
+    pub fn x(&self) -> u8 {
+        self.netlist.read_bits(&self.x) as u8
+    }
+
+    pub fn y(&self) -> u8 {
+        self.netlist.read_bits(&self.y) as u8
+    }
+
+    pub fn sp(&self) -> u8 {
+        self.netlist.read_bits(&self.sp) as u8
+    }
+
+    pub fn p(&self) -> u8 {
+        self.p
+            .iter()
+            .enumerate()
+            .fold(0, |value, (bit, node)| value | ((node.is_some_and(|node| self.netlist.is_high(node)) as u8) << bit))
+    }
+
+    pub fn pc(&self) -> u16 {
+        u16::from_le_bytes([self.netlist.read_bits(&self.pcl) as u8, self.netlist.read_bits(&self.pch) as u8])
+    }
+}
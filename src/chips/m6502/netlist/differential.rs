@@ -0,0 +1,198 @@
+use std::fmt;
+
+use crate::chips::test_bus::Ram;
+
+use super::super::{M6502, M6502Options, M6502Variant};
+use super::super::registers::SplitRegister16;
+use super::Netlist6502;
+
+/// The first pin that differed between the netlist and [`M6502`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Divergence {
+    /// Number of half-cycles since the first opcode fetch after RESET.
+    pub half_cycle: u64,
+
+    pub pin: &'static str,
+
+    /// The value from the netlist.
+    pub expected: u16,
+
+    /// The value from `M6502`.
+    pub actual: u16,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} differs at half-cycle {}: netlist has ${:04X}, M6502 has ${:04X}",
+            self.pin, self.half_cycle, self.expected, self.actual)
+    }
+}
+
+/// Maximum number of half-cycles to wait for the first opcode fetch after RESET.
+const MAX_RESET_HALF_CYCLES: u32 = 100;
+
+fn compare(half_cycle: u64, pin: &'static str, expected: u16, actual: u16) -> Result<(), Divergence> {
+    if expected == actual {
+        Ok(())
+    } else {
+        Err(Divergence { half_cycle, pin, expected, actual })
+    }
+}
+
+/// Runs the netlist and an NMOS [`M6502`] in lock-step, each with its own copy of `memory`
+/// (which must be 64KB, including the RESET vector), and compares their pins every half-cycle.
+///
+/// Both CPUs are reset, and run until their first opcode fetch. The registers that RESET
+/// doesn't initialise are then copied from the netlist to the `M6502`.
+pub fn compare_with_netlist(netlist: &mut Netlist6502, memory: &[u8], half_cycles: u64) -> Result<(), Divergence> {
+    assert_eq!(0x10000, memory.len());
+
+    let mut netlist_ram = Ram::from_data(memory.to_vec());
+    let mut ram = Ram::from_data(memory.to_vec());
+
+    netlist.power_on();
+    for reset_half_cycles in 1.. {
+        netlist.half_step(&mut netlist_ram);
+        if !netlist.clk0() && netlist.sync() {
+            break;
+        }
+        assert!(reset_half_cycles < MAX_RESET_HALF_CYCLES, "Netlist did not fetch an opcode after RESET");
+    }
+
+    let mut cpu = M6502::new_with_options(M6502Options {
+        bcd_enabled: true,
+        variant: M6502Variant::Nmos6502,
+    });
+    cpu.set_res(false);
+    cpu.set_res(true);
+    cpu.step_instruction(&mut ram);
+
+    cpu.a = netlist.a();
+    cpu.x = netlist.x();
+    cpu.y = netlist.y();
+    cpu.sp = netlist.sp();
+    cpu.p.set_from_u8(netlist.p());
+    compare(0, "pc", netlist.pc(), cpu.pc.to_u16())?;
+    cpu.pc = SplitRegister16::from_u16(netlist.pc());
+
+    let mut half_cycle = 0;
+    while half_cycle < half_cycles {
+        // CLK0 rising edge: a write cycle writes to memory.
+        netlist.half_step(&mut netlist_ram);
+        half_cycle += 1;
+        if !cpu.rw {
+            compare(half_cycle, "data", netlist.data() as u16, cpu.data() as u16)?;
+        }
+
+        // CLK0 falling edge: the next cycle starts.
+        netlist.half_step(&mut netlist_ram);
+        cpu.step_cycle(&mut ram);
+        half_cycle += 1;
+        compare(half_cycle, "address", netlist.address(), cpu.get_address())?;
+        compare(half_cycle, "rw", netlist.rw() as u16, cpu.rw as u16)?;
+        compare(half_cycle, "sync", netlist.sync() as u16, cpu.sync() as u16)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use super::*;
+    use super::super::super::{assemble_with_options, AssemblerOptions};
+    use super::super::Netlist;
+
+    const ASSET_PATH: &str = "test_assets/chips/mos6502/visual6502";
+
+    /// Loads visual6502's data files, which must have been copied into the test assets.
+    fn load_visual6502() -> Netlist6502 {
+        let path = Path::new(ASSET_PATH);
+        let read = |name| fs::read_to_string(path.join(name))
+            .unwrap_or_else(|error| panic!("Can't read {} from {}: {}", name, ASSET_PATH, error));
+        let (segdefs, transdefs, nodenames) = (read("segdefs.js"), read("transdefs.js"), read("nodenames.js"));
+
+        let netlist = Netlist::from_visual6502(&segdefs, &transdefs, &nodenames).unwrap();
+        Netlist6502::new(netlist).unwrap()
+    }
+
+    /// The undocumented instructions whose behaviour comes from text documents, rather than the chip itself,
+    /// each with the code that sets up and checks its result.
+    const UNOFFICIAL_OPCODE_CASES: &[(&str, &str)] = &[
+        ("SHA abs,Y", "SHA $1210,Y"),
+        ("SHA abs,Y crossing a page", "SHA $12F0,Y"),
+        ("SHA (zp),Y", "
+            LDA #$20
+            STA $80
+            LDA #$12
+            STA $81
+            LDA #$5A
+            SHA ($80),Y
+        "),
+        ("SHX abs,Y", "SHX $1220,Y"),
+        ("SHX abs,Y crossing a page", "SHX $12F0,Y"),
+        ("SHY abs,X", "SHY $1230,X"),
+        ("SHY abs,X crossing a page", "SHY $12F0,X"),
+        ("ANE #imm", "
+            LDA #$00
+            ANE #$FF
+            STA $90
+        "),
+        ("LXA #imm", "
+            LDA #$00
+            LXA #$FF
+            STA $91
+            STX $92
+        "),
+        ("ARR #imm", "
+            LDA #$FF
+            ARR #$AA
+            PHP
+        "),
+        ("ARR #imm in decimal mode", "
+            SED
+            LDA #$59
+            ARR #$5F
+            PHP
+            LDA #$FF
+            SEC
+            ARR #$F5
+            PHP
+        "),
+    ];
+
+    #[test]
+    #[ignore = "needs segdefs.js, transdefs.js and nodenames.js from visual6502 in test_assets/chips/mos6502/visual6502"]
+    fn unofficial_opcodes_match_visual6502() {
+        let mut netlist = load_visual6502();
+
+        let mut failures = Vec::new();
+        for (name, code) in UNOFFICIAL_OPCODE_CASES {
+            let program = assemble_with_options(&format!("
+                    .org $0400
+                    LDX #$FF
+                    TXS
+                    LDA #$5A
+                    LDX #$C3
+                    LDY #$F0
+                    {}
+                done:
+                    JMP done
+
+                    .org $FFFC
+                    .word $0400
+            ", code), AssemblerOptions { unofficial_opcodes: true, ..Default::default() }).unwrap();
+
+            let mut memory = vec![0; 0x10000];
+            program.load_into(&mut memory);
+
+            if let Err(divergence) = compare_with_netlist(&mut netlist, &memory, 200) {
+                failures.push(format!("{}: {}", name, divergence));
+            }
+        }
+
+        assert!(failures.is_empty(), "{} of {} cases differ from visual6502:\n{}",
+            failures.len(), UNOFFICIAL_OPCODE_CASES.len(), failures.join("\n"));
+    }
+}
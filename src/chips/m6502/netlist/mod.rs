@@ -0,0 +1,436 @@
+//! A switch-level simulator for NMOS netlists, using the same algorithm as
+//! [visual6502](https://github.com/trebonian/visual6502/blob/master/chipsim.js).
+//!
+//! Each node is either high or low. Transistors connect two nodes together when their gate node is high.
+//! When a node changes, the group of nodes that is connected to it through switched-on transistors
+//! is recalculated: it is low if connected to ground or a pulled-down node, and high if connected to
+//! power or a pulled-up node. Otherwise it keeps its charge, which is high if any of its nodes were high.
+
+mod cpu;
+mod differential;
+
+use std::collections::HashMap;
+
+pub use self::cpu::Netlist6502;
+
+struct Node {
+    pullup: bool,
+    pulldown: bool,
+    state: bool,
+
+    /// Transistors whose gate is this node.
+    gates: Vec<usize>,
+
+    /// Transistors whose source or drain is this node.
+    channels: Vec<usize>,
+}
+
+impl Node {
+    fn new() -> Self {
+        Self {
+            pullup: false,
+            pulldown: false,
+            state: false,
+            gates: Vec::new(),
+            channels: Vec::new(),
+        }
+    }
+}
+
+/// A transistor connects `c1` and `c2` when it's on. Its gate is found through [`Node::gates`].
+struct Transistor {
+    c1: usize,
+    c2: usize,
+    on: bool,
+}
+
+pub struct Netlist {
+    nodes: Vec<Node>,
+    transistors: Vec<Transistor>,
+    names: HashMap<String, usize>,
+    gnd: usize,
+    pwr: usize,
+
+    // Scratch space for recalculating nodes.
+    group: Vec<usize>,
+    in_group: Vec<bool>,
+    recalc: Vec<usize>,
+    in_recalc: Vec<bool>,
+}
+
+/// A change that is still propagating after this many iterations means the circuit oscillates.
+const MAX_ITERATIONS: usize = 100;
+
+impl Netlist {
+    /// Builds a netlist from the contents of visual6502's `segdefs.js`, `transdefs.js`
+    /// and `nodenames.js`. The names must include `vss` (ground) and `vcc` (power).
+    pub fn from_visual6502(segdefs: &str, transdefs: &str, nodenames: &str) -> Result<Self, String> {
+        let mut names = HashMap::new();
+        for line in nodenames.lines() {
+            let line = line.split("//").next().unwrap().trim().trim_end_matches(',');
+            if let Some((name, number)) = line.split_once(':') {
+                let name = name.trim().trim_matches(|c| c == '"' || c == '\'');
+                let number = number.trim().parse().map_err(|_| format!("Invalid node number in '{}'", line))?;
+                names.insert(name.to_string(), number);
+            }
+        }
+
+        let gnd = *names.get("vss").ok_or("Node names don't include vss")?;
+        let pwr = *names.get("vcc").ok_or("Node names don't include vcc")?;
+
+        let mut netlist = Self {
+            nodes: Vec::new(),
+            transistors: Vec::new(),
+            names,
+            gnd,
+            pwr,
+            group: Vec::new(),
+            in_group: Vec::new(),
+            recalc: Vec::new(),
+            in_recalc: Vec::new(),
+        };
+        netlist.ensure_node(gnd.max(pwr));
+
+        // Each segment is "[node, '+' or '-', layer, coordinates...]". '+' means the node has a pullup.
+        for line in segdefs.lines() {
+            let fields = match list_fields(line) {
+                Some(fields) if fields.len() >= 2 => fields,
+                _ => continue,
+            };
+            let node = parse_number(fields[0])?;
+            netlist.ensure_node(node);
+            if fields[1].trim_matches('\'') == "+" {
+                netlist.nodes[node].pullup = true;
+            }
+        }
+
+        // Each transistor is "['name', gate, c1, c2, ...]".
+        for line in transdefs.lines() {
+            let fields = match list_fields(line) {
+                Some(fields) if fields.len() >= 4 && fields[0].starts_with('\'') => fields,
+                _ => continue,
+            };
+            let gate = parse_number(fields[1])?;
+            let mut c1 = parse_number(fields[2])?;
+            let mut c2 = parse_number(fields[3])?;
+
+            // Keep power and ground in c2, so they are never recalculated.
+            if c1 == gnd || c1 == pwr {
+                std::mem::swap(&mut c1, &mut c2);
+            }
+
+            netlist.ensure_node(gate.max(c1).max(c2));
+            let index = netlist.transistors.len();
+            netlist.transistors.push(Transistor { c1, c2, on: false });
+            netlist.nodes[gate].gates.push(index);
+            netlist.nodes[c1].channels.push(index);
+            netlist.nodes[c2].channels.push(index);
+        }
+
+        if netlist.transistors.is_empty() {
+            return Err("Netlist has no transistors".to_string());
+        }
+
+        netlist.reset();
+        Ok(netlist)
+    }
+
+    fn ensure_node(&mut self, node: usize) {
+        while self.nodes.len() <= node {
+            self.nodes.push(Node::new());
+        }
+        self.in_group.resize(self.nodes.len(), false);
+        self.in_recalc.resize(self.nodes.len(), false);
+    }
+
+    /// Returns the number of a named node.
+    pub fn node(&self, name: &str) -> Option<usize> {
+        self.names.get(name).copied()
+    }
+
+    /// Sets every node low and every transistor off, and then settles the whole netlist.
+    pub fn reset(&mut self) {
+        for node in &mut self.nodes {
+            node.state = false;
+        }
+        self.nodes[self.pwr].state = true;
+        for transistor in &mut self.transistors {
+            transistor.on = false;
+        }
+
+        let all: Vec<usize> = (0..self.nodes.len()).collect();
+        self.recalc_nodes(&all);
+    }
+
+    pub fn is_high(&self, node: usize) -> bool {
+        self.nodes[node].state
+    }
+
+    /// Drives a node high or low, as if it were an input pad.
+    pub fn set(&mut self, node: usize, high: bool) {
+        self.drive(node, high);
+        self.recalc_nodes(&[node]);
+    }
+
+    /// Drives several nodes at once, with bit N of `value` going to the Nth node.
+    pub fn set_bits(&mut self, nodes: &[usize], value: u32) {
+        for (bit, node) in nodes.iter().enumerate() {
+            self.drive(*node, value & (1 << bit) != 0);
+        }
+        self.recalc_nodes(nodes);
+    }
+
+    /// Reads several nodes at once, with the Nth node going to bit N of the result.
+    pub fn read_bits(&self, nodes: &[usize]) -> u32 {
+        nodes
+            .iter()
+            .enumerate()
+            .fold(0, |value, (bit, node)| value | ((self.is_high(*node) as u32) << bit))
+    }
+
+    fn drive(&mut self, node: usize, high: bool) {
+        self.nodes[node].pullup = high;
+        self.nodes[node].pulldown = !high;
+    }
+
+    /// Recalculates `nodes`, and everything their changes affect, until the netlist settles.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the netlist is still changing after [`MAX_ITERATIONS`].
+    fn recalc_nodes(&mut self, nodes: &[usize]) {
+        let mut list: Vec<usize> = nodes.to_vec();
+
+        for _ in 0..MAX_ITERATIONS {
+            if list.is_empty() {
+                return;
+            }
+
+            for node in &list {
+                self.recalc_node(*node);
+            }
+
+            list = std::mem::take(&mut self.recalc);
+            for node in &list {
+                self.in_recalc[*node] = false;
+            }
+        }
+
+        if !list.is_empty() {
+            panic!("Netlist did not settle after {} iterations, {} nodes still changing", MAX_ITERATIONS, list.len());
+        }
+    }
+
+    fn recalc_node(&mut self, node: usize) {
+        if node == self.gnd || node == self.pwr {
+            return;
+        }
+
+        self.group.clear();
+        self.add_to_group(node);
+        let state = self.group_value();
+
+        let group = std::mem::take(&mut self.group);
+        for member in &group {
+            self.in_group[*member] = false;
+
+            if self.nodes[*member].state == state {
+                continue;
+            }
+            self.nodes[*member].state = state;
+
+            for gate_index in 0..self.nodes[*member].gates.len() {
+                let transistor = self.nodes[*member].gates[gate_index];
+                self.switch_transistor(transistor, state);
+            }
+        }
+        self.group = group;
+    }
+
+    fn add_to_group(&mut self, node: usize) {
+        if self.in_group[node] {
+            return;
+        }
+        self.in_group[node] = true;
+        self.group.push(node);
+
+        if node == self.gnd || node == self.pwr {
+            return;
+        }
+
+        for channel_index in 0..self.nodes[node].channels.len() {
+            let transistor = &self.transistors[self.nodes[node].channels[channel_index]];
+            if !transistor.on {
+                continue;
+            }
+            let other = if transistor.c1 == node { transistor.c2 } else { transistor.c1 };
+            self.add_to_group(other);
+        }
+    }
+
+    fn group_value(&self) -> bool {
+        if self.in_group[self.gnd] {
+            return false;
+        }
+        if self.in_group[self.pwr] {
+            return true;
+        }
+
+        for node in &self.group {
+            let node = &self.nodes[*node];
+            if node.pullup {
+                return true;
+            }
+            if node.pulldown {
+                return false;
+            }
+            if node.state {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn switch_transistor(&mut self, index: usize, on: bool) {
+        let transistor = &mut self.transistors[index];
+        if transistor.on == on {
+            return;
+        }
+        transistor.on = on;
+
+        let (c1, c2) = (transistor.c1, transistor.c2);
+        self.add_recalc(c1);
+        if !on {
+            self.add_recalc(c2);
+        }
+    }
+
+    fn add_recalc(&mut self, node: usize) {
+        if node == self.gnd || node == self.pwr || self.in_recalc[node] {
+            return;
+        }
+        self.in_recalc[node] = true;
+        self.recalc.push(node);
+    }
+}
+
+/// Returns the comma-separated fields of a line like "[1, '+', 2],", or `None` if it isn't a list.
+fn list_fields(line: &str) -> Option<Vec<&str>> {
+    let line = line.trim().trim_end_matches(',');
+    let inner = line.strip_prefix('[')?.strip_suffix(']')?;
+    Some(inner.split(',').map(str::trim).collect())
+}
+
+fn parse_number(text: &str) -> Result<usize, String> {
+    text.parse().map_err(|_| format!("Invalid node number '{}'", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Node 1 is ground and node 2 is power. Nodes with '+' have a pullup.
+    const NODENAMES: &str = "
+        var nodenames = {
+        vss: 1,
+        vcc: 2,
+        a: 3,       // Inverter input, and NAND input
+        not_a: 4,
+        b: 5,
+        nand: 6,
+        clk: 8,
+        stored: 9,  // Only driven through a pass transistor, so holds its charge
+        out: 10,
+        }
+    ";
+
+    const SEGDEFS: &str = "
+        var segdefs = [
+        [ 1,'-',0,0,0],
+        [ 2,'+',0,0,0],
+        [ 3,'-',1,0,0],
+        [ 4,'+',1,0,0],
+        [ 5,'-',1,0,0],
+        [ 6,'+',1,0,0],
+        [ 7,'-',1,0,0],
+        [ 8,'-',1,0,0],
+        [ 9,'-',1,0,0],
+        [ 10,'+',1,0,0],
+        ]
+    ";
+
+    const TRANSDEFS: &str = "
+        var transdefs = [
+        ['t1',3,4,1,[0,0,0,0],[0,0,0,0,0],false],
+        ['t2',3,6,7,[0,0,0,0],[0,0,0,0,0],false],
+        ['t3',5,1,7,[0,0,0,0],[0,0,0,0,0],false],
+        ['t4',8,3,9,[0,0,0,0],[0,0,0,0,0],false],
+        ['t5',9,10,1,[0,0,0,0],[0,0,0,0,0],false],
+        ]
+    ";
+
+    fn netlist() -> Netlist {
+        Netlist::from_visual6502(SEGDEFS, TRANSDEFS, NODENAMES).unwrap()
+    }
+
+    #[test]
+    fn inverter() {
+        let mut netlist = netlist();
+        let (a, not_a) = (netlist.node("a").unwrap(), netlist.node("not_a").unwrap());
+
+        netlist.set(a, false);
+        assert!(netlist.is_high(not_a));
+
+        netlist.set(a, true);
+        assert!(!netlist.is_high(not_a));
+    }
+
+    #[test]
+    fn nand() {
+        let mut netlist = netlist();
+        let inputs = [netlist.node("a").unwrap(), netlist.node("b").unwrap()];
+        let nand = netlist.node("nand").unwrap();
+
+        for value in 0..4 {
+            netlist.set_bits(&inputs, value);
+            assert_eq!(value != 3, netlist.is_high(nand), "inputs {:02b}", value);
+            assert_eq!(value, netlist.read_bits(&inputs));
+        }
+    }
+
+    #[test]
+    fn dynamic_latch() {
+        let mut netlist = netlist();
+        let (a, clk, out) = (netlist.node("a").unwrap(), netlist.node("clk").unwrap(), netlist.node("out").unwrap());
+
+        // While the clock is high, the stored node follows the input.
+        netlist.set(clk, true);
+        netlist.set(a, true);
+        assert!(!netlist.is_high(out));
+
+        // Once the clock goes low, it holds its charge.
+        netlist.set(clk, false);
+        netlist.set(a, false);
+        assert!(!netlist.is_high(out));
+
+        netlist.set(clk, true);
+        assert!(netlist.is_high(out));
+    }
+
+    #[test]
+    #[should_panic(expected = "did not settle")]
+    fn oscillator() {
+        // A pulled-up node that pulls itself down when it's high never settles.
+        let nodenames = "vss: 1\nvcc: 2";
+        let segdefs = "[ 1,'-',0,0,0],\n[ 2,'+',0,0,0],\n[ 3,'+',1,0,0],";
+        let transdefs = "['t1',3,3,1,[0,0,0,0],[0,0,0,0,0],false],";
+        let _ = Netlist::from_visual6502(segdefs, transdefs, nodenames);
+    }
+
+    #[test]
+    fn invalid_data() {
+        assert!(Netlist::from_visual6502(SEGDEFS, TRANSDEFS, "a: 3").is_err());
+        assert!(Netlist::from_visual6502(SEGDEFS, "", NODENAMES).is_err());
+        assert!(Netlist::from_visual6502("[x,'+',1]", TRANSDEFS, NODENAMES).is_err());
+    }
+}
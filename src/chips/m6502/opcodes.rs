@@ -0,0 +1,66 @@
+use super::M6502Variant;
+
+/// How an instruction's operand is written in assembly source.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum AddressingMode {
+    Implied,
+    Accumulator,
+    Immediate,
+    Relative,
+    ZeroPage,
+    ZeroPageX,
+    ZeroPageY,
+    Absolute,
+    AbsoluteX,
+    AbsoluteY,
+    IndexedIndirectX,
+    IndirectIndexedY,
+    Indirect,
+
+    // 65C02 only
+    ZeroPageIndirect,
+    AbsoluteIndexedIndirect,
+    ZeroPageRelative,
+}
+
+impl AddressingMode {
+    /// Number of bytes that follow the opcode.
+    pub fn operand_length(self) -> u8 {
+        match self {
+            AddressingMode::Implied | AddressingMode::Accumulator => 0,
+            AddressingMode::Immediate | AddressingMode::Relative |
+            AddressingMode::ZeroPage | AddressingMode::ZeroPageX | AddressingMode::ZeroPageY |
+            AddressingMode::IndexedIndirectX | AddressingMode::IndirectIndexedY |
+            AddressingMode::ZeroPageIndirect => 1,
+            AddressingMode::Absolute | AddressingMode::AbsoluteX | AddressingMode::AbsoluteY |
+            AddressingMode::Indirect | AddressingMode::AbsoluteIndexedIndirect |
+            AddressingMode::ZeroPageRelative => 2,
+        }
+    }
+}
+
+/// Metadata for a single opcode, generated from the same table as the CPU itself.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Opcode {
+    pub mnemonic: &'static str,
+    pub addressing_mode: AddressingMode,
+
+    /// Number of bytes, including the opcode.
+    pub length: u8,
+
+    /// Number of cycles, when no page boundary is crossed and no branch is taken.
+    pub cycles: u8,
+
+    /// False for undocumented NMOS opcodes, and for unused 65C02 opcodes.
+    pub official: bool,
+}
+
+include!(concat!(env!("OUT_DIR"), "/m6502_opcodes.generated.rs"));
+
+/// Returns the opcode table for the given variant, indexed by opcode.
+pub fn opcodes(variant: M6502Variant) -> &'static [Opcode; 256] {
+    match variant {
+        M6502Variant::Nmos6502 => &NMOS_OPCODES,
+        M6502Variant::Cmos65C02 => &CMOS_OPCODES,
+    }
+}
\ No newline at end of file
@@ -0,0 +1,530 @@
+use std::ops::RangeInclusive;
+
+use super::{opcodes, AddressingMode, Bus, BrkFlags, M6502, M6502Variant};
+
+/// Something that a program did which works in the emulator, but probably shouldn't.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Violation {
+    /// An instruction read RAM that hadn't been written. Each address is only reported once.
+    UninitialisedRead { address: u16 },
+
+    /// A push with the stack pointer at $00, which wraps around to $FF.
+    StackOverflow,
+
+    /// A pull with the stack pointer at $FF, which wraps around to $00.
+    StackUnderflow,
+
+    /// An instruction byte was executed after the CPU wrote it as data. Reported once for each write.
+    SelfModifyingCode { address: u16 },
+
+    /// A write to ROM.
+    RomWrite { address: u16, value: u8 },
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Finding {
+    pub violation: Violation,
+
+    /// Address of the instruction that caused it.
+    pub pc: u16,
+
+    /// The cycle it happened in, counting from when the sanitizer was created.
+    pub cycle: u64,
+}
+
+pub struct SanitizerOptions {
+    /// Addresses that hold RAM. Only these are checked for reads before writes, and for self-modifying code.
+    pub ram: Vec<RangeInclusive<u16>>,
+
+    /// Addresses that hold ROM, which shouldn't be written.
+    pub rom: Vec<RangeInclusive<u16>>,
+
+    /// Maps an address on the bus to the address it is looked up as, for systems that don't decode
+    /// every address line. The ranges above are for the mapped addresses.
+    pub mirror: fn(u16) -> u16,
+}
+
+impl Default for SanitizerOptions {
+    fn default() -> Self {
+        Self {
+            ram: vec![0x0000..=0xFFFF],
+            rom: vec![],
+            mirror: |address| address,
+        }
+    }
+}
+
+const RAM: u8 = 0x01;
+const ROM: u8 = 0x02;
+const INITIALISED: u8 = 0x04;
+
+/// Written by the CPU, and not executed since.
+const DATA: u8 = 0x08;
+
+/// Mnemonics that write to their effective address, without needing what was there before.
+/// Some of them read it first anyway, on their way to the write.
+const STORES: [&str; 9] = ["STA", "STX", "STY", "STZ", "SAX", "SHA", "SHS", "SHX", "SHY"];
+
+/// The instruction that is currently being checked.
+struct Instruction {
+    pc: u16,
+    mnemonic: &'static str,
+    addressing_mode: AddressingMode,
+    length: u8,
+    x: u8,
+    y: u8,
+    sp: u8,
+    operand: [Option<u8>; 2],
+    pointer: [Option<u8>; 2],
+
+    /// False until the cycle after the opcode fetch, when it's known whether an interrupt took over.
+    started: bool,
+    interrupt: bool,
+}
+
+impl Instruction {
+    fn operand_byte(&self) -> Option<u16> {
+        self.operand[0].map(u16::from)
+    }
+
+    fn operand_word(&self) -> Option<u16> {
+        Some(u16::from_le_bytes([self.operand[0]?, self.operand[1]?]))
+    }
+
+    fn pointer_word(&self) -> Option<u16> {
+        Some(u16::from_le_bytes([self.pointer[0]?, self.pointer[1]?]))
+    }
+
+    /// Addresses that the low and high bytes of an indirect pointer are read from.
+    fn pointer_addresses(&self, cmos: bool) -> Option<[u16; 2]> {
+        let zero_page = |address: u16| [address & 0xFF, address.wrapping_add(1) & 0xFF];
+
+        match self.addressing_mode {
+            AddressingMode::IndexedIndirectX => Some(zero_page(self.operand_byte()? + self.x as u16)),
+            AddressingMode::IndirectIndexedY | AddressingMode::ZeroPageIndirect => Some(zero_page(self.operand_byte()?)),
+            AddressingMode::Indirect => {
+                let address = self.operand_word()?;
+                // The NMOS 6502 doesn't carry into the high byte of the pointer address.
+                let high = if cmos { address.wrapping_add(1) } else { (address & 0xFF00) | (address.wrapping_add(1) & 0xFF) };
+                Some([address, high])
+            }
+            AddressingMode::AbsoluteIndexedIndirect => {
+                let address = self.operand_word()?.wrapping_add(self.x as u16);
+                Some([address, address.wrapping_add(1)])
+            }
+            _ => None,
+        }
+    }
+
+    /// The address that the instruction reads its data from, once enough of it is known.
+    fn effective_address(&self) -> Option<u16> {
+        match self.addressing_mode {
+            AddressingMode::ZeroPage | AddressingMode::ZeroPageRelative => self.operand_byte(),
+            AddressingMode::ZeroPageX => Some((self.operand_byte()? + self.x as u16) & 0xFF),
+            AddressingMode::ZeroPageY => Some((self.operand_byte()? + self.y as u16) & 0xFF),
+            AddressingMode::Absolute => self.operand_word(),
+            AddressingMode::AbsoluteX => Some(self.operand_word()?.wrapping_add(self.x as u16)),
+            AddressingMode::AbsoluteY => Some(self.operand_word()?.wrapping_add(self.y as u16)),
+            AddressingMode::IndexedIndirectX | AddressingMode::ZeroPageIndirect => self.pointer_word(),
+            AddressingMode::IndirectIndexedY => Some(self.pointer_word()?.wrapping_add(self.y as u16)),
+            _ => None,
+        }
+    }
+
+    /// Number of bytes the instruction pulls from the stack.
+    fn pulls(&self) -> u8 {
+        match self.mnemonic {
+            "PLA" | "PLP" | "PLX" | "PLY" => 1,
+            "RTS" => 2,
+            "RTI" => 3,
+            _ => 0,
+        }
+    }
+
+    /// The 6502 does plenty of reads that it ignores. This returns true for the ones that it doesn't,
+    /// and remembers any pointer bytes so that the effective address can be worked out.
+    fn is_data_read(&mut self, address: u16, value: u8, cmos: bool) -> bool {
+        if self.interrupt {
+            return false;
+        }
+
+        let pulls = self.pulls();
+        if pulls > 0 {
+            return (1..=pulls).any(|offset| address == 0x0100 | self.sp.wrapping_add(offset) as u16);
+        }
+
+        if let Some(pointer) = self.pointer_addresses(cmos) {
+            if let Some(index) = pointer.iter().position(|&pointer_address| pointer_address == address) {
+                self.pointer[index] = Some(value);
+                return true;
+            }
+        }
+
+        let ignores_data = STORES.contains(&self.mnemonic) || matches!(self.mnemonic, "JMP" | "JSR" | "NOP" | "JAM");
+        !ignores_data && self.effective_address() == Some(address)
+    }
+}
+
+/// Watches the CPU for reads of uninitialised RAM, stack pointer wrap-around, self-modifying code,
+/// and writes to ROM. Each of these is recorded as a [`Finding`].
+///
+/// Call [`Sanitizer::check`] at the end of every CPU cycle, or wrap a [`Bus`] in a [`SanitizedBus`].
+/// Like [`super::Tracer`], this needs to see every cycle, so it doesn't work with
+/// [`M6502::execute_instruction`].
+///
+/// Memory that was loaded without going through the CPU, such as a program copied into RAM,
+/// can be marked with [`Sanitizer::mark_initialised`].
+pub struct Sanitizer {
+    mirror: fn(u16) -> u16,
+    memory: Vec<u8>,
+    instruction: Option<Instruction>,
+    previous_cycle: Option<(u16, bool, bool)>,
+    previous_sp: Option<u8>,
+    cycles: u64,
+    findings: Vec<Finding>,
+}
+
+impl Sanitizer {
+    pub fn new() -> Self {
+        Sanitizer::new_with_options(SanitizerOptions::default())
+    }
+
+    pub fn new_with_options(options: SanitizerOptions) -> Self {
+        let mut memory = vec![0; 0x10000];
+        for range in &options.ram {
+            memory[*range.start() as usize..=*range.end() as usize].fill(RAM);
+        }
+        for range in &options.rom {
+            memory[*range.start() as usize..=*range.end() as usize].fill(ROM);
+        }
+
+        Self {
+            mirror: options.mirror,
+            memory,
+            instruction: None,
+            previous_cycle: None,
+            previous_sp: None,
+            cycles: 0,
+            findings: vec![],
+        }
+    }
+
+    /// Treats RAM in the range as initialised, without it counting as data for self-modifying code.
+    pub fn mark_initialised(&mut self, addresses: RangeInclusive<u16>) {
+        for flags in &mut self.memory[*addresses.start() as usize..=*addresses.end() as usize] {
+            *flags |= INITIALISED;
+        }
+    }
+
+    pub fn findings(&self) -> &[Finding] {
+        &self.findings
+    }
+
+    /// Returns the findings so far, and clears them.
+    pub fn take_findings(&mut self) -> Vec<Finding> {
+        std::mem::take(&mut self.findings)
+    }
+
+    /// Number of cycles checked so far.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    pub fn check(&mut self, cpu: &M6502) {
+        let address = cpu.get_address();
+        let cycle = (address, cpu.rw, cpu.sync);
+
+        // A read that is repeated while RDY is low has already been checked, and DMA cycles don't belong to the CPU.
+        let repeated = cpu.rw && self.previous_cycle == Some(cycle);
+        if !repeated && !cpu.is_dma_cycle() {
+            self.check_cycle(cpu, address);
+            self.previous_cycle = Some(cycle);
+        }
+
+        self.cycles += 1;
+    }
+
+    fn check_cycle(&mut self, cpu: &M6502, address: u16) {
+        if cpu.sync {
+            let opcode = &opcodes(cpu.variant())[cpu.data as usize];
+            self.instruction = Some(Instruction {
+                pc: address,
+                mnemonic: opcode.mnemonic,
+                addressing_mode: opcode.addressing_mode,
+                length: opcode.length,
+                x: cpu.x,
+                y: cpu.y,
+                sp: cpu.sp,
+                operand: [None, None],
+                pointer: [None, None],
+                started: false,
+                interrupt: false,
+            });
+        } else if let Some(instruction) = self.instruction.as_mut().filter(|instruction| !instruction.started) {
+            instruction.started = true;
+            instruction.interrupt = cpu.brk_flags != BrkFlags::NONE;
+            if !instruction.interrupt {
+                let (pc, length) = (instruction.pc, instruction.length as u16);
+                for offset in 0..length {
+                    self.check_execution(pc.wrapping_add(offset));
+                }
+            }
+        }
+
+        self.check_stack(cpu, address);
+
+        if cpu.rw {
+            self.check_read(cpu, address);
+        } else {
+            self.check_write(address, cpu.data);
+        }
+    }
+
+    fn report(&mut self, violation: Violation) {
+        let pc = self.instruction.as_ref().map_or(0, |instruction| instruction.pc);
+        self.findings.push(Finding { violation, pc, cycle: self.cycles });
+    }
+
+    fn check_execution(&mut self, address: u16) {
+        let index = (self.mirror)(address) as usize;
+        if self.memory[index] & DATA != 0 {
+            self.memory[index] &= !DATA;
+            self.report(Violation::SelfModifyingCode { address });
+        }
+    }
+
+    /// The stack pointer moves in the same cycle as the push or pull that it wraps around in.
+    fn check_stack(&mut self, cpu: &M6502, address: u16) {
+        if let (Some(previous_sp), 0x01) = (self.previous_sp, address >> 8) {
+            if !cpu.rw && previous_sp == 0x00 && cpu.sp == 0xFF {
+                self.report(Violation::StackOverflow);
+            } else if cpu.rw && previous_sp == 0xFF && cpu.sp == 0x00 {
+                self.report(Violation::StackUnderflow);
+            }
+        }
+        self.previous_sp = Some(cpu.sp);
+    }
+
+    fn check_read(&mut self, cpu: &M6502, address: u16) {
+        let cmos = cpu.variant() == M6502Variant::Cmos65C02;
+        let data_read = match &mut self.instruction {
+            Some(instruction) if !cpu.sync => {
+                // Operand bytes are read from the addresses following the opcode, though not always straight after it.
+                let offset = address.wrapping_sub(instruction.pc) as usize;
+                if (1..instruction.length as usize).contains(&offset) && instruction.operand[offset - 1].is_none() {
+                    instruction.operand[offset - 1] = Some(cpu.data);
+                    false
+                } else {
+                    instruction.is_data_read(address, cpu.data, cmos)
+                }
+            }
+            _ => false,
+        };
+
+        let index = (self.mirror)(address) as usize;
+        if data_read && self.memory[index] & (RAM | INITIALISED) == RAM {
+            self.memory[index] |= INITIALISED;
+            self.report(Violation::UninitialisedRead { address });
+        }
+    }
+
+    fn check_write(&mut self, address: u16, value: u8) {
+        let index = (self.mirror)(address) as usize;
+        if self.memory[index] & ROM != 0 {
+            self.report(Violation::RomWrite { address, value });
+        } else if self.memory[index] & RAM != 0 {
+            self.memory[index] |= INITIALISED | DATA;
+        }
+    }
+}
+
+impl Default for Sanitizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A [`Bus`] that passes everything through to another bus, and checks each cycle with a [`Sanitizer`].
+pub struct SanitizedBus<'a, B: Bus> {
+    pub bus: &'a mut B,
+    pub sanitizer: &'a mut Sanitizer,
+}
+
+impl<B: Bus> Bus for SanitizedBus<'_, B> {
+    fn read(&mut self, address: u16) -> u8 {
+        self.bus.read(address)
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        self.bus.write(address, value);
+    }
+
+    fn peek(&self, address: u16) -> u8 {
+        self.bus.peek(address)
+    }
+
+    fn dma_cycle(&mut self, cpu: &M6502) {
+        self.bus.dma_cycle(cpu);
+    }
+
+    fn on_cycle(&mut self, cpu: &M6502) {
+        self.bus.on_cycle(cpu);
+        self.sanitizer.check(cpu);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::assemble;
+    use crate::chips::test_bus::Ram;
+
+    /// Runs the program from $0400 for a fixed number of cycles, holding RDY low in the cycles where `rdy` says so.
+    fn run(source: &str, mut sanitizer: Sanitizer, rdy: impl Fn(u32) -> bool) -> Vec<Finding> {
+        let program = assemble(&format!("{}\n.org $FFFC\n.word $0400", source)).unwrap();
+        let mut ram = Ram::new();
+        program.load_into(&mut ram.data);
+
+        let mut cpu = M6502::new();
+        cpu.set_res(false);
+        cpu.set_res(true);
+
+        for cycle in 0..300 {
+            cpu.set_rdy(rdy(cycle));
+            cpu.step_cycle(&mut SanitizedBus { bus: &mut ram, sanitizer: &mut sanitizer });
+        }
+        sanitizer.take_findings()
+    }
+
+    fn violations(findings: &[Finding]) -> Vec<(Violation, u16)> {
+        findings.iter().map(|finding| (finding.violation, finding.pc)).collect()
+    }
+
+    const UNINITIALISED_READS: &str = "
+            .org $0400
+            LDA $20
+            LDA #$00
+            STA $21
+            LDA $21
+            LDX #$20
+            LDA $02F0,X
+            STA $0500,X
+            LDA $0520
+            LDY #$01
+            LDA ($21),Y
+            PHA
+            PLA
+            JSR sub
+        loop:
+            JMP loop
+        sub:
+            RTS
+    ";
+
+    #[test]
+    fn reports_uninitialised_reads() {
+        let findings = run(UNINITIALISED_READS, Sanitizer::new(), |_| true);
+
+        // The dummy reads of $0210 (before the carry into the high byte), of $0520 (before the store),
+        // and of the stack, aren't reported.
+        assert_eq!(vec![
+            (Violation::UninitialisedRead { address: 0x0020 }, 0x0400),
+            (Violation::UninitialisedRead { address: 0x0310 }, 0x040A),
+            (Violation::UninitialisedRead { address: 0x0022 }, 0x0415),
+            (Violation::UninitialisedRead { address: 0x0001 }, 0x0415),
+        ], violations(&findings));
+        assert!(findings.windows(2).all(|pair| pair[0].cycle < pair[1].cycle));
+    }
+
+    #[test]
+    fn ignores_reads_repeated_by_rdy() {
+        let expected = violations(&run(UNINITIALISED_READS, Sanitizer::new(), |_| true));
+        let findings = run(UNINITIALISED_READS, Sanitizer::new(), |cycle| cycle % 3 == 0);
+
+        assert_eq!(expected, violations(&findings));
+    }
+
+    #[test]
+    fn reports_stack_wrap_around() {
+        let findings = run("
+                .org $0400
+                LDX #$00
+                TXS
+                PHA
+                PLA
+            loop:
+                JMP loop
+        ", Sanitizer::new(), |_| true);
+
+        assert_eq!(vec![
+            (Violation::StackOverflow, 0x0403),
+            (Violation::StackUnderflow, 0x0404),
+        ], violations(&findings));
+    }
+
+    #[test]
+    fn reports_self_modifying_code_and_rom_writes() {
+        let options = SanitizerOptions {
+            ram: vec![0x0000..=0xEFFF],
+            rom: vec![0xF000..=0xFFFF],
+            ..Default::default()
+        };
+        let findings = run("
+                .org $0400
+                LDA #$E8
+                STA patch
+            patch:
+                NOP
+                STA $F000
+                LDX #$00
+                INC $0300
+            loop:
+                JMP loop
+        ", Sanitizer::new_with_options(options), |_| true);
+
+        // Writing $0300 as data only matters if it is executed.
+        assert_eq!(vec![
+            (Violation::SelfModifyingCode { address: 0x0405 }, 0x0405),
+            (Violation::RomWrite { address: 0xF000, value: 0xE8 }, 0x0406),
+            (Violation::UninitialisedRead { address: 0x0300 }, 0x040B),
+        ], violations(&findings));
+    }
+
+    #[test]
+    fn looks_up_mirrored_addresses() {
+        // Like the Atari 2600, where the stack is in the same 128 bytes of RAM as the zero page.
+        let options = SanitizerOptions {
+            ram: vec![0x0080..=0x00FF],
+            mirror: |address| if address & 0xFF80 == 0x0180 { address & 0xFF } else { address },
+            ..Default::default()
+        };
+        let findings = run("
+                .org $0400
+                LDX #$FF
+                TXS
+                PHA
+                LDA $FF
+                LDA $FE
+                LDA $7F
+            loop:
+                JMP loop
+        ", Sanitizer::new_with_options(options), |_| true);
+
+        assert_eq!(vec![(Violation::UninitialisedRead { address: 0x00FE }, 0x0406)], violations(&findings));
+    }
+
+    #[test]
+    fn marked_memory_is_initialised() {
+        let mut sanitizer = Sanitizer::new();
+        sanitizer.mark_initialised(0x0000..=0x00FF);
+        let findings = run("
+                .org $0400
+                LDA $20
+                LDA $0120
+            loop:
+                JMP loop
+        ", sanitizer, |_| true);
+
+        assert_eq!(vec![(Violation::UninitialisedRead { address: 0x0120 }, 0x0402)], violations(&findings));
+    }
+}
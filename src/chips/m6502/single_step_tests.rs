@@ -0,0 +1,193 @@
+//! Runs tests in the format used by https://github.com/SingleStepTests/65x02.
+//!
+//! Each file contains the tests for a single opcode. A test gives the registers and
+//! memory before and after the instruction, and the address, data and direction of
+//! every bus cycle in between.
+//!
+//! A few hand-written tests are included in the test assets. To run the whole corpus,
+//! set `SINGLE_STEP_TESTS_6502` and / or `SINGLE_STEP_TESTS_65C02` to the directory
+//! containing the JSON files (such as `65x02/6502/v1`), and run the ignored tests with
+//! `cargo test --release single_step_tests_corpus -- --ignored`.
+
+use std::{env, fs, path::{Path, PathBuf}};
+
+use serde::Deserialize;
+
+use super::*;
+
+const ASSET_PATH: &str = "test_assets/chips/mos6502/single_step_tests";
+
+//////////////////////////////////////////
+// Test files
+//////////////////////////////////////////
+
+#[derive(Deserialize)]
+struct Test {
+    name: String,
+    initial: State,
+    #[serde(rename = "final")]
+    expected: State,
+    cycles: Vec<(u16, u8, String)>,
+}
+
+#[derive(Deserialize)]
+struct State {
+    pc: u16,
+    s: u8,
+    a: u8,
+    x: u8,
+    y: u8,
+    p: u8,
+    ram: Vec<(u16, u8)>,
+}
+
+//////////////////////////////////////////
+// Test runner
+//////////////////////////////////////////
+
+struct TestBus {
+    ram: Vec<u8>,
+    cycles: Vec<(u16, u8, &'static str)>,
+}
+
+impl Bus for TestBus {
+    fn read(&mut self, address: u16) -> u8 {
+        let value = self.ram[address as usize];
+        self.cycles.push((address, value, "read"));
+        value
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        self.ram[address as usize] = value;
+        self.cycles.push((address, value, "write"));
+    }
+
+    fn peek(&self, address: u16) -> u8 {
+        self.ram[address as usize]
+    }
+}
+
+/// Bits 4 and 5 of P aren't stored in the CPU, so they aren't compared.
+const P_MASK: u8 = 0xCF;
+
+/// Runs a single test, and returns a description of the first difference, if any.
+fn run_test(test: &Test, variant: M6502Variant) -> Result<(), String> {
+    let initial = &test.initial;
+    let expected = &test.expected;
+
+    let mut cpu = M6502::new_with_options(M6502Options {
+        bcd_enabled: true,
+        variant,
+    });
+    cpu.a = initial.a;
+    cpu.x = initial.x;
+    cpu.y = initial.y;
+    cpu.sp = initial.s;
+    cpu.p.set_from_u8(initial.p);
+    cpu.pc = SplitRegister16::from_u16(initial.pc);
+
+    let mut bus = TestBus {
+        ram: vec![0; 0x10000],
+        cycles: Vec::new(),
+    };
+    for (address, value) in &initial.ram {
+        bus.ram[*address as usize] = *value;
+    }
+
+    // The first cycle is the opcode fetch. The CPU starts in the middle of the previous
+    // instruction's last cycle, so that the opcode is on the data bus when it's clocked.
+    cpu.fetch_next_instruction();
+    cpu.data = bus.read(cpu.get_address());
+
+    for _ in 1..test.cycles.len() {
+        cpu.step_cycle(&mut bus);
+    }
+
+    // The registers are written at the start of the next cycle, which also fetches the next opcode.
+    // Clock the CPU without doing that read, so that it doesn't appear in the list of cycles.
+    cpu.set_phi0(true);
+    cpu.set_phi0(false);
+
+    let actual_cycles: Vec<String> = bus.cycles.iter().map(|(address, value, kind)| format!("{:04X} {:02X} {}", address, value, kind)).collect();
+    let expected_cycles: Vec<String> = test.cycles.iter().map(|(address, value, kind)| format!("{:04X} {:02X} {}", address, value, kind)).collect();
+    if actual_cycles != expected_cycles {
+        return Err(format!("cycles were {:?}, expected {:?}", actual_cycles, expected_cycles));
+    }
+
+    if !cpu.sync {
+        return Err("CPU did not fetch the next opcode after the last cycle".to_string());
+    }
+
+    let registers = [
+        ("pc", cpu.pc.to_u16(), expected.pc),
+        ("s", cpu.sp as u16, expected.s as u16),
+        ("a", cpu.a as u16, expected.a as u16),
+        ("x", cpu.x as u16, expected.x as u16),
+        ("y", cpu.y as u16, expected.y as u16),
+        ("p", (cpu.p.as_u8(false) & P_MASK) as u16, (expected.p & P_MASK) as u16),
+    ];
+    for (name, actual, expected) in registers.iter() {
+        if actual != expected {
+            return Err(format!("{} was ${:02X}, expected ${:02X}", name, actual, expected));
+        }
+    }
+
+    for &(address, value) in &expected.ram {
+        if bus.ram[address as usize] != value {
+            return Err(format!("${:04X} was ${:02X}, expected ${:02X}", address, bus.ram[address as usize], value));
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs every test file in a directory, and returns a description of each failing test.
+fn run_test_directory(path: &Path, variant: M6502Variant) -> Vec<String> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(path)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "No test files found in {}", path.display());
+
+    let mut failures = Vec::new();
+    for path in paths {
+        let tests: Vec<Test> = serde_json::from_str(&fs::read_to_string(&path).unwrap())
+            .unwrap_or_else(|error| panic!("Couldn't parse {}: {}", path.display(), error));
+        for test in &tests {
+            if let Err(message) = run_test(test, variant) {
+                failures.push(format!("{}: {}: {}", path.file_name().unwrap().to_string_lossy(), test.name, message));
+            }
+        }
+    }
+    failures
+}
+
+fn assert_no_failures(failures: Vec<String>) {
+    assert!(failures.is_empty(), "{} tests failed:\n{}", failures.len(), failures.join("\n"));
+}
+
+#[test]
+fn single_step_tests_samples() {
+    assert_no_failures(run_test_directory(&Path::new(ASSET_PATH).join("6502"), M6502Variant::Nmos6502));
+}
+
+#[test]
+fn single_step_tests_samples_65c02() {
+    assert_no_failures(run_test_directory(&Path::new(ASSET_PATH).join("wdc65c02"), M6502Variant::Cmos65C02));
+}
+
+#[test]
+#[ignore = "needs SINGLE_STEP_TESTS_6502 set to a directory of the 6502 corpus"]
+fn single_step_tests_corpus() {
+    let path = env::var("SINGLE_STEP_TESTS_6502").expect("SINGLE_STEP_TESTS_6502 should be set to a directory of the 6502 corpus");
+    assert_no_failures(run_test_directory(Path::new(&path), M6502Variant::Nmos6502));
+}
+
+#[test]
+#[ignore = "needs SINGLE_STEP_TESTS_65C02 set to a directory of the 65C02 corpus"]
+fn single_step_tests_corpus_65c02() {
+    let path = env::var("SINGLE_STEP_TESTS_65C02").expect("SINGLE_STEP_TESTS_65C02 should be set to a directory of the 65C02 corpus");
+    assert_no_failures(run_test_directory(Path::new(&path), M6502Variant::Cmos65C02));
+}
@@ -0,0 +1,331 @@
+use std::fmt;
+
+use super::registers::SplitRegister16;
+use super::{BrkFlags, M6502, M6502Variant, RunState};
+
+/// Incremented whenever the byte layout of [`M6502State`] changes.
+pub const M6502_STATE_VERSION: u8 = 2;
+
+pub(super) const STATE_LENGTH: usize = 26;
+
+/// A snapshot of everything inside an [`M6502`], including the pins and the
+/// internal state that isn't otherwise visible, so that it can be restored
+/// exactly, even in the middle of an instruction.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct M6502State {
+    address: u16,
+    data: u8,
+    rdy: bool,
+    irq: bool,
+    nmi: bool,
+    sync: bool,
+    res: bool,
+    phi0: bool,
+    phi1: bool,
+    phi2: bool,
+    rw: bool,
+
+    a: u8,
+    x: u8,
+    y: u8,
+    pc: u16,
+    sp: u8,
+    p: u8,
+    ir: u8,
+    tr: u8,
+
+    brk_flags: BrkFlags,
+    ad: u16,
+    irq_pipeline: u16,
+    nmi_pipeline: u16,
+    dma_cycles: u16,
+    dma_cycle: bool,
+    run_state: RunState,
+
+    bcd_enabled: bool,
+    variant: M6502Variant,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum StateError {
+    /// The state was saved by a different version of the emulator.
+    UnsupportedVersion(u8),
+
+    /// The data is shorter or longer than expected.
+    InvalidLength,
+
+    /// A field contains a value that doesn't correspond to any valid state.
+    InvalidValue(&'static str),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateError::UnsupportedVersion(version) => write!(f, "Unsupported state version {} (expected {})", version, M6502_STATE_VERSION),
+            StateError::InvalidLength => write!(f, "State data has the wrong length"),
+            StateError::InvalidValue(field) => write!(f, "State data has an invalid value for {}", field),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+impl M6502State {
+    /// Serializes the state. The first byte is always the version.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let pins = [self.rdy, self.irq, self.nmi, self.sync, self.res, self.phi0, self.phi1, self.phi2, self.rw, self.bcd_enabled, self.dma_cycle]
+            .iter()
+            .enumerate()
+            .fold(0u16, |pins, (index, value)| pins | ((*value as u16) << index));
+
+        let mut bytes = vec![M6502_STATE_VERSION];
+        bytes.extend_from_slice(&self.address.to_le_bytes());
+        bytes.push(self.data);
+        bytes.extend_from_slice(&pins.to_le_bytes());
+        bytes.extend_from_slice(&[self.a, self.x, self.y]);
+        bytes.extend_from_slice(&self.pc.to_le_bytes());
+        bytes.extend_from_slice(&[self.sp, self.p, self.ir, self.tr, self.brk_flags.bits()]);
+        bytes.extend_from_slice(&self.ad.to_le_bytes());
+        bytes.extend_from_slice(&self.irq_pipeline.to_le_bytes());
+        bytes.extend_from_slice(&self.nmi_pipeline.to_le_bytes());
+        bytes.push(match self.run_state {
+            RunState::Running => 0,
+            RunState::Waiting => 1,
+            RunState::Stopped => 2,
+            RunState::Jammed => 3,
+        });
+        bytes.push(match self.variant {
+            M6502Variant::Nmos6502 => 0,
+            M6502Variant::Cmos65C02 => 1,
+        });
+        bytes.extend_from_slice(&self.dma_cycles.to_le_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, StateError> {
+        match bytes.first() {
+            Some(&M6502_STATE_VERSION) => {},
+            Some(version) => return Err(StateError::UnsupportedVersion(*version)),
+            None => return Err(StateError::InvalidLength),
+        }
+
+        if bytes.len() != STATE_LENGTH {
+            return Err(StateError::InvalidLength);
+        }
+
+        let word = |index: usize| u16::from_le_bytes([bytes[index], bytes[index + 1]]);
+        let pins = word(4);
+        let pin = |index: u16| pins & (1 << index) != 0;
+
+        if pins >> 11 != 0 {
+            return Err(StateError::InvalidValue("pins"));
+        }
+
+        Ok(Self {
+            address: word(1),
+            data: bytes[3],
+            rdy: pin(0),
+            irq: pin(1),
+            nmi: pin(2),
+            sync: pin(3),
+            res: pin(4),
+            phi0: pin(5),
+            phi1: pin(6),
+            phi2: pin(7),
+            rw: pin(8),
+            bcd_enabled: pin(9),
+            dma_cycle: pin(10),
+
+            a: bytes[6],
+            x: bytes[7],
+            y: bytes[8],
+            pc: word(9),
+            sp: bytes[11],
+            p: bytes[12],
+            ir: bytes[13],
+            tr: bytes[14],
+
+            brk_flags: BrkFlags::from_bits(bytes[15]).ok_or(StateError::InvalidValue("brk_flags"))?,
+            ad: word(16),
+            irq_pipeline: word(18),
+            nmi_pipeline: word(20),
+            dma_cycles: word(24),
+            run_state: match bytes[22] {
+                0 => RunState::Running,
+                1 => RunState::Waiting,
+                2 => RunState::Stopped,
+                3 => RunState::Jammed,
+                _ => return Err(StateError::InvalidValue("run_state")),
+            },
+            variant: match bytes[23] {
+                0 => M6502Variant::Nmos6502,
+                1 => M6502Variant::Cmos65C02,
+                _ => return Err(StateError::InvalidValue("variant")),
+            },
+        })
+    }
+}
+
+impl M6502 {
+    pub fn save_state(&self) -> M6502State {
+        M6502State {
+            address: self.get_address(),
+            data: self.data,
+            rdy: self.rdy,
+            irq: self.irq,
+            nmi: self.nmi,
+            sync: self.sync,
+            res: self.res,
+            phi0: self.phi0,
+            phi1: self.phi1,
+            phi2: self.phi2,
+            rw: self.rw,
+
+            a: self.a,
+            x: self.x,
+            y: self.y,
+            pc: self.pc.to_u16(),
+            sp: self.sp,
+            p: self.p.as_u8(false),
+            ir: self.ir,
+            tr: self.tr,
+
+            brk_flags: self.brk_flags,
+            ad: self.ad.to_u16(),
+            irq_pipeline: self.irq_pipeline,
+            nmi_pipeline: self.nmi_pipeline,
+            dma_cycles: self.dma_cycles,
+            dma_cycle: self.dma_cycle,
+            run_state: self.run_state,
+
+            bcd_enabled: self.bcd_enabled,
+            variant: self.variant,
+        }
+    }
+
+    /// Restores a state saved by [`M6502::save_state`]. This also restores the variant and options.
+    pub fn load_state(&mut self, state: &M6502State) {
+        self.set_address(SplitRegister16::from_u16(state.address));
+        self.data = state.data;
+        self.rdy = state.rdy;
+        self.irq = state.irq;
+        self.nmi = state.nmi;
+        self.sync = state.sync;
+        self.res = state.res;
+        self.phi0 = state.phi0;
+        self.phi1 = state.phi1;
+        self.phi2 = state.phi2;
+        self.rw = state.rw;
+
+        self.a = state.a;
+        self.x = state.x;
+        self.y = state.y;
+        self.pc = SplitRegister16::from_u16(state.pc);
+        self.sp = state.sp;
+        self.p.set_from_u8(state.p);
+        self.ir = state.ir;
+        self.tr = state.tr;
+
+        self.brk_flags = state.brk_flags;
+        self.ad = SplitRegister16::from_u16(state.ad);
+        self.irq_pipeline = state.irq_pipeline;
+        self.nmi_pipeline = state.nmi_pipeline;
+        self.dma_cycles = state.dma_cycles;
+        self.dma_cycle = state.dma_cycle;
+        self.run_state = state.run_state;
+
+        self.bcd_enabled = state.bcd_enabled;
+        self.variant = state.variant;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::assemble;
+    use crate::chips::test_bus::Ram;
+
+    fn setup() -> (M6502, Ram) {
+        let program = assemble("
+                .org $0400
+                CLI
+            loop:
+                INC $10
+                LDA ($20),Y
+                PHA
+                PLA
+                INY
+                JMP loop
+
+            irq:
+                INC $11
+                RTI
+
+                .org $FFFC
+                .word $0400, irq
+        ").unwrap();
+
+        let mut ram = Ram::new();
+        program.load_into(&mut ram.data);
+
+        let mut cpu = M6502::new();
+        cpu.set_res(false);
+        cpu.set_res(true);
+        (cpu, ram)
+    }
+
+    #[test]
+    fn restores_mid_instruction() {
+        for saved_at in 0..40 {
+            let (mut cpu, mut ram) = setup();
+            for cycle in 0..saved_at {
+                cpu.set_irq(cycle % 30 >= 25);
+                cpu.step_cycle(&mut ram);
+            }
+
+            let bytes = cpu.save_state().to_bytes();
+            let mut restored = M6502::new();
+            restored.load_state(&M6502State::from_bytes(&bytes).unwrap());
+            let mut restored_ram = ram.clone();
+
+            for cycle in saved_at..(saved_at + 200) {
+                cpu.set_irq(cycle % 30 >= 25);
+                restored.set_irq(cycle % 30 >= 25);
+                cpu.step_cycle(&mut ram);
+                restored.step_cycle(&mut restored_ram);
+                assert_eq!(cpu.save_state(), restored.save_state(), "saved at cycle {}, diverged at cycle {}", saved_at, cycle);
+            }
+            assert_eq!(ram.data, restored_ram.data);
+            assert_ne!(0, ram.data[0x11]);
+        }
+    }
+
+    #[test]
+    fn round_trips_through_bytes() {
+        let (mut cpu, mut ram) = setup();
+        for _ in 0..7 {
+            cpu.step_cycle(&mut ram);
+        }
+
+        let state = cpu.save_state();
+        let bytes = state.to_bytes();
+        assert_eq!(M6502_STATE_VERSION, bytes[0]);
+        assert_eq!(Ok(state), M6502State::from_bytes(&bytes));
+    }
+
+    #[test]
+    fn rejects_invalid_data() {
+        let bytes = M6502::new().save_state().to_bytes();
+
+        let mut wrong_version = bytes.clone();
+        wrong_version[0] = M6502_STATE_VERSION + 1;
+        assert_eq!(Err(StateError::UnsupportedVersion(M6502_STATE_VERSION + 1)), M6502State::from_bytes(&wrong_version));
+
+        assert_eq!(Err(StateError::InvalidLength), M6502State::from_bytes(&[]));
+        assert_eq!(Err(StateError::InvalidLength), M6502State::from_bytes(&bytes[..10]));
+
+        let mut invalid_run_state = bytes;
+        invalid_run_state[22] = 9;
+        assert_eq!(Err(StateError::InvalidValue("run_state")), M6502State::from_bytes(&invalid_run_state));
+    }
+}
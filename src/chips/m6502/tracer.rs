@@ -0,0 +1,238 @@
+use std::fmt::Write as _;
+use std::io;
+
+use super::{disassemble_instruction, M6502, M6502Variant};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TraceLevel {
+    /// One line per instruction, with the registers as they are when the opcode is fetched.
+    Instruction,
+
+    /// As for `Instruction`, followed by a line for every read and write.
+    Cycle,
+}
+
+pub struct TracerOptions {
+    pub level: TraceLevel,
+
+    /// Adds the instruction bytes and disassembly to each instruction line.
+    pub disassembly: bool,
+}
+
+impl Default for TracerOptions {
+    fn default() -> Self {
+        Self {
+            level: TraceLevel::Instruction,
+            disassembly: false,
+        }
+    }
+}
+
+/// The instruction that is currently being traced. Its line can't be written until
+/// the operand bytes have been seen on the data bus.
+struct PendingInstruction {
+    variant: M6502Variant,
+    pc: u16,
+    registers: String,
+    bytes: [Option<u8>; 3],
+    cycle_lines: String,
+}
+
+/// Writes a trace of the CPU's execution, in the format used by nestest.log:
+///
+/// ```text
+/// C000  A:00 X:00 Y:00 P:24 SP:FD CPUC:0
+///       READ      $C000 => $4C
+/// ```
+///
+/// Call [`Tracer::trace`] at the end of every CPU cycle, after the read or write has happened.
+/// This only looks at the CPU, so it works in any system, whether or not it uses [`super::Bus`].
+///
+/// With disassembly enabled, the instruction bytes are taken from the data bus as
+/// they are read, so each instruction is written once the next one starts.
+pub struct Tracer<W: io::Write> {
+    writer: W,
+    options: TracerOptions,
+    cycles: u64,
+    pending: Option<PendingInstruction>,
+}
+
+impl<W: io::Write> Tracer<W> {
+    pub fn new(writer: W) -> Self {
+        Tracer::new_with_options(writer, TracerOptions::default())
+    }
+
+    pub fn new_with_options(writer: W, options: TracerOptions) -> Self {
+        Self {
+            writer,
+            options,
+            cycles: 0,
+            pending: None,
+        }
+    }
+
+    /// Number of cycles traced so far. This is the CPUC value in the log.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    pub fn trace(&mut self, cpu: &M6502) -> io::Result<()> {
+        let address = cpu.get_address();
+
+        if cpu.sync() {
+            self.write_pending()?;
+
+            let registers = format!(
+                "A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} CPUC:{}",
+                cpu.a,
+                cpu.x,
+                cpu.y,
+                cpu.p.as_u8(false),
+                cpu.sp,
+                self.cycles);
+
+            if self.options.disassembly {
+                self.pending = Some(PendingInstruction {
+                    variant: cpu.variant(),
+                    pc: address,
+                    registers,
+                    bytes: [Some(cpu.data()), None, None],
+                    cycle_lines: String::new(),
+                });
+            } else {
+                writeln!(self.writer, "{:04X}  {}", address, registers)?;
+            }
+        } else if let Some(pending) = &mut self.pending {
+            // Operand bytes are read from the addresses following the opcode, though not always straight after it.
+            let offset = address.wrapping_sub(pending.pc) as usize;
+            if cpu.rw && (1..=2).contains(&offset) && pending.bytes[offset].is_none() {
+                pending.bytes[offset] = Some(cpu.data());
+            }
+        }
+
+        if self.options.level == TraceLevel::Cycle {
+            let line = if cpu.rw {
+                format!("      READ      ${:04X} => ${:02X}", address, cpu.data())
+            } else {
+                format!("      WRITE     ${:04X} <= ${:02X}", address, cpu.data())
+            };
+
+            match &mut self.pending {
+                Some(pending) => writeln!(pending.cycle_lines, "{}", line).unwrap(),
+                None => writeln!(self.writer, "{}", line)?,
+            }
+        }
+
+        self.cycles += 1;
+
+        Ok(())
+    }
+
+    /// Writes the instruction that is still being traced, and flushes the writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.write_pending()?;
+        self.writer.flush()
+    }
+
+    /// Flushes the trace and returns the writer.
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write_pending(&mut self) -> io::Result<()> {
+        let pending = match self.pending.take() {
+            Some(pending) => pending,
+            None => return Ok(()),
+        };
+
+        let instruction = disassemble_instruction(
+            pending.variant,
+            pending.pc,
+            |address| pending.bytes[address.wrapping_sub(pending.pc) as usize].unwrap_or(0));
+
+        let bytes: Vec<String> = instruction.bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+
+        writeln!(self.writer, "{:04X}  {:<8}  {:<14}  {}", pending.pc, bytes.join(" "), instruction.to_string(), pending.registers)?;
+        self.writer.write_all(pending.cycle_lines.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::assemble;
+    use crate::chips::test_bus::Ram;
+
+    fn run_traced(options: TracerOptions) -> String {
+        let program = assemble("
+                .org $0400
+                LDX #$05
+                STX $10
+                JSR sub
+                .org $0480
+            sub:
+                RTS
+                .org $FFFC
+                .word $0400
+        ").unwrap();
+
+        let mut ram = Ram::new();
+        program.load_into(&mut ram.data);
+
+        let mut cpu = M6502::new();
+        cpu.set_res(false);
+        cpu.set_res(true);
+        for _ in 0..6 {
+            cpu.step_cycle(&mut ram);
+        }
+
+        let mut tracer = Tracer::new_with_options(Vec::new(), options);
+        for _ in 0..14 {
+            cpu.step_cycle(&mut ram);
+            tracer.trace(&cpu).unwrap();
+        }
+        assert_eq!(14, tracer.cycles());
+
+        String::from_utf8(tracer.into_inner().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn instruction_level() {
+        assert_eq!(
+            "0400  A:00 X:00 Y:00 P:24 SP:FD CPUC:0\n\
+             0402  A:00 X:05 Y:00 P:24 SP:FD CPUC:2\n\
+             0404  A:00 X:05 Y:00 P:24 SP:FD CPUC:5\n\
+             0480  A:00 X:05 Y:00 P:24 SP:FB CPUC:11\n",
+            run_traced(TracerOptions::default()));
+    }
+
+    #[test]
+    fn cycle_level_with_disassembly() {
+        let options = TracerOptions {
+            level: TraceLevel::Cycle,
+            disassembly: true,
+        };
+
+        assert_eq!(
+            "0400  A2 05     LDX #$05        A:00 X:00 Y:00 P:24 SP:FD CPUC:0\n\
+             \x20     READ      $0400 => $A2\n\
+             \x20     READ      $0401 => $05\n\
+             0402  86 10     STX $10         A:00 X:05 Y:00 P:24 SP:FD CPUC:2\n\
+             \x20     READ      $0402 => $86\n\
+             \x20     READ      $0403 => $10\n\
+             \x20     WRITE     $0010 <= $05\n\
+             0404  20 80 04  JSR $0480       A:00 X:05 Y:00 P:24 SP:FD CPUC:5\n\
+             \x20     READ      $0404 => $20\n\
+             \x20     READ      $0405 => $80\n\
+             \x20     READ      $01FD => $00\n\
+             \x20     WRITE     $01FD <= $04\n\
+             \x20     WRITE     $01FC <= $06\n\
+             \x20     READ      $0406 => $04\n\
+             0480  60        RTS             A:00 X:05 Y:00 P:24 SP:FB CPUC:11\n\
+             \x20     READ      $0480 => $60\n\
+             \x20     READ      $0481 => $00\n\
+             \x20     READ      $01FB => $00\n",
+            run_traced(options));
+    }
+}
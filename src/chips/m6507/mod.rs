@@ -1,4 +1,6 @@
-use super::m6502::{M6502, M6502Options};
+use std::io;
+
+use super::m6502::{M6502, M6502Options, M6502State, M6502Variant, Sanitizer, Tracer};
 
 pub struct M6507 {
     inner: M6502,
@@ -8,7 +10,8 @@ impl M6507 {
     pub fn new() -> Self {
         Self {
             inner: M6502::new_with_options(M6502Options {
-                bcd_enabled: true
+                bcd_enabled: true,
+                variant: M6502Variant::Nmos6502,
             })
         }
     }
@@ -44,4 +47,30 @@ impl M6507 {
     pub fn pin_rw(&self) -> bool {
         self.inner.rw
     }
-}
\ No newline at end of file
+
+    pub fn is_jammed(&self) -> bool {
+        self.inner.is_jammed()
+    }
+
+    pub fn save_state(&self) -> M6502State {
+        self.inner.save_state()
+    }
+
+    pub fn load_state(&mut self, state: &M6502State) {
+        self.inner.load_state(state);
+    }
+
+    pub fn trace<W: io::Write>(&self, tracer: &mut Tracer<W>) -> io::Result<()> {
+        tracer.trace(&self.inner)
+    }
+
+    pub fn sanitize(&self, sanitizer: &mut Sanitizer) {
+        sanitizer.check(&self.inner);
+    }
+}
+
+impl Default for M6507 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
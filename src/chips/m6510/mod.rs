@@ -0,0 +1,359 @@
+use std::io;
+
+use super::m6502::{Bus, M6502, M6502Options, M6502Variant, Tracer};
+
+/// Address of the data direction register. A 1 bit makes the matching pin an output.
+const DDR_ADDRESS: u16 = 0x0000;
+
+/// Address of the data register.
+const DATA_ADDRESS: u16 = 0x0001;
+
+/// The 6510 only has pins for P0 to P5. Bits 6 and 7 of the port registers always float.
+const PORT_PINS: u8 = 0b0011_1111;
+
+pub struct M6510Options {
+    /// How many cycles a floating input keeps reading as 1, after it stops being driven high.
+    /// VICE measures around 350,000 for the 6510.
+    pub fade_cycles: u64,
+}
+
+impl Default for M6510Options {
+    fn default() -> Self {
+        Self {
+            fade_cycles: 350_000,
+        }
+    }
+}
+
+/// The 6502 core with a 6-bit I/O port at $0000 (data direction) and $0001 (data).
+/// Reads and writes of those two addresses are handled by the port, and don't reach the bus.
+pub struct M6510 {
+    inner: M6502,
+    port: ProcessorPort,
+}
+
+impl M6510 {
+    pub fn new() -> Self {
+        M6510::new_with_options(M6510Options::default())
+    }
+
+    pub fn new_with_options(options: M6510Options) -> Self {
+        Self {
+            inner: M6502::new_with_options(M6502Options {
+                bcd_enabled: true,
+                variant: M6502Variant::Nmos6502,
+            }),
+            port: ProcessorPort::new(options.fade_cycles),
+        }
+    }
+
+    /// The 6502 core, for its registers and pins.
+    pub fn cpu(&self) -> &M6502 {
+        &self.inner
+    }
+
+    pub fn cpu_mut(&mut self) -> &mut M6502 {
+        &mut self.inner
+    }
+
+    pub fn set_pin_res(&mut self, value: bool) {
+        self.inner.set_res(value);
+    }
+
+    pub fn set_pin_rdy(&mut self, value: bool) {
+        self.inner.set_rdy(value);
+    }
+
+    pub fn set_pin_irq(&mut self, value: bool) {
+        self.inner.set_irq(value);
+    }
+
+    pub fn set_pin_nmi(&mut self, value: bool) {
+        self.inner.set_nmi(value);
+    }
+
+    /// Sets what the external circuit does to the I/O port pins. When a pin in `connected` is an input,
+    /// it reads as the matching bit of `levels`. Unconnected inputs float, and keep the last value that
+    /// the port drove onto them until it fades away. Only P0 to P5 can be connected.
+    pub fn set_port_input(&mut self, connected: u8, levels: u8) {
+        self.port.connected = connected & PORT_PINS;
+        self.port.levels = levels;
+    }
+
+    /// Returns the levels on the I/O port pins, which is also what the CPU reads from $0001.
+    pub fn port_pins(&self) -> u8 {
+        self.port.pins()
+    }
+
+    /// Runs a single clock cycle, and then performs the read or write that the CPU requested,
+    /// on the I/O port or the given bus.
+    pub fn step_cycle(&mut self, bus: &mut impl Bus) {
+        self.inner.step_cycle(&mut PortBus { port: &mut self.port, bus });
+    }
+
+    /// Runs clock cycles until the CPU is about to fetch the next opcode,
+    /// or has been jammed. Returns the number of cycles that were run.
+    pub fn step_instruction(&mut self, bus: &mut impl Bus) -> u32 {
+        self.inner.step_instruction(&mut PortBus { port: &mut self.port, bus })
+    }
+
+    /// Runs a whole instruction at once. See [`M6502::execute_instruction`].
+    pub fn execute_instruction(&mut self, bus: &mut impl Bus) -> u32 {
+        // Bus::on_cycle is only called once in this mode, so count the cycles here instead.
+        let start = self.port.cycles;
+        let cycles = self.inner.execute_instruction(&mut PortBus { port: &mut self.port, bus });
+        self.port.cycles = start + cycles as u64;
+        cycles
+    }
+
+    pub fn is_jammed(&self) -> bool {
+        self.inner.is_jammed()
+    }
+
+    pub fn trace<W: io::Write>(&self, tracer: &mut Tracer<W>) -> io::Result<()> {
+        tracer.trace(&self.inner)
+    }
+}
+
+impl Default for M6510 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct ProcessorPort {
+    /// Data direction register.
+    ddr: u8,
+
+    /// Data register. Only reaches the pins that are outputs.
+    data: u8,
+
+    /// Pins that are pulled up or down, or driven, by the external circuit.
+    connected: u8,
+    levels: u8,
+
+    /// The charge left on each floating pin, from when it was last driven.
+    floating: u8,
+
+    /// The cycle at which each floating pin's charge has faded away.
+    fade_at: [u64; 8],
+    fade_cycles: u64,
+
+    cycles: u64,
+}
+
+impl ProcessorPort {
+    fn new(fade_cycles: u64) -> Self {
+        Self {
+            ddr: 0,
+            data: 0,
+            connected: 0,
+            levels: 0,
+            floating: 0,
+            fade_at: [0; 8],
+            fade_cycles,
+            cycles: 0,
+        }
+    }
+
+    fn pins(&self) -> u8 {
+        let mut floating = 0;
+        for bit in 0..8 {
+            if self.cycles < self.fade_at[bit] {
+                floating |= self.floating & (1 << bit);
+            }
+        }
+
+        let inputs = (self.levels & self.connected) | (floating & !self.connected);
+        (self.data & self.ddr) | (inputs & !self.ddr)
+    }
+
+    fn write_ddr(&mut self, value: u8) {
+        // Pins that stop being outputs keep the charge of the value they were driving.
+        let released = self.ddr & !value;
+        self.floating = (self.floating & !released) | (self.data & released);
+        for bit in 0..8 {
+            if released & (1 << bit) != 0 {
+                self.fade_at[bit] = self.cycles + self.fade_cycles;
+            }
+        }
+
+        self.ddr = value;
+    }
+}
+
+/// Puts the I/O port registers in front of the bus.
+struct PortBus<'a, B: Bus> {
+    port: &'a mut ProcessorPort,
+    bus: &'a mut B,
+}
+
+impl<B: Bus> Bus for PortBus<'_, B> {
+    fn read(&mut self, address: u16) -> u8 {
+        match address {
+            DDR_ADDRESS => self.port.ddr,
+            DATA_ADDRESS => self.port.pins(),
+            _ => self.bus.read(address),
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        match address {
+            DDR_ADDRESS => self.port.write_ddr(value),
+            DATA_ADDRESS => self.port.data = value,
+            _ => self.bus.write(address, value),
+        }
+    }
+
+    fn peek(&self, address: u16) -> u8 {
+        match address {
+            DDR_ADDRESS => self.port.ddr,
+            DATA_ADDRESS => self.port.pins(),
+            _ => self.bus.peek(address),
+        }
+    }
+
+    fn dma_cycle(&mut self, cpu: &M6502) {
+        self.bus.dma_cycle(cpu);
+    }
+
+    fn on_cycle(&mut self, cpu: &M6502) {
+        // Floating pins fade cycle by cycle, so a read later in an instruction sees a later charge.
+        self.port.cycles += 1;
+        self.bus.on_cycle(cpu);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::m6502::assemble;
+    use super::super::m6502::c64_test_suite::{setup_registers, C64Bus};
+    use super::super::test_bus::Ram;
+
+    /// Resets the CPU, and runs the program at $0400 until it reaches its `done: JMP done` loop.
+    fn run_program(cpu: &mut M6510, program: &str) -> Ram {
+        let program = assemble(&format!("
+                .org $0400
+                {}
+            done:
+                JMP done
+
+                .org $FFFC
+                .word $0400
+        ", program)).unwrap();
+
+        let mut ram = Ram::new();
+        program.load_into(&mut ram.data);
+        let done = program.label("done").unwrap();
+
+        cpu.set_pin_res(false);
+        cpu.set_pin_res(true);
+        while cpu.cpu().pc.to_u16() != done {
+            cpu.step_instruction(&mut ram);
+        }
+        ram
+    }
+
+    #[test]
+    fn port_registers_are_not_on_the_bus() {
+        let mut cpu = M6510::new();
+        let ram = run_program(&mut cpu, "
+                LDA #$2F
+                STA $00
+                LDA #$25
+                STA $01
+                LDA $00
+                STA $10
+                LDA $01
+                STA $11
+        ");
+
+        assert_eq!(0x00, ram.data[0x0000]);
+        assert_eq!(0x00, ram.data[0x0001]);
+        assert_eq!(0x2F, ram.data[0x0010]);
+        assert_eq!(0x25, ram.data[0x0011] & 0x2F);
+        assert_eq!(0x25, cpu.port_pins() & 0x2F);
+    }
+
+    #[test]
+    fn inputs_read_the_external_circuit() {
+        let mut cpu = M6510::new();
+        cpu.set_port_input(0xFF, 0b1101_0111);
+        run_program(&mut cpu, "
+                LDA #$07
+                STA $00
+                LDA #$00
+                STA $01
+        ");
+
+        // P0 to P2 are driven low. P3 and P5 are pulled low, and P4 high.
+        // P6 and P7 don't have pins, so they float.
+        assert_eq!(0b0001_0000, cpu.port_pins());
+    }
+
+    #[test]
+    fn floating_inputs_fade() {
+        let mut cpu = M6510::new_with_options(M6510Options { fade_cycles: 1000 });
+        let mut ram = run_program(&mut cpu, "
+                LDA #$FF
+                STA $01
+                STA $00
+                LDA #$00
+                STA $00
+        ");
+
+        assert_eq!(0xFF, cpu.port_pins());
+
+        for _ in 0..400 {
+            cpu.step_instruction(&mut ram);
+        }
+        assert_eq!(0x00, cpu.port_pins());
+    }
+
+    #[test]
+    fn floating_inputs_fade_within_an_instruction() {
+        let mut cpu = M6510::new_with_options(M6510Options { fade_cycles: 4 });
+        let ram = run_program(&mut cpu, "
+                LDA #$FF
+                STA $01
+                STA $00
+                LDA #$00
+                STA $00             ; Releases the pins on its third cycle
+                .byte $AD, $01, $00 ; LDA $0001, which reads the pins on its fourth cycle
+                STA $10
+        ");
+
+        assert_eq!(0x00, ram.data[0x0010]);
+    }
+
+    #[test]
+    fn c64_suite() {
+        // Runs a test, and returns the name of the one it asks to load next.
+        fn run_test(filename: &str) -> String {
+            let mut bus = C64Bus::new(filename);
+
+            // The C64 pulls up LORAM, HIRAM, CHAREN and the cassette sense line,
+            // and the cassette motor driver pulls P5 low. P3 floats.
+            let mut cpu = M6510::new();
+            cpu.set_port_input(0b0011_0111, 0b0001_0111);
+            cpu.set_pin_res(false);
+            cpu.set_pin_res(true);
+            setup_registers(cpu.cpu_mut());
+
+            // The KERNAL leaves the port set up like this.
+            cpu.port.ddr = 0x2F;
+            cpu.port.data = 0x37;
+
+            bus.run(|bus| cpu.step_cycle(bus))
+        }
+
+        // The M6502 runs the suite up to trap16. These are the rest of the tests that only need
+        // the CPU and its I/O port. Of the others, mmufetch and mmu check what the port's banking
+        // maps in, so they need the C64's BASIC, KERNAL and character ROMs. cputiming, irq and nmi
+        // are timed and triggered by CIA timers, and the remaining tests are of the CIAs themselves.
+        assert_eq!("branchwrap", run_test("trap17"));
+        assert_eq!("mmufetch", run_test("branchwrap"));
+        assert_eq!("cputiming", run_test("cpuport"));
+    }
+}
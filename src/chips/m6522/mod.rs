@@ -1,122 +1,1236 @@
-// extern crate aemula_macros;
-
-// use aemula_macros::PinAccessors;
-
-// #[derive(PinAccessors)]
-// pub(crate) struct M6522 {
-//     // -----------------------------------------------
-//     // Processor Interface
-//     // -----------------------------------------------
-
-//     /// Phase two clock. Data transfers between the 6522 and CPU only take place while
-//     /// phi2 is high.
-//     #[pin(in)]
-//     #[handle(transition_lo_to_hi, transition_hi_to_lo)]
-//     phi2: bool,
-
-//     // Chip select. On a real 6522 there are two chip select pins, CS1 and CS2.
-//     // Here we simply to a single pin.
-//     cs: bool,
-
-//     /// Register select (RS0-RS3)
-//     rs: u8,
-
-//     /// Read/write (read = true, write = false)
-//     rw: bool,
-
-//     /// Data bus (D0-D7)
-//     #[pin(bidirectional)]
-//     d: u8,
-
-//     /// Reset
-//     #[pin(in)]
-//     #[handle(always)]
-//     res: bool,
-
-//     /// Interrupt request
-//     irq: bool,
-
-//     // -----------------------------------------------
-//     // Peripheral Interface
-//     // -----------------------------------------------
-    
-//     /// Peripheral A Port (PA0-PA7)
-//     pa: u8,
-
-//     /// Peripheral A control line (CA1)
-//     ca1: bool,
-
-//     /// Peripheral A control line (CA2)
-//     ca2: bool,
-
-//     /// Peripheral B port (PB0-PB7)
-//     pb: u8,
-
-//     /// Peripheral B control line (CB1)
-//     cb1: bool,
-
-//     /// Peripheral B control line (CB2)
-//     cb2: bool,
-// }
-
-// impl M6522 {
-//     pub(crate) fn new() -> Self {
-//         Self {
-//             phi2: false,
-//             cs: false,
-//             rs: 0,
-//             rw: false,
-//             d: 0,
-//             res: false,
-//             irq: false,
-
-//             pa: 0,
-//             ca1: false,
-//             ca2: false,
-
-//             pb: 0,
-//             cb1: false,
-//             cb2: false,
-//         }
-//     }
-
-//     // Pin handlers
-
-//     fn on_phi2_transition_lo_to_hi(&mut self) {
-//         // TODO
-//     }
-
-//     fn on_phi2_transition_hi_to_lo(&mut self) {
-//         // TODO
-//     }
-
-//     // pub fn set_res(&mut self, value: bool) {
-//     //     self.res = value;
-//     //     self.on_res_set(); 
-//     // }
-
-//     fn on_res_set(&mut self) {
-//         if !self.res {
-//             // TODO: Reset all values.
-//         }
-//     }
-
-//     // pub(crate) fn tick(&mut self, pins: &mut Pins) {
-//     //     match (self.last_pins.phi2, pins.phi2) {
-//     //         // phi2 low -> high
-//     //         (false, true) => {
-
-//     //         }
-
-//     //         // phi2 high -> low
-//     //         (true, false) => {
-
-//     //         }
-
-//     //         // phi2 didn't change
-//     //         _ => {}
-//     //     }
-
-//     //     self.last_pins = pins;
-//     // }
-// }
\ No newline at end of file
+extern crate aemula_macros;
+
+use aemula_macros::PinAccessors;
+
+// Interrupt flag register (IFR) and interrupt enable register (IER) bits.
+const CA2_FLAG: u8 = 0x01;
+const CA1_FLAG: u8 = 0x02;
+const SR_FLAG:  u8 = 0x04;
+const CB2_FLAG: u8 = 0x08;
+const CB1_FLAG: u8 = 0x10;
+const T2_FLAG:  u8 = 0x20;
+const T1_FLAG:  u8 = 0x40;
+const IRQ_FLAG: u8 = 0x80;
+
+// Auxiliary control register (ACR) bits.
+const ACR_PA_LATCH:     u8 = 0x01;
+const ACR_PB_LATCH:     u8 = 0x02;
+const ACR_T2_PB6_COUNT: u8 = 0x20;
+const ACR_T1_FREE_RUN:  u8 = 0x40;
+const ACR_T1_PB7:       u8 = 0x80;
+
+// CA2 / CB2 control modes, from the PCR.
+const C2_INDEPENDENT: u8 = 0b001;
+const C2_HANDSHAKE:   u8 = 0b100;
+const C2_PULSE:       u8 = 0b101;
+const C2_LOW:         u8 = 0b110;
+const C2_HIGH:        u8 = 0b111;
+
+// Shift register modes, from ACR bits 2-4.
+const SR_DISABLED:       u8 = 0b000;
+const SR_IN_T2:          u8 = 0b001;
+const SR_IN_PHI2:        u8 = 0b010;
+const SR_IN_CB1:         u8 = 0b011;
+const SR_OUT_T2_FREE:    u8 = 0b100;
+const SR_OUT_T2:         u8 = 0b101;
+const SR_OUT_PHI2:       u8 = 0b110;
+const SR_OUT_CB1:        u8 = 0b111;
+
+/// 6522 chip, originally manufactured by MOS Technologies.
+///
+/// Known as VIA (Versatile Interface Adapter), it contains:
+/// - Two 8-bit bidirectional ports, each with two control lines for interrupts and handshaking
+/// - Two 16-bit timers. Timer 1 can run continuously and drive PB7, and timer 2 can count pulses on PB6
+/// - An 8-bit shift register
+///
+/// Registers are read and written while phi2 is high, and the timers and shift register
+/// count on the falling edge of phi2.
+#[derive(PinAccessors)]
+pub struct M6522 {
+    // -----------------------------------------------
+    // Processor Interface
+    // -----------------------------------------------
+
+    /// Phase two clock. Data transfers between the 6522 and CPU only take place while
+    /// phi2 is high.
+    #[pin(in)]
+    #[handle(transition_lo_to_hi, transition_hi_to_lo)]
+    phi2: bool,
+
+    /// Chip select. On a real 6522 there are two chip select pins, CS1 and CS2.
+    /// Here they are combined into a single pin, which must be high to enable the chip.
+    #[pin(in)]
+    cs: bool,
+
+    /// Register select (RS0-RS3)
+    #[pin(in)]
+    rs: u8,
+
+    /// Read/write (read = true, write = false)
+    #[pin(in)]
+    rw: bool,
+
+    /// Data bus (D0-D7)
+    #[pin(bidirectional)]
+    d: u8,
+
+    /// Reset (active low)
+    #[pin(in)]
+    #[handle(always)]
+    res: bool,
+
+    /// Interrupt request (active low). Low while any enabled interrupt flag is set.
+    #[pin(out)]
+    irq: bool,
+
+    // -----------------------------------------------
+    // Peripheral Interface
+    // -----------------------------------------------
+
+    /// Peripheral A Port (PA0-PA7). Bits set in DDRA are driven from ORA.
+    #[pin(bidirectional)]
+    pa: u8,
+
+    /// Peripheral A control line (CA1). Input only.
+    #[pin(in)]
+    #[handle(change)]
+    ca1: bool,
+
+    /// Peripheral A control line (CA2). An input or an output, depending on the PCR.
+    #[pin(bidirectional)]
+    #[handle(change)]
+    ca2: bool,
+
+    /// Peripheral B port (PB0-PB7). Bits set in DDRB are driven from ORB, and PB7 can be driven by timer 1.
+    #[pin(bidirectional)]
+    pb: u8,
+
+    /// Peripheral B control line (CB1). An output when the shift register is clocked internally.
+    #[pin(bidirectional)]
+    #[handle(change)]
+    cb1: bool,
+
+    /// Peripheral B control line (CB2). Used by the shift register when it is enabled.
+    #[pin(bidirectional)]
+    #[handle(change)]
+    cb2: bool,
+
+    // -----------------------------------------------
+    // Registers
+    // -----------------------------------------------
+
+    /// Output Register A
+    ora: u8,
+
+    /// Output Register B
+    orb: u8,
+
+    /// Input Register A, latched on an active CA1 transition when latching is enabled.
+    ira: u8,
+
+    /// Input Register B, latched on an active CB1 transition when latching is enabled.
+    irb: u8,
+
+    /// Data Direction Register A. A bit set to 1 makes that pin an output.
+    ddra: u8,
+
+    /// Data Direction Register B. A bit set to 1 makes that pin an output.
+    ddrb: u8,
+
+    /// Auxiliary Control Register
+    acr: u8,
+
+    /// Peripheral Control Register
+    pcr: u8,
+
+    /// Interrupt Flag Register, without bit 7, which is worked out when read.
+    ifr: u8,
+
+    /// Interrupt Enable Register, without bit 7.
+    ier: u8,
+
+    // -----------------------------------------------
+    // Timer 1
+    // -----------------------------------------------
+
+    t1_counter: u16,
+    t1_latch: u16,
+
+    /// Whether the next timeout sets the T1 flag. Always true in free-run mode.
+    t1_armed: bool,
+
+    /// The counter was loaded during this cycle, so it doesn't count until the next one.
+    t1_loaded: bool,
+
+    /// The counter rolled over, and is reloaded from the latch in the next cycle.
+    t1_reload: bool,
+
+    /// Level driven onto PB7 when ACR bit 7 is set.
+    t1_pb7: bool,
+
+    // -----------------------------------------------
+    // Timer 2
+    // -----------------------------------------------
+
+    t2_counter: u16,
+    t2_latch_lo: u8,
+    t2_armed: bool,
+    t2_loaded: bool,
+
+    /// PB6 as it was on the previous falling edge of phi2, for counting pulses.
+    pb6: bool,
+
+    // -----------------------------------------------
+    // Shift register
+    // -----------------------------------------------
+
+    sr: u8,
+
+    /// Number of bits shifted since the SR was last read or written.
+    sr_count: u8,
+
+    /// Whether the shift register is currently shifting.
+    sr_active: bool,
+
+    /// The SR was accessed during this cycle, so the shift clock doesn't start until the next one.
+    sr_loaded: bool,
+
+    /// Counts down from T2's low latch to time the CB1 shift clock.
+    sr_timer: u8,
+    sr_timer_reload: bool,
+
+    // -----------------------------------------------
+    // Control lines
+    // -----------------------------------------------
+
+    /// CA2 went low for a pulse, and goes high again at the start of the next cycle.
+    ca2_pulse: bool,
+
+    /// CB2 went low for a pulse, and goes high again at the start of the next cycle.
+    cb2_pulse: bool,
+}
+
+impl M6522 {
+    pub fn new() -> Self {
+        Self {
+            phi2: false,
+            cs: false,
+            rs: 0,
+            rw: true,
+            d: 0,
+            res: true,
+            irq: true,
+
+            pa: 0,
+            ca1: false,
+            ca2: false,
+
+            pb: 0,
+            cb1: false,
+            cb2: false,
+
+            ora: 0,
+            orb: 0,
+            ira: 0,
+            irb: 0,
+            ddra: 0,
+            ddrb: 0,
+            acr: 0,
+            pcr: 0,
+            ifr: 0,
+            ier: 0,
+
+            t1_counter: 0,
+            t1_latch: 0,
+            t1_armed: false,
+            t1_loaded: false,
+            t1_reload: false,
+            t1_pb7: true,
+
+            t2_counter: 0,
+            t2_latch_lo: 0,
+            t2_armed: false,
+            t2_loaded: false,
+            pb6: false,
+
+            sr: 0,
+            sr_count: 0,
+            sr_active: false,
+            sr_loaded: false,
+            sr_timer: 0,
+            sr_timer_reload: false,
+
+            ca2_pulse: false,
+            cb2_pulse: false,
+        }
+    }
+
+    pub fn is_selected(&self) -> bool {
+        self.cs
+    }
+
+    // Pin handlers
+
+    /// Reset clears all registers except the timers and the shift register.
+    fn on_res_set(&mut self) {
+        if !self.res {
+            self.ora = 0;
+            self.orb = 0;
+            self.ddra = 0;
+            self.ddrb = 0;
+            self.acr = 0;
+            self.pcr = 0;
+            self.ifr = 0;
+            self.ier = 0;
+            self.t1_armed = false;
+            self.t1_pb7 = true;
+            self.t2_armed = false;
+            self.sr_active = false;
+            self.ca2_pulse = false;
+            self.cb2_pulse = false;
+            self.update_irq();
+        }
+    }
+
+    fn on_phi2_transition_lo_to_hi(&mut self) {
+        // Pulse output lasts for one cycle.
+        if self.ca2_pulse {
+            self.ca2_pulse = false;
+            self.ca2 = true;
+        }
+        if self.cb2_pulse {
+            self.cb2_pulse = false;
+            self.cb2 = true;
+        }
+
+        if self.is_selected() {
+            let register = self.rs & 0xF;
+            if self.rw {
+                self.d = self.read_register(register);
+            } else {
+                self.write_register(register);
+            }
+        }
+
+        self.update_ports();
+        self.update_irq();
+    }
+
+    fn on_phi2_transition_hi_to_lo(&mut self) {
+        self.tick_timer_1();
+        self.tick_timer_2();
+        self.tick_shift_clock();
+
+        self.update_ports();
+        self.update_irq();
+    }
+
+    fn on_ca1_change(&mut self) {
+        if self.ca1 != self.ca1_active_edge() {
+            return;
+        }
+
+        self.ifr |= CA1_FLAG;
+        if self.acr & ACR_PA_LATCH != 0 {
+            self.ira = self.pa;
+        }
+        if self.ca2_control() == C2_HANDSHAKE {
+            self.ca2 = true;
+        }
+        self.update_irq();
+    }
+
+    fn on_ca2_change(&mut self) {
+        let control = self.ca2_control();
+        if control & 0b100 == 0 && self.ca2 == (control & 0b010 != 0) {
+            self.ifr |= CA2_FLAG;
+            self.update_irq();
+        }
+    }
+
+    fn on_cb1_change(&mut self) {
+        // CB1 is an output when the shift register makes its own clock.
+        if self.sr_internal_clock() {
+            return;
+        }
+
+        if matches!(self.sr_mode(), SR_IN_CB1 | SR_OUT_CB1) && self.sr_active {
+            if self.cb1 {
+                self.shift_clock_rising();
+            } else {
+                self.shift_clock_falling();
+            }
+        }
+
+        if self.cb1 == self.cb1_active_edge() {
+            self.ifr |= CB1_FLAG;
+            if self.acr & ACR_PB_LATCH != 0 {
+                self.irb = self.pb;
+            }
+            if self.sr_mode() == SR_DISABLED && self.cb2_control() == C2_HANDSHAKE {
+                self.cb2 = true;
+            }
+        }
+        self.update_irq();
+    }
+
+    fn on_cb2_change(&mut self) {
+        // CB2 belongs to the shift register when it is enabled.
+        if self.sr_mode() != SR_DISABLED {
+            return;
+        }
+
+        let control = self.cb2_control();
+        if control & 0b100 == 0 && self.cb2 == (control & 0b010 != 0) {
+            self.ifr |= CB2_FLAG;
+            self.update_irq();
+        }
+    }
+
+    // Registers
+
+    fn read_register(&mut self, register: u8) -> u8 {
+        match register {
+            // ORB / IRB
+            0x0 => {
+                self.clear_port_b_flags();
+                let input = if self.acr & ACR_PB_LATCH != 0 { self.irb } else { self.pb };
+                let output_mask = self.port_b_output_mask();
+                (self.port_b_output() & output_mask) | (input & !output_mask)
+            }
+
+            // ORA / IRA, with handshake
+            0x1 => {
+                self.clear_port_a_flags();
+                self.port_a_handshake();
+                self.port_a_input()
+            }
+
+            // DDRB
+            0x2 => self.ddrb,
+
+            // DDRA
+            0x3 => self.ddra,
+
+            // T1 low-order counter
+            0x4 => {
+                self.ifr &= !T1_FLAG;
+                self.t1_counter as u8
+            }
+
+            // T1 high-order counter
+            0x5 => (self.t1_counter >> 8) as u8,
+
+            // T1 low-order latch
+            0x6 => self.t1_latch as u8,
+
+            // T1 high-order latch
+            0x7 => (self.t1_latch >> 8) as u8,
+
+            // T2 low-order counter
+            0x8 => {
+                self.ifr &= !T2_FLAG;
+                self.t2_counter as u8
+            }
+
+            // T2 high-order counter
+            0x9 => (self.t2_counter >> 8) as u8,
+
+            // SR
+            0xA => {
+                self.start_shift_register();
+                self.sr
+            }
+
+            // ACR
+            0xB => self.acr,
+
+            // PCR
+            0xC => self.pcr,
+
+            // IFR
+            0xD => self.ifr | if self.ifr & self.ier != 0 { IRQ_FLAG } else { 0 },
+
+            // IER
+            0xE => self.ier | 0x80,
+
+            // ORA / IRA, without handshake
+            0xF => self.port_a_input(),
+
+            _ => unreachable!()
+        }
+    }
+
+    fn write_register(&mut self, register: u8) {
+        let value = self.d;
+        match register {
+            // ORB
+            0x0 => {
+                self.orb = value;
+                self.clear_port_b_flags();
+                if self.sr_mode() == SR_DISABLED {
+                    match self.cb2_control() {
+                        C2_HANDSHAKE => self.cb2 = false,
+                        C2_PULSE => {
+                            self.cb2 = false;
+                            self.cb2_pulse = true;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+
+            // ORA, with handshake
+            0x1 => {
+                self.ora = value;
+                self.clear_port_a_flags();
+                self.port_a_handshake();
+            }
+
+            // DDRB
+            0x2 => self.ddrb = value,
+
+            // DDRA
+            0x3 => self.ddra = value,
+
+            // T1 low-order latch
+            0x4 | 0x6 => self.t1_latch = (self.t1_latch & 0xFF00) | value as u16,
+
+            // T1 high-order latch, and start timer 1
+            0x5 => {
+                self.t1_latch = (self.t1_latch & 0x00FF) | ((value as u16) << 8);
+                self.t1_counter = self.t1_latch;
+                self.t1_loaded = true;
+                self.t1_reload = false;
+                self.t1_armed = true;
+                self.t1_pb7 = false;
+                self.ifr &= !T1_FLAG;
+            }
+
+            // T1 high-order latch, without starting timer 1
+            0x7 => {
+                self.t1_latch = (self.t1_latch & 0x00FF) | ((value as u16) << 8);
+                self.ifr &= !T1_FLAG;
+            }
+
+            // T2 low-order latch
+            0x8 => self.t2_latch_lo = value,
+
+            // T2 high-order counter, and start timer 2
+            0x9 => {
+                self.t2_counter = ((value as u16) << 8) | self.t2_latch_lo as u16;
+                self.t2_loaded = true;
+                self.t2_armed = true;
+                self.ifr &= !T2_FLAG;
+            }
+
+            // SR
+            0xA => {
+                self.sr = value;
+                self.start_shift_register();
+            }
+
+            // ACR
+            0xB => {
+                self.acr = value;
+                if self.sr_mode() == SR_DISABLED {
+                    self.sr_active = false;
+                } else if self.sr_internal_clock() && !self.sr_active {
+                    self.cb1 = true;
+                }
+            }
+
+            // PCR
+            0xC => {
+                self.pcr = value;
+                match self.ca2_control() {
+                    C2_HANDSHAKE | C2_PULSE | C2_HIGH => self.ca2 = true,
+                    C2_LOW => self.ca2 = false,
+                    _ => {}
+                }
+                if self.sr_mode() == SR_DISABLED {
+                    match self.cb2_control() {
+                        C2_HANDSHAKE | C2_PULSE | C2_HIGH => self.cb2 = true,
+                        C2_LOW => self.cb2 = false,
+                        _ => {}
+                    }
+                }
+            }
+
+            // IFR. Writing a 1 clears that flag.
+            0xD => self.ifr &= !value,
+
+            // IER. Bit 7 chooses whether the other set bits are enabled or disabled.
+            0xE => {
+                if value & 0x80 != 0 {
+                    self.ier |= value & 0x7F;
+                } else {
+                    self.ier &= !value;
+                }
+            }
+
+            // ORA, without handshake
+            0xF => self.ora = value,
+
+            _ => unreachable!()
+        }
+    }
+
+    // Ports and control lines
+
+    fn ca1_active_edge(&self) -> bool {
+        self.pcr & 0x01 != 0
+    }
+
+    fn ca2_control(&self) -> u8 {
+        (self.pcr >> 1) & 0x7
+    }
+
+    fn cb1_active_edge(&self) -> bool {
+        self.pcr & 0x10 != 0
+    }
+
+    fn cb2_control(&self) -> u8 {
+        (self.pcr >> 5) & 0x7
+    }
+
+    fn port_a_input(&self) -> u8 {
+        if self.acr & ACR_PA_LATCH != 0 {
+            self.ira
+        } else {
+            self.pa
+        }
+    }
+
+    /// Reading or writing ORA starts a handshake or pulse on CA2.
+    fn port_a_handshake(&mut self) {
+        match self.ca2_control() {
+            C2_HANDSHAKE => self.ca2 = false,
+            C2_PULSE => {
+                self.ca2 = false;
+                self.ca2_pulse = true;
+            }
+            _ => {}
+        }
+    }
+
+    /// Accessing ORA clears the CA1 flag, and the CA2 flag unless CA2 is an independent interrupt.
+    fn clear_port_a_flags(&mut self) {
+        self.ifr &= !CA1_FLAG;
+        if self.ca2_control() & 0b101 != C2_INDEPENDENT {
+            self.ifr &= !CA2_FLAG;
+        }
+    }
+
+    /// Accessing ORB clears the CB1 flag, and the CB2 flag unless CB2 is an independent interrupt.
+    fn clear_port_b_flags(&mut self) {
+        self.ifr &= !CB1_FLAG;
+        if self.cb2_control() & 0b101 != C2_INDEPENDENT {
+            self.ifr &= !CB2_FLAG;
+        }
+    }
+
+    /// PB7 is an output when DDRB says so, or when timer 1 drives it.
+    fn port_b_output_mask(&self) -> u8 {
+        if self.acr & ACR_T1_PB7 != 0 {
+            self.ddrb | 0x80
+        } else {
+            self.ddrb
+        }
+    }
+
+    fn port_b_output(&self) -> u8 {
+        if self.acr & ACR_T1_PB7 != 0 {
+            (self.orb & 0x7F) | if self.t1_pb7 { 0x80 } else { 0 }
+        } else {
+            self.orb
+        }
+    }
+
+    /// Drives the output bits of both ports, leaving the input bits as they were set from outside.
+    fn update_ports(&mut self) {
+        self.pa = (self.ora & self.ddra) | (self.pa & !self.ddra);
+
+        let output_mask = self.port_b_output_mask();
+        self.pb = (self.port_b_output() & output_mask) | (self.pb & !output_mask);
+    }
+
+    fn update_irq(&mut self) {
+        // IRQ pin is active low.
+        self.irq = (self.ifr & self.ier) == 0;
+    }
+
+    // Timers
+
+    /// Timer 1 counts down to zero, then rolls over to $FFFF, which sets the T1 flag.
+    /// It is then reloaded from the latch in the next cycle, so in free-run mode it
+    /// times out every N + 2 cycles.
+    fn tick_timer_1(&mut self) {
+        if self.t1_loaded {
+            self.t1_loaded = false;
+            return;
+        }
+
+        if self.t1_reload {
+            self.t1_reload = false;
+            self.t1_counter = self.t1_latch;
+            return;
+        }
+
+        self.t1_counter = self.t1_counter.wrapping_sub(1);
+        if self.t1_counter == 0xFFFF {
+            self.t1_reload = true;
+            if self.t1_armed {
+                self.ifr |= T1_FLAG;
+                if self.acr & ACR_T1_FREE_RUN != 0 {
+                    self.t1_pb7 = !self.t1_pb7;
+                } else {
+                    self.t1_armed = false;
+                    self.t1_pb7 = true;
+                }
+            }
+        }
+    }
+
+    /// Timer 2 either counts phi2 cycles, in which case it times out like timer 1 but keeps
+    /// counting down from $FFFF, or counts falling edges on PB6, in which case it times out
+    /// when it reaches zero. Either way it only sets the T2 flag once per load.
+    fn tick_timer_2(&mut self) {
+        let pb6 = self.pb & 0x40 != 0;
+        let pb6_fell = self.pb6 && !pb6;
+        self.pb6 = pb6;
+
+        if self.t2_loaded {
+            self.t2_loaded = false;
+            return;
+        }
+
+        if self.acr & ACR_T2_PB6_COUNT != 0 {
+            if pb6_fell {
+                self.t2_counter = self.t2_counter.wrapping_sub(1);
+                if self.t2_counter == 0 && self.t2_armed {
+                    self.t2_armed = false;
+                    self.ifr |= T2_FLAG;
+                }
+            }
+        } else {
+            self.t2_counter = self.t2_counter.wrapping_sub(1);
+            if self.t2_counter == 0xFFFF && self.t2_armed {
+                self.t2_armed = false;
+                self.ifr |= T2_FLAG;
+            }
+        }
+    }
+
+    // Shift register
+
+    fn sr_mode(&self) -> u8 {
+        (self.acr >> 2) & 0x7
+    }
+
+    fn sr_shifts_out(&self) -> bool {
+        self.sr_mode() & 0b100 != 0
+    }
+
+    /// CB1 is the shift clock output in every mode except the external clock modes.
+    fn sr_internal_clock(&self) -> bool {
+        !matches!(self.sr_mode(), SR_DISABLED | SR_IN_CB1 | SR_OUT_CB1)
+    }
+
+    /// Reading or writing the SR clears the SR flag and starts shifting eight bits.
+    fn start_shift_register(&mut self) {
+        self.ifr &= !SR_FLAG;
+        if self.sr_mode() != SR_DISABLED {
+            self.sr_active = true;
+            self.sr_loaded = true;
+            self.sr_count = 0;
+            self.sr_timer_reload = true;
+            if self.sr_internal_clock() {
+                self.cb1 = true;
+            }
+        }
+    }
+
+    /// With an internal clock, CB1 changes level every cycle in the phi2 modes, or every
+    /// N + 2 cycles in the timer 2 modes, where N is T2's low-order latch.
+    fn tick_shift_clock(&mut self) {
+        if self.sr_loaded {
+            self.sr_loaded = false;
+            return;
+        }
+
+        if !self.sr_active || !self.sr_internal_clock() {
+            return;
+        }
+
+        match self.sr_mode() {
+            SR_IN_PHI2 | SR_OUT_PHI2 => {}
+
+            SR_IN_T2 | SR_OUT_T2 | SR_OUT_T2_FREE => {
+                if self.sr_timer_reload {
+                    self.sr_timer_reload = false;
+                    self.sr_timer = self.t2_latch_lo;
+                    return;
+                }
+
+                self.sr_timer = self.sr_timer.wrapping_sub(1);
+                if self.sr_timer != 0xFF {
+                    return;
+                }
+                self.sr_timer_reload = true;
+            }
+
+            _ => unreachable!()
+        }
+
+        self.cb1 = !self.cb1;
+        if self.cb1 {
+            self.shift_clock_rising();
+        } else {
+            self.shift_clock_falling();
+        }
+    }
+
+    /// Data is shifted out, most significant bit first, on the falling edge of CB1.
+    fn shift_clock_falling(&mut self) {
+        if self.sr_shifts_out() {
+            self.sr = self.sr.rotate_left(1);
+            self.cb2 = self.sr & 0x01 != 0;
+        }
+    }
+
+    /// Data is shifted in from CB2 on the rising edge of CB1, which also ends each bit.
+    fn shift_clock_rising(&mut self) {
+        if !self.sr_shifts_out() {
+            self.sr = (self.sr << 1) | self.cb2 as u8;
+        }
+
+        self.sr_count += 1;
+        if self.sr_count == 8 {
+            self.sr_count = 0;
+
+            // In free-running mode, the shift register keeps recirculating its data, and never interrupts.
+            if self.sr_mode() != SR_OUT_T2_FREE {
+                self.sr_active = false;
+                self.ifr |= SR_FLAG;
+            }
+        }
+    }
+}
+
+impl Default for M6522 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cycle(chip: &mut M6522) {
+        chip.set_phi2(true);
+        chip.set_phi2(false);
+    }
+
+    fn write(chip: &mut M6522, register: u8, value: u8) {
+        chip.cs = true;
+        chip.rw = false;
+        chip.rs = register;
+        chip.d = value;
+        cycle(chip);
+        chip.cs = false;
+    }
+
+    fn read(chip: &mut M6522, register: u8) -> u8 {
+        chip.cs = true;
+        chip.rw = true;
+        chip.rs = register;
+        cycle(chip);
+        chip.cs = false;
+        chip.d
+    }
+
+    #[test]
+    fn port_pins_follow_data_direction() {
+        let mut chip = M6522::new();
+
+        write(&mut chip, 0x3, 0xF0); // DDRA
+        write(&mut chip, 0x1, 0xAB); // ORA
+        chip.set_pa((chip.pa() & 0xF0) | 0x05);
+        assert_eq!(0xA5, read(&mut chip, 0xF));
+
+        // Port B reads back ORB for its outputs, rather than the pins.
+        write(&mut chip, 0x2, 0x0F); // DDRB
+        write(&mut chip, 0x0, 0x3C); // ORB
+        assert_eq!(0x0C, chip.pb() & 0x0F);
+        chip.set_pb(0x9F);
+        assert_eq!(0x9C, read(&mut chip, 0x0));
+    }
+
+    #[test]
+    fn ca1_latches_port_a() {
+        let mut chip = M6522::new();
+
+        write(&mut chip, 0xB, ACR_PA_LATCH);
+        write(&mut chip, 0xC, 0x01); // CA1 positive edge
+        write(&mut chip, 0xE, 0x80 | CA1_FLAG);
+
+        chip.set_pa(0x42);
+        chip.set_ca1(true);
+        assert!(!chip.irq());
+        chip.set_pa(0x99);
+        assert_eq!(CA1_FLAG | IRQ_FLAG, read(&mut chip, 0xD));
+
+        // Reading ORA clears the CA1 flag.
+        assert_eq!(0x42, read(&mut chip, 0x1));
+        assert!(chip.irq());
+        assert_eq!(0x00, read(&mut chip, 0xD));
+    }
+
+    #[test]
+    fn ca2_independent_interrupt_survives_port_access() {
+        let mut chip = M6522::new();
+
+        write(&mut chip, 0xC, 0b0010); // CA2 independent interrupt, negative edge
+        chip.set_ca2(true);
+        chip.set_ca2(false);
+        read(&mut chip, 0x1);
+        assert_eq!(CA2_FLAG, read(&mut chip, 0xD));
+
+        write(&mut chip, 0xC, 0b0000); // CA2 interrupt, negative edge
+        chip.set_ca2(true);
+        chip.set_ca2(false);
+        read(&mut chip, 0x1);
+        assert_eq!(0x00, read(&mut chip, 0xD));
+    }
+
+    #[test]
+    fn ca2_handshake() {
+        let mut chip = M6522::new();
+
+        write(&mut chip, 0xC, 0b1000); // CA2 handshake, CA1 negative edge
+        assert!(chip.ca2());
+
+        read(&mut chip, 0x1);
+        assert!(!chip.ca2());
+        cycle(&mut chip);
+        assert!(!chip.ca2());
+
+        // Data ready.
+        chip.set_ca1(true);
+        assert!(!chip.ca2());
+        chip.set_ca1(false);
+        assert!(chip.ca2());
+    }
+
+    #[test]
+    fn cb2_pulse() {
+        let mut chip = M6522::new();
+
+        write(&mut chip, 0xC, 0b1010_0000); // CB2 pulse output
+        assert!(chip.cb2());
+
+        // Only writes to ORB start a pulse.
+        read(&mut chip, 0x0);
+        assert!(chip.cb2());
+
+        write(&mut chip, 0x0, 0x00);
+        assert!(!chip.cb2());
+        cycle(&mut chip);
+        assert!(chip.cb2());
+    }
+
+    #[test]
+    fn timer_1_one_shot_interrupt_timing() {
+        // The IRQ output goes low N + 1.5 cycles after writing T1C-H, according to
+        // figure 16 of the MOS 6522 data sheet.
+
+        let mut chip = M6522::new();
+
+        // Enable timer 1 interrupts, and drive PB7.
+        chip.ier = T1_FLAG;
+        chip.acr = ACR_T1_PB7;
+        chip.t1_latch = 0x0034;
+
+        for i in 0..=500 {
+            if i == 0 {
+                // Write T1C-H
+                chip.cs = true;
+                chip.rw = false;
+                chip.rs = 0x5;
+                chip.d = 0x00;
+            } else {
+                chip.cs = false;
+            }
+
+            chip.set_phi2(true);
+
+            match i {
+                0   => assert_eq!(0x0034, chip.t1_counter),
+                1   => assert_eq!(0x0034, chip.t1_counter),
+                2   => assert_eq!(0x0033, chip.t1_counter),
+                53  => assert_eq!(0x0000, chip.t1_counter),
+                54  => assert_eq!(0xFFFF, chip.t1_counter),
+                55  => assert_eq!(0x0034, chip.t1_counter),
+                108 => assert_eq!(0xFFFF, chip.t1_counter),
+                _ => {}
+            }
+
+            match i {
+                0..=53 => {
+                    assert!(chip.irq);
+                    assert_eq!(0x00, chip.pb & 0x80);
+                }
+                _ => {
+                    assert!(!chip.irq);
+                    assert_eq!(0x80, chip.pb & 0x80);
+                }
+            }
+
+            chip.set_phi2(false);
+        }
+
+        // A one-shot only interrupts once.
+        read(&mut chip, 0x4);
+        assert!(chip.irq());
+        for _ in 0..500 {
+            cycle(&mut chip);
+            assert!(chip.irq);
+        }
+    }
+
+    #[test]
+    fn timer_1_free_run_toggles_pb7() {
+        let mut chip = M6522::new();
+
+        write(&mut chip, 0xB, ACR_T1_FREE_RUN | ACR_T1_PB7);
+        write(&mut chip, 0xE, 0x80 | T1_FLAG);
+        write(&mut chip, 0x4, 0x04);
+        write(&mut chip, 0x5, 0x00);
+
+        // PB7 is low from the write, then changes level every N + 2 cycles.
+        let mut pb7_levels = Vec::new();
+        let mut irq_levels = Vec::new();
+        for _ in 0..18 {
+            chip.set_phi2(true);
+            pb7_levels.push(chip.pb() & 0x80 != 0);
+            chip.set_phi2(false);
+            irq_levels.push(chip.irq());
+
+            // Acknowledge the interrupt.
+            if !chip.irq() {
+                chip.ifr &= !T1_FLAG;
+                chip.update_irq();
+            }
+        }
+
+        assert_eq!(vec![
+            false, false, false, false, false,
+            true, true, true, true, true, true,
+            false, false, false, false, false, false,
+            true,
+        ], pb7_levels);
+        assert_eq!(vec![
+            true, true, true, true, false,
+            true, true, true, true, true, false,
+            true, true, true, true, true, false,
+            true,
+        ], irq_levels);
+    }
+
+    #[test]
+    fn timer_2_one_shot_interrupt_timing() {
+        let mut chip = M6522::new();
+
+        chip.ier = T2_FLAG;
+        chip.t2_latch_lo = 0x10;
+
+        for i in 0..=100 {
+            if i == 0 {
+                // Write T2C-H
+                chip.cs = true;
+                chip.rw = false;
+                chip.rs = 0x9;
+                chip.d = 0x00;
+            } else {
+                chip.cs = false;
+            }
+
+            chip.set_phi2(true);
+
+            match i {
+                0  => assert_eq!(0x0010, chip.t2_counter),
+                1  => assert_eq!(0x0010, chip.t2_counter),
+                17 => assert_eq!(0x0000, chip.t2_counter),
+                18 => assert_eq!(0xFFFF, chip.t2_counter),
+                19 => assert_eq!(0xFFFE, chip.t2_counter), // Timer 2 isn't reloaded.
+                _ => {}
+            }
+
+            match i {
+                0..=17 => assert!(chip.irq),
+                _      => assert!(!chip.irq),
+            }
+
+            chip.set_phi2(false);
+        }
+
+        // Reading T2C-L clears the interrupt.
+        read(&mut chip, 0x8);
+        assert!(chip.irq());
+    }
+
+    #[test]
+    fn timer_2_counts_pb6_pulses() {
+        let mut chip = M6522::new();
+
+        write(&mut chip, 0xB, ACR_T2_PB6_COUNT);
+        write(&mut chip, 0xE, 0x80 | T2_FLAG);
+        write(&mut chip, 0x8, 0x03);
+        write(&mut chip, 0x9, 0x00);
+
+        for pulse in 1..=3 {
+            // Cycles without pulses don't count.
+            cycle(&mut chip);
+            cycle(&mut chip);
+
+            chip.set_pb(0x40);
+            cycle(&mut chip);
+            chip.set_pb(0x00);
+            cycle(&mut chip);
+
+            assert_eq!(3 - pulse, chip.t2_counter);
+            assert_eq!(pulse != 3, chip.irq());
+        }
+    }
+
+    #[test]
+    fn shift_out_under_phi2() {
+        let mut chip = M6522::new();
+
+        write(&mut chip, 0xB, SR_OUT_PHI2 << 2);
+        write(&mut chip, 0xE, 0x80 | SR_FLAG);
+        write(&mut chip, 0xA, 0b1011_0010);
+
+        // CB1 goes low as each bit is shifted out onto CB2, then high again.
+        let mut bits = Vec::new();
+        for i in 0..16 {
+            assert!(chip.irq());
+            chip.set_phi2(true);
+            chip.set_phi2(false);
+            assert_eq!(i % 2 == 1, chip.cb1());
+            if !chip.cb1() {
+                bits.push(chip.cb2() as u8);
+            }
+        }
+
+        assert_eq!(vec![1, 0, 1, 1, 0, 0, 1, 0], bits);
+        assert!(!chip.irq());
+
+        // The clock stops after eight bits.
+        cycle(&mut chip);
+        assert!(chip.cb1());
+        assert_eq!(0b1011_0010, read(&mut chip, 0xA));
+        assert!(chip.irq());
+    }
+
+    #[test]
+    fn shift_in_under_timer_2() {
+        let mut chip = M6522::new();
+
+        write(&mut chip, 0xB, SR_IN_T2 << 2);
+        write(&mut chip, 0x8, 0x02);
+        read(&mut chip, 0xA);
+
+        // Each half of the CB1 clock lasts N + 2 cycles.
+        let input = [0, 1, 1, 0, 1, 0, 0, 1];
+        let mut cb1_levels = Vec::new();
+        for i in 0..64 {
+            chip.set_cb2(input[(i / 8) % 8] == 1);
+            cycle(&mut chip);
+            cb1_levels.push(chip.cb1());
+        }
+
+        assert_eq!(vec![true, true, true, false, false, false, false, true], cb1_levels[..8].to_vec());
+        assert_eq!(0b0110_1001, chip.sr);
+        assert_eq!(SR_FLAG, chip.ifr);
+    }
+
+    #[test]
+    fn shift_in_under_cb1() {
+        let mut chip = M6522::new();
+
+        write(&mut chip, 0xB, SR_IN_CB1 << 2);
+        read(&mut chip, 0xA);
+
+        for bit in [1, 1, 0, 0, 1, 0, 1, 0] {
+            chip.set_cb1(false);
+            chip.set_cb2(bit == 1);
+            chip.set_cb1(true);
+        }
+
+        assert_eq!(0b1100_1010, read(&mut chip, 0xA));
+    }
+
+    #[test]
+    fn free_running_shift_out_recirculates() {
+        let mut chip = M6522::new();
+
+        write(&mut chip, 0xB, SR_OUT_T2_FREE << 2);
+        write(&mut chip, 0x8, 0x00);
+        write(&mut chip, 0xA, 0x81);
+
+        let mut bits = Vec::new();
+        for _ in 0..64 {
+            let cb1 = chip.cb1();
+            cycle(&mut chip);
+            if cb1 && !chip.cb1() {
+                bits.push(chip.cb2() as u8);
+            }
+        }
+
+        assert_eq!(vec![1, 0, 0, 0, 0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 0, 1], bits);
+        assert_eq!(0x00, chip.ifr);
+    }
+
+    #[test]
+    fn interrupt_enable_register() {
+        let mut chip = M6522::new();
+
+        write(&mut chip, 0xE, 0x80 | T1_FLAG | CB1_FLAG);
+        assert_eq!(0x80 | T1_FLAG | CB1_FLAG, read(&mut chip, 0xE));
+        write(&mut chip, 0xE, T1_FLAG);
+        assert_eq!(0x80 | CB1_FLAG, read(&mut chip, 0xE));
+
+        // A disabled flag is still set, but doesn't cause an interrupt.
+        chip.set_cb1(true);
+        chip.set_cb1(false);
+        chip.set_ca1(true);
+        chip.set_ca1(false);
+        assert!(!chip.irq());
+        assert_eq!(0x80 | CB1_FLAG | CA1_FLAG, read(&mut chip, 0xD));
+
+        write(&mut chip, 0xD, CB1_FLAG);
+        assert!(chip.irq());
+        assert_eq!(CA1_FLAG, read(&mut chip, 0xD));
+    }
+
+    #[test]
+    fn reset_clears_registers() {
+        let mut chip = M6522::new();
+
+        write(&mut chip, 0x3, 0xFF);
+        write(&mut chip, 0xE, 0xFF);
+        write(&mut chip, 0x4, 0x12);
+        write(&mut chip, 0x5, 0x34);
+
+        chip.set_res(false);
+        chip.set_res(true);
+
+        assert_eq!(0x00, read(&mut chip, 0x3));
+        assert_eq!(0x80, read(&mut chip, 0xE));
+        assert_eq!(0x34, read(&mut chip, 0x7));
+        assert!(chip.irq());
+    }
+}
@@ -38,12 +38,14 @@ pub struct M6532 {
     #[pin(in)]
     a: u8,
 
-    /// Peripheral A Port Pins (PA0-PA7)
+    /// Peripheral A Port Pins (PA0-PA7). Bits set in DDRA are driven from ORA.
     #[pin(bidirectional)]
+    #[handle(change)]
     pa: u8,
 
-    /// Peripheral B Port Pins (PB0-PB7)
+    /// Peripheral B Port Pins (PB0-PB7). Bits set in DDRB are driven from ORB.
     #[pin(bidirectional)]
+    #[handle(change)]
     pb: u8,
 
     /// Input Clock Pin
@@ -94,6 +96,9 @@ pub struct M6532 {
 
     /// True for positive edge-detect, false for negative edge-detect.
     pa7_active_edge_direction: bool,
+
+    /// Level of PA7 when it was last checked for an edge.
+    pa7: bool,
 }
 
 impl M6532 {
@@ -119,6 +124,7 @@ impl M6532 {
             irq_enabled: 0,
             irq_state: 0,
             pa7_active_edge_direction: false,
+            pa7: false,
         }
     }
 
@@ -133,16 +139,24 @@ impl M6532 {
         self.ora = 0;
         self.orb = 0;
 
-        // TODO: Reset timer.
+        self.timer = timer::Timer::new();
+        self.irq_enabled = 0;
+        self.irq_state = 0;
+        self.pa7_active_edge_direction = false;
+        self.update_irq();
+    }
+
+    /// Peripherals can only drive the port pins that are inputs.
+    fn on_pa_change(&mut self) {
+        self.update_ports();
+    }
+
+    fn on_pb_change(&mut self) {
+        self.update_ports();
     }
 
     fn on_phi2_transition_lo_to_hi(&mut self) {
-        // Set IRQ pin based on interrupt flags.
-        // The following condition tests whether one of the following are true:
-        // - Timer interrupts are enabled, and the timer interrupt flag is set, or
-        // - PA7 interrupts are enabled, and the PA7 interrupt flag is set.
-        // Note that IRQ pin is active low.
-        self.irq = (self.irq_state & self.irq_enabled) == 0;
+        self.update_irq();
 
         // To access chip, CS1 must be high and CS2 must be low.
         if self.is_selected() {
@@ -153,7 +167,7 @@ impl M6532 {
                     if self.rw {
                         if self.a & 0x1 != 0 { // Check A0 pin
                             // Read interrupt flags.
-                            self.db = self.irq_enabled;
+                            self.db = self.irq_state;
                             self.irq_state &= !PA7_FLAG; // Clear PA7 flag
                         } else {
                             // Read timer.
@@ -212,6 +226,8 @@ impl M6532 {
                 }
             }
         }
+
+        self.update_ports();
     }
 
     /// According to the diagram on page 2-57 of the R6532 data sheet,
@@ -225,11 +241,12 @@ impl M6532 {
         }
     }
 
+    /// Output bits read back from the output register, and input bits from the pins.
     fn read_io_register(&mut self, register: u8) {
         self.db = match register {
-            0b00 => self.ora,
+            0b00 => (self.ora & self.ddra) | (self.pa & !self.ddra),
             0b01 => self.ddra,
-            0b10 => self.orb,
+            0b10 => (self.orb & self.ddrb) | (self.pb & !self.ddrb),
             0b11 => self.ddrb,
             _ => unreachable!()
         };
@@ -245,6 +262,32 @@ impl M6532 {
         }
     }
 
+    /// Drives the output bits of both ports, leaving the input bits as they were set from outside,
+    /// then checks PA7 for an edge.
+    fn update_ports(&mut self) {
+        self.pa = (self.ora & self.ddra) | (self.pa & !self.ddra);
+        self.pb = (self.orb & self.ddrb) | (self.pb & !self.ddrb);
+
+        // The edge detector sets the PA7 flag whether or not PA7 interrupts are enabled.
+        let pa7 = self.pa & 0x80 != 0;
+        if pa7 != self.pa7 {
+            self.pa7 = pa7;
+            if pa7 == self.pa7_active_edge_direction {
+                self.irq_state |= PA7_FLAG;
+                self.update_irq();
+            }
+        }
+    }
+
+    fn update_irq(&mut self) {
+        // Set IRQ pin based on interrupt flags.
+        // The following condition tests whether one of the following are true:
+        // - Timer interrupts are enabled, and the timer interrupt flag is set, or
+        // - PA7 interrupts are enabled, and the PA7 interrupt flag is set.
+        // Note that IRQ pin is active low.
+        self.irq = (self.irq_state & self.irq_enabled) == 0;
+    }
+
     fn get_interval_duration(a1_a0: u8) -> u16 {
         match a1_a0 {
             0b00 => 1,
@@ -256,6 +299,12 @@ impl M6532 {
     }
 }
 
+impl Default for M6532 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -296,6 +345,97 @@ mod tests {
         assert_eq!(42, chip.ram[10]);
     }
 
+    fn access(chip: &mut M6532, a: u8, rw: bool, db: u8) -> u8 {
+        chip.cs1 = true;
+        chip.cs2 = false;
+        chip.rs = true;
+        chip.rw = rw;
+        chip.a = a;
+        chip.db = db;
+
+        chip.set_phi2(true);
+        chip.set_phi2(false);
+
+        chip.cs1 = false;
+        chip.db
+    }
+
+    #[test]
+    fn ports_mix_output_registers_with_pins() {
+        let mut chip = M6532::new();
+
+        access(&mut chip, 0b001, false, 0x0F); // DDRA
+        access(&mut chip, 0b000, false, 0xA5); // ORA
+        assert_eq!(0x05, chip.pa());
+
+        // Peripherals can only drive the input pins.
+        chip.set_pa(0xF0);
+        assert_eq!(0xF5, chip.pa());
+        assert_eq!(0xF5, access(&mut chip, 0b000, true, 0));
+
+        access(&mut chip, 0b011, false, 0xC0); // DDRB
+        access(&mut chip, 0b010, false, 0x80); // ORB
+        chip.set_pb(0x7F);
+        assert_eq!(0xBF, chip.pb());
+        assert_eq!(0xBF, access(&mut chip, 0b010, true, 0));
+    }
+
+    #[test]
+    fn pa7_edge_interrupt() {
+        let mut chip = M6532::new();
+
+        // Enable PA7 interrupts, on a positive edge.
+        access(&mut chip, 0b0111, false, 0);
+
+        chip.set_pa(0x7F);
+        assert!(chip.irq());
+        chip.set_pa(0x80);
+        assert!(!chip.irq());
+
+        // Reading the interrupt flags clears the PA7 flag.
+        assert_eq!(PA7_FLAG, access(&mut chip, 0b0101, true, 0));
+        assert!(!chip.irq());
+        chip.set_phi2(true);
+        assert!(chip.irq());
+        chip.set_phi2(false);
+
+        // The flag is set even when PA7 interrupts are disabled, and only on the active edge.
+        access(&mut chip, 0b0100, false, 0); // Disable PA7 interrupts, negative edge
+        chip.set_pa(0x00);
+        assert!(chip.irq());
+        assert_eq!(PA7_FLAG, access(&mut chip, 0b0101, true, 0));
+        chip.set_pa(0x80);
+        assert_eq!(0, access(&mut chip, 0b0101, true, 0));
+
+        // An output on PA7 triggers the edge detector too.
+        access(&mut chip, 0b001, false, 0x80); // DDRA, with ORA still 0
+        assert_eq!(0x00, chip.pa());
+        assert_eq!(PA7_FLAG, access(&mut chip, 0b0101, true, 0));
+    }
+
+    #[test]
+    fn reset() {
+        let mut chip = M6532::new();
+
+        access(&mut chip, 0b001, false, 0xFF); // DDRA
+        access(&mut chip, 0b000, false, 0x12); // ORA
+        access(&mut chip, 0b0011100, false, 0x01); // Write Timer 1T, enable timer interrupts
+        chip.set_phi2(true);
+        chip.set_phi2(false);
+        chip.set_phi2(true);
+        assert!(!chip.irq());
+        chip.set_phi2(false);
+
+        chip.set_res(false);
+        chip.set_res(true);
+        assert!(chip.irq());
+
+        // PA is all inputs again.
+        chip.set_pa(0x00);
+        assert_eq!(0x00, access(&mut chip, 0b000, true, 0));
+        assert!(!chip.timer.expired());
+    }
+
     #[test]
     fn interrupt_timing() {
         // This test is based on the example on page 9 of the MOS6532 data sheet:
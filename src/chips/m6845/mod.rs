@@ -1,7 +1,9 @@
+#[derive(Copy, Clone)]
 struct RegisterDefinition(u8, bool, bool); // mask, can_read, can_write
 
 // Although there are only 18 internal registers, we index using a 5-bit value,
 // so to avoid out-of-range indexing we set indices 18 to 31 to zeros.
+// These are the registers of the HD6845S. Other variants differ as described in `M6845Variant::register_definition`.
 const REGISTER_DEFINITONS: [RegisterDefinition; 32] = [
     RegisterDefinition(0xFF, false, true), // R0
     RegisterDefinition(0xFF, false, true), // R1
@@ -11,12 +13,12 @@ const REGISTER_DEFINITONS: [RegisterDefinition; 32] = [
     RegisterDefinition(0x1F, false, true), // R5
     RegisterDefinition(0x7F, false, true), // R6
     RegisterDefinition(0x7F, false, true), // R7
-    RegisterDefinition(0x03, false, true), // R8
+    RegisterDefinition(0xF3, false, true), // R8
     RegisterDefinition(0x1F, false, true), // R9
     RegisterDefinition(0x7F, false, true), // R10
     RegisterDefinition(0x1F, false, true), // R11
-    RegisterDefinition(0x3F, false, true), // R12
-    RegisterDefinition(0xFF, false, true), // R13
+    RegisterDefinition(0x3F, true, true),  // R12
+    RegisterDefinition(0xFF, true, true),  // R13
     RegisterDefinition(0x3F, true, true),  // R14
     RegisterDefinition(0xFF, true, true),  // R15
     RegisterDefinition(0x00, true, false), // R16
@@ -37,6 +39,64 @@ const REGISTER_DEFINITONS: [RegisterDefinition; 32] = [
     RegisterDefinition(0x00, false, false),
 ];
 
+/// Which 6845 to emulate. The 6845 was made by several manufacturers, and the
+/// clones differ in small ways. On the Amstrad CPC, they are known as CRTC types 0 to 4.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum M6845Variant {
+    /// Type 0: Hitachi HD6845S and UMC UM6845. R8 has skew bits, and a sync width of 0 means no horizontal sync.
+    Hd6845s,
+
+    /// Type 1: UMC UM6845R. R8 has no skew bits, and vertical sync is always 16 scanlines. The start address
+    /// is reloaded on every scanline of the first character row, and R6 is checked on every scanline.
+    Um6845r,
+
+    /// Type 2: Motorola MC6845. R8 has no skew bits, and vertical sync is always 16 scanlines.
+    Mc6845,
+
+    /// Type 3: Amstrad AMS40489, built into the CPC+ ASIC.
+    Ams40489,
+
+    /// Type 4: Amstrad AMS40226, the pre-ASIC.
+    Ams40226,
+}
+
+impl M6845Variant {
+    fn register_definition(self, register: u8) -> RegisterDefinition {
+        let RegisterDefinition(mask, can_read, can_write) = REGISTER_DEFINITONS[register as usize];
+        match (self, register) {
+            // Only the interlace mode bits exist.
+            (M6845Variant::Um6845r | M6845Variant::Mc6845, 8) => RegisterDefinition(0x03, can_read, can_write),
+
+            // The start address can't be read back.
+            (M6845Variant::Um6845r | M6845Variant::Mc6845, 12 | 13) => RegisterDefinition(mask, false, can_write),
+
+            _ => RegisterDefinition(mask, can_read, can_write),
+        }
+    }
+
+    /// Whether a horizontal sync width of 0 means 16 characters, rather than no sync at all.
+    fn has_16_character_horizontal_sync_for_0(self) -> bool {
+        !matches!(self, M6845Variant::Hd6845s | M6845Variant::Um6845r)
+    }
+
+    /// Whether the vertical sync width in R3 is ignored, and vertical sync always lasts 16 scanlines.
+    fn has_fixed_vertical_sync_width(self) -> bool {
+        matches!(self, M6845Variant::Um6845r | M6845Variant::Mc6845)
+    }
+}
+
+pub struct M6845Options {
+    pub variant: M6845Variant,
+}
+
+impl Default for M6845Options {
+    fn default() -> Self {
+        Self {
+            variant: M6845Variant::Hd6845s,
+        }
+    }
+}
+
 #[derive(Copy, Clone)]
 pub struct Pins {
     // -----------------------------------------------
@@ -82,14 +142,14 @@ pub struct Pins {
     // Other
     // -----------------------------------------------
 
-    /// Cursor
+    /// Cursor display
     pub cursor: bool,
 
     /// Character clock
     pub clk: bool,
 
-    // /// Light pen strobe
-    // lpstb: bool,
+    /// Light pen strobe. The refresh memory address is latched into R16 and R17 on the rising edge.
+    pub lpstb: bool,
 }
 
 impl Pins {
@@ -109,7 +169,7 @@ impl Pins {
 
             cursor: false,
             clk: false,
-            //lpstb: false,
+            lpstb: false,
         }
     }
 }
@@ -145,6 +205,76 @@ impl RegisterNames {
     fn get_vertical_sync_width(&self) -> u8 {
         self.sync_widths >> 4
     }
+
+    fn get_interlace_mode(&self) -> InterlaceMode {
+        match self.interlace_mode_and_skew & 0x3 {
+            0b01 => InterlaceMode::InterlaceSync,
+            0b11 => InterlaceMode::InterlaceSyncAndVideo,
+            _ => InterlaceMode::NonInterlace,
+        }
+    }
+
+    /// Number of characters that DISPTMG is delayed by, or `None` if it is disabled.
+    fn get_display_skew(&self) -> Option<u8> {
+        match (self.interlace_mode_and_skew >> 4) & 0x3 {
+            3 => None,
+            skew => Some(skew),
+        }
+    }
+
+    /// Number of characters that CUDISP is delayed by, or `None` if it is disabled.
+    fn get_cursor_skew(&self) -> Option<u8> {
+        match self.interlace_mode_and_skew >> 6 {
+            3 => None,
+            skew => Some(skew),
+        }
+    }
+
+    fn get_cursor_blink_mode(&self) -> CursorBlinkMode {
+        match (self.cursor_start >> 5) & 0x3 {
+            0b00 => CursorBlinkMode::Steady,
+            0b01 => CursorBlinkMode::Off,
+            0b10 => CursorBlinkMode::Fast,
+            0b11 => CursorBlinkMode::Slow,
+            _ => unreachable!()
+        }
+    }
+
+    fn get_cursor_start_raster(&self) -> u8 {
+        self.cursor_start & 0x1F
+    }
+
+    fn get_cursor_address(&self) -> u16 {
+        ((self.cursor_address_hi as u16) << 8) | (self.cursor_address_lo as u16)
+    }
+}
+
+/// Set by bits 0 and 1 of R8.
+#[derive(PartialEq)]
+enum InterlaceMode {
+    NonInterlace,
+
+    /// Both fields show the same scanlines, but vertical sync is half a scanline later in odd fields,
+    /// so they are drawn in between each other.
+    InterlaceSync,
+
+    /// As `InterlaceSync`, but even fields show the even raster addresses, and odd fields the odd ones.
+    InterlaceSyncAndVideo,
+}
+
+/// Set by bits 5 and 6 of R10.
+enum CursorBlinkMode {
+    /// Cursor is always displayed.
+    Steady,
+
+    /// Cursor is never displayed.
+    Off,
+
+    /// Cursor blinks at 1/16 of the field rate.
+    Fast,
+
+    /// Cursor blinks at 1/32 of the field rate.
+    Slow,
 }
 
 #[repr(C)]
@@ -161,13 +291,18 @@ impl Registers {
     }
 }
 
-pub(crate) struct M6845 {
+pub struct M6845 {
     pub pins: Pins,
 
+    variant: M6845Variant,
+
     address: u8,
     registers: Registers,
 
     memory_address: u16,
+
+    /// Memory address at the start of the current character row, or of the next one once
+    /// the last scanline of this row has passed the end of horizontal displayed.
     memory_address_stored: u16,
 
     horizontal_counter: u8,
@@ -181,13 +316,36 @@ pub(crate) struct M6845 {
     vertical_sync: bool,
     vertical_sync_counter: u8,
     vertical_display_enable: bool,
+
+    /// Vertical sync as it is output, which lags `vertical_sync` by half a scanline in odd interlaced fields.
+    vertical_sync_output: bool,
+
+    /// Whether the extra scanlines from R5 are being output, at the end of the frame.
+    vertical_adjust: bool,
+    vertical_adjust_counter: u8,
+
+    /// Counts fields, for blinking the cursor. Bit 0 is set in odd fields.
+    field_counter: u8,
+
+    /// DISPTMG and CUDISP for the last few characters, most recent in bit 0, so they can be skewed.
+    display_history: u8,
+    cursor_history: u8,
+
+    /// Light pen strobe, as it was on the previous tick.
+    lpstb: bool,
 }
 
 impl M6845 {
-    pub(crate) fn new() -> Self {
+    pub fn new() -> Self {
+        Self::new_with_options(M6845Options::default())
+    }
+
+    pub fn new_with_options(options: M6845Options) -> Self {
         Self {
             pins: Pins::new(),
 
+            variant: options.variant,
+
             address: 0,
             registers: Registers::new(),
 
@@ -204,10 +362,27 @@ impl M6845 {
             vertical_sync: false,
             vertical_sync_counter: 0,
             vertical_display_enable: false,
+
+            vertical_sync_output: false,
+
+            vertical_adjust: false,
+            vertical_adjust_counter: 0,
+
+            field_counter: 0,
+
+            display_history: 0,
+            cursor_history: 0,
+
+            lpstb: false,
         }
     }
 
-    pub(crate) fn tick(&mut self) {
+    pub fn tick(&mut self) {
+        if self.pins.lpstb && !self.lpstb {
+            self.latch_light_pen();
+        }
+        self.lpstb = self.pins.lpstb;
+
         if self.pins.cs {
             self.tick_processor_interface();
         }
@@ -226,7 +401,7 @@ impl M6845 {
     fn tick_processor_interface(&mut self) {
         if self.pins.rs {
             // Data registers
-            let register_definition = &REGISTER_DEFINITONS[self.address as usize];
+            let register_definition = self.variant.register_definition(self.address);
             unsafe {
                 if self.pins.rw { // Read
                     if register_definition.1 { // can_read
@@ -252,6 +427,13 @@ impl M6845 {
         //println!("Tick 6845 rs {} data {:02X} rw {}", self.pins.rs, self.pins.d, self.pins.rw);
     }
 
+    /// The light pen registers hold the refresh memory address at the time of the strobe.
+    fn latch_light_pen(&mut self) {
+        // Writing to a union field is safe.
+        self.registers.names.lightpen_hi = ((self.memory_address >> 8) & 0x3F) as u8;
+        self.registers.names.lightpen_lo = self.memory_address as u8;
+    }
+
     fn at_end_of_scanline(&self) -> bool {
         unsafe {
             self.horizontal_counter >= self.registers.names.horizontal_total
@@ -260,14 +442,13 @@ impl M6845 {
 
     fn at_end_of_character_row(&self) -> bool {
         unsafe {
-            let mut max_raster_address = self.registers.names.max_raster_address;
-
-            // Are we in the vertical adjust area?
-            if self.vertical_counter >= self.registers.names.vertical_total {
-                max_raster_address += self.registers.names.vertical_total_adjust;
-            }
+            self.raster_address >= self.registers.names.max_raster_address
+        }
+    }
 
-            self.raster_address >= (max_raster_address + 1)
+    fn at_end_of_vertical_adjust(&self) -> bool {
+        unsafe {
+            self.vertical_adjust_counter >= self.registers.names.vertical_total_adjust
         }
     }
 
@@ -279,31 +460,38 @@ impl M6845 {
 
     fn at_start_of_vertical_sync(&self) -> bool {
         unsafe {
-            self.vertical_counter >= self.registers.names.vertical_sync_position
+            self.raster_address == self.get_first_raster_address() && self.vertical_counter == self.registers.names.vertical_sync_position
         }
     }
 
+    /// A vertical sync width of 0 means 16 scanlines.
     fn at_end_of_vertical_sync(&self) -> bool {
-        unsafe {
-            self.vertical_sync_counter == self.registers.names.get_vertical_sync_width()
-        }
+        let width = if self.variant.has_fixed_vertical_sync_width() {
+            0
+        } else {
+            unsafe { self.registers.names.get_vertical_sync_width() }
+        };
+        self.vertical_sync_counter & 0xF == width
     }
 
     fn at_end_of_horizontal_displayed(&self) -> bool {
         unsafe {
-            self.horizontal_counter >= self.registers.names.horizontal_displayed
+            self.horizontal_counter == self.registers.names.horizontal_displayed
         }
     }
 
     fn at_start_of_horizontal_sync(&self) -> bool {
         unsafe {
-            self.horizontal_counter >= self.registers.names.horizontal_sync_position
+            let width = self.registers.names.get_horizontal_sync_width();
+            self.horizontal_counter == self.registers.names.horizontal_sync_position
+                && (width != 0 || self.variant.has_16_character_horizontal_sync_for_0())
         }
     }
 
+    /// Where a horizontal sync width of 0 is allowed, it means 16 characters.
     fn at_end_of_horizontal_sync(&self) -> bool {
         unsafe {
-            self.horizontal_sync_counter == self.registers.names.get_horizontal_sync_width()
+            self.horizontal_sync_counter & 0xF == self.registers.names.get_horizontal_sync_width()
         }
     }
 
@@ -313,6 +501,43 @@ impl M6845 {
         }
     }
 
+    fn get_interlace_mode(&self) -> InterlaceMode {
+        unsafe {
+            self.registers.names.get_interlace_mode()
+        }
+    }
+
+    fn is_odd_field(&self) -> bool {
+        self.field_counter & 1 == 1
+    }
+
+    /// In interlace sync and video mode, odd fields start each character row from raster address 1.
+    fn get_first_raster_address(&self) -> u8 {
+        if self.get_interlace_mode() == InterlaceMode::InterlaceSyncAndVideo && self.is_odd_field() {
+            1
+        } else {
+            0
+        }
+    }
+
+    /// In interlace sync and video mode, each field only shows every other raster address.
+    fn next_raster_address(&self) -> u8 {
+        let step = if self.get_interlace_mode() == InterlaceMode::InterlaceSyncAndVideo { 2 } else { 1 };
+        (self.raster_address + step) & 0x1F // Wrap at 5 bits.
+    }
+
+    /// Vertical sync starts and ends at the start of a scanline, or half way along it in odd
+    /// interlaced fields.
+    fn at_vertical_sync_output_position(&self) -> bool {
+        if self.get_interlace_mode() != InterlaceMode::NonInterlace && self.is_odd_field() {
+            unsafe {
+                self.horizontal_counter == self.registers.names.horizontal_total.div_ceil(2)
+            }
+        } else {
+            self.horizontal_counter == 0
+        }
+    }
+
     fn get_start_address(&self) -> u16 {
         unsafe {
             let register_names = self.registers.names;
@@ -325,80 +550,567 @@ impl M6845 {
     /// except the processor interface (handled by `cycle_processor_interface` above).
     fn tick_crt(&mut self) {
         if self.at_end_of_scanline() {
-            //println!("End of scanline");
+            self.horizontal_counter = 0;
+            self.tick_scanline();
+        } else {
+            self.horizontal_counter = self.horizontal_counter.wrapping_add(1);
+            self.memory_address = (self.memory_address + 1) & 0x3FFF; // Wrap at 14 bits.
+        }
 
-            if self.at_end_of_character_row() {
-                //println!("End of character row");
-                self.raster_address = 0;
+        if self.horizontal_counter == 0 {
+            self.horizontal_display_enable = true;
+        }
 
-                if self.at_end_of_vertical_displayed() {
-                   // println!("End of vertical displayed");
-                    self.vertical_display_enable = false;
-                }
+        if self.at_end_of_horizontal_displayed() {
+            //println!("End of horizontal displayed");
+            self.horizontal_display_enable = false;
 
-                if self.at_end_of_frame() {
-                    //println!("End of frame");
-                    self.vertical_counter = 0;
-                    self.vertical_display_enable = true;
-                    self.memory_address_stored = self.get_start_address();
-                }
+            // Keep track of which memory address we've reached on the last scanline
+            // of the row, so the next row can start from here.
+            if self.at_end_of_character_row() && !self.vertical_adjust {
+                self.memory_address_stored = self.memory_address;
+            }
+        }
+
+        if self.horizontal_sync {
+            self.horizontal_sync_counter += 1;
+            if self.at_end_of_horizontal_sync() {
+                //println!("End of horizontal sync");
+                self.horizontal_sync = false;
+            }
+        }
 
-                if self.at_start_of_vertical_sync() {
-                    //println!("Start of vertical sync");
-                    self.vertical_sync = true;
-                    self.vertical_sync_counter = 0;
+        if self.at_start_of_horizontal_sync() {
+            // println!("Start of horizontal sync");
+            self.horizontal_sync = true;
+            self.horizontal_sync_counter = 0;
+        }
+
+        if self.at_vertical_sync_output_position() {
+            self.vertical_sync_output = self.vertical_sync;
+        }
+
+        let display_enable = self.horizontal_display_enable && self.vertical_display_enable;
+        let cursor = display_enable && self.cursor_enabled();
+
+        // Set pins.
+        self.pins.hs = self.horizontal_sync;
+        self.pins.vs = self.vertical_sync_output;
+        self.pins.disptmg = Self::skew(&mut self.display_history, display_enable, unsafe { self.registers.names.get_display_skew() });
+        self.pins.cursor = Self::skew(&mut self.cursor_history, cursor, unsafe { self.registers.names.get_cursor_skew() });
+        self.pins.ma = self.memory_address;
+        self.pins.ra = self.raster_address;
+    }
+
+    /// Moves on to the next scanline, at the end of the current one.
+    fn tick_scanline(&mut self) {
+        if self.vertical_sync {
+            self.vertical_sync_counter += 1;
+            if self.at_end_of_vertical_sync() {
+                //println!("End of vertical sync");
+                self.vertical_sync = false;
+            }
+        }
+
+        if self.vertical_adjust {
+            self.vertical_adjust_counter += 1;
+            self.raster_address = self.next_raster_address();
+            if self.at_end_of_vertical_adjust() {
+                self.start_frame();
+            }
+        } else if self.at_end_of_character_row() {
+            //println!("End of character row");
+            self.raster_address = self.get_first_raster_address();
+
+            if self.at_end_of_frame() {
+                //println!("End of frame");
+                self.vertical_adjust = true;
+                self.vertical_adjust_counter = 0;
+                self.vertical_counter = (self.vertical_counter + 1) & 0x7F;
+                if self.at_end_of_vertical_adjust() {
+                    self.start_frame();
                 }
             } else {
-                self.raster_address = (self.raster_address + 1) & 0x1F; // Wrap at 5 bits.
+                self.vertical_counter = (self.vertical_counter + 1) & 0x7F;
             }
+        } else {
+            self.raster_address = self.next_raster_address();
+        }
 
-            self.horizontal_counter = 0;
-            self.horizontal_display_enable = true;
+        // The UM6845R checks R6 on every scanline, so it can be changed part way through a row.
+        let row_started = self.raster_address == self.get_first_raster_address();
+        if self.at_end_of_vertical_displayed() && (row_started || self.variant == M6845Variant::Um6845r) {
+            // println!("End of vertical displayed");
+            self.vertical_display_enable = false;
+        }
+
+        if self.at_start_of_vertical_sync() && !self.vertical_sync {
+            //println!("Start of vertical sync");
+            self.vertical_sync = true;
+            self.vertical_sync_counter = 0;
+        }
+
+        // The UM6845R reloads the start address on every scanline of the first row.
+        if self.variant == M6845Variant::Um6845r && self.vertical_counter == 0 && !self.vertical_adjust {
+            self.memory_address_stored = self.get_start_address();
+        }
 
-            // Start ma from the value we stored in the previous scanline.
-            self.memory_address = self.memory_address_stored;
+        // Start ma from the value we stored in the previous scanline.
+        self.memory_address = self.memory_address_stored;
+    }
+
+    fn start_frame(&mut self) {
+        self.vertical_adjust = false;
+        self.field_counter = self.field_counter.wrapping_add(1);
+        self.vertical_counter = 0;
+        self.raster_address = self.get_first_raster_address();
+        self.vertical_display_enable = true;
+        self.memory_address_stored = self.get_start_address();
+    }
+
+    /// Whether the cursor is at the current character and scanline, and blinked on.
+    fn cursor_enabled(&self) -> bool {
+        let registers = unsafe { self.registers.names };
+
+        let blink_on = match registers.get_cursor_blink_mode() {
+            CursorBlinkMode::Steady => true,
+            CursorBlinkMode::Off => false,
+            CursorBlinkMode::Fast => self.field_counter & 0x08 == 0,
+            CursorBlinkMode::Slow => self.field_counter & 0x10 == 0,
+        };
+
+        // When the start raster is after the end raster, the cursor wraps around the character row.
+        let start = registers.get_cursor_start_raster();
+        let end = registers.cursor_end;
+        let in_cursor_rows = if start <= end {
+            (start..=end).contains(&self.raster_address)
+        } else {
+            self.raster_address >= start || self.raster_address <= end
+        };
+
+        blink_on && in_cursor_rows && self.memory_address == registers.get_cursor_address()
+    }
+
+    /// Delays a signal by the skew from R8, using the history of its last few values.
+    fn skew(history: &mut u8, value: bool, skew: Option<u8>) -> bool {
+        *history = (*history << 1) | value as u8;
+        match skew {
+            Some(skew) => *history & (1 << skew) != 0,
+            None => false,
+        }
+    }
+}
+
+impl Default for M6845 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+    use super::*;
+
+    fn write_register(crtc: &mut M6845, register: u8, value: u8) {
+        crtc.pins.cs = true;
+        crtc.pins.rw = false;
+        crtc.pins.rs = false;
+        crtc.pins.d = register;
+        crtc.tick();
+        crtc.pins.rs = true;
+        crtc.pins.d = value;
+        crtc.tick();
+        crtc.pins.cs = false;
+    }
+
+    fn read_register(crtc: &mut M6845, register: u8) -> u8 {
+        crtc.pins.cs = true;
+        crtc.pins.rw = false;
+        crtc.pins.rs = false;
+        crtc.pins.d = register;
+        crtc.tick();
+        crtc.pins.rw = true;
+        crtc.pins.rs = true;
+        crtc.tick();
+        crtc.pins.cs = false;
+        crtc.pins.d
+    }
+
+    fn tick_character(crtc: &mut M6845) {
+        crtc.pins.clk = true;
+        crtc.tick();
+        crtc.pins.clk = false;
+    }
+
+    /// A small screen: 10 characters per scanline with 4 displayed, and 6 rows of
+    /// 2 scanlines with 3 displayed.
+    fn small_screen(crtc: &mut M6845) {
+        write_register(crtc, 0, 9);     // Horizontal total
+        write_register(crtc, 1, 4);     // Horizontal displayed
+        write_register(crtc, 2, 6);     // Horizontal sync position
+        write_register(crtc, 3, 0x22);  // Sync widths
+        write_register(crtc, 4, 5);     // Vertical total
+        write_register(crtc, 5, 0);     // Vertical total adjust
+        write_register(crtc, 6, 3);     // Vertical displayed
+        write_register(crtc, 7, 4);     // Vertical sync position
+        write_register(crtc, 9, 1);     // Max raster address
+        write_register(crtc, 12, 0x01); // Start address
+        write_register(crtc, 13, 0x00);
+    }
+
+    /// Runs until the start of the next frame.
+    fn start_frame(crtc: &mut M6845) {
+        loop {
+            tick_character(crtc);
+            if crtc.horizontal_counter == 0 && crtc.vertical_counter == 0 && crtc.raster_address == crtc.get_first_raster_address() {
+                break;
+            }
+        }
+    }
+
+    /// Records `f` for every character of a frame, as a string of scanlines.
+    fn frame(crtc: &mut M6845, f: impl Fn(&M6845) -> bool) -> Vec<String> {
+        let mut scanlines = vec![String::new()];
+        for _ in 0..10 * 12 {
+            if f(crtc) {
+                scanlines.last_mut().unwrap().push('#');
+            } else {
+                scanlines.last_mut().unwrap().push('.');
+            }
+            if crtc.horizontal_counter == 9 && scanlines.len() < 12 {
+                scanlines.push(String::new());
+            }
+            tick_character(crtc);
+        }
+        scanlines
+    }
+
+    #[test]
+    fn refresh_addresses() {
+        let mut crtc = M6845::new();
+        small_screen(&mut crtc);
+        start_frame(&mut crtc);
+
+        let mut addresses = Vec::new();
+        for _ in 0..10 * 6 {
+            if crtc.pins.disptmg {
+                addresses.push((crtc.pins.ma, crtc.pins.ra));
+            }
+            tick_character(&mut crtc);
+        }
 
-            if self.vertical_sync {
-                self.vertical_counter += 1;
-                if self.at_end_of_vertical_sync() {
-                    //println!("End of vertical sync");
-                    self.vertical_sync = false;
+        // Each scanline of a row reads the same addresses.
+        assert_eq!(vec![
+            (0x100, 0), (0x101, 0), (0x102, 0), (0x103, 0),
+            (0x100, 1), (0x101, 1), (0x102, 1), (0x103, 1),
+            (0x104, 0), (0x105, 0), (0x106, 0), (0x107, 0),
+            (0x104, 1), (0x105, 1), (0x106, 1), (0x107, 1),
+            (0x108, 0), (0x109, 0), (0x10A, 0), (0x10B, 0),
+            (0x108, 1), (0x109, 1), (0x10A, 1), (0x10B, 1),
+        ], addresses);
+    }
+
+    #[test]
+    fn sync_timing() {
+        let mut crtc = M6845::new();
+        small_screen(&mut crtc);
+        start_frame(&mut crtc);
+
+        assert_eq!(vec![
+            "......##..",
+            "......##..",
+            "......##..",
+            "......##..",
+            "......##..",
+            "......##..",
+            "......##..",
+            "......##..",
+            "######..##",
+            "######..##",
+            "......##..",
+            "......##..",
+        ], frame(&mut crtc, |crtc| crtc.pins.hs != crtc.pins.vs));
+    }
+
+    #[test]
+    fn cursor() {
+        let mut crtc = M6845::new();
+        small_screen(&mut crtc);
+        write_register(&mut crtc, 10, 0x01); // Cursor start, steady
+        write_register(&mut crtc, 11, 0x01); // Cursor end
+        write_register(&mut crtc, 14, 0x01); // Cursor address
+        write_register(&mut crtc, 15, 0x06);
+        start_frame(&mut crtc);
+
+        assert_eq!(vec![
+            "..........",
+            "..........",
+            "..........",
+            "..#.......",
+            "..........",
+            "..........",
+            "..........",
+            "..........",
+            "..........",
+            "..........",
+            "..........",
+            "..........",
+        ], frame(&mut crtc, |crtc| crtc.pins.cursor));
+
+        // The cursor address can be read back.
+        assert_eq!(0x01, read_register(&mut crtc, 14));
+        assert_eq!(0x06, read_register(&mut crtc, 15));
+    }
+
+    #[test]
+    fn cursor_blink_modes() {
+        let mut crtc = M6845::new();
+        small_screen(&mut crtc);
+        write_register(&mut crtc, 11, 0x01); // Cursor end
+        write_register(&mut crtc, 14, 0x01); // Cursor address
+        write_register(&mut crtc, 15, 0x00);
+
+        let fields_with_cursor = |crtc: &mut M6845, cursor_start: u8| {
+            write_register(crtc, 10, cursor_start);
+            let mut fields = String::new();
+            for _ in 0..64 {
+                start_frame(crtc);
+                fields.push(if crtc.pins.cursor { '#' } else { '.' });
+            }
+            fields
+        };
+
+        let steady = fields_with_cursor(&mut crtc, 0x00);
+        assert_eq!("#".repeat(64), steady);
+
+        let off = fields_with_cursor(&mut crtc, 0x20);
+        assert_eq!(".".repeat(64), off);
+
+        // Fast blinking is on for 8 fields and off for 8, and slow blinking is on for 16 and off for 16.
+        let fast = fields_with_cursor(&mut crtc, 0x40);
+        assert!(fast.contains(&format!(".{}.", "#".repeat(8))));
+        assert!(fast.contains(&format!("#{}#", ".".repeat(8))));
+        assert_eq!(32, fast.matches('#').count());
+
+        let slow = fields_with_cursor(&mut crtc, 0x60);
+        assert!(slow.contains(&format!(".{}.", "#".repeat(16))));
+        assert!(slow.contains(&format!("#{}#", ".".repeat(16))));
+        assert_eq!(32, slow.matches('#').count());
+    }
+
+    #[test]
+    fn display_and_cursor_skew() {
+        let mut crtc = M6845::new();
+        small_screen(&mut crtc);
+        write_register(&mut crtc, 10, 0x00); // Cursor start, steady
+        write_register(&mut crtc, 11, 0x00); // Cursor end
+        write_register(&mut crtc, 14, 0x01); // Cursor address
+        write_register(&mut crtc, 15, 0x01);
+
+        write_register(&mut crtc, 8, 0x10); // DISPTMG one character late, CUDISP not delayed
+        start_frame(&mut crtc);
+        let scanlines = frame(&mut crtc, |crtc| crtc.pins.disptmg);
+        assert_eq!(".####.....", scanlines[0]);
+        let scanlines = frame(&mut crtc, |crtc| crtc.pins.cursor);
+        assert_eq!(".#........", scanlines[0]);
+
+        write_register(&mut crtc, 8, 0x80); // CUDISP two characters late
+        let scanlines = frame(&mut crtc, |crtc| crtc.pins.cursor);
+        assert_eq!("...#......", scanlines[0]);
+        let scanlines = frame(&mut crtc, |crtc| crtc.pins.disptmg);
+        assert_eq!("####......", scanlines[0]);
+
+        // The pins change from the next character.
+        write_register(&mut crtc, 8, 0xF0); // Both disabled
+        tick_character(&mut crtc);
+        let scanlines = frame(&mut crtc, |crtc| crtc.pins.disptmg || crtc.pins.cursor);
+        assert!(!scanlines.concat().contains('#'));
+    }
+
+    #[test]
+    fn interlace_sync_delays_odd_vertical_sync() {
+        let mut crtc = M6845::new();
+        small_screen(&mut crtc);
+
+        // Finds the character where vertical sync starts, for the next two fields.
+        let vertical_sync_starts = |crtc: &mut M6845| {
+            let mut starts = Vec::new();
+            start_frame(crtc);
+            let mut previous_vs = crtc.pins.vs;
+            for _ in 0..2 * 10 * 12 {
+                tick_character(crtc);
+                if crtc.pins.vs && !previous_vs {
+                    starts.push((crtc.vertical_counter, crtc.raster_address, crtc.horizontal_counter));
                 }
+                previous_vs = crtc.pins.vs;
             }
+            starts.sort();
+            starts
+        };
+
+        assert_eq!(vec![(4, 0, 0), (4, 0, 0)], vertical_sync_starts(&mut crtc));
+
+        write_register(&mut crtc, 8, 0x01); // Interlace sync
+        assert_eq!(vec![(4, 0, 0), (4, 0, 5)], vertical_sync_starts(&mut crtc));
+
+        // The vertical sync is still two scanlines long.
+        start_frame(&mut crtc);
+        let mut scanlines = frame(&mut crtc, |crtc| crtc.pins.vs);
+        scanlines.extend(frame(&mut crtc, |crtc| crtc.pins.vs));
+        assert_eq!(4, scanlines.concat().matches('#').count() / 10);
+    }
+
+    #[test]
+    fn interlace_sync_and_video_raster_addresses() {
+        let mut crtc = M6845::new();
+        small_screen(&mut crtc);
+        write_register(&mut crtc, 8, 0x03); // Interlace sync and video
+        write_register(&mut crtc, 9, 2);    // Max raster address
+
+        // Each field has two scanlines per row, and the fields take turns to show even and odd scanlines.
+        let mut fields = Vec::new();
+        for _ in 0..2 {
+            start_frame(&mut crtc);
+            let mut raster_addresses = Vec::new();
+            for _ in 0..6 {
+                raster_addresses.push((crtc.pins.ma, crtc.pins.ra));
+                for _ in 0..10 {
+                    tick_character(&mut crtc);
+                }
+            }
+            fields.push((crtc.is_odd_field(), raster_addresses));
+        }
+        fields.sort();
+
+        assert_eq!(vec![
+            (false, vec![(0x100, 0), (0x100, 2), (0x104, 0), (0x104, 2), (0x108, 0), (0x108, 2)]),
+            (true,  vec![(0x100, 1), (0x100, 3), (0x104, 1), (0x104, 3), (0x108, 1), (0x108, 3)]),
+        ], fields);
+    }
+
+    #[test_case(M6845Variant::Hd6845s, true, 0xF3 ; "hd6845s")]
+    #[test_case(M6845Variant::Um6845r, false, 0x03 ; "um6845r")]
+    #[test_case(M6845Variant::Mc6845, false, 0x03 ; "mc6845")]
+    #[test_case(M6845Variant::Ams40489, true, 0xF3 ; "ams40489")]
+    #[test_case(M6845Variant::Ams40226, true, 0xF3 ; "ams40226")]
+    #[allow(clippy::unused_unit)]
+    fn variant_registers(variant: M6845Variant, start_address_readable: bool, r8_mask: u8) {
+        let mut crtc = M6845::new_with_options(M6845Options { variant });
+
+        write_register(&mut crtc, 12, 0x12);
+        write_register(&mut crtc, 13, 0x34);
+        if start_address_readable {
+            assert_eq!(0x12, read_register(&mut crtc, 12));
+            assert_eq!(0x34, read_register(&mut crtc, 13));
         } else {
-            self.horizontal_counter += 1;
-            self.memory_address = (self.memory_address + 1) & 0x3FFF; // Wrap at 14 bits.
+            assert_eq!(0x00, read_register(&mut crtc, 12));
+            assert_eq!(0x00, read_register(&mut crtc, 13));
         }
 
-        if self.at_end_of_horizontal_displayed() {
-            //println!("End of horizontal displayed");
-            self.horizontal_display_enable = false;
+        write_register(&mut crtc, 8, 0xFF);
+        assert_eq!(r8_mask, unsafe { crtc.registers.names.interlace_mode_and_skew });
+    }
 
-            // Keep track of which memory address we've reached,
-            // so we can restart from here in the next scanline.
-            self.memory_address_stored = self.memory_address;
+    #[test_case(M6845Variant::Hd6845s, 0, 2 ; "hd6845s")]
+    #[test_case(M6845Variant::Um6845r, 0, 16 ; "um6845r")]
+    #[test_case(M6845Variant::Mc6845, 16, 16 ; "mc6845")]
+    #[test_case(M6845Variant::Ams40489, 16, 2 ; "ams40489")]
+    #[test_case(M6845Variant::Ams40226, 16, 2 ; "ams40226")]
+    #[allow(clippy::unused_unit)]
+    fn variant_sync_widths(variant: M6845Variant, horizontal_sync_width: usize, vertical_sync_width: usize) {
+        let mut crtc = M6845::new_with_options(M6845Options { variant });
+        small_screen(&mut crtc);
+        write_register(&mut crtc, 0, 39);    // Horizontal total
+        write_register(&mut crtc, 2, 20);    // Horizontal sync position
+        write_register(&mut crtc, 3, 0x20);  // Sync widths
+        write_register(&mut crtc, 4, 15);    // Vertical total
+        write_register(&mut crtc, 9, 1);     // Max raster address
+
+        // Count the characters in one scanline, and the scanlines in one frame.
+        start_frame(&mut crtc);
+        let mut horizontal_sync = 0;
+        for _ in 0..40 {
+            tick_character(&mut crtc);
+            horizontal_sync += crtc.pins.hs as usize;
+        }
+        let mut vertical_sync = 0;
+        for _ in 0..32 {
+            vertical_sync += crtc.pins.vs as usize;
+            for _ in 0..40 {
+                tick_character(&mut crtc);
+            }
         }
 
-        if self.at_start_of_horizontal_sync() {
-           // println!("Start of horizontal sync");
-            self.horizontal_sync = true;
-            self.horizontal_sync_counter = 0;
+        assert_eq!(horizontal_sync_width, horizontal_sync);
+        assert_eq!(vertical_sync_width, vertical_sync);
+    }
+
+    #[test_case(M6845Variant::Hd6845s, 0x100 ; "hd6845s")]
+    #[test_case(M6845Variant::Um6845r, 0x200 ; "um6845r")]
+    #[allow(clippy::unused_unit)]
+    fn start_address_change_in_first_row(variant: M6845Variant, second_scanline_address: u16) {
+        let mut crtc = M6845::new_with_options(M6845Options { variant });
+        small_screen(&mut crtc);
+        start_frame(&mut crtc);
+
+        write_register(&mut crtc, 12, 0x02);
+        for _ in 0..10 {
+            tick_character(&mut crtc);
         }
 
-        if self.horizontal_sync {
-            if self.at_end_of_horizontal_sync() {
-                //println!("End of horizontal sync");
-                self.horizontal_sync = false;
-            } else {
-                self.horizontal_sync_counter += 1;
+        assert_eq!(second_scanline_address, crtc.pins.ma);
+        assert_eq!(1, crtc.pins.ra);
+    }
+
+    #[test_case(M6845Variant::Hd6845s, 4 + 4 * 2 * 4 ; "hd6845s")]
+    #[test_case(M6845Variant::Um6845r, 0 ; "um6845r")]
+    #[allow(clippy::unused_unit)]
+    fn vertical_displayed_change_part_way_through_row(variant: M6845Variant, displayed_characters: usize) {
+        let mut crtc = M6845::new_with_options(M6845Options { variant });
+        small_screen(&mut crtc);
+        start_frame(&mut crtc);
+
+        // Go to the middle of the second row, and try to end the display there. The HD6845S has already
+        // checked R6 for this row, and doesn't check it again, so it carries on to the end of the frame.
+        for _ in 0..25 {
+            tick_character(&mut crtc);
+        }
+        write_register(&mut crtc, 6, 1);
+
+        let mut displayed = 0;
+        loop {
+            tick_character(&mut crtc);
+            if crtc.horizontal_counter == 0 && crtc.vertical_counter == 0 && crtc.raster_address == 0 {
+                break;
             }
+            displayed += crtc.pins.disptmg as usize;
         }
+        assert_eq!(displayed_characters, displayed);
+    }
 
-        // Set pins.
-        self.pins.hs = self.horizontal_sync;
-        self.pins.vs = self.vertical_sync;
-        self.pins.disptmg = self.horizontal_display_enable && self.vertical_display_enable;
-        self.pins.ma = self.memory_address;
-        self.pins.ra = self.raster_address;
+    #[test]
+    fn light_pen() {
+        let mut crtc = M6845::new();
+        small_screen(&mut crtc);
+        start_frame(&mut crtc);
+
+        // Move to the third character of the second row.
+        for _ in 0..(2 * 10 + 2) {
+            tick_character(&mut crtc);
+        }
+        assert_eq!(0x106, crtc.pins.ma);
+
+        crtc.pins.lpstb = true;
+        crtc.tick();
+        tick_character(&mut crtc);
+        crtc.pins.lpstb = false;
+
+        assert_eq!(0x01, read_register(&mut crtc, 16));
+        assert_eq!(0x06, read_register(&mut crtc, 17));
+
+        // The light pen registers can't be written.
+        write_register(&mut crtc, 16, 0x12);
+        assert_eq!(0x01, read_register(&mut crtc, 16));
     }
-}
\ No newline at end of file
+}
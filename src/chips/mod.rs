@@ -1,6 +1,11 @@
 pub mod m6502;
 pub mod m6507;
+pub mod m6510;
 pub mod m6522;
 pub mod m6532;
 pub mod m6845;
-pub mod saa5050;
\ No newline at end of file
+pub mod rp2a03;
+pub mod saa5050;
+#[cfg(test)]
+mod test_bus;
+pub mod w65c816;
\ No newline at end of file
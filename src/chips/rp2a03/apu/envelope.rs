@@ -0,0 +1,58 @@
+/// Produces the volume for the pulse and noise channels: either a constant,
+/// or a sawtooth that decays from 15 to 0. Clocked by the frame counter's quarter frames.
+pub struct Envelope {
+    start: bool,
+    looping: bool,
+    constant_volume: bool,
+
+    /// The constant volume, and also the period of the divider.
+    volume: u8,
+
+    divider: u8,
+    decay_level: u8,
+}
+
+impl Envelope {
+    pub fn new() -> Self {
+        Self {
+            start: false,
+            looping: false,
+            constant_volume: false,
+            volume: 0,
+            divider: 0,
+            decay_level: 0,
+        }
+    }
+
+    /// Handles a write to the channel's first register (`--LC VVVV`).
+    pub fn write_control(&mut self, value: u8) {
+        self.looping = value & 0x20 != 0;
+        self.constant_volume = value & 0x10 != 0;
+        self.volume = value & 0x0F;
+    }
+
+    pub fn restart(&mut self) {
+        self.start = true;
+    }
+
+    pub fn clock(&mut self) {
+        if self.start {
+            self.start = false;
+            self.decay_level = 15;
+            self.divider = self.volume;
+        } else if self.divider > 0 {
+            self.divider -= 1;
+        } else {
+            self.divider = self.volume;
+            if self.decay_level > 0 {
+                self.decay_level -= 1;
+            } else if self.looping {
+                self.decay_level = 15;
+            }
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        if self.constant_volume { self.volume } else { self.decay_level }
+    }
+}
@@ -0,0 +1,107 @@
+/// The frame counter's steps, in CPU cycles since it was last reset, for NTSC.
+const QUARTER_FRAME_1: u32 = 7457;
+const HALF_FRAME_1: u32 = 14913;
+const QUARTER_FRAME_3: u32 = 22371;
+const FOUR_STEP_IRQ: u32 = 29828;
+const FOUR_STEP_HALF_FRAME: u32 = 29829;
+const FOUR_STEP_END: u32 = 29830;
+const FIVE_STEP_HALF_FRAME: u32 = 37281;
+const FIVE_STEP_END: u32 = 37282;
+
+/// Which units to clock in a cycle.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct FrameClocks {
+    /// Envelopes and the triangle's linear counter.
+    pub quarter: bool,
+
+    /// Length counters and sweep units.
+    pub half: bool,
+}
+
+/// Generates the quarter and half frame clocks, and the frame IRQ, from $4017.
+pub struct FrameCounter {
+    five_step: bool,
+    irq_inhibit: bool,
+    pub irq: bool,
+
+    cycle: u32,
+
+    /// A write to $4017 resets the sequence a few cycles later.
+    pending_write: Option<(u8, u8)>,
+}
+
+impl FrameCounter {
+    pub fn new() -> Self {
+        Self {
+            five_step: false,
+            irq_inhibit: false,
+            irq: false,
+            cycle: 0,
+            pending_write: None,
+        }
+    }
+
+    /// `odd_cycle` is true if the write happens between APU cycles, which delays it by one more CPU cycle.
+    pub fn write(&mut self, value: u8, odd_cycle: bool) {
+        self.irq_inhibit = value & 0x40 != 0;
+        if self.irq_inhibit {
+            self.irq = false;
+        }
+
+        let delay = if odd_cycle { 4 } else { 3 };
+        self.pending_write = Some((value, delay));
+    }
+
+    /// Clocked every CPU cycle.
+    pub fn clock(&mut self) -> FrameClocks {
+        let mut clocks = FrameClocks::default();
+        self.cycle += 1;
+
+        if self.five_step {
+            match self.cycle {
+                QUARTER_FRAME_1 | QUARTER_FRAME_3 => clocks.quarter = true,
+                HALF_FRAME_1 | FIVE_STEP_HALF_FRAME => clocks = FrameClocks { quarter: true, half: true },
+                FIVE_STEP_END => self.cycle = 0,
+                _ => {},
+            }
+        } else {
+            match self.cycle {
+                QUARTER_FRAME_1 | QUARTER_FRAME_3 => clocks.quarter = true,
+                HALF_FRAME_1 => clocks = FrameClocks { quarter: true, half: true },
+                FOUR_STEP_IRQ => self.set_irq(),
+                FOUR_STEP_HALF_FRAME => {
+                    clocks = FrameClocks { quarter: true, half: true };
+                    self.set_irq();
+                },
+                FOUR_STEP_END => {
+                    self.set_irq();
+                    self.cycle = 0;
+                },
+                _ => {},
+            }
+        }
+
+        if let Some((value, delay)) = self.pending_write {
+            if delay > 1 {
+                self.pending_write = Some((value, delay - 1));
+            } else {
+                self.pending_write = None;
+                self.cycle = 0;
+                self.five_step = value & 0x80 != 0;
+
+                // Switching to five steps clocks everything straight away.
+                if self.five_step {
+                    clocks = FrameClocks { quarter: true, half: true };
+                }
+            }
+        }
+
+        clocks
+    }
+
+    fn set_irq(&mut self) {
+        if !self.irq_inhibit {
+            self.irq = true;
+        }
+    }
+}
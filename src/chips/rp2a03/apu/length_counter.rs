@@ -0,0 +1,69 @@
+const LENGTH_TABLE: [u8; 32] = [
+    10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14,
+    12, 16, 24, 18, 48, 20, 96, 22, 192, 24, 72, 26, 16, 28, 32, 30,
+];
+
+/// Silences a channel once it has counted down to 0. Clocked by the frame counter's half frames.
+pub struct LengthCounter {
+    enabled: bool,
+    halt: bool,
+    counter: u8,
+
+    /// Writes to the halt flag and length take effect at the end of the cycle,
+    /// after the frame counter has had a chance to clock the counter.
+    new_halt: bool,
+    reload_value: Option<u8>,
+    previous_counter: u8,
+}
+
+impl LengthCounter {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            halt: false,
+            counter: 0,
+            new_halt: false,
+            reload_value: None,
+            previous_counter: 0,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.counter > 0
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.counter = 0;
+        }
+    }
+
+    pub fn set_halt(&mut self, halt: bool) {
+        self.new_halt = halt;
+    }
+
+    /// Loads the counter from the top five bits of a length register write.
+    pub fn load(&mut self, value: u8) {
+        if self.enabled {
+            self.reload_value = Some(LENGTH_TABLE[(value >> 3) as usize]);
+            self.previous_counter = self.counter;
+        }
+    }
+
+    pub fn clock(&mut self) {
+        if self.counter > 0 && !self.halt {
+            self.counter -= 1;
+        }
+    }
+
+    pub fn end_cycle(&mut self) {
+        // A reload in the same cycle as the counter is clocked is ignored, unless the counter was already 0.
+        if let Some(value) = self.reload_value.take() {
+            if self.counter == self.previous_counter {
+                self.counter = value;
+            }
+        }
+        self.halt = self.new_halt;
+    }
+}
@@ -0,0 +1,176 @@
+mod dmc;
+mod envelope;
+mod frame_counter;
+mod length_counter;
+mod noise;
+mod pulse;
+mod triangle;
+
+use dmc::Dmc;
+use frame_counter::FrameCounter;
+use noise::Noise;
+use pulse::Pulse;
+use triangle::Triangle;
+
+/// CPU clock rate of an NTSC NES, in Hz.
+pub const CPU_CLOCK_RATE: f64 = 1_789_773.0;
+
+/// The audio processing unit, with its registers at $4000-$4013, $4015 and $4017.
+pub struct Apu {
+    pulse1: Pulse,
+    pulse2: Pulse,
+    triangle: Triangle,
+    noise: Noise,
+    dmc: Dmc,
+    frame_counter: FrameCounter,
+
+    /// Pulse and noise timers are clocked on every other CPU cycle.
+    odd_cycle: bool,
+
+    cycles_per_sample: f64,
+    sample_clock: f64,
+    sample_sum: f32,
+    sample_count: u32,
+    samples: Vec<f32>,
+}
+
+impl Apu {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            pulse1: Pulse::new(true),
+            pulse2: Pulse::new(false),
+            triangle: Triangle::new(),
+            noise: Noise::new(),
+            dmc: Dmc::new(),
+            frame_counter: FrameCounter::new(),
+            odd_cycle: false,
+            cycles_per_sample: CPU_CLOCK_RATE / sample_rate as f64,
+            sample_clock: 0.0,
+            sample_sum: 0.0,
+            sample_count: 0,
+            samples: Vec::new(),
+        }
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) {
+        match address {
+            0x4000..=0x4003 => self.pulse1.write(address & 0x03, value),
+            0x4004..=0x4007 => self.pulse2.write(address & 0x03, value),
+            0x4008..=0x400B => self.triangle.write(address & 0x03, value),
+            0x400C..=0x400F => self.noise.write(address & 0x03, value),
+            0x4010..=0x4013 => self.dmc.write(address & 0x03, value),
+            0x4015 => {
+                self.pulse1.length_counter.set_enabled(value & 0x01 != 0);
+                self.pulse2.length_counter.set_enabled(value & 0x02 != 0);
+                self.triangle.length_counter.set_enabled(value & 0x04 != 0);
+                self.noise.length_counter.set_enabled(value & 0x08 != 0);
+                self.dmc.set_enabled(value & 0x10 != 0);
+                self.dmc.irq = false;
+            },
+            0x4017 => self.frame_counter.write(value, self.odd_cycle),
+            _ => {},
+        }
+    }
+
+    /// Reads $4015, which also acknowledges the frame IRQ.
+    pub fn read_status(&mut self) -> u8 {
+        let value = self.peek_status();
+        self.frame_counter.irq = false;
+        value
+    }
+
+    pub fn peek_status(&self) -> u8 {
+        (self.pulse1.length_counter.is_active() as u8)
+            | (self.pulse2.length_counter.is_active() as u8) << 1
+            | (self.triangle.length_counter.is_active() as u8) << 2
+            | (self.noise.length_counter.is_active() as u8) << 3
+            | (self.dmc.is_active() as u8) << 4
+            | (self.frame_counter.irq as u8) << 6
+            | (self.dmc.irq as u8) << 7
+    }
+
+    /// True while the frame counter or DMC is asking for an interrupt.
+    pub fn irq(&self) -> bool {
+        self.frame_counter.irq || self.dmc.irq
+    }
+
+    /// The address that the DMC wants to fetch its next sample byte from.
+    pub fn dmc_dma_address(&self) -> Option<u16> {
+        self.dmc.dma_address()
+    }
+
+    pub fn fill_dmc_sample_buffer(&mut self, value: u8) {
+        self.dmc.fill_sample_buffer(value);
+    }
+
+    /// Returns true if the current CPU cycle is between APU cycles.
+    pub fn is_odd_cycle(&self) -> bool {
+        self.odd_cycle
+    }
+
+    /// Runs one CPU cycle.
+    pub fn clock(&mut self) {
+        let clocks = self.frame_counter.clock();
+        if clocks.quarter {
+            self.pulse1.envelope.clock();
+            self.pulse2.envelope.clock();
+            self.triangle.clock_linear_counter();
+            self.noise.envelope.clock();
+        }
+        if clocks.half {
+            self.pulse1.length_counter.clock();
+            self.pulse1.clock_sweep();
+            self.pulse2.length_counter.clock();
+            self.pulse2.clock_sweep();
+            self.triangle.length_counter.clock();
+            self.noise.length_counter.clock();
+        }
+
+        self.triangle.clock_timer();
+        self.noise.clock_timer();
+        self.dmc.clock_timer();
+        if self.odd_cycle {
+            self.pulse1.clock_timer();
+            self.pulse2.clock_timer();
+        }
+
+        self.pulse1.length_counter.end_cycle();
+        self.pulse2.length_counter.end_cycle();
+        self.triangle.length_counter.end_cycle();
+        self.noise.length_counter.end_cycle();
+
+        self.odd_cycle = !self.odd_cycle;
+
+        self.output_sample();
+    }
+
+    /// Averages the mixer output over each sample period.
+    fn output_sample(&mut self) {
+        self.sample_sum += self.mix();
+        self.sample_count += 1;
+
+        self.sample_clock += 1.0;
+        if self.sample_clock >= self.cycles_per_sample {
+            self.sample_clock -= self.cycles_per_sample;
+            self.samples.push(self.sample_sum / self.sample_count as f32);
+            self.sample_sum = 0.0;
+            self.sample_count = 0;
+        }
+    }
+
+    /// The non-linear mixer, using the approximations from the NESdev wiki. Returns a value from 0.0 to 1.0.
+    fn mix(&self) -> f32 {
+        let pulse = (self.pulse1.output() + self.pulse2.output()) as f32;
+        let pulse_out = if pulse == 0.0 { 0.0 } else { 95.88 / (8128.0 / pulse + 100.0) };
+
+        let tnd = self.triangle.output() as f32 / 8227.0 + self.noise.output() as f32 / 12241.0 + self.dmc.output() as f32 / 22638.0;
+        let tnd_out = if tnd == 0.0 { 0.0 } else { 159.79 / (1.0 / tnd + 100.0) };
+
+        pulse_out + tnd_out
+    }
+
+    /// Takes the samples produced since the last call.
+    pub fn take_samples(&mut self) -> Vec<f32> {
+        std::mem::take(&mut self.samples)
+    }
+}
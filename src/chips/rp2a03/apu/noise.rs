@@ -0,0 +1,73 @@
+use super::envelope::Envelope;
+use super::length_counter::LengthCounter;
+
+/// Timer periods in CPU cycles, for NTSC.
+const PERIOD_TABLE: [u16; 16] = [
+    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
+];
+
+/// The pseudo-random noise channel, at $400C-$400F.
+pub struct Noise {
+    /// Short mode takes its feedback from bit 6, for a 93-step sequence.
+    short_mode: bool,
+    shift_register: u16,
+
+    period: u16,
+    timer: u16,
+
+    pub envelope: Envelope,
+    pub length_counter: LengthCounter,
+}
+
+impl Noise {
+    pub fn new() -> Self {
+        Self {
+            short_mode: false,
+            shift_register: 1,
+            period: PERIOD_TABLE[0],
+            timer: 0,
+            envelope: Envelope::new(),
+            length_counter: LengthCounter::new(),
+        }
+    }
+
+    pub fn write(&mut self, register: u16, value: u8) {
+        match register {
+            0 => {
+                self.length_counter.set_halt(value & 0x20 != 0);
+                self.envelope.write_control(value);
+            },
+            1 => {},
+            2 => {
+                self.short_mode = value & 0x80 != 0;
+                self.period = PERIOD_TABLE[(value & 0x0F) as usize];
+            },
+            3 => {
+                self.length_counter.load(value);
+                self.envelope.restart();
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    /// Clocked every CPU cycle.
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.period - 1;
+
+            let tap = if self.short_mode { 6 } else { 1 };
+            let feedback = (self.shift_register ^ (self.shift_register >> tap)) & 1;
+            self.shift_register = (self.shift_register >> 1) | (feedback << 14);
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        if self.shift_register & 1 != 0 || !self.length_counter.is_active() {
+            0
+        } else {
+            self.envelope.output()
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use super::envelope::Envelope;
+use super::length_counter::LengthCounter;
+
+const DUTY_TABLE: [[u8; 8]; 4] = [
+    [0, 1, 0, 0, 0, 0, 0, 0],
+    [0, 1, 1, 0, 0, 0, 0, 0],
+    [0, 1, 1, 1, 1, 0, 0, 0],
+    [1, 0, 0, 1, 1, 1, 1, 1],
+];
+
+/// One of the two square wave channels, at $4000-$4003 and $4004-$4007.
+pub struct Pulse {
+    /// The first channel negates its sweep with ones' complement, so sweeps down by one more than the second.
+    ones_complement: bool,
+
+    duty: u8,
+    sequence_step: u8,
+
+    /// 11-bit timer period, in APU cycles.
+    period: u16,
+    timer: u16,
+
+    sweep_enabled: bool,
+    sweep_period: u8,
+    sweep_negate: bool,
+    sweep_shift: u8,
+    sweep_reload: bool,
+    sweep_divider: u8,
+
+    pub envelope: Envelope,
+    pub length_counter: LengthCounter,
+}
+
+impl Pulse {
+    pub fn new(ones_complement: bool) -> Self {
+        Self {
+            ones_complement,
+            duty: 0,
+            sequence_step: 0,
+            period: 0,
+            timer: 0,
+            sweep_enabled: false,
+            sweep_period: 0,
+            sweep_negate: false,
+            sweep_shift: 0,
+            sweep_reload: false,
+            sweep_divider: 0,
+            envelope: Envelope::new(),
+            length_counter: LengthCounter::new(),
+        }
+    }
+
+    pub fn write(&mut self, register: u16, value: u8) {
+        match register {
+            0 => {
+                self.duty = value >> 6;
+                self.length_counter.set_halt(value & 0x20 != 0);
+                self.envelope.write_control(value);
+            },
+            1 => {
+                self.sweep_enabled = value & 0x80 != 0;
+                self.sweep_period = (value >> 4) & 0x07;
+                self.sweep_negate = value & 0x08 != 0;
+                self.sweep_shift = value & 0x07;
+                self.sweep_reload = true;
+            },
+            2 => {
+                self.period = (self.period & 0x0700) | value as u16;
+            },
+            3 => {
+                self.period = (self.period & 0x00FF) | ((value as u16 & 0x07) << 8);
+                self.length_counter.load(value);
+                self.sequence_step = 0;
+                self.envelope.restart();
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    /// Clocked every APU cycle, which is every other CPU cycle.
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.period;
+            self.sequence_step = self.sequence_step.wrapping_sub(1) & 0x07;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn sweep_target(&self) -> u16 {
+        let change = self.period >> self.sweep_shift;
+        if !self.sweep_negate {
+            self.period + change
+        } else if self.ones_complement {
+            self.period.saturating_sub(change + 1)
+        } else {
+            self.period.saturating_sub(change)
+        }
+    }
+
+    /// The sweep unit silences the channel when the period is out of range, even when it is disabled.
+    fn is_muted(&self) -> bool {
+        self.period < 8 || self.sweep_target() > 0x07FF
+    }
+
+    pub fn clock_sweep(&mut self) {
+        if self.sweep_divider == 0 && self.sweep_enabled && self.sweep_shift > 0 && !self.is_muted() {
+            self.period = self.sweep_target();
+        }
+
+        if self.sweep_divider == 0 || self.sweep_reload {
+            self.sweep_divider = self.sweep_period;
+            self.sweep_reload = false;
+        } else {
+            self.sweep_divider -= 1;
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        if DUTY_TABLE[self.duty as usize][self.sequence_step as usize] == 0
+            || !self.length_counter.is_active()
+            || self.is_muted() {
+            0
+        } else {
+            self.envelope.output()
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use super::length_counter::LengthCounter;
+
+const SEQUENCE: [u8; 32] = [
+    15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0,
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+];
+
+/// The triangle wave channel, at $4008-$400B.
+pub struct Triangle {
+    /// Also halts the length counter.
+    control: bool,
+
+    linear_counter: u8,
+    linear_reload_value: u8,
+    linear_reload: bool,
+
+    /// 11-bit timer period, in CPU cycles.
+    period: u16,
+    timer: u16,
+    sequence_step: u8,
+
+    pub length_counter: LengthCounter,
+}
+
+impl Triangle {
+    pub fn new() -> Self {
+        Self {
+            control: false,
+            linear_counter: 0,
+            linear_reload_value: 0,
+            linear_reload: false,
+            period: 0,
+            timer: 0,
+            sequence_step: 0,
+            length_counter: LengthCounter::new(),
+        }
+    }
+
+    pub fn write(&mut self, register: u16, value: u8) {
+        match register {
+            0 => {
+                self.control = value & 0x80 != 0;
+                self.length_counter.set_halt(self.control);
+                self.linear_reload_value = value & 0x7F;
+            },
+            1 => {},
+            2 => {
+                self.period = (self.period & 0x0700) | value as u16;
+            },
+            3 => {
+                self.period = (self.period & 0x00FF) | ((value as u16 & 0x07) << 8);
+                self.length_counter.load(value);
+                self.linear_reload = true;
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    /// Clocked every CPU cycle.
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.period;
+            if self.linear_counter > 0 && self.length_counter.is_active() {
+                self.sequence_step = (self.sequence_step + 1) & 0x1F;
+            }
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    pub fn clock_linear_counter(&mut self) {
+        if self.linear_reload {
+            self.linear_counter = self.linear_reload_value;
+        } else if self.linear_counter > 0 {
+            self.linear_counter -= 1;
+        }
+
+        if !self.control {
+            self.linear_reload = false;
+        }
+    }
+
+    /// The sequencer stops where it is, rather than returning to 0, so silencing the channel doesn't pop.
+    pub fn output(&self) -> u8 {
+        SEQUENCE[self.sequence_step as usize]
+    }
+}
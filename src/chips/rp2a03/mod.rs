@@ -0,0 +1,548 @@
+use std::io;
+
+use super::m6502::{Bus, M6502, M6502Options, M6502Variant, Tracer};
+
+mod apu;
+
+pub use apu::CPU_CLOCK_RATE;
+use apu::Apu;
+
+/// Writing a page number here copies that page to the PPU's OAM, through $2004.
+const OAM_DMA_ADDRESS: u16 = 0x4014;
+const OAM_DATA_ADDRESS: u16 = 0x2004;
+
+pub struct Rp2a03Options {
+    /// Rate at which audio samples are produced, in Hz.
+    pub sample_rate: u32,
+}
+
+impl Default for Rp2a03Options {
+    fn default() -> Self {
+        Self {
+            sample_rate: 44_100,
+        }
+    }
+}
+
+/// Ricoh 2A03, the CPU in the NTSC NES. A 6502 core without decimal mode,
+/// with the APU and the OAM and DMC DMA units on the same die.
+pub struct Rp2a03 {
+    inner: M6502,
+    apu: Apu,
+    dma: Dma,
+
+    /// The IRQ pin, as driven by the rest of the system (active low).
+    irq: bool,
+}
+
+impl Rp2a03 {
+    pub fn new() -> Self {
+        Rp2a03::new_with_options(Rp2a03Options::default())
+    }
+
+    pub fn new_with_options(options: Rp2a03Options) -> Self {
+        Self {
+            inner: M6502::new_with_options(M6502Options {
+                bcd_enabled: false,
+                variant: M6502Variant::Nmos6502,
+            }),
+            apu: Apu::new(options.sample_rate),
+            dma: Dma::new(),
+            irq: true,
+        }
+    }
+
+    /// The 6502 core, for its registers and pins.
+    pub fn cpu(&self) -> &M6502 {
+        &self.inner
+    }
+
+    pub fn cpu_mut(&mut self) -> &mut M6502 {
+        &mut self.inner
+    }
+
+    /// RESET also silences the APU.
+    pub fn set_pin_res(&mut self, value: bool) {
+        if !value {
+            self.apu.write(0x4015, 0x00);
+        }
+        self.inner.set_res(value);
+    }
+
+    pub fn set_pin_irq(&mut self, value: bool) {
+        self.irq = value;
+        self.update_irq();
+    }
+
+    pub fn set_pin_nmi(&mut self, value: bool) {
+        self.inner.set_nmi(value);
+    }
+
+    fn update_irq(&mut self) {
+        self.inner.set_irq(self.irq && !self.apu.irq());
+    }
+
+    /// Runs a single clock cycle. Either the CPU or a DMA transfer uses the bus.
+    pub fn step_cycle(&mut self, bus: &mut impl Bus) {
+        // The length of a transfer depends on how it lines up with the APU, so DMA asks for one cycle at a time.
+        if self.is_dma_active() && self.inner.dma_cycles_remaining() == 0 {
+            self.inner.request_dma(1);
+        }
+
+        self.inner.step_cycle(&mut IoBus { apu: &mut self.apu, dma: &mut self.dma, bus });
+        if !self.inner.is_dma_cycle() {
+            self.dma.halted = false;
+        }
+
+        self.apu.clock();
+        self.update_irq();
+    }
+
+    /// Runs clock cycles until the CPU is about to fetch the next opcode,
+    /// or has been jammed. Returns the number of cycles that were run, including any taken by DMA.
+    pub fn step_instruction(&mut self, bus: &mut impl Bus) -> u32 {
+        let mut cycles = 0;
+
+        loop {
+            self.step_cycle(bus);
+            cycles += 1;
+
+            if (self.inner.sync() && !self.is_dma_active()) || self.inner.is_halted() {
+                return cycles;
+            }
+        }
+    }
+
+    fn is_dma_active(&self) -> bool {
+        self.dma.oam_page.is_some() || self.apu.dmc_dma_address().is_some()
+    }
+
+    /// Takes the audio samples produced since the last call, as values from 0.0 to 1.0.
+    pub fn take_audio_samples(&mut self) -> Vec<f32> {
+        self.apu.take_samples()
+    }
+
+    pub fn is_jammed(&self) -> bool {
+        self.inner.is_jammed()
+    }
+
+    pub fn trace<W: io::Write>(&self, tracer: &mut Tracer<W>) -> io::Result<()> {
+        tracer.trace(&self.inner)
+    }
+}
+
+impl Default for Rp2a03 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct Dma {
+    /// Set once the CPU has been halted for a transfer.
+    halted: bool,
+
+    /// The page being copied by OAM DMA.
+    oam_page: Option<u8>,
+    oam_index: u8,
+    oam_value: Option<u8>,
+
+    dmc_dummy_done: bool,
+}
+
+impl Dma {
+    fn new() -> Self {
+        Self {
+            halted: false,
+            oam_page: None,
+            oam_index: 0,
+            oam_value: None,
+            dmc_dummy_done: false,
+        }
+    }
+}
+
+/// Puts the APU and DMA registers in front of the bus. The controller ports,
+/// which share $4016 and $4017 with the APU, are left to the bus.
+struct IoBus<'a, B: Bus> {
+    apu: &'a mut Apu,
+    dma: &'a mut Dma,
+    bus: &'a mut B,
+}
+
+impl<B: Bus> Bus for IoBus<'_, B> {
+    fn read(&mut self, address: u16) -> u8 {
+        match address {
+            0x4015 => self.apu.read_status(),
+            _ => self.bus.read(address),
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        match address {
+            0x4000..=0x4013 | 0x4015 | 0x4017 => self.apu.write(address, value),
+            OAM_DMA_ADDRESS => self.dma.oam_page = Some(value),
+            _ => self.bus.write(address, value),
+        }
+    }
+
+    fn peek(&self, address: u16) -> u8 {
+        match address {
+            0x4015 => self.apu.peek_status(),
+            _ => self.bus.peek(address),
+        }
+    }
+
+    /// DMA reads happen on APU get cycles, and writes on put cycles. The first cycle halts the CPU,
+    /// and reads from wherever it was reading, as do any cycles spent waiting for the right kind of cycle.
+    fn dma_cycle(&mut self, cpu: &M6502) {
+        let cpu_address = cpu.get_address();
+        let get_cycle = !self.apu.is_odd_cycle();
+        let dmc_address = self.apu.dmc_dma_address();
+
+        if !self.dma.halted {
+            self.dma.halted = true;
+            self.read(cpu_address);
+        } else if dmc_address.is_some() && !self.dma.dmc_dummy_done && self.dma.oam_page.is_none() {
+            // On its own, DMC DMA spends a cycle before it can fetch.
+            self.dma.dmc_dummy_done = true;
+            self.read(cpu_address);
+        } else if get_cycle {
+            if let Some(address) = dmc_address {
+                let value = self.read(address);
+                self.apu.fill_dmc_sample_buffer(value);
+                self.dma.dmc_dummy_done = false;
+            } else if let (Some(page), None) = (self.dma.oam_page, self.dma.oam_value) {
+                self.dma.oam_value = Some(self.read(u16::from_le_bytes([self.dma.oam_index, page])));
+            } else {
+                self.read(cpu_address);
+            }
+        } else if let Some(value) = self.dma.oam_value.take() {
+            self.bus.write(OAM_DATA_ADDRESS, value);
+            self.dma.oam_index = self.dma.oam_index.wrapping_add(1);
+            if self.dma.oam_index == 0 {
+                self.dma.oam_page = None;
+            }
+        } else {
+            self.read(cpu_address);
+        }
+    }
+
+    fn on_cycle(&mut self, cpu: &M6502) {
+        self.bus.on_cycle(cpu);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, path::Path};
+    use super::super::m6502::assemble;
+    use super::super::test_bus::{Access, Ram};
+    use test_case::test_case;
+
+    const ASSET_PATH: &str = "test_assets/chips/rp2a03";
+
+    /// Assembles a program at $8000, with IRQs counted at $00, and resets the CPU.
+    fn setup(program: &str) -> (Rp2a03, Ram) {
+        let program = assemble(&format!("
+                .org $8000
+                {}
+            done:
+                JMP done
+
+            irq:
+                INC $00
+                BIT $4015       ; Acknowledge the frame IRQ
+                LDA #$00        ; and the DMC IRQ
+                STA $4010
+                RTI
+
+                .org $FFFC
+                .word $8000
+                .word irq
+        ", program)).unwrap();
+
+        let mut ram = Ram::new().with_access_log();
+        program.load_into(&mut ram.data);
+
+        let mut cpu = Rp2a03::new();
+        cpu.set_pin_res(false);
+        cpu.set_pin_res(true);
+        cpu.step_instruction(&mut ram);
+        (cpu, ram)
+    }
+
+    fn run_cycles(cpu: &mut Rp2a03, ram: &mut Ram, cycles: u32) {
+        for _ in 0..cycles {
+            cpu.step_cycle(ram);
+        }
+    }
+
+    #[test]
+    fn length_counter_silences_channel() {
+        let (mut cpu, mut ram) = setup("
+                LDA #$40        ; No frame IRQ
+                STA $4017
+                LDA #$01
+                STA $4015
+                LDA #$18        ; Length of 2
+                STA $4003
+        ");
+
+        run_cycles(&mut cpu, &mut ram, 20);
+        assert_eq!(0x01, cpu.apu.peek_status());
+
+        // Each four step frame clocks the length counter twice.
+        run_cycles(&mut cpu, &mut ram, 29830);
+        assert_eq!(0x00, cpu.apu.peek_status());
+    }
+
+    #[test]
+    fn length_counter_is_not_loaded_while_disabled() {
+        let (mut cpu, mut ram) = setup("
+                LDA #$18
+                STA $4003
+        ");
+
+        run_cycles(&mut cpu, &mut ram, 10);
+        assert_eq!(0x00, cpu.apu.peek_status() & 0x01);
+    }
+
+    #[test]
+    fn frame_irq() {
+        let (mut cpu, mut ram) = setup("
+                CLI
+                LDA #$00
+                STA $4017
+        ");
+
+        run_cycles(&mut cpu, &mut ram, 29000);
+        assert_eq!(0, ram.data[0x00]);
+
+        run_cycles(&mut cpu, &mut ram, 1000);
+        assert_eq!(1, ram.data[0x00]);
+        assert_eq!(0x00, cpu.apu.peek_status() & 0x40);
+    }
+
+    #[test]
+    fn frame_irq_is_inhibited() {
+        let (mut cpu, mut ram) = setup("
+                CLI
+                LDA #$40
+                STA $4017
+        ");
+
+        run_cycles(&mut cpu, &mut ram, 100_000);
+        assert_eq!(0, ram.data[0x00]);
+        assert_eq!(0x00, cpu.apu.peek_status() & 0x40);
+    }
+
+    #[test]
+    fn five_step_mode_has_no_frame_irq() {
+        let (mut cpu, mut ram) = setup("
+                CLI
+                LDA #$80
+                STA $4017
+        ");
+
+        run_cycles(&mut cpu, &mut ram, 100_000);
+        assert_eq!(0, ram.data[0x00]);
+    }
+
+    #[test]
+    fn oam_dma() {
+        let (mut cpu, mut ram) = setup("
+                LDA #$02
+                STA $4014
+        ");
+        for i in 0..256 {
+            ram.data[0x0200 + i] = i as u8 ^ 0x5A;
+        }
+
+        cpu.step_instruction(&mut ram);
+        let cycles = cpu.step_instruction(&mut ram);
+
+        // STA abs takes 4 cycles, and DMA takes 513 or 514 more, depending on alignment.
+        assert!(cycles == 4 + 513 || cycles == 4 + 514, "Took {} cycles", cycles);
+        let oam_writes: Vec<u8> = ram.accesses.iter().filter_map(|access| match *access {
+            Access::Write(address, value) if address == OAM_DATA_ADDRESS as u32 => Some(value),
+            _ => None,
+        }).collect();
+        assert_eq!((0..=255).map(|i| i ^ 0x5A).collect::<Vec<u8>>(), oam_writes);
+    }
+
+    #[test]
+    fn dmc_fetches_sample_with_dma() {
+        let (mut cpu, mut ram) = setup("
+                CLI
+                LDA #$40
+                STA $4017
+                LDA #$8F        ; IRQ enabled, fastest rate
+                STA $4010
+                LDA #$01        ; $C040
+                STA $4012
+                LDA #$01        ; 17 bytes
+                STA $4013
+                LDA #$10
+                STA $4015
+        ");
+
+        run_cycles(&mut cpu, &mut ram, 100);
+        assert_eq!(0x10, cpu.apu.peek_status() & 0x90);
+        assert!(ram.accesses.contains(&Access::Read(0xC040)));
+
+        // The buffer is refilled every 8 output bits, 54 cycles each.
+        run_cycles(&mut cpu, &mut ram, 17 * 8 * 54);
+        assert_eq!(0x00, cpu.apu.peek_status() & 0x10);
+        assert_eq!(1, ram.data[0x00]);
+        assert!(ram.accesses.contains(&Access::Read(0xC050)));
+        assert!(!ram.accesses.contains(&Access::Read(0xC051)));
+    }
+
+    #[test]
+    fn dmc_dma_steals_cycles() {
+        let (mut cpu, mut ram) = setup("
+                LDA #$0F
+                STA $4010
+                LDA #$00
+                STA $4013
+                LDA #$10
+                STA $4015
+                NOP
+        ");
+
+        let mut cycles = [0; 7];
+        for cycles in cycles.iter_mut() {
+            *cycles = cpu.step_instruction(&mut ram);
+        }
+
+        // The single byte sample is fetched straight away, halting the CPU as it fetches the NOP.
+        assert_eq!([2, 4, 2, 4, 2], cycles[..5]);
+        assert!(cycles[5] == 4 + 3 || cycles[5] == 4 + 4, "STA took {} cycles", cycles[5]);
+        assert_eq!(2, cycles[6]);
+    }
+
+    #[test]
+    fn produces_audio_samples() {
+        let (mut cpu, mut ram) = setup("
+                LDA #$01
+                STA $4015
+                LDA #$BF        ; 50% duty, constant volume 15
+                STA $4000
+                LDA #$FD        ; 440Hz
+                STA $4002
+                LDA #$08
+                STA $4003
+        ");
+
+        run_cycles(&mut cpu, &mut ram, CPU_CLOCK_RATE as u32);
+        let samples = cpu.take_audio_samples();
+
+        assert!((44_099..=44_101).contains(&samples.len()), "{} samples", samples.len());
+        // The square wave is high for about half of the time.
+        let min = samples.iter().cloned().fold(f32::MAX, f32::min);
+        let max = samples.iter().cloned().fold(f32::MIN, f32::max);
+        assert!(max - min > 0.1);
+        let high = samples.iter().filter(|&&sample| sample > (min + max) / 2.0).count();
+        assert!(high > 15_000 && high < 30_000, "{} high samples", high);
+        assert!(cpu.take_audio_samples().is_empty());
+    }
+
+    /// A minimal NES for blargg's test ROMs, which only use mapper 0, and report through $6000.
+    struct TestRomBus {
+        ram: [u8; 0x0800],
+        prg_ram: [u8; 0x2000],
+        prg_rom: Vec<u8>,
+        cycles: u32,
+        vblank: bool,
+    }
+
+    /// Cycles per NTSC frame, to fake the PPU's vertical blank flag.
+    const FRAME_CYCLES: u32 = 29781;
+
+    impl Bus for TestRomBus {
+        fn read(&mut self, address: u16) -> u8 {
+            if address & 0xE007 == 0x2002 {
+                let value = (self.vblank as u8) << 7;
+                self.vblank = false;
+                return value;
+            }
+            self.peek(address)
+        }
+
+        fn write(&mut self, address: u16, value: u8) {
+            match address {
+                0x0000..=0x1FFF => self.ram[address as usize & 0x07FF] = value,
+                0x6000..=0x7FFF => self.prg_ram[address as usize - 0x6000] = value,
+                _ => {},
+            }
+        }
+
+        fn peek(&self, address: u16) -> u8 {
+            match address {
+                0x0000..=0x1FFF => self.ram[address as usize & 0x07FF],
+                0x6000..=0x7FFF => self.prg_ram[address as usize - 0x6000],
+                0x8000..=0xFFFF => self.prg_rom[(address as usize - 0x8000) % self.prg_rom.len()],
+                _ => 0,
+            }
+        }
+
+        fn on_cycle(&mut self, _cpu: &M6502) {
+            self.cycles += 1;
+            if self.cycles.is_multiple_of(FRAME_CYCLES) {
+                self.vblank = true;
+            }
+        }
+    }
+
+    /// Runs one of blargg's `apu_test` ROMs. Copy `rom_singles` from
+    /// https://github.com/christopherpow/nes-test-roms/tree/master/apu_test into `test_assets/chips/rp2a03/apu_test`.
+    #[test_case("1-len_ctr.nes")]
+    #[test_case("2-len_table.nes")]
+    #[test_case("3-irq_flag.nes")]
+    #[test_case("4-jitter.nes")]
+    #[test_case("5-len_timing.nes")]
+    #[test_case("6-irq_flag_timing.nes")]
+    #[test_case("7-dmc_basics.nes")]
+    #[test_case("8-dmc_rates.nes")]
+    #[ignore = "needs blargg's apu_test ROMs in test_assets/chips/rp2a03/apu_test"]
+    #[allow(clippy::unused_unit)]
+    fn blargg_apu_test(file_name: &str) {
+        let path = Path::new(ASSET_PATH).join("apu_test").join(file_name);
+        let rom = fs::read(&path).unwrap_or_else(|error| panic!("Can't read {}: {}", path.display(), error));
+
+        assert_eq!(b"NES\x1A", &rom[0..4]);
+        let prg_rom_size = rom[4] as usize * 0x4000;
+        let prg_rom_start = 16 + if rom[6] & 0x04 != 0 { 512 } else { 0 };
+
+        let mut bus = TestRomBus {
+            ram: [0; 0x0800],
+            prg_ram: [0; 0x2000],
+            prg_rom: rom[prg_rom_start..prg_rom_start + prg_rom_size].to_vec(),
+            cycles: 0,
+            vblank: false,
+        };
+
+        let mut cpu = Rp2a03::new();
+        cpu.set_pin_res(false);
+        cpu.set_pin_res(true);
+
+        // The result is at $6000 once the signature at $6001 is there, and $6000 is no longer $80 (running).
+        let mut seconds = 0;
+        loop {
+            for _ in 0..CPU_CLOCK_RATE as u32 {
+                cpu.step_cycle(&mut bus);
+            }
+            cpu.take_audio_samples();
+            seconds += 1;
+
+            if bus.prg_ram[1..4] == [0xDE, 0xB0, 0x61] && bus.prg_ram[0] != 0x80 {
+                break;
+            }
+            assert!(seconds < 60, "{} did not finish", file_name);
+        }
+
+        let text: String = bus.prg_ram[4..].iter().take_while(|&&c| c != 0).map(|&c| c as char).collect();
+        assert_eq!(0, bus.prg_ram[0], "{}", text);
+    }
+}
@@ -44,10 +44,90 @@ impl Pins {
     }
 }
 
+// Colours, as used by the alphanumeric and graphics colour codes. Bit 0 is red, bit 1 is green and bit 2 is blue.
+const BLACK: u8 = 0;
+const WHITE: u8 = 7;
+
+/// Each character is 6 dots wide, but is output in half-dots, so that character rounding can
+/// fill in the diagonals.
+const HALF_DOTS_PER_CHARACTER: u8 = 12;
+
+/// Each character row is 10 scanlines in each field.
+const LINES_PER_ROW: u8 = 10;
+
+/// Flashing characters are shown for 48 fields out of every 64.
+const FLASH_PERIOD: u8 = 64;
+const FLASH_ON_FIELDS: u8 = 48;
+
+/// Settings made by the control codes. These go back to their defaults at the start of each line.
+#[derive(Clone, Copy)]
+struct Attributes {
+    foreground: u8,
+    background: u8,
+    graphics: bool,
+    separated: bool,
+    flash: bool,
+    conceal: bool,
+    double_height: bool,
+    hold: bool,
+
+    /// The last graphics character, for hold graphics. Space when there isn't one.
+    held_character: u8,
+    held_separated: bool,
+}
+
+impl Attributes {
+    fn new() -> Self {
+        Self {
+            foreground: WHITE,
+            background: BLACK,
+            graphics: false,
+            separated: false,
+            flash: false,
+            conceal: false,
+            double_height: false,
+            hold: false,
+            held_character: 0x20,
+            held_separated: false,
+        }
+    }
+}
+
+/// Mullard SAA5050 teletext character generator.
+///
+/// A character is loaded on each rising edge of F1, and shifted out onto the R, G and B pins
+/// one half-dot at a time, on each edge of TR6. The chip counts the scanlines of each character
+/// row itself, from the falling edges of LOSE, and starts again from the top when DEW is high.
+/// CRS is high in the odd field, where each scanline shows the lower half of the rounded dots.
 pub(crate) struct SAA5050 {
     pub pins: Pins,
 
     character_data_stored: u8,
+
+    attributes: Attributes,
+
+    /// Scanline within the current character row, for this field.
+    line: u8,
+
+    /// Whether the current character row has any double height characters.
+    double_height_in_row: bool,
+
+    /// Whether the current character row shows the bottom halves of the row above's double height characters.
+    double_height_bottom_row: bool,
+
+    /// Counts fields, for flashing.
+    field_counter: u8,
+
+    /// Half-dots of the current character, most significant bit first.
+    shift_register: u16,
+    foreground: u8,
+    background: u8,
+
+    // Pins as they were on the previous tick, for finding edges.
+    f1: bool,
+    tr6: bool,
+    lose: bool,
+    dew: bool,
 }
 
 impl SAA5050 {
@@ -55,16 +135,435 @@ impl SAA5050 {
         Self {
             pins: Pins::new(),
             character_data_stored: 0,
+            attributes: Attributes::new(),
+            line: 0,
+            double_height_in_row: false,
+            double_height_bottom_row: false,
+            field_counter: 0,
+            shift_register: 0,
+            foreground: BLACK,
+            background: BLACK,
+            f1: false,
+            tr6: false,
+            lose: false,
+            dew: false,
         }
     }
 
-    /// Should be called at 6MHz.
+    /// Should be called at least twice as often as TR6 changes.
     pub(crate) fn tick(&mut self) {
-        if self.pins.f1 {
+        if self.pins.dew {
+            if !self.dew {
+                self.field_counter = self.field_counter.wrapping_add(1);
+            }
+            self.line = 0;
+            self.double_height_in_row = false;
+            self.double_height_bottom_row = false;
+        }
+
+        match (self.lose, self.pins.lose) {
+            (false, true) => self.attributes = Attributes::new(),
+            (true, false) => self.end_line(),
+            _ => {}
+        }
+
+        if self.pins.f1 && !self.f1 {
             self.character_data_stored = self.pins.character_data;
+            if self.pins.lose {
+                self.load_character(self.character_data_stored & 0x7F);
+            } else {
+                self.shift_register = 0;
+                self.foreground = BLACK;
+                self.background = BLACK;
+            }
+        }
+
+        if self.pins.tr6 != self.tr6 {
+            self.shift_out();
+        }
+
+        self.f1 = self.pins.f1;
+        self.tr6 = self.pins.tr6;
+        self.lose = self.pins.lose;
+        self.dew = self.pins.dew;
+    }
+
+    fn end_line(&mut self) {
+        self.line += 1;
+        if self.line == LINES_PER_ROW {
+            self.line = 0;
+
+            // The row after a row with double height characters shows their bottom halves.
+            self.double_height_bottom_row = self.double_height_in_row && !self.double_height_bottom_row;
+            self.double_height_in_row = false;
+        }
+    }
+
+    fn shift_out(&mut self) {
+        let dot = self.shift_register & (1 << (HALF_DOTS_PER_CHARACTER - 1)) != 0;
+        self.shift_register = (self.shift_register << 1) & ((1 << HALF_DOTS_PER_CHARACTER) - 1);
+
+        let colour = if dot { self.foreground } else { self.background };
+        self.pins.r = colour & 1 != 0;
+        self.pins.g = colour & 2 != 0;
+        self.pins.b = colour & 4 != 0;
+    }
+
+    /// Acts on a character's "set-at" control codes, works out what to show for it, then acts on its "set-after" control codes.
+    fn load_character(&mut self, code: u8) {
+        let attributes = &mut self.attributes;
+
+        // Set-at
+        match code {
+            0x09 => attributes.flash = false,
+            0x0C => {
+                if attributes.double_height {
+                    attributes.held_character = 0x20;
+                }
+                attributes.double_height = false;
+            }
+            0x18 => attributes.conceal = true,
+            0x19 => attributes.separated = false,
+            0x1A => attributes.separated = true,
+            0x1C => attributes.background = BLACK,
+            0x1D => attributes.background = attributes.foreground,
+            0x1E => attributes.hold = true,
+            _ => {}
         }
 
-        // Lookup character.
-        let _ = rom::CHARACTERS[0]; // TODO
+        let line = self.character_line();
+        let attributes = &mut self.attributes;
+
+        // Control codes show as spaces, or as the held graphics character.
+        let half_dots = if code < 0x20 {
+            if attributes.graphics && attributes.hold {
+                Self::graphics_half_dots(attributes.held_character, attributes.held_separated, line)
+            } else {
+                0
+            }
+        } else if attributes.graphics && code & 0x20 != 0 {
+            attributes.held_character = code;
+            attributes.held_separated = attributes.separated;
+            Self::graphics_half_dots(code, attributes.separated, line)
+        } else {
+            Self::alphanumeric_half_dots(code, line)
+        };
+
+        let flashed_off = attributes.flash && self.field_counter % FLASH_PERIOD >= FLASH_ON_FIELDS;
+        let hidden_by_double_height = self.double_height_bottom_row && !attributes.double_height;
+        self.shift_register = if attributes.conceal || flashed_off || hidden_by_double_height { 0 } else { half_dots };
+        self.foreground = attributes.foreground;
+        self.background = attributes.background;
+
+        // Set-after
+        match code {
+            0x01..=0x07 => {
+                attributes.foreground = code;
+                attributes.graphics = false;
+                attributes.conceal = false;
+                attributes.held_character = 0x20;
+            }
+            0x08 => attributes.flash = true,
+            0x0D => {
+                if !attributes.double_height {
+                    attributes.held_character = 0x20;
+                }
+                attributes.double_height = true;
+                self.double_height_in_row = true;
+            }
+            0x11..=0x17 => {
+                attributes.foreground = code & 0x7;
+                attributes.graphics = true;
+                attributes.conceal = false;
+            }
+            0x1F => attributes.hold = false,
+            _ => {}
+        }
     }
-}
\ No newline at end of file
+
+    /// Which of the 20 scanlines of a character (counting both fields) to show. Double height
+    /// characters show each scanline of their top or bottom half twice.
+    fn character_line(&self) -> u8 {
+        let line = self.line * 2 + self.pins.crs as u8;
+        if !self.attributes.double_height {
+            line
+        } else if self.double_height_bottom_row {
+            LINES_PER_ROW + line / 2
+        } else {
+            line / 2
+        }
+    }
+
+    /// Each row of the ROM is shown on one scanline in each field. The even field's scanline is
+    /// rounded towards the row above, and the odd field's towards the row below.
+    fn alphanumeric_half_dots(code: u8, line: u8) -> u16 {
+        let row = line / 2;
+        let rounding_row = if line & 1 == 1 { row.checked_add(1) } else { row.checked_sub(1) };
+        let dots = Self::rom_row(code, row);
+        let rounding_dots = rounding_row.map_or(0, |rounding_row| Self::rom_row(code, rounding_row));
+        Self::round(dots, rounding_dots)
+    }
+
+    /// A row of the character's dots, with the leftmost dot in bit 5.
+    fn rom_row(code: u8, row: u8) -> u8 {
+        if row >= LINES_PER_ROW {
+            return 0;
+        }
+
+        let offset = (code as usize - 0x20) * 60 + (row as usize) * 6;
+        rom::CHARACTERS[offset..offset + 6]
+            .iter()
+            .fold(0, |dots, &dot| (dots << 1) | dot)
+    }
+
+    /// Character rounding fills in half a dot next to each dot that only touches a dot in the
+    /// other row diagonally.
+    fn round(dots: u8, other_dots: u8) -> u16 {
+        let dot = |dots: u8, x: i8| (0..6).contains(&x) && dots & (0x20 >> x) != 0;
+
+        let mut half_dots = 0;
+        for x in 0..6 {
+            if !dot(dots, x) {
+                continue;
+            }
+            half_dots |= 0b11 << (10 - 2 * x);
+
+            if dot(other_dots, x + 1) && !dot(dots, x + 1) && !dot(other_dots, x) {
+                half_dots |= 0x800 >> (2 * x + 2);
+            }
+            if dot(other_dots, x - 1) && !dot(dots, x - 1) && !dot(other_dots, x) {
+                half_dots |= 0x800 >> (2 * x - 1);
+            }
+        }
+        half_dots
+    }
+
+    /// Graphics characters are split into six blocks, two wide and three high. Each bit of the
+    /// character code sets one of the blocks. Separated graphics leave a gap to the left of and
+    /// below each block.
+    fn graphics_half_dots(code: u8, separated: bool, line: u8) -> u16 {
+        let row = line / 2;
+        let (left_bit, right_bit, last_row) = match row {
+            0..=2 => (0x01, 0x02, 2),
+            3..=6 => (0x04, 0x08, 6),
+            _ => (0x10, 0x40, 9),
+        };
+
+        if separated && row == last_row {
+            return 0;
+        }
+
+        let (left, right) = if separated { (0x3C0, 0x00F) } else { (0xFC0, 0x03F) };
+        let mut half_dots = 0;
+        if code & left_bit != 0 {
+            half_dots |= left;
+        }
+        if code & right_bit != 0 {
+            half_dots |= right;
+        }
+        half_dots
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Shows a line of characters, and returns the colour of each half-dot.
+    fn show_line(chip: &mut SAA5050, characters: &[u8]) -> Vec<u8> {
+        let mut colours = Vec::new();
+
+        chip.pins.lose = true;
+        chip.tick();
+        for &character in characters {
+            chip.pins.character_data = character;
+            chip.pins.f1 = true;
+            chip.tick();
+            chip.pins.f1 = false;
+
+            for _ in 0..HALF_DOTS_PER_CHARACTER {
+                chip.pins.tr6 = !chip.pins.tr6;
+                chip.tick();
+                colours.push(chip.pins.r as u8 | (chip.pins.g as u8) << 1 | (chip.pins.b as u8) << 2);
+            }
+        }
+        chip.pins.lose = false;
+        chip.tick();
+
+        colours
+    }
+
+    /// Shows a line of characters, and returns the half-dots of the last character as a string.
+    fn show_last_character(chip: &mut SAA5050, characters: &[u8]) -> String {
+        let colours = show_line(chip, characters);
+        colours[colours.len() - HALF_DOTS_PER_CHARACTER as usize..]
+            .iter()
+            .map(|&colour| char::from_digit(colour as u32, 8).unwrap())
+            .collect()
+    }
+
+    fn start_field(chip: &mut SAA5050, odd: bool) {
+        chip.pins.dew = true;
+        chip.tick();
+        chip.pins.dew = false;
+        chip.pins.crs = odd;
+        chip.tick();
+    }
+
+    fn skip_lines(chip: &mut SAA5050, lines: u8) {
+        for _ in 0..lines {
+            show_line(chip, &[]);
+        }
+    }
+
+    #[test]
+    fn character_rounding() {
+        // A dot at x = 2, and one at x = 3 in the row below, get joined by a half-dot.
+        assert_eq!(0x0E0, SAA5050::round(0b001000, 0b000100));
+
+        // Dots that also touch orthogonally aren't rounded.
+        assert_eq!(0x0F0, SAA5050::round(0b001100, 0b000110));
+    }
+
+    #[test]
+    fn alphanumerics() {
+        let mut chip = SAA5050::new();
+
+        // The second row of 'A' has a single dot in the middle. In the even field it's next to
+        // the blank row above, and in the odd field it's rounded towards the row below.
+        start_field(&mut chip, false);
+        skip_lines(&mut chip, 1);
+        assert_eq!("000000770000", show_last_character(&mut chip, b"A"));
+
+        start_field(&mut chip, true);
+        skip_lines(&mut chip, 1);
+        assert_eq!("000007777000", show_last_character(&mut chip, b"A"));
+
+        // Bit 7 is ignored.
+        start_field(&mut chip, false);
+        skip_lines(&mut chip, 1);
+        assert_eq!("000000770000", show_last_character(&mut chip, &[b'A' | 0x80]));
+    }
+
+    #[test]
+    fn colour_codes_and_background() {
+        let mut chip = SAA5050::new();
+        start_field(&mut chip, false);
+        skip_lines(&mut chip, 5);
+
+        // Colour codes are "set-after", so they show as a space in the old colour.
+        let colours = show_line(&mut chip, &[0x01, 0x7F, 0x1D, 0x02, 0x7F, 0x1C, 0x7F]);
+        let characters: Vec<String> = colours
+            .chunks(HALF_DOTS_PER_CHARACTER as usize)
+            .map(|character| character.iter().map(|&colour| char::from_digit(colour as u32, 8).unwrap()).collect())
+            .collect();
+
+        assert_eq!(vec![
+            "000000000000", // Alphanumeric red
+            "001111111111", // Block
+            "111111111111", // New background, "set-at"
+            "111111111111", // Alphanumeric green
+            "112222222222", // Block
+            "000000000000", // Black background, "set-at"
+            "002222222222", // Block
+        ], characters);
+
+        // Colours go back to white on black on the next line.
+        assert_eq!("007777777777", show_last_character(&mut chip, &[0x7F]));
+    }
+
+    #[test]
+    fn graphics() {
+        let mut chip = SAA5050::new();
+        start_field(&mut chip, false);
+
+        // Top blocks, in the first three scanlines.
+        assert_eq!("777777777777", show_last_character(&mut chip, &[0x17, 0x23]));
+        assert_eq!("777777000000", show_last_character(&mut chip, &[0x17, 0x21]));
+        assert_eq!("000000777777", show_last_character(&mut chip, &[0x17, 0x22]));
+
+        // Separated graphics leave a gap on the left of each block, and at the bottom.
+        skip_lines(&mut chip, 2);
+        assert_eq!("007777007777", show_last_character(&mut chip, &[0x17, 0x1A, 0x7F]));
+        assert_eq!("000000000000", show_last_character(&mut chip, &[0x17, 0x1A, 0x7F]));
+        assert_eq!("777777777777", show_last_character(&mut chip, &[0x17, 0x7F]));
+
+        // Capital letters are shown in graphics mode.
+        assert_eq!(show_last_character(&mut chip, b"A"), show_last_character(&mut chip, &[0x17, b'A']));
+    }
+
+    #[test]
+    fn hold_graphics() {
+        let mut chip = SAA5050::new();
+        start_field(&mut chip, false);
+
+        // Without hold, control codes show as spaces.
+        assert_eq!("000000000000", show_last_character(&mut chip, &[0x17, 0x7F, 0x12]));
+
+        // With hold, they show the last graphics character, in the current colour.
+        assert_eq!("777777777777", show_last_character(&mut chip, &[0x17, 0x7F, 0x1E, 0x12]));
+        assert_eq!("222222222222", show_last_character(&mut chip, &[0x17, 0x7F, 0x1E, 0x12, 0x09]));
+
+        // Release is "set-after".
+        assert_eq!("777777777777", show_last_character(&mut chip, &[0x17, 0x7F, 0x1E, 0x1F]));
+        assert_eq!("000000000000", show_last_character(&mut chip, &[0x17, 0x7F, 0x1E, 0x1F, 0x09]));
+    }
+
+    #[test]
+    fn conceal() {
+        let mut chip = SAA5050::new();
+        start_field(&mut chip, false);
+
+        assert_eq!("000000000000", show_last_character(&mut chip, &[0x18, 0x7F]));
+
+        // A colour code reveals the rest of the line.
+        assert_eq!("002222222222", show_last_character(&mut chip, &[0x18, 0x02, 0x7F]));
+    }
+
+    #[test]
+    fn flash() {
+        let mut chip = SAA5050::new();
+
+        for field in 0..FLASH_PERIOD as u32 * 2 {
+            start_field(&mut chip, false);
+            skip_lines(&mut chip, 3);
+            let visible = show_last_character(&mut chip, &[0x08, 0x7F]) != "000000000000";
+            assert_eq!((field + 1) % FLASH_PERIOD as u32 >= FLASH_ON_FIELDS as u32, !visible);
+
+            // Steady is "set-at".
+            assert_eq!("000000000000", show_last_character(&mut chip, &[0x08, 0x09]));
+            assert_eq!("007777777777", show_last_character(&mut chip, &[0x08, 0x09, 0x7F]));
+        }
+    }
+
+    #[test]
+    fn double_height() {
+        let mut chip = SAA5050::new();
+        start_field(&mut chip, false);
+
+        // Each row of the top half of 'A' is shown on two scanlines, each rounded a different way.
+        let mut top_row = Vec::new();
+        for _ in 0..LINES_PER_ROW {
+            top_row.push(show_last_character(&mut chip, &[0x0D, b'A']));
+        }
+        assert_eq!("000000770000", top_row[2]);
+        assert_eq!("000007777000", top_row[3]);
+        assert_eq!("000077777700", top_row[4]);
+
+        // The next row shows the bottom half, and hides single height characters.
+        let mut bottom_row = Vec::new();
+        for _ in 0..LINES_PER_ROW {
+            bottom_row.push(show_last_character(&mut chip, &[0x0D, b'A', 0x0C, b'A']));
+        }
+        assert!(bottom_row.iter().all(|line| line == "000000000000"));
+
+        start_field(&mut chip, false);
+        show_line(&mut chip, &[0x0D]);
+        skip_lines(&mut chip, LINES_PER_ROW - 1);
+        assert_eq!("007777777777", show_last_character(&mut chip, &[0x0D, b'A']));
+
+        // The row after that is a top row again.
+        skip_lines(&mut chip, LINES_PER_ROW - 1 + 2);
+        assert_eq!("000000770000", show_last_character(&mut chip, &[0x0D, b'A']));
+    }
+}
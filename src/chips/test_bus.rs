@@ -0,0 +1,87 @@
+//! A RAM-only bus for the CPU tests, which works with both the 6502 family's [`m6502::Bus`]
+//! and the 65C816's [`w65c816::Bus`].
+
+use super::{m6502, w65c816};
+
+/// A read or write that reached the bus.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Access {
+    Read(u32),
+    Write(u32, u8),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(crate) struct Ram {
+    pub data: Vec<u8>,
+
+    /// Every access, once [`Ram::with_access_log`] has turned logging on. It's off by default,
+    /// because long-running tests would fill up memory.
+    pub accesses: Vec<Access>,
+    log_accesses: bool,
+}
+
+impl Ram {
+    /// 64KB of RAM, filled with zeros.
+    pub fn new() -> Self {
+        Self::from_data(vec![0; 0x10000])
+    }
+
+    pub fn from_data(data: Vec<u8>) -> Self {
+        Self {
+            data,
+            accesses: Vec::new(),
+            log_accesses: false,
+        }
+    }
+
+    pub fn with_access_log(mut self) -> Self {
+        self.log_accesses = true;
+        self
+    }
+
+    pub fn load(&mut self, address: u32, bytes: &[u8]) {
+        self.data[address as usize..address as usize + bytes.len()].copy_from_slice(bytes);
+    }
+
+    fn read(&mut self, address: u32) -> u8 {
+        if self.log_accesses {
+            self.accesses.push(Access::Read(address));
+        }
+        self.data[address as usize]
+    }
+
+    fn write(&mut self, address: u32, value: u8) {
+        if self.log_accesses {
+            self.accesses.push(Access::Write(address, value));
+        }
+        self.data[address as usize] = value;
+    }
+}
+
+impl m6502::Bus for Ram {
+    fn read(&mut self, address: u16) -> u8 {
+        Ram::read(self, address as u32)
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        Ram::write(self, address as u32, value);
+    }
+
+    fn peek(&self, address: u16) -> u8 {
+        self.data[address as usize]
+    }
+}
+
+impl w65c816::Bus for Ram {
+    fn read(&mut self, address: u32) -> u8 {
+        Ram::read(self, address)
+    }
+
+    fn write(&mut self, address: u32, value: u8) {
+        Ram::write(self, address, value);
+    }
+
+    fn peek(&self, address: u32) -> u8 {
+        self.data[address as usize]
+    }
+}
@@ -0,0 +1,84 @@
+use super::W65C816;
+
+// These run at the end of a bus cycle, and pick up the byte that was read from the data pins.
+
+impl W65C816 {
+    pub(crate) fn aa_lo(&mut self) {
+        self.aa = (self.aa & 0xFF00) | self.data as u16;
+    }
+
+    pub(crate) fn aa_hi(&mut self) {
+        self.aa = (self.aa & 0x00FF) | ((self.data as u16) << 8);
+    }
+
+    pub(crate) fn aa_bank(&mut self) {
+        self.aa_bank = self.data;
+    }
+
+    pub(crate) fn value_lo(&mut self) {
+        self.value = self.data as u16;
+    }
+
+    pub(crate) fn value_hi(&mut self) {
+        self.value |= (self.data as u16) << 8;
+    }
+
+    /// Direct page addressing, with an optional index. In emulation mode, when the direct page
+    /// is aligned to a page, the 6502 addressing modes wrap within that page.
+    pub(crate) fn direct_address(&mut self, index: u16) {
+        let offset = (self.data as u16).wrapping_add(index);
+        if self.e && self.d & 0xFF == 0 {
+            self.ea = (self.d | (offset & 0xFF)) as u32;
+            self.ea_high = (self.d | (offset.wrapping_add(1) & 0xFF)) as u32;
+        } else {
+            let address = self.d.wrapping_add(offset);
+            self.ea = address as u32;
+            self.ea_high = address.wrapping_add(1) as u32;
+        }
+    }
+
+    /// Long pointers in the direct page never wrap within a page.
+    pub(crate) fn direct_long_address(&mut self) {
+        let address = self.d.wrapping_add(self.data as u16);
+        self.ea = address as u32;
+        self.ea_high = address.wrapping_add(1) as u32;
+    }
+
+    pub(crate) fn stack_relative_address(&mut self) {
+        let address = self.s.wrapping_add(self.data as u16);
+        self.ea = address as u32;
+        self.ea_high = address.wrapping_add(1) as u32;
+    }
+
+    /// Data addresses are 24 bits, so indexing can carry into the next bank.
+    pub(crate) fn set_effective_address(&mut self, bank: u8, index: u16) {
+        let base = ((bank as u32) << 16) | self.aa as u32;
+        self.ea = (base + index as u32) & 0xFFFFFF;
+        self.ea_high = (self.ea + 1) & 0xFFFFFF;
+        self.page_crossed = (base ^ self.ea) & 0xFFFF00 != 0;
+        self.uncarried = (base & 0xFFFF00) | (self.ea & 0xFF);
+    }
+
+    /// Pointer for JMP (a) and JML [a], which is always in bank 0.
+    pub(crate) fn set_pointer(&mut self) {
+        self.ea = self.aa as u32;
+        self.ea_high = self.aa.wrapping_add(1) as u32;
+    }
+
+    /// Pointer for JMP (a,x) and JSR (a,x), which is in the program bank.
+    pub(crate) fn set_program_pointer(&mut self, index: u16) {
+        let address = self.aa.wrapping_add(index);
+        self.ea = ((self.pbr as u32) << 16) | address as u32;
+        self.ea_high = ((self.pbr as u32) << 16) | address.wrapping_add(1) as u32;
+    }
+
+    /// Indexed reads only take an extra cycle when they cross a page, or when the index is 16 bits wide.
+    pub(crate) fn index_cycle_needed(&self) -> bool {
+        self.page_crossed || !self.p.x
+    }
+
+    /// The direct page modes take an extra cycle when the direct page isn't aligned to a page.
+    pub(crate) fn direct_page_cycle_needed(&self) -> bool {
+        self.d & 0xFF != 0
+    }
+}
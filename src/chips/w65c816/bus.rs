@@ -0,0 +1,23 @@
+use super::W65C816;
+
+/// Memory and I/O seen by the CPU when it is driven by [`W65C816::step_cycle`]
+/// or [`W65C816::step_instruction`].
+///
+/// Addresses are 24 bits wide: the bank byte that the CPU puts on the data bus
+/// while PHI2 is low, followed by the 16-bit address bus.
+pub trait Bus {
+    /// Called for every read cycle where VDA or VPA is high.
+    /// The returned value is placed on the data bus.
+    fn read(&mut self, address: u32) -> u8;
+
+    /// Called for every write cycle, with the value the CPU put on the data bus.
+    fn write(&mut self, address: u32, value: u8);
+
+    /// Returns the value at an address without any side effects.
+    /// Used by debugging tools, which must not disturb I/O registers.
+    fn peek(&self, address: u32) -> u8;
+
+    /// Called once at the end of every cycle, including internal operation cycles
+    /// where the CPU doesn't access the bus.
+    fn on_cycle(&mut self, _cpu: &W65C816) {}
+}
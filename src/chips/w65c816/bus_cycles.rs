@@ -0,0 +1,171 @@
+use super::{BrkFlags, W65C816};
+
+// Each of these puts the address and control pins for the next bus cycle in place.
+// They run at the start of the cycle, while PHI2 is low.
+
+impl W65C816 {
+    fn set_bus(&mut self, address: u32, rw: bool, vda: bool, vpa: bool) {
+        self.bank = (address >> 16) as u8;
+        self.address = address as u16;
+        self.rw = rw;
+        self.vda = vda;
+        self.vpa = vpa;
+        self.vpb = true;
+        self.mlb = true;
+    }
+
+    fn program_address(&self) -> u32 {
+        ((self.pbr as u32) << 16) | self.pc as u32
+    }
+
+    pub(crate) fn fetch_next_instruction(&mut self) {
+        self.set_bus(self.program_address(), true, true, true);
+    }
+
+    /// Reads the next byte of the instruction stream. The program counter wraps within the program bank.
+    pub(crate) fn read_pc(&mut self) {
+        self.set_bus(self.program_address(), true, false, true);
+        self.pc = self.pc.wrapping_add(1);
+    }
+
+    /// Internal operation. VDA and VPA are both low, so nothing on the bus responds.
+    pub(crate) fn io(&mut self) {
+        self.set_bus(self.program_address(), true, false, false);
+    }
+
+    pub(crate) fn io_stack(&mut self) {
+        self.set_bus(self.s as u32, true, false, false);
+    }
+
+    /// The indexing cycle shows the address before the carry into the high byte.
+    pub(crate) fn io_uncarried(&mut self) {
+        self.set_bus(self.uncarried, true, false, false);
+    }
+
+    pub(crate) fn read_ea(&mut self) {
+        self.set_bus(self.ea, true, true, false);
+    }
+
+    pub(crate) fn read_ea_high(&mut self) {
+        self.set_bus(self.ea_high, true, true, false);
+    }
+
+    /// Bank byte of a long pointer, which never leaves bank 0.
+    pub(crate) fn read_ea_bank(&mut self) {
+        self.set_bus((self.ea & 0xFF0000) | (self.ea.wrapping_add(2) & 0xFFFF), true, true, false);
+    }
+
+    pub(crate) fn write_ea(&mut self) {
+        self.set_bus(self.ea, false, true, false);
+        self.data_out = self.value as u8;
+    }
+
+    pub(crate) fn write_ea_high(&mut self) {
+        self.set_bus(self.ea_high, false, true, false);
+        self.data_out = (self.value >> 8) as u8;
+    }
+
+    /// Holds MLB low for the rest of a read / modify / write instruction.
+    pub(crate) fn lock(&mut self) {
+        self.mlb = false;
+    }
+
+    /// In emulation mode the unmodified value is written back, as on the NMOS 6502.
+    pub(crate) fn modify_cycle(&mut self) {
+        if self.e {
+            self.write_ea();
+        } else {
+            self.set_bus(self.ea_high, true, false, false);
+        }
+        self.lock();
+    }
+
+    /// The stack pointer stays in page 1 in emulation mode.
+    fn set_stack_pointer(&mut self, value: u16) {
+        self.s = if self.e { 0x0100 | (value & 0xFF) } else { value };
+    }
+
+    /// Pushes are turned into reads during RESET, although the stack pointer still moves.
+    fn push(&mut self, value: u8) {
+        let rw = self.brk_flags.contains(BrkFlags::RESET);
+        self.set_bus(self.s as u32, rw, true, false);
+        self.data_out = value;
+        self.set_stack_pointer(self.s.wrapping_sub(1));
+    }
+
+    pub(crate) fn push_high(&mut self, value: u16) {
+        self.push((value >> 8) as u8);
+    }
+
+    pub(crate) fn push_low(&mut self, value: u16) {
+        self.push(value as u8);
+    }
+
+    pub(crate) fn pull(&mut self) {
+        self.set_stack_pointer(self.s.wrapping_add(1));
+        self.set_bus(self.s as u32, true, true, false);
+    }
+
+    pub(crate) fn read_block_source(&mut self) {
+        self.set_bus(((self.aa_bank as u32) << 16) | self.x as u32, true, true, false);
+    }
+
+    pub(crate) fn write_block_destination(&mut self) {
+        self.set_bus(((self.dbr as u32) << 16) | self.y as u32, false, true, false);
+        self.data_out = self.value as u8;
+    }
+
+    /// BRK and COP skip their signature byte. Hardware interrupts don't touch the program counter.
+    pub(crate) fn interrupt_signature(&mut self) {
+        if self.brk_flags == BrkFlags::NONE {
+            self.read_pc();
+        } else {
+            self.io();
+        }
+    }
+
+    /// In emulation mode, bit 4 is the B flag, which is clear for hardware interrupts.
+    pub(crate) fn push_status(&mut self) {
+        let mut value = self.p.as_u8();
+        if self.e && self.brk_flags != BrkFlags::NONE {
+            value &= !0x10;
+        }
+        self.push_low(value as u16);
+    }
+
+    fn interrupt_vector(&self) -> u16 {
+        if self.brk_flags.contains(BrkFlags::RESET) {
+            0xFFFC
+        } else if self.brk_flags.contains(BrkFlags::NMI) {
+            if self.e { 0xFFFA } else { 0xFFEA }
+        } else if self.brk_flags.contains(BrkFlags::IRQ) {
+            if self.e { 0xFFFE } else { 0xFFEE }
+        } else if self.ir == 0x02 {
+            if self.e { 0xFFF4 } else { 0xFFE4 }
+        } else if self.e {
+            0xFFFE
+        } else {
+            0xFFE6
+        }
+    }
+
+    pub(crate) fn read_vector_low(&mut self) {
+        self.ea = self.interrupt_vector() as u32;
+        self.set_bus(self.ea, true, true, false);
+        self.vpb = false;
+
+        self.p.i = true;
+        self.p.d = false;
+        self.pbr = 0;
+    }
+
+    pub(crate) fn read_vector_high(&mut self) {
+        self.set_bus(self.ea + 1, true, true, false);
+        self.vpb = false;
+    }
+
+    pub(crate) fn finish_interrupt(&mut self) {
+        self.pc = self.aa;
+        self.brk_flags = BrkFlags::NONE;
+    }
+}
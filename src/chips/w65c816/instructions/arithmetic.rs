@@ -0,0 +1,106 @@
+use super::super::W65C816;
+
+impl W65C816 {
+    /// Adjusts one decimal digit of a sum or difference, following the 65C816's digit-by-digit carry.
+    fn decimal_adjust(result: i32, shift: u32, subtract: bool) -> i32 {
+        if subtract {
+            if result < (0x10 << shift) { result - (0x6 << shift) } else { result }
+        } else if result >= (0xA << shift) {
+            result + (0x6 << shift)
+        } else {
+            result
+        }
+    }
+
+    /// ADC, and SBC with the operand inverted. The width follows the M flag.
+    /// Unlike the 65C02, decimal mode doesn't take an extra cycle, and all flags are valid.
+    fn add_with_carry(&mut self, value: u16, subtract: bool) -> u16 {
+        let (nibbles, mask, sign) = if self.p.m { (2, 0xFF, 0x80) } else { (4, 0xFFFF, 0x8000) };
+        let a = (self.a & mask) as i32;
+        let b = if subtract { !value & mask } else { value & mask } as i32;
+
+        let mut result = a + b + self.p.c as i32;
+        if self.p.d {
+            let mut carry = self.p.c as i32;
+            result = 0;
+            for nibble in 0..nibbles {
+                let shift = nibble * 4;
+                let digit = 0xF << shift;
+                result = (a & digit) + (b & digit) + (carry << shift) + (result & ((1 << shift) - 1));
+                if nibble < nibbles - 1 {
+                    result = Self::decimal_adjust(result, shift, subtract);
+                    carry = (result >= (0x10 << shift)) as i32;
+                }
+            }
+        }
+
+        self.p.v = !(a ^ b) & (a ^ result) & sign != 0;
+        if self.p.d {
+            result = Self::decimal_adjust(result, (nibbles - 1) * 4, subtract);
+        }
+        self.p.c = result > mask as i32;
+
+        result as u16 & mask
+    }
+
+    pub(crate) fn adc(&mut self) {
+        let result = self.add_with_carry(self.value, false);
+        self.set_a(result);
+    }
+
+    pub(crate) fn sbc(&mut self) {
+        let result = self.add_with_carry(self.value, true);
+        self.set_a(result);
+    }
+
+    fn compare(&mut self, register: u16, sixteen_bit: bool) {
+        let mask = if sixteen_bit { 0xFFFF } else { 0xFF };
+        let (register, value) = (register & mask, self.value & mask);
+        self.p.c = register >= value;
+        self.p.set_zero_negative_flags(register.wrapping_sub(value), sixteen_bit);
+    }
+
+    pub(crate) fn cmp(&mut self) {
+        self.compare(self.a, !self.p.m);
+    }
+
+    pub(crate) fn cpx(&mut self) {
+        self.compare(self.x, !self.p.x);
+    }
+
+    pub(crate) fn cpy(&mut self) {
+        self.compare(self.y, !self.p.x);
+    }
+
+    pub(crate) fn inc(&mut self) {
+        self.value = self.p.set_zero_negative_flags(self.value.wrapping_add(1), !self.p.m);
+    }
+
+    pub(crate) fn dec(&mut self) {
+        self.value = self.p.set_zero_negative_flags(self.value.wrapping_sub(1), !self.p.m);
+    }
+
+    pub(crate) fn inca(&mut self) {
+        self.set_a(self.a.wrapping_add(1));
+    }
+
+    pub(crate) fn deca(&mut self) {
+        self.set_a(self.a.wrapping_sub(1));
+    }
+
+    pub(crate) fn inx(&mut self) {
+        self.x = self.set_index(self.x.wrapping_add(1));
+    }
+
+    pub(crate) fn dex(&mut self) {
+        self.x = self.set_index(self.x.wrapping_sub(1));
+    }
+
+    pub(crate) fn iny(&mut self) {
+        self.y = self.set_index(self.y.wrapping_add(1));
+    }
+
+    pub(crate) fn dey(&mut self) {
+        self.y = self.set_index(self.y.wrapping_sub(1));
+    }
+}
@@ -0,0 +1,28 @@
+use super::super::W65C816;
+
+impl W65C816 {
+    /// The first operand byte of MVN and MVP is the destination bank, which becomes the data bank.
+    pub(crate) fn block_move_destination(&mut self) {
+        self.dbr = self.data;
+    }
+
+    /// Moves one byte, and repeats the instruction until the accumulator underflows.
+    /// Interrupts can be taken between bytes, since each byte is a separate instruction.
+    fn block_move(&mut self, step: u16) {
+        let mask = if self.p.x { 0xFF } else { 0xFFFF };
+        self.x = self.x.wrapping_add(step) & mask;
+        self.y = self.y.wrapping_add(step) & mask;
+        self.a = self.a.wrapping_sub(1);
+        if self.a != 0xFFFF {
+            self.pc = self.pc.wrapping_sub(3);
+        }
+    }
+
+    pub(crate) fn mvn(&mut self) {
+        self.block_move(1);
+    }
+
+    pub(crate) fn mvp(&mut self) {
+        self.block_move(0xFFFF);
+    }
+}
@@ -0,0 +1,19 @@
+use super::super::W65C816;
+
+impl W65C816 {
+    pub(crate) fn branch_offset(&mut self) {
+        self.aa = self.data as i8 as u16;
+        self.page_crossed = false;
+    }
+
+    pub(crate) fn branch(&mut self) {
+        let target = self.pc.wrapping_add(self.aa);
+        self.page_crossed = (target ^ self.pc) & 0xFF00 != 0;
+        self.pc = target;
+    }
+
+    /// BRL is always taken, and never takes an extra cycle.
+    pub(crate) fn brl(&mut self) {
+        self.pc = self.pc.wrapping_add(self.aa);
+    }
+}
@@ -0,0 +1,51 @@
+use super::super::W65C816;
+
+impl W65C816 {
+    pub(crate) fn clc(&mut self) {
+        self.p.c = false;
+    }
+
+    pub(crate) fn cld(&mut self) {
+        self.p.d = false;
+    }
+
+    pub(crate) fn cli(&mut self) {
+        self.p.i = false;
+    }
+
+    pub(crate) fn clv(&mut self) {
+        self.p.v = false;
+    }
+
+    pub(crate) fn sec(&mut self) {
+        self.p.c = true;
+    }
+
+    pub(crate) fn sed(&mut self) {
+        self.p.d = true;
+    }
+
+    pub(crate) fn sei(&mut self) {
+        self.p.i = true;
+    }
+
+    pub(crate) fn rep(&mut self) {
+        self.set_status(self.p.as_u8() & !(self.value as u8));
+    }
+
+    pub(crate) fn sep(&mut self) {
+        self.set_status(self.p.as_u8() | self.value as u8);
+    }
+
+    /// Exchanges the carry and emulation flags. Entering emulation mode
+    /// forces 8-bit registers, and moves the stack back to page 1.
+    pub(crate) fn xce(&mut self) {
+        std::mem::swap(&mut self.p.c, &mut self.e);
+        if self.e {
+            self.s = 0x0100 | (self.s & 0xFF);
+        }
+        self.set_status(self.p.as_u8());
+    }
+
+    pub(crate) fn nop(&mut self) {}
+}
@@ -0,0 +1,11 @@
+use super::super::{RunState, W65C816};
+
+impl W65C816 {
+    pub(crate) fn wai(&mut self) {
+        self.run_state = RunState::Waiting;
+    }
+
+    pub(crate) fn stp(&mut self) {
+        self.run_state = RunState::Stopped;
+    }
+}
@@ -0,0 +1,15 @@
+use super::super::W65C816;
+
+impl W65C816 {
+    pub(crate) fn lda(&mut self) {
+        self.set_a(self.value);
+    }
+
+    pub(crate) fn ldx(&mut self) {
+        self.x = self.set_index(self.value);
+    }
+
+    pub(crate) fn ldy(&mut self) {
+        self.y = self.set_index(self.value);
+    }
+}
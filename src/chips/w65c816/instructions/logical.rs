@@ -0,0 +1,38 @@
+use super::super::W65C816;
+
+impl W65C816 {
+    pub(crate) fn and(&mut self) {
+        self.set_a(self.a & self.value);
+    }
+
+    pub(crate) fn eor(&mut self) {
+        self.set_a(self.a ^ self.value);
+    }
+
+    pub(crate) fn ora(&mut self) {
+        self.set_a(self.a | self.value);
+    }
+
+    pub(crate) fn bit(&mut self) {
+        let sign = if self.p.m { 0x80 } else { 0x8000 };
+        self.p.n = self.value & sign != 0;
+        self.p.v = self.value & (sign >> 1) != 0;
+        self.bit_immediate();
+    }
+
+    /// BIT immediate only affects the Z flag.
+    pub(crate) fn bit_immediate(&mut self) {
+        let mask = if self.p.m { 0xFF } else { 0xFFFF };
+        self.p.z = self.a & self.value & mask == 0;
+    }
+
+    pub(crate) fn tsb(&mut self) {
+        self.bit_immediate();
+        self.value |= self.a;
+    }
+
+    pub(crate) fn trb(&mut self) {
+        self.bit_immediate();
+        self.value &= !self.a;
+    }
+}
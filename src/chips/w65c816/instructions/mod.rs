@@ -0,0 +1,12 @@
+mod arithmetic;
+mod block_move;
+mod branch;
+mod flags;
+mod interrupt;
+mod load;
+mod logical;
+mod shift;
+mod stack;
+mod store;
+mod subroutine;
+mod transfer;
@@ -0,0 +1,53 @@
+use super::super::W65C816;
+
+// The memory versions work on `value`, and the accumulator versions reuse them.
+
+impl W65C816 {
+    fn sign_bit(&self) -> u16 {
+        if self.p.m { 0x80 } else { 0x8000 }
+    }
+
+    pub(crate) fn asl(&mut self) {
+        self.p.c = self.value & self.sign_bit() != 0;
+        self.value = self.p.set_zero_negative_flags(self.value << 1, !self.p.m);
+    }
+
+    pub(crate) fn lsr(&mut self) {
+        self.p.c = self.value & 1 != 0;
+        self.value = self.p.set_zero_negative_flags(self.value >> 1, !self.p.m);
+    }
+
+    pub(crate) fn rol(&mut self) {
+        let carry = self.p.c as u16;
+        self.p.c = self.value & self.sign_bit() != 0;
+        self.value = self.p.set_zero_negative_flags((self.value << 1) | carry, !self.p.m);
+    }
+
+    pub(crate) fn ror(&mut self) {
+        let carry = if self.p.c { self.sign_bit() } else { 0 };
+        self.p.c = self.value & 1 != 0;
+        self.value = self.p.set_zero_negative_flags((self.value >> 1) | carry, !self.p.m);
+    }
+
+    fn shift_accumulator(&mut self, operation: fn(&mut Self)) {
+        self.value = if self.p.m { self.a & 0xFF } else { self.a };
+        operation(self);
+        self.a = if self.p.m { (self.a & 0xFF00) | self.value } else { self.value };
+    }
+
+    pub(crate) fn asla(&mut self) {
+        self.shift_accumulator(Self::asl);
+    }
+
+    pub(crate) fn lsra(&mut self) {
+        self.shift_accumulator(Self::lsr);
+    }
+
+    pub(crate) fn rola(&mut self) {
+        self.shift_accumulator(Self::rol);
+    }
+
+    pub(crate) fn rora(&mut self) {
+        self.shift_accumulator(Self::ror);
+    }
+}
@@ -0,0 +1,32 @@
+use super::super::W65C816;
+
+impl W65C816 {
+    pub(crate) fn pla(&mut self) {
+        self.set_a(self.value);
+    }
+
+    pub(crate) fn plx(&mut self) {
+        self.x = self.set_index(self.value);
+    }
+
+    pub(crate) fn ply(&mut self) {
+        self.y = self.set_index(self.value);
+    }
+
+    pub(crate) fn plb(&mut self) {
+        self.dbr = self.p.set_zero_negative_flags(self.value, false) as u8;
+    }
+
+    pub(crate) fn pld(&mut self) {
+        self.d = self.p.set_zero_negative_flags(self.value, true);
+    }
+
+    pub(crate) fn plp(&mut self) {
+        self.set_status(self.value as u8);
+    }
+
+    /// PER pushes an address relative to the next instruction.
+    pub(crate) fn per(&mut self) {
+        self.aa = self.pc.wrapping_add(self.aa);
+    }
+}
@@ -0,0 +1,19 @@
+use super::super::W65C816;
+
+impl W65C816 {
+    pub(crate) fn sta(&mut self) {
+        self.value = self.a;
+    }
+
+    pub(crate) fn stx(&mut self) {
+        self.value = self.x;
+    }
+
+    pub(crate) fn sty(&mut self) {
+        self.value = self.y;
+    }
+
+    pub(crate) fn stz(&mut self) {
+        self.value = 0;
+    }
+}
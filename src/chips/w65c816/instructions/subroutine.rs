@@ -0,0 +1,30 @@
+use super::super::W65C816;
+
+impl W65C816 {
+    pub(crate) fn jmp(&mut self) {
+        self.pc = self.aa;
+    }
+
+    pub(crate) fn jml(&mut self) {
+        self.pc = self.aa;
+        self.pbr = self.aa_bank;
+    }
+
+    /// JSR and JSL push the address of their last byte.
+    pub(crate) fn rts(&mut self) {
+        self.pc = self.aa.wrapping_add(1);
+    }
+
+    pub(crate) fn rtl(&mut self) {
+        self.pc = self.aa.wrapping_add(1);
+        self.pbr = self.aa_bank;
+    }
+
+    /// The program bank is only pulled in native mode.
+    pub(crate) fn rti(&mut self) {
+        self.pc = self.aa;
+        if !self.e {
+            self.pbr = self.aa_bank;
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use super::super::W65C816;
+
+impl W65C816 {
+    pub(crate) fn tax(&mut self) {
+        self.x = self.set_index(self.a);
+    }
+
+    pub(crate) fn tay(&mut self) {
+        self.y = self.set_index(self.a);
+    }
+
+    pub(crate) fn txa(&mut self) {
+        self.set_a(self.x);
+    }
+
+    pub(crate) fn tya(&mut self) {
+        self.set_a(self.y);
+    }
+
+    pub(crate) fn txy(&mut self) {
+        self.y = self.set_index(self.x);
+    }
+
+    pub(crate) fn tyx(&mut self) {
+        self.x = self.set_index(self.y);
+    }
+
+    pub(crate) fn tsx(&mut self) {
+        self.x = self.set_index(self.s);
+    }
+
+    /// TXS and TCS don't affect the flags.
+    pub(crate) fn txs(&mut self) {
+        self.s = if self.e { 0x0100 | (self.x & 0xFF) } else { self.x };
+    }
+
+    pub(crate) fn tcs(&mut self) {
+        self.s = if self.e { 0x0100 | (self.a & 0xFF) } else { self.a };
+    }
+
+    /// TSC, TCD and TDC always transfer 16 bits, whatever the M flag says.
+    pub(crate) fn tsc(&mut self) {
+        self.a = self.p.set_zero_negative_flags(self.s, true);
+    }
+
+    pub(crate) fn tcd(&mut self) {
+        self.d = self.p.set_zero_negative_flags(self.a, true);
+    }
+
+    pub(crate) fn tdc(&mut self) {
+        self.a = self.p.set_zero_negative_flags(self.d, true);
+    }
+
+    /// Flags come from the new low byte, whatever the M flag says.
+    pub(crate) fn xba(&mut self) {
+        self.a = self.a.swap_bytes();
+        self.p.set_zero_negative_flags(self.a, false);
+    }
+}
@@ -0,0 +1,821 @@
+mod addressing_modes;
+mod bus;
+mod bus_cycles;
+mod instructions;
+mod status_register;
+
+use aemula_macros::PinAccessors;
+
+pub use self::bus::Bus;
+
+use self::status_register::StatusRegister;
+
+bitflags! {
+    pub struct BrkFlags: u8 {
+        const NONE = 0;
+        const IRQ = 1;
+        const NMI = 2;
+        const RESET = 4;
+    }
+}
+
+/// WDC 65C816. A 65C02 with 16-bit registers and a 24-bit address bus,
+/// which starts up in an emulation mode that runs 6502 code.
+///
+/// Unlike [`M6502`](super::m6502::M6502), the bus is driven from PHI2 alone. A cycle starts when
+/// PHI2 goes low: the CPU finishes the previous cycle, puts the next address on the address pins,
+/// and puts the bank address (A16-A23) on the data pins. While PHI2 is high, the data pins carry data.
+#[derive(PinAccessors)]
+pub struct W65C816 {
+    //////////////////////////////////////////
+    // Pins
+    //////////////////////////////////////////
+
+    /// A0-A15.
+    #[pin(out)]
+    address: u16,
+
+    /// D0-D7. Multiplexed with A16-A23 while PHI2 is low.
+    #[pin(bidirectional)]
+    data: u8,
+
+    #[pin(in)]
+    rdy: bool,
+
+    /// Interrupt request (active low). Level-triggered, and ignored while the I flag is set.
+    #[pin(in)]
+    irq: bool,
+
+    /// Non-maskable interrupt (active low). Edge-triggered on the high to low transition.
+    #[pin(in)]
+    #[handle(transition_hi_to_lo)]
+    nmi: bool,
+
+    #[pin(in)]
+    #[handle(always)]
+    res: bool,
+
+    #[pin(in)]
+    #[handle(transition_lo_to_hi, transition_hi_to_lo)]
+    phi2: bool,
+
+    /// Valid data address. High when the address bus holds a data address.
+    #[pin(out)]
+    vda: bool,
+
+    /// Valid program address. High when the address bus holds a program address.
+    /// Both VDA and VPA are high for an opcode fetch, and both are low for an internal operation.
+    #[pin(out)]
+    vpa: bool,
+
+    /// Vector pull (active low). Low while an interrupt vector is read.
+    #[pin(out)]
+    vpb: bool,
+
+    /// Memory lock (active low). Low during the data cycles of read / modify / write instructions.
+    #[pin(out)]
+    mlb: bool,
+
+    /// Emulation status. Also the E flag, which is swapped with the carry by XCE.
+    #[pin(out)]
+    e: bool,
+
+    /// The M flag while PHI2 is low, and the X flag while it is high.
+    #[pin(out)]
+    mx: bool,
+
+    /// Read/write (read = true, write = false)
+    pub rw: bool,
+
+    //////////////////////////////////////////
+    // Registers
+    //////////////////////////////////////////
+
+    /// Accumulator. Only the low byte is affected by most instructions when the M flag is set,
+    /// but XBA and the 16-bit transfers use all of it.
+    pub a: u16,
+
+    /// X index register. The high byte is zero when the X flag is set.
+    pub x: u16,
+
+    /// Y index register. The high byte is zero when the X flag is set.
+    pub y: u16,
+
+    /// Stack pointer. Kept in page 1 in emulation mode.
+    pub s: u16,
+
+    /// Direct page register
+    pub d: u16,
+
+    /// Data bank register
+    pub dbr: u8,
+
+    /// Program bank register
+    pub pbr: u8,
+
+    /// Program counter
+    pub pc: u16,
+
+    /// Processor status
+    pub p: StatusRegister,
+
+    /// Instruction register - stores opcode of instruction being executed.
+    ir: u8,
+
+    /// Timing register - stores the progress through the current instruction.
+    tr: u8,
+
+    //////////////////////////////////////////
+    // Other internal storage
+    //////////////////////////////////////////
+
+    brk_flags: BrkFlags,
+
+    /// Operand, or a pointer that has been read from memory.
+    aa: u16,
+    aa_bank: u8,
+
+    /// Effective address, and the address of its high byte, which wraps differently
+    /// depending on the addressing mode.
+    ea: u32,
+    ea_high: u32,
+
+    /// Address shown on the bus during the indexing cycle, before the carry.
+    uncarried: u32,
+    page_crossed: bool,
+
+    /// Data that is read from or written to memory.
+    value: u16,
+
+    /// Bank address for the current cycle, and data to put on the data pins while PHI2 is high.
+    bank: u8,
+    data_out: u8,
+
+    /// Interrupt pipelines. Bit 0 is set in the cycle that an interrupt is detected,
+    /// and the pipelines are shifted left at the end of each cycle.
+    irq_pipeline: u16,
+    nmi_pipeline: u16,
+
+    run_state: RunState,
+}
+
+/// Set by WAI and STP.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum RunState {
+    Running,
+    Waiting,
+    Stopped,
+}
+
+impl W65C816 {
+    /// Creates a CPU that is about to run its RESET sequence.
+    pub fn new() -> Self {
+        let mut cpu = Self {
+            address: 0,
+            data: 0,
+            rdy: true,
+            irq: true,
+            nmi: true,
+            res: true,
+            phi2: true,
+            vda: true,
+            vpa: true,
+            vpb: true,
+            mlb: true,
+            e: true,
+            mx: true,
+            rw: true,
+
+            a: 0,
+            x: 0,
+            y: 0,
+            s: 0x0100,
+            d: 0,
+            dbr: 0,
+            pbr: 0,
+            pc: 0,
+
+            p: StatusRegister::new(),
+
+            ir: 0,
+            tr: 0,
+            brk_flags: BrkFlags::NONE,
+
+            aa: 0,
+            aa_bank: 0,
+            ea: 0,
+            ea_high: 0,
+            uncarried: 0,
+            page_crossed: false,
+            value: 0,
+            bank: 0,
+            data_out: 0,
+
+            irq_pipeline: 0,
+            nmi_pipeline: 0,
+
+            run_state: RunState::Running,
+        };
+
+        cpu.set_res(false);
+        cpu.set_res(true);
+        cpu
+    }
+
+    /// Full 24-bit address of the current cycle.
+    pub fn full_address(&self) -> u32 {
+        ((self.bank as u32) << 16) | self.address as u32
+    }
+
+    /// Runs a single clock cycle, and then performs the read or write
+    /// that the CPU requested on the given bus.
+    pub fn step_cycle(&mut self, bus: &mut impl Bus) {
+        self.set_phi2(false);
+
+        // The bank address is only on the data pins while PHI2 is low, so it needs to be latched.
+        let address = ((self.data as u32) << 16) | self.address as u32;
+
+        self.set_phi2(true);
+
+        if !self.rw {
+            bus.write(address, self.data);
+        } else if self.vda || self.vpa {
+            self.data = bus.read(address);
+        }
+
+        bus.on_cycle(self);
+    }
+
+    /// Runs clock cycles until the CPU is about to fetch the next opcode,
+    /// or has been halted by WAI or STP. Returns the number of cycles that were run.
+    pub fn step_instruction(&mut self, bus: &mut impl Bus) -> u32 {
+        let mut cycles = 0;
+
+        loop {
+            self.step_cycle(bus);
+            cycles += 1;
+
+            if (self.vda && self.vpa) || self.is_halted() {
+                return cycles;
+            }
+        }
+    }
+
+    /// Returns true if the CPU has been halted by WAI or STP.
+    pub fn is_halted(&self) -> bool {
+        self.run_state != RunState::Running
+    }
+
+    /// Replaces the accumulator, or just its low byte when the M flag is set, and sets N and Z.
+    fn set_a(&mut self, value: u16) {
+        if self.p.m {
+            self.a = (self.a & 0xFF00) | self.p.set_zero_negative_flags(value, false);
+        } else {
+            self.a = self.p.set_zero_negative_flags(value, true);
+        }
+    }
+
+    /// Returns a new value for an index register, truncated to 8 bits when the X flag is set, and sets N and Z.
+    fn set_index(&mut self, value: u16) -> u16 {
+        self.p.set_zero_negative_flags(value, !self.p.x)
+    }
+
+    /// Setting the X flag clears the high bytes of the index registers.
+    /// M and X can't be cleared in emulation mode.
+    fn set_status(&mut self, value: u8) {
+        self.p.set_from_u8(value);
+        if self.e {
+            self.p.m = true;
+            self.p.x = true;
+        }
+        if self.p.x {
+            self.x &= 0xFF;
+            self.y &= 0xFF;
+        }
+    }
+
+    /// RESET puts the CPU into emulation mode straight away. The rest of the
+    /// registers are left alone, apart from the high bytes of the stack and index registers.
+    fn on_res_set(&mut self) {
+        if !self.res {
+            self.vda = true;
+            self.vpa = true;
+            self.brk_flags = BrkFlags::RESET;
+            self.run_state = RunState::Running;
+
+            self.e = true;
+            self.d = 0;
+            self.dbr = 0;
+            self.pbr = 0;
+            self.s = 0x0100 | (self.s & 0xFF);
+            self.p.d = false;
+            self.set_status(self.p.as_u8());
+        }
+    }
+
+    fn on_nmi_transition_hi_to_lo(&mut self) {
+        self.nmi_pipeline |= 1;
+    }
+
+    fn on_phi2_transition_lo_to_hi(&mut self) {
+        if !self.rw {
+            self.data = self.data_out;
+        }
+        self.mx = self.p.x;
+    }
+
+    fn on_phi2_transition_hi_to_lo(&mut self) {
+        // IRQ is sampled every cycle, even while the CPU is paused by RDY.
+        if !self.irq && !self.p.i {
+            self.irq_pipeline |= 1;
+        }
+
+        // WAI waits until an interrupt is about to be taken. An IRQ that is masked
+        // by the I flag also ends the wait, and execution continues without taking it.
+        if self.run_state == RunState::Waiting
+            && (self.irq_pipeline & 0b10 != 0 || self.nmi_pipeline & 0b10 != 0 || (!self.irq && self.p.i)) {
+            self.run_state = RunState::Running;
+        }
+
+        // A low RDY pin, combined with a read cycle, pauses the CPU.
+        let paused = !self.rdy && self.rw;
+
+        if !paused && self.run_state == RunState::Running {
+            // VDA and VPA are both high for an opcode fetch.
+            // We will have the new opcode in the DATA pins.
+            if self.vda && self.vpa {
+                self.ir = self.data;
+                self.tr = 0;
+
+                if self.irq_pipeline & 0b100 != 0 {
+                    self.brk_flags |= BrkFlags::IRQ;
+                }
+                if self.nmi_pipeline & 0xFFFC != 0 {
+                    self.brk_flags |= BrkFlags::NMI;
+                }
+                self.irq_pipeline &= 0b11;
+                self.nmi_pipeline &= 0b11;
+
+                if self.brk_flags != BrkFlags::NONE {
+                    self.ir = 0;
+                } else {
+                    self.pc = self.pc.wrapping_add(1);
+                }
+            }
+
+            // Include generated file with actual instruction implementations.
+            include!(concat!(env!("OUT_DIR"), "/w65c816_instructions.generated.rs"));
+
+            // Increment timing register.
+            self.tr += 1;
+        }
+
+        // A detected NMI is not forgotten while the CPU is paused by RDY.
+        if !paused {
+            self.nmi_pipeline <<= 1;
+        }
+
+        self.irq_pipeline <<= 1;
+
+        self.data = self.bank;
+        self.mx = self.p.m;
+    }
+}
+
+impl Default for W65C816 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::test_bus::{Access, Ram};
+    use test_case::test_case;
+
+    /// 16MB of RAM, with a log of every bus access, and a program in bank 0 that the RESET vector points at.
+    fn ram_with_program(address: u16, program: &[u8]) -> Ram {
+        let mut ram = Ram::from_data(vec![0; 0x1000000]).with_access_log();
+        ram.load(address as u32, program);
+        ram.load(0xFFFC, &address.to_le_bytes());
+        ram
+    }
+
+    /// Runs the RESET sequence, and returns the number of cycles it took.
+    fn reset(cpu: &mut W65C816, ram: &mut Ram) -> u32 {
+        let cycles = cpu.step_instruction(ram);
+        ram.accesses.clear();
+        cycles
+    }
+
+    /// Runs a program from $8000 until it reaches STP.
+    fn run(program: &[u8]) -> (W65C816, Ram) {
+        let mut cpu = W65C816::new();
+        let mut ram = ram_with_program(0x8000, program);
+        reset(&mut cpu, &mut ram);
+        for _ in 0..10_000 {
+            if cpu.is_halted() {
+                return (cpu, ram);
+            }
+            cpu.step_instruction(&mut ram);
+        }
+        panic!("Program didn't finish");
+    }
+
+    const CLC: u8 = 0x18;
+    const XCE: u8 = 0xFB;
+    const STP: u8 = 0xDB;
+
+    #[test]
+    fn reset_enters_emulation_mode() {
+        let mut cpu = W65C816::new();
+        let mut ram = ram_with_program(0x1234, &[STP]);
+
+        assert_eq!(reset(&mut cpu, &mut ram), 7);
+        assert!(cpu.e());
+        assert!(cpu.p.m && cpu.p.x && cpu.p.i);
+        assert_eq!(cpu.pc, 0x1234);
+        assert_eq!(cpu.s & 0xFF00, 0x0100);
+    }
+
+    #[test]
+    fn xce_switches_modes() {
+        // CLC; XCE; REP #$30; LDA #$1234; LDX #$5678; SEC; XCE; STP
+        let (cpu, _) = run(&[CLC, XCE, 0xC2, 0x30, 0xA9, 0x34, 0x12, 0xA2, 0x78, 0x56, 0x38, XCE, STP]);
+
+        // Going back to emulation mode forces 8-bit registers, and clears the high byte of X.
+        assert!(cpu.e());
+        assert!(cpu.p.m && cpu.p.x);
+        assert!(!cpu.p.c);
+        assert_eq!(cpu.a, 0x1234);
+        assert_eq!(cpu.x, 0x0078);
+    }
+
+    #[test]
+    fn m_and_x_flags_are_fixed_in_emulation_mode() {
+        // REP #$30; STP
+        let (cpu, _) = run(&[0xC2, 0x30, STP]);
+
+        assert!(cpu.p.m && cpu.p.x);
+    }
+
+    #[test]
+    fn sixteen_bit_accumulator() {
+        // CLC; XCE; REP #$20; LDA #$12FF; CLC; ADC #$0001; STA $2000; STP
+        let (cpu, ram) = run(&[CLC, XCE, 0xC2, 0x20, 0xA9, 0xFF, 0x12, CLC, 0x69, 0x01, 0x00, 0x8D, 0x00, 0x20, STP]);
+
+        assert_eq!(cpu.a, 0x1300);
+        assert_eq!(&ram.data[0x2000..0x2002], &[0x00, 0x13]);
+    }
+
+    #[test]
+    fn eight_bit_accumulator_keeps_high_byte() {
+        // CLC; XCE; REP #$20; LDA #$1234; SEP #$20; LDA #$FF; INC A; XBA; STP
+        let (cpu, _) = run(&[CLC, XCE, 0xC2, 0x20, 0xA9, 0x34, 0x12, 0xE2, 0x20, 0xA9, 0xFF, 0x1A, 0xEB, STP]);
+
+        // INC A wraps within the low byte, and XBA sets the flags from the new low byte.
+        assert_eq!(cpu.a, 0x0012);
+        assert!(!cpu.p.z);
+    }
+
+    #[test]
+    fn sixteen_bit_index_registers() {
+        // CLC; XCE; REP #$10; LDX #$FFFF; INX; LDY #$8000; STP
+        let (cpu, _) = run(&[CLC, XCE, 0xC2, 0x10, 0xA2, 0xFF, 0xFF, 0xE8, 0xA0, 0x00, 0x80, STP]);
+
+        assert_eq!(cpu.x, 0x0000);
+        assert_eq!(cpu.y, 0x8000);
+        assert!(cpu.p.n);
+    }
+
+    #[test]
+    fn sixteen_bit_read_modify_write() {
+        // CLC; XCE; REP #$20; ASL $2000; STP
+        let mut ram = ram_with_program(0x8000, &[CLC, XCE, 0xC2, 0x20, 0x0E, 0x00, 0x20, STP]);
+        ram.load(0x2000, &[0x01, 0x80]);
+        let mut cpu = W65C816::new();
+        reset(&mut cpu, &mut ram);
+        for _ in 0..3 {
+            cpu.step_instruction(&mut ram);
+        }
+        ram.accesses.clear();
+
+        assert_eq!(cpu.step_instruction(&mut ram), 8);
+        assert_eq!(&ram.data[0x2000..0x2002], &[0x02, 0x00]);
+        assert!(cpu.p.c);
+
+        // Read low, read high, write high, write low.
+        assert_eq!(&ram.accesses[2..6], &[Access::Read(0x2000), Access::Read(0x2001), Access::Write(0x2001, 0x00), Access::Write(0x2000, 0x02)]);
+    }
+
+    #[test]
+    fn long_addressing() {
+        // LDA #$42; STA $123456; LDA #$00; LDA $123456,X (X = 1); STP
+        let mut ram = ram_with_program(0x8000, &[0xA9, 0x42, 0x8F, 0x56, 0x34, 0x12, 0xA2, 0x01, 0xBF, 0x55, 0x34, 0x12, STP]);
+        let mut cpu = W65C816::new();
+        reset(&mut cpu, &mut ram);
+        while !cpu.is_halted() {
+            cpu.step_instruction(&mut ram);
+        }
+
+        assert_eq!(ram.data[0x123456], 0x42);
+        assert_eq!(cpu.a, 0x42);
+        assert!(ram.accesses.contains(&Access::Write(0x123456, 0x42)));
+    }
+
+    #[test]
+    fn bank_address_is_on_data_pins_while_phi2_is_low() {
+        // JML $7E1000; then STP in bank $7E
+        let mut ram = ram_with_program(0x8000, &[0x5C, 0x00, 0x10, 0x7E]);
+        ram.load(0x7E1000, &[STP]);
+        let mut cpu = W65C816::new();
+        reset(&mut cpu, &mut ram);
+        for _ in 0..3 {
+            cpu.step_cycle(&mut ram);
+        }
+
+        // The opcode fetch from bank $7E has been set up, but not run.
+        cpu.set_phi2(false);
+        assert_eq!(cpu.data(), 0x7E);
+        assert_eq!(cpu.address(), 0x1000);
+        assert!(cpu.vda() && cpu.vpa());
+        cpu.set_phi2(true);
+    }
+
+    #[test]
+    fn direct_page() {
+        // CLC; XCE; REP #$20; LDA #$1234; TCD; SEP #$20; LDA #$99; STA $10; LDX #$02; STA ($20,X); STP
+        let mut ram = ram_with_program(0x8000, &[
+            CLC, XCE, 0xC2, 0x20, 0xA9, 0x34, 0x12, 0x5B, 0xE2, 0x20,
+            0xA9, 0x99, 0x85, 0x10, 0xA2, 0x02, 0x81, 0x20, STP]);
+        ram.load(0x1256, &[0x00, 0x30]);
+        let mut cpu = W65C816::new();
+        reset(&mut cpu, &mut ram);
+        let mut cycles = Vec::new();
+        while !cpu.is_halted() {
+            cycles.push(cpu.step_instruction(&mut ram));
+        }
+
+        assert_eq!(ram.data[0x1244], 0x99);
+        assert_eq!(ram.data[0x3000], 0x99);
+
+        // The direct page isn't aligned, so STA d and STA (d,x) take an extra cycle.
+        assert_eq!(cycles[7], 4);
+        assert_eq!(cycles[9], 7);
+    }
+
+    #[test]
+    fn emulation_mode_wraps_direct_page() {
+        // LDX #$10; LDA $F8,X; STP, with the value at $0008 rather than $0108.
+        let mut ram = ram_with_program(0x8000, &[0xA2, 0x10, 0xB5, 0xF8, STP]);
+        ram.load(0x0008, &[0x55]);
+        ram.load(0x0108, &[0xAA]);
+        let mut cpu = W65C816::new();
+        reset(&mut cpu, &mut ram);
+        while !cpu.is_halted() {
+            cpu.step_instruction(&mut ram);
+        }
+
+        assert_eq!(cpu.a, 0x55);
+    }
+
+    #[test]
+    fn stack_relative() {
+        // CLC; XCE; REP #$30; PEA $1234; PEA $5678; LDA $03,S; LDY #$0000; LDA ($01,S),Y; STP
+        let mut ram = ram_with_program(0x8000, &[
+            CLC, XCE, 0xC2, 0x30, 0xF4, 0x34, 0x12, 0xF4, 0x78, 0x56,
+            0xA3, 0x03, 0xAA, 0xA0, 0x00, 0x00, 0xB3, 0x01, STP]);
+        ram.load(0x5678, &[0xCD, 0xAB]);
+        let mut cpu = W65C816::new();
+        reset(&mut cpu, &mut ram);
+        while !cpu.is_halted() {
+            cpu.step_instruction(&mut ram);
+        }
+
+        assert_eq!(cpu.x, 0x1234);
+        assert_eq!(cpu.a, 0xABCD);
+    }
+
+    #[test]
+    fn jsl_and_rtl() {
+        // CLC; XCE; JSL $028000; STP, and LDA #$77; RTL in bank 2.
+        let mut ram = ram_with_program(0x8000, &[CLC, XCE, 0x22, 0x00, 0x80, 0x02, STP]);
+        ram.load(0x028000, &[0xA9, 0x77, 0x6B]);
+        let mut cpu = W65C816::new();
+        reset(&mut cpu, &mut ram);
+        let mut cycles = Vec::new();
+        while !cpu.is_halted() {
+            cycles.push(cpu.step_instruction(&mut ram));
+        }
+
+        assert_eq!(cpu.a, 0x77);
+        assert_eq!(cpu.pbr, 0);
+        assert_eq!(cycles, vec![2, 2, 8, 2, 6, 2]);
+    }
+
+    #[test]
+    fn native_brk_pushes_program_bank() {
+        // CLC; XCE; JML $038000, with BRK there, and the native BRK vector pointing to STP.
+        let mut ram = ram_with_program(0x8000, &[CLC, XCE, 0x5C, 0x00, 0x80, 0x03]);
+        ram.load(0x038000, &[0x00, 0xEA]);
+        ram.load(0xFFE6, &[0x00, 0x90]);
+        ram.load(0x9000, &[STP]);
+        let mut cpu = W65C816::new();
+        reset(&mut cpu, &mut ram);
+        for _ in 0..3 {
+            cpu.step_instruction(&mut ram);
+        }
+
+        assert_eq!(cpu.step_instruction(&mut ram), 8);
+        assert_eq!(cpu.pc, 0x9000);
+        assert_eq!(cpu.pbr, 0);
+        let s = cpu.s as usize;
+        assert_eq!(&ram.data[s + 1..s + 5], &[0x35, 0x02, 0x80, 0x03]);
+    }
+
+    #[test]
+    fn emulation_irq_uses_6502_vector() {
+        // CLI; NOP; NOP...
+        let mut ram = ram_with_program(0x8000, &[0x58, 0xEA, 0xEA, 0xEA, 0xEA]);
+        ram.load(0xFFFE, &[0x00, 0x90]);
+        ram.load(0x9000, &[STP]);
+        let mut cpu = W65C816::new();
+        reset(&mut cpu, &mut ram);
+        cpu.set_irq(false);
+        while !cpu.is_halted() {
+            cpu.step_instruction(&mut ram);
+        }
+
+        // The pushed status has the B flag clear.
+        let s = cpu.s as usize;
+        assert_eq!(ram.data[s + 1] & 0x10, 0);
+        assert_eq!(ram.data[s + 3], 0x80);
+    }
+
+    #[test]
+    fn block_move() {
+        // CLC; XCE; REP #$30; LDA #$0003; LDX #$1000; LDY #$2000; MVN $05,$04; STP
+        let mut ram = ram_with_program(0x8000, &[
+            CLC, XCE, 0xC2, 0x30, 0xA9, 0x03, 0x00, 0xA2, 0x00, 0x10, 0xA0, 0x00, 0x20,
+            0x54, 0x05, 0x04, STP]);
+        ram.load(0x041000, &[1, 2, 3, 4, 5]);
+        let mut cpu = W65C816::new();
+        reset(&mut cpu, &mut ram);
+        let mut cycles = Vec::new();
+        while !cpu.is_halted() {
+            cycles.push(cpu.step_instruction(&mut ram));
+        }
+
+        assert_eq!(&ram.data[0x052000..0x052005], &[1, 2, 3, 4, 0]);
+        assert_eq!(cpu.a, 0xFFFF);
+        assert_eq!(cpu.x, 0x1004);
+        assert_eq!(cpu.y, 0x2004);
+        assert_eq!(cpu.dbr, 0x05);
+        assert_eq!(&cycles[6..10], &[7, 7, 7, 7]);
+    }
+
+    #[test]
+    fn decimal_mode() {
+        // CLC; XCE; REP #$20; SED; LDA #$1999; CLC; ADC #$0001; STA $2000; SEC; SBC #$0001; STP
+        let (cpu, ram) = run(&[
+            CLC, XCE, 0xC2, 0x20, 0xF8, 0xA9, 0x99, 0x19, CLC, 0x69, 0x01, 0x00,
+            0x8D, 0x00, 0x20, 0x38, 0xE9, 0x01, 0x00, STP]);
+
+        assert_eq!(&ram.data[0x2000..0x2002], &[0x00, 0x20]);
+        assert_eq!(cpu.a, 0x1999);
+        assert!(cpu.p.c);
+    }
+
+    /// Cycle counts from table 5-7 of the W65C816S data sheet, in native mode
+    /// with 8-bit registers and an aligned direct page.
+    #[test_case(0x03, 4; "ORA d,s")]
+    #[test_case(0x07, 6; "ORA [d]")]
+    #[test_case(0x13, 7; "ORA (d,s),y")]
+    #[test_case(0x17, 6; "ORA [d],y")]
+    #[test_case(0x0F, 5; "ORA al")]
+    #[test_case(0x1F, 5; "ORA al,x")]
+    #[test_case(0x04, 5; "TSB d")]
+    #[test_case(0x9E, 5; "STZ a,x")]
+    #[test_case(0x0B, 4; "PHD")]
+    #[test_case(0x2B, 5; "PLD")]
+    #[test_case(0x8B, 3; "PHB")]
+    #[test_case(0xAB, 4; "PLB")]
+    #[test_case(0x4B, 3; "PHK")]
+    #[test_case(0xF4, 5; "PEA")]
+    #[test_case(0xD4, 6; "PEI")]
+    #[test_case(0x62, 6; "PER")]
+    #[test_case(0x82, 4; "BRL")]
+    #[test_case(0x1B, 2; "TCS")]
+    #[test_case(0x5B, 2; "TCD")]
+    #[test_case(0x9B, 2; "TXY")]
+    #[test_case(0xEB, 3; "XBA")]
+    #[test_case(0xC2, 3; "REP")]
+    #[test_case(0x42, 2; "WDM")]
+    #[test_case(0x7C, 6; "JMP (a,x)")]
+    #[test_case(0xDC, 6; "JML [a]")]
+    #[test_case(0xFC, 8; "JSR (a,x)")]
+    #[test_case(0x22, 8; "JSL")]
+    #[test_case(0x6B, 6; "RTL")]
+    #[test_case(0x40, 7; "RTI")]
+    #[test_case(0x02, 8; "COP")]
+    #[allow(clippy::unused_unit)]
+    fn native_cycle_counts(opcode: u8, expected_cycles: u32) {
+        let mut ram = ram_with_program(0x8000, &[CLC, XCE, opcode, 0x00, 0x00, 0x00]);
+        let mut cpu = W65C816::new();
+        reset(&mut cpu, &mut ram);
+        cpu.step_instruction(&mut ram);
+        cpu.step_instruction(&mut ram);
+
+        assert_eq!(cpu.step_instruction(&mut ram), expected_cycles);
+    }
+
+    #[test]
+    fn branch_page_crossing_only_costs_a_cycle_in_emulation_mode() {
+        // BRA to the next page, in emulation mode and then native mode.
+        let mut ram = ram_with_program(0x80FC, &[CLC, 0x80, 0x02]);
+        ram.load(0x8101, &[XCE, 0x80, 0xFB]);
+        ram.load(0x80FF, &[STP]);
+        let mut cpu = W65C816::new();
+        reset(&mut cpu, &mut ram);
+        let mut cycles = Vec::new();
+        while !cpu.is_halted() {
+            cycles.push(cpu.step_instruction(&mut ram));
+        }
+
+        assert_eq!(cycles, vec![2, 4, 2, 3, 2]);
+    }
+
+    #[test]
+    fn indexed_read_cycles() {
+        // LDA $20F0,X with X = $0F and $10, then with 16-bit X.
+        let program = [0xA2, 0x0F, 0xBD, 0xF0, 0x20, 0xA2, 0x10, 0xBD, 0xF0, 0x20,
+                       CLC, XCE, 0xC2, 0x10, 0xA2, 0x00, 0x00, 0xBD, 0xF0, 0x20, STP];
+        let mut ram = ram_with_program(0x8000, &program);
+        let mut cpu = W65C816::new();
+        reset(&mut cpu, &mut ram);
+        let mut cycles = Vec::new();
+        while !cpu.is_halted() {
+            cycles.push(cpu.step_instruction(&mut ram));
+        }
+
+        assert_eq!(cycles, vec![2, 4, 2, 5, 2, 2, 3, 3, 5, 2]);
+    }
+
+    #[test]
+    fn rdy_pauses_read_cycles() {
+        let mut ram = ram_with_program(0x8000, &[0xA9, 0x42, STP]);
+        let mut cpu = W65C816::new();
+        reset(&mut cpu, &mut ram);
+
+        cpu.set_rdy(false);
+        for _ in 0..5 {
+            cpu.step_cycle(&mut ram);
+        }
+        assert_eq!(cpu.pc, 0x8000);
+
+        cpu.set_rdy(true);
+        cpu.step_instruction(&mut ram);
+        assert_eq!(cpu.a, 0x42);
+    }
+
+    #[test]
+    fn wai_waits_for_interrupt() {
+        // SEI; WAI; LDA #$42; STP
+        let mut ram = ram_with_program(0x8000, &[0x78, 0xCB, 0xA9, 0x42, STP]);
+        let mut cpu = W65C816::new();
+        reset(&mut cpu, &mut ram);
+        cpu.step_instruction(&mut ram);
+        cpu.step_instruction(&mut ram);
+        assert!(cpu.is_halted());
+
+        for _ in 0..10 {
+            cpu.step_cycle(&mut ram);
+        }
+        assert!(cpu.is_halted());
+
+        // A masked IRQ ends the wait without being taken.
+        cpu.set_irq(false);
+        while cpu.a != 0x42 {
+            cpu.step_instruction(&mut ram);
+        }
+        assert_eq!(cpu.pc, 0x8004);
+    }
+
+    #[test]
+    fn read_modify_write_locks_the_bus() {
+        // INC $2000 in emulation mode writes the old value back.
+        let mut ram = ram_with_program(0x8000, &[0xEE, 0x00, 0x20, STP]);
+        let mut cpu = W65C816::new();
+        reset(&mut cpu, &mut ram);
+
+        let mut locked = Vec::new();
+        for _ in 0..6 {
+            cpu.step_cycle(&mut ram);
+            locked.push(!cpu.mlb());
+        }
+
+        assert_eq!(locked, vec![false, false, true, true, true, false]);
+        assert_eq!(&ram.accesses[2..5], &[Access::Read(0x2000), Access::Write(0x2000, 0x00), Access::Write(0x2000, 0x01)]);
+    }
+}
@@ -0,0 +1,90 @@
+pub struct StatusRegister {
+    /// Carry
+    pub(crate) c: bool,
+
+    /// Zero
+    pub(crate) z: bool,
+
+    /// Interrupt disable
+    pub(crate) i: bool,
+
+    /// Binary coded decimal
+    pub(crate) d: bool,
+
+    /// Index register select (8 bits when set). Always set in emulation mode,
+    /// where this bit is pushed as the 6502's B flag.
+    pub(crate) x: bool,
+
+    /// Memory and accumulator select (8 bits when set). Always set in emulation mode.
+    pub(crate) m: bool,
+
+    /// Overflow
+    pub(crate) v: bool,
+
+    /// Negative
+    pub(crate) n: bool,
+}
+
+impl StatusRegister {
+    pub(crate) fn new() -> StatusRegister {
+        StatusRegister {
+            c: false,
+            z: false,
+            i: true,
+            d: false,
+            x: true,
+            m: true,
+            v: false,
+            n: false,
+        }
+    }
+
+    pub(crate) fn set_from_u8(&mut self, value: u8) {
+        self.c = (value & 0x01) == 0x01;
+        self.z = (value & 0x02) == 0x02;
+        self.i = (value & 0x04) == 0x04;
+        self.d = (value & 0x08) == 0x08;
+        self.x = (value & 0x10) == 0x10;
+        self.m = (value & 0x20) == 0x20;
+        self.v = (value & 0x40) == 0x40;
+        self.n = (value & 0x80) == 0x80;
+    }
+
+    /// Sets N and Z from the low 8 or 16 bits of a result, and returns those bits.
+    pub(crate) fn set_zero_negative_flags(&mut self, value: u16, sixteen_bit: bool) -> u16 {
+        let (value, sign) = if sixteen_bit { (value, 0x8000) } else { (value & 0xFF, 0x80) };
+        self.z = value == 0;
+        self.n = (value & sign) == sign;
+
+        value
+    }
+
+    pub(crate) fn as_u8(&self) -> u8 {
+        let mut result = 0;
+        if self.c {
+            result |= 0x01;
+        }
+        if self.z {
+            result |= 0x02;
+        }
+        if self.i {
+            result |= 0x04;
+        }
+        if self.d {
+            result |= 0x08;
+        }
+        if self.x {
+            result |= 0x10;
+        }
+        if self.m {
+            result |= 0x20;
+        }
+        if self.v {
+            result |= 0x40;
+        }
+        if self.n {
+            result |= 0x80;
+        }
+        result
+    }
+}
@@ -2,14 +2,33 @@ pub mod cartridge;
 mod tia;
 mod palette;
 
+use std::io;
+
 use crate::util::Bit;
-use crate::chips::{m6507::M6507, m6532::M6532};
+use crate::chips::{m6502::{Finding, Sanitizer, SanitizerOptions, Tracer}, m6507::M6507, m6532::M6532};
 use cartridge::Cartridge;
 use tia::TIA;
 
 pub const WIDTH: usize = 160;
 pub const HEIGHT: usize = 192;
 
+/// Options for a [`Sanitizer`] that knows the 2600's memory map. The 128 bytes of RAM in the RIOT
+/// also appear at $0180, where the stack uses them, and the cartridge ROM is at $1000.
+pub fn sanitizer_options() -> SanitizerOptions {
+    SanitizerOptions {
+        ram: vec![0x0080..=0x00FF],
+        rom: vec![0x1000..=0x1FFF],
+        mirror: |address| {
+            let address = address & 0x1FFF;
+            if address & 0x1280 == 0x0080 {
+                0x0080 | (address & 0x7F)
+            } else {
+                address
+            }
+        },
+    }
+}
+
 pub struct Atari2600 {
     cpu: M6507,
     riot: M6532,
@@ -22,6 +41,9 @@ pub struct Atari2600 {
     sync_counter: usize,
     current_scanline: usize,
     current_pos: usize,
+
+    tracer: Option<Tracer<Box<dyn io::Write>>>,
+    sanitizer: Option<Sanitizer>,
 }
 
 impl Atari2600 {
@@ -37,9 +59,28 @@ impl Atari2600 {
             sync_counter: 0,
             current_scanline: 0,
             current_pos: 0,
+
+            tracer: None,
+            sanitizer: None,
         }
     }
 
+    /// Traces every CPU cycle from now on, or stops tracing if `tracer` is `None`.
+    pub fn set_tracer(&mut self, tracer: Option<Tracer<Box<dyn io::Write>>>) {
+        self.tracer = tracer;
+    }
+
+    /// Checks every CPU cycle with the sanitizer from now on, or stops checking if `sanitizer` is `None`.
+    /// See [`sanitizer_options`].
+    pub fn set_sanitizer(&mut self, sanitizer: Option<Sanitizer>) {
+        self.sanitizer = sanitizer;
+    }
+
+    /// Returns what the sanitizer has found since this was last called.
+    pub fn take_sanitizer_findings(&mut self) -> Vec<Finding> {
+        self.sanitizer.as_mut().map_or(vec![], |sanitizer| sanitizer.take_findings())
+    }
+
     pub fn insert_cartridge(&mut self, cartridge: Box<dyn Cartridge>) {
         self.cartridge = Some(cartridge);
     }
@@ -69,6 +110,9 @@ impl Atari2600 {
         // TODO: Optimise this. If PHI0 didn't chance during last OSC edge,
         // then nothing else relevant to a CPU tick will have changed either.
 
+        // The CPU starts a new cycle when PHI0 goes low.
+        let cycle_started = self.cpu.pin_phi2() && !self.tia.pin_phi0();
+
         self.cpu.set_pin_rdy(self.tia.pin_rdy());
         self.cpu.set_pin_phi0(self.tia.pin_phi0());
 
@@ -119,6 +163,14 @@ impl Atari2600 {
             }
             // TODO: Write to cartridge?
         }
+
+        if let (true, Some(tracer)) = (cycle_started, &mut self.tracer) {
+            self.cpu.trace(tracer).expect("Failed to write CPU trace");
+        }
+
+        if let (true, Some(sanitizer)) = (cycle_started, &mut self.sanitizer) {
+            self.cpu.sanitize(sanitizer);
+        }
     }
 
     // Based on https://github.com/SavourySnaX/EDL/blob/a6a19f9db0a939230458d36bfe2715466cfad5d2/examples/2600/2600.c#L824
@@ -153,10 +205,17 @@ impl Atari2600 {
     }
 }
 
+impl Default for Atari2600 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::{fs, path::Path};
-    use super::Atari2600;
+    use crate::chips::m6502::{assemble, Sanitizer, Violation};
+    use super::{sanitizer_options, Atari2600};
     use super::cartridge::Cartridge;
 
     #[test]
@@ -175,4 +234,40 @@ mod tests {
             system.tick();
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn sanitizer_sees_memory_map() {
+        let program = assemble("
+                .org $F800
+                SEI
+                LDA #$00
+                STA $01FF
+                LDA $FF
+                LDA $FE
+                STA $1000
+            loop:
+                JMP loop
+
+                .org $FFFC
+                .word $F800
+        ").unwrap();
+        let mut memory = vec![0; 0x10000];
+        program.load_into(&mut memory);
+
+        let mut system = Atari2600::new();
+        system.insert_cartridge(<dyn Cartridge>::from_data(memory[0xF800..].to_vec()));
+        system.set_sanitizer(Some(Sanitizer::new_with_options(sanitizer_options())));
+        system.reset();
+
+        for _ in 0..1000 {
+            system.tick();
+        }
+
+        // The write to $01FF initialised $FF, but nothing wrote $FE.
+        let violations: Vec<_> = system.take_sanitizer_findings().iter().map(|finding| finding.violation).collect();
+        assert_eq!(vec![
+            Violation::UninitialisedRead { address: 0x00FE },
+            Violation::RomWrite { address: 0x1000, value: 0x00 },
+        ], violations);
+    }
+}
@@ -1,4 +1,6 @@
-use crate::chips::{m6502, /*m6522, */ m6845, saa5050};
+use std::io;
+
+use crate::chips::{m6502, m6522, m6845, saa5050};
 
 mod video_ula;
 
@@ -6,6 +8,10 @@ pub struct BBCMicro {
     ram: [u8; 0x8000],
 
     cpu: m6502::M6502,
+    cpu_execution_mode: m6502::ExecutionMode,
+
+    /// In `ExecutionMode::Instruction`, the number of cycles left before the next instruction runs.
+    cpu_cycles_remaining: u32,
 
     crtc: m6845::M6845,
 
@@ -13,14 +19,31 @@ pub struct BBCMicro {
 
     teletext: saa5050::SAA5050,
 
-    // system_via: m6522::M6522,
-    // user_via: m6522::M6522,
-    
+    system_via: m6522::M6522,
+    user_via: m6522::M6522,
+
+    /// A VIA access that is waiting for the VIAs' 1MHz clock. The CPU is stalled until it has happened.
+    via_access: Option<ViaAccess>,
+
+    /// In `ExecutionMode::Instruction`, VIA accesses clock the VIAs as soon as they are made.
+    /// This many of the following 1MHz edges are skipped to make up for it.
+    via_clocks_ahead: u32,
+
+    /// The next CPU cycle is the second of the two in the current 1MHz cycle.
+    cpu_second_half: bool,
+
+    /// 16MHz ticks since the last 1MHz tick, for generating the teletext chip's TR6 clock.
+    teletext_clock_phase: u8,
+
     os_rom: Vec<u8>,
     basic_rom: Vec<u8>,
 
     // For debugging.
     clock_counter: u64,
+    tracer: Option<m6502::Tracer<Box<dyn io::Write>>>,
+    debugger: Option<m6502::Debugger>,
+    stop_reason: Option<m6502::StopReason>,
+    journal: Option<m6502::Journal>,
 }
 
 impl BBCMicro {
@@ -30,32 +53,131 @@ impl BBCMicro {
         let ram = [0; 0x8000];
 
         let cpu = m6502::M6502::new_with_options(m6502::M6502Options {
-            bcd_enabled: true
+            bcd_enabled: true,
+            variant: m6502::M6502Variant::Nmos6502,
         });
 
-        let crtc = m6845::M6845::new();
+        let crtc = m6845::M6845::new_with_options(m6845::M6845Options {
+            variant: m6845::M6845Variant::Hd6845s,
+        });
 
         let video_ula = video_ula::VideoULA::new();
 
         let teletext = saa5050::SAA5050::new();
 
-        // let system_via = m6522::M6522::new();
-        // let user_via = m6522::M6522::new();
+        let system_via = m6522::M6522::new();
+        let user_via = m6522::M6522::new();
 
         Self {
             ram,
             cpu,
+            cpu_execution_mode: m6502::ExecutionMode::Cycle,
+            cpu_cycles_remaining: 0,
             crtc,
             video_ula,
             teletext,
-            // system_via,
-            // user_via,
+            system_via,
+            user_via,
+            via_access: None,
+            via_clocks_ahead: 0,
+            cpu_second_half: false,
             os_rom,
             basic_rom,
             clock_counter: 0,
+            teletext_clock_phase: 0,
+            tracer: None,
+            debugger: None,
+            stop_reason: None,
+            journal: None,
         }
     }
 
+    /// In `ExecutionMode::Instruction`, each CPU instruction runs all at once on its first cycle,
+    /// and the CPU then sits out its remaining cycles. The tracer and debugger only see the CPU
+    /// between instructions.
+    pub fn set_cpu_execution_mode(&mut self, mode: m6502::ExecutionMode) {
+        self.cpu_execution_mode = mode;
+    }
+
+    /// Traces every CPU cycle from now on, or stops tracing if `tracer` is `None`.
+    pub fn set_tracer(&mut self, tracer: Option<m6502::Tracer<Box<dyn io::Write>>>) {
+        self.tracer = tracer;
+    }
+
+    /// Checks the debugger's breakpoints and watchpoints after every CPU cycle.
+    pub fn set_debugger(&mut self, debugger: Option<m6502::Debugger>) {
+        self.debugger = debugger;
+    }
+
+    /// Returns why the debugger asked to stop, if it has since this was last called.
+    /// Callers should check this after each tick and stop ticking if it returns a reason.
+    pub fn take_stop_reason(&mut self) -> Option<m6502::StopReason> {
+        self.stop_reason.take()
+    }
+
+    /// Records every CPU instruction from now on, so that they can be undone, or stops if `journal` is `None`.
+    /// The journal's RAM should be $0000-$7FFF, so that stepping back doesn't write to the I/O registers.
+    pub fn set_journal(&mut self, journal: Option<m6502::Journal>) {
+        self.journal = journal;
+    }
+
+    /// The journal, for finding out which instruction last wrote an address.
+    pub fn journal(&self) -> Option<&m6502::Journal> {
+        self.journal.as_ref()
+    }
+
+    /// Puts the CPU and RAM back to how they were at the start of the previous instruction.
+    /// The rest of the system isn't rewound. Returns false if there is no journal, or nothing left in it.
+    pub fn step_back(&mut self) -> bool {
+        let Some(journal) = &mut self.journal else {
+            return false;
+        };
+
+        self.cpu_cycles_remaining = 0;
+        self.via_access = None;
+        let data_bus = self.cpu.data();
+        journal.step_back(&mut self.cpu, &mut CpuBus {
+            ram: &mut self.ram,
+            crtc: &mut self.crtc,
+            video_ula: &mut self.video_ula,
+            system_via: &mut self.system_via,
+            user_via: &mut self.user_via,
+            via_access: &mut self.via_access,
+            via_clocks_ahead: &mut self.via_clocks_ahead,
+            latch_via_accesses: false,
+            cpu_second_half: self.cpu_second_half,
+            via_accesses: 0,
+            data_bus,
+            os_rom: &self.os_rom,
+            basic_rom: &self.basic_rom,
+        })
+    }
+
+    /// Steps back until the most recent write to the address has been undone. Returns that write,
+    /// which says which instruction made it.
+    pub fn run_back_to_write(&mut self, address: u16) -> Option<m6502::WriteRecord> {
+        let journal = self.journal.as_mut()?;
+
+        self.cpu_cycles_remaining = 0;
+        self.via_access = None;
+        let data_bus = self.cpu.data();
+        journal.run_back_to_write(&mut self.cpu, &mut CpuBus {
+            ram: &mut self.ram,
+            crtc: &mut self.crtc,
+            video_ula: &mut self.video_ula,
+            system_via: &mut self.system_via,
+            user_via: &mut self.user_via,
+            via_access: &mut self.via_access,
+            via_clocks_ahead: &mut self.via_clocks_ahead,
+            latch_via_accesses: false,
+            cpu_second_half: self.cpu_second_half,
+            via_accesses: 0,
+            data_bus,
+            os_rom: &self.os_rom,
+            basic_rom: &self.basic_rom,
+        }, address)
+    }
+
     /// Called at 16MHz.
     pub fn tick(&mut self) {
         // Tick Video ULA at 16MHz.
@@ -70,11 +192,16 @@ impl BBCMicro {
             self.tick_cpu();
         }
 
+        if self.video_ula.pins.clk_1mhz {
+            self.tick_vias();
+        }
+
         // Tick CRTC.
         // TODO: Not sure of order here, should CRTC and SAA5050 be ticked before Video ULA?
         self.crtc.pins.clk = self.video_ula.pins.crtc_clk;
         self.crtc.tick();
         self.crtc.pins.clk = false;
+        self.system_via.set_ca1(self.crtc.pins.vs);
         //println!("CRTC ma ${:04X} ra ${:02X}", self.crtc_pins.ma, self.crtc_pins.ra);
 
         // Only read screen memory if CRTC clock pin is active.
@@ -90,7 +217,10 @@ impl BBCMicro {
         self.teletext.pins.crs = (self.crtc.pins.ra & 1) == 1;
         self.teletext.pins.lose = self.crtc.pins.disptmg;
         self.teletext.pins.f1 = self.video_ula.pins.clk_1mhz;
-        self.teletext.pins.tr6 = self.clock_counter % 6 == 0; // Hacky way to generate 6MHz clock.
+        // TR6 should be a 6MHz clock, but we only tick at 16MHz, so approximate it with 12 edges
+        // per microsecond, spread as evenly as 16MHz allows.
+        self.teletext_clock_phase = if self.video_ula.pins.clk_1mhz { 0 } else { (self.teletext_clock_phase + 1) % 16 };
+        self.teletext.pins.tr6 = (self.teletext_clock_phase * 3 / 4) & 1 == 1;
         self.teletext.tick();
 
         // TODO: Do something with Video ULA's RGB output.
@@ -101,105 +231,317 @@ impl BBCMicro {
 
     fn tick_cpu(&mut self) {
         self.clock_counter += 1;
-        
+
+        let second_half = self.cpu_second_half;
+        self.cpu_second_half = !second_half;
+
+        // The CPU is stretched while it waits for a VIA access.
+        if self.via_access.is_some() {
+            return;
+        }
+
+        // Both VIAs share the CPU's IRQ line.
+        self.cpu.set_irq(self.system_via.irq() && self.user_via.irq());
+
         // Tick CPU and perform requested memory reads / writes.
-        
-        // TODO: 1MHz cycle stretching.
-        self.cpu.set_phi0(true);
-        self.cpu.set_phi0(false);
 
-        // if cpu_pins.sync {
-        //     println!("{:04X}  A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} CPUC:{}", 
-        //              self.cpu.registers.pc.to_u16(),
-        //              self.cpu.registers.a,
-        //              self.cpu.registers.x,
-        //              self.cpu.registers.y,
-        //              self.cpu.registers.p.as_u8(false),
-        //              self.cpu.registers.sp,
-        //              self.cycles);
-        // }
+        let mut bus = CpuBus {
+            ram: &mut self.ram,
+            crtc: &mut self.crtc,
+            video_ula: &mut self.video_ula,
+            system_via: &mut self.system_via,
+            user_via: &mut self.user_via,
+            via_access: &mut self.via_access,
+            via_clocks_ahead: &mut self.via_clocks_ahead,
+            latch_via_accesses: self.cpu_execution_mode == m6502::ExecutionMode::Cycle,
+            cpu_second_half: second_half,
+            via_accesses: 0,
+            data_bus: self.cpu.data(),
+            os_rom: &self.os_rom,
+            basic_rom: &self.basic_rom,
+        };
+
+        let cpu_stepped = match &mut self.journal {
+            Some(journal) => step_cpu(&mut self.cpu, self.cpu_execution_mode, &mut self.cpu_cycles_remaining, &mut m6502::JournaledBus { bus: &mut bus, journal }),
+            None => step_cpu(&mut self.cpu, self.cpu_execution_mode, &mut self.cpu_cycles_remaining, &mut bus),
+        };
+
+        // A whole instruction can't wait for each of its VIA accesses, so it's stretched afterwards instead.
+        // The accesses are taken to be its last cycles, as they are for most instructions that reach the VIAs.
+        // Each one is stretched to the end of a 1MHz cycle, which is one more cycle, or two for one that
+        // starts in the second half. Read-modify-write instructions come out a microsecond short, because
+        // execute_instruction leaves out their dummy write.
+        if bus.via_accesses > 0 {
+            let first_access = self.cpu_cycles_remaining + 1 - bus.via_accesses;
+            let starts_in_second_half = second_half ^ (first_access % 2 == 1);
+            self.cpu_cycles_remaining += bus.via_accesses + starts_in_second_half as u32;
+        }
+
+        // The tracer and debugger see a VIA access once the VIA has answered it.
+        if cpu_stepped && self.via_access.is_none() {
+            self.cpu_cycle_finished();
+        }
+    }
+
+    /// Passes a CPU cycle to the tracer and debugger.
+    fn cpu_cycle_finished(&mut self) {
+        if let Some(tracer) = &mut self.tracer {
+            tracer.trace(&self.cpu).expect("Failed to write CPU trace");
+        }
+
+        if let Some(debugger) = &mut self.debugger {
+            if let Some(reason) = debugger.check(&self.cpu) {
+                self.stop_reason = Some(reason);
+            }
+        }
+    }
+
+    /// Called on the 1MHz clock. Makes the VIA access that the CPU is waiting for, if this is its edge.
+    fn tick_vias(&mut self) {
+        self.cpu_second_half = false;
+
+        if self.via_clocks_ahead > 0 {
+            self.via_clocks_ahead -= 1;
+            return;
+        }
+
+        let access = match &mut self.via_access {
+            Some(access) if access.edges_to_skip == 0 => self.via_access.take().unwrap(),
+            Some(access) => {
+                access.edges_to_skip -= 1;
+                cycle_vias(&mut self.system_via, &mut self.user_via);
+                return;
+            },
+            None => {
+                cycle_vias(&mut self.system_via, &mut self.user_via);
+                return;
+            },
+        };
+
+        let data = access_via(&mut self.system_via, &mut self.user_via, access.address, access.rw, access.data);
+        if access.rw {
+            self.cpu.set_data(data);
+        }
+        self.cpu_cycle_finished();
+    }
+}
+
+/// Runs the CPU for one cycle. Returns true if it did something that the tracer and debugger should see.
+fn step_cpu(cpu: &mut m6502::M6502, mode: m6502::ExecutionMode, cycles_remaining: &mut u32, bus: &mut impl m6502::Bus) -> bool {
+    match mode {
+        m6502::ExecutionMode::Cycle => {
+            cpu.step_cycle(bus);
+            true
+        },
+        m6502::ExecutionMode::Instruction => {
+            let instruction_started = *cycles_remaining == 0;
+            if instruction_started {
+                *cycles_remaining = cpu.execute_instruction(bus);
+            }
+            *cycles_remaining -= 1;
+            instruction_started
+        },
+    }
+}
+
+/// Runs both VIAs for one 1MHz cycle.
+fn cycle_vias(system_via: &mut m6522::M6522, user_via: &mut m6522::M6522) {
+    system_via.set_phi2(true);
+    user_via.set_phi2(true);
+    system_via.set_phi2(false);
+    user_via.set_phi2(false);
+}
+
+/// Runs both VIAs for one 1MHz cycle, in which the CPU reads or writes a register of one of them.
+/// Returns the VIA's data bus.
+fn access_via(system_via: &mut m6522::M6522, user_via: &mut m6522::M6522, address: u16, rw: bool, data: u8) -> u8 {
+    let via = if address & 0x20 == 0 { &mut *system_via } else { &mut *user_via };
+    via.set_cs(true);
+    via.set_rs((address & 0xF) as u8);
+    via.set_rw(rw);
+    via.set_d(data);
+
+    cycle_vias(system_via, user_via);
+
+    let via = if address & 0x20 == 0 { &mut *system_via } else { &mut *user_via };
+    via.set_cs(false);
+    via.d()
+}
+
+/// A read or write of a VIA register, which happens on a 1MHz clock edge.
+struct ViaAccess {
+    address: u16,
+    rw: bool,
+    data: u8,
 
-        let address = self.cpu.get_address();
+    /// Edges to let go by first. An access that starts in the second half of a 1MHz cycle
+    /// is too late for the edge at the end of it, so it waits for the next one.
+    edges_to_skip: u8,
+}
+
+/// The parts of the system that the CPU can see through its address and data buses.
+struct CpuBus<'a> {
+    ram: &'a mut [u8; 0x8000],
+    crtc: &'a mut m6845::M6845,
+    video_ula: &'a mut video_ula::VideoULA,
+    system_via: &'a mut m6522::M6522,
+    user_via: &'a mut m6522::M6522,
+    via_access: &'a mut Option<ViaAccess>,
+    via_clocks_ahead: &'a mut u32,
+
+    /// When true, VIA accesses go in `via_access`, for the CPU to wait for. Otherwise they happen straight away.
+    latch_via_accesses: bool,
+
+    /// The CPU cycle is the second of the two in a 1MHz cycle.
+    cpu_second_half: bool,
+
+    /// Number of VIA accesses that happened straight away.
+    via_accesses: u32,
+
+    /// The value left on the data bus by the previous cycle. Reads from addresses that nothing
+    /// drives see this value.
+    data_bus: u8,
+
+    os_rom: &'a [u8],
+    basic_rom: &'a [u8],
+}
+
+impl CpuBus<'_> {
+    fn access_crtc(&mut self, address: u16, rw: bool, data: u8) -> u8 {
+        self.crtc.pins.cs = true;
+        self.crtc.pins.rs = (address & 1) == 1;
+        self.crtc.pins.rw = rw;
+        self.crtc.pins.d = data;
+        self.crtc.tick();
+        self.crtc.pins.cs = false;
+        println!("CRTC rs {:05} d ${:02X} rw {}", self.crtc.pins.rs, data, self.crtc.pins.rw);
+        self.crtc.pins.d
+    }
+
+    /// The VIAs run at 1MHz, so the CPU's access waits for their clock. When it's latched, the data
+    /// that a read returns is put on the CPU's data bus once the VIA has answered.
+    fn access_via(&mut self, address: u16, rw: bool, data: u8) -> u8 {
+        if self.latch_via_accesses {
+            *self.via_access = Some(ViaAccess { address, rw, data, edges_to_skip: self.cpu_second_half as u8 });
+            return self.data_bus;
+        }
+
+        self.via_accesses += 1;
+        *self.via_clocks_ahead += 1;
+        access_via(self.system_via, self.user_via, address, rw, data)
+    }
+}
+
+impl m6502::Bus for CpuBus<'_> {
+    fn read(&mut self, address: u16) -> u8 {
+        match address {
+            // SHEILA - 6845 CRTC
+            0xFE00..=0xFE07 => self.access_crtc(address, true, 0),
+
+            // SHEILA - 6522 system VIA and user VIA
+            0xFE40..=0xFE7F => self.access_via(address, true, 0),
 
-        if self.clock_counter % 10000 == 0 {
-            println!("Mode {}", self.ram[0x355]);
+            _ => self.peek(address),
         }
+    }
 
+    fn write(&mut self, address: u16, value: u8) {
         match address {
             // RAM
-            0x0000..=0x7FFF => {
-                if self.cpu.rw {
-                    self.cpu.set_data(self.ram[address as usize]);
-                } else {
-                    self.ram[address as usize] = self.cpu.data();
-                }
-            }
+            0x0000..=0x7FFF => self.ram[address as usize] = value,
 
             // 0xFC00..=0xFCFF => 0, // FRED I/O
             // 0xFD00..=0xFDFF => 0, // JIM I/O
 
-            // SHEILA
-            0xFE00..=0xFEFF => {
-                match address as u8 {
-                    // 6845 CRTC
-                    0x00..=0x07 => {
-                        self.crtc.pins.cs = true;
-                        self.crtc.pins.rs = (address & 1) == 1;
-                        self.crtc.pins.rw = self.cpu.rw;
-                        self.crtc.pins.d = self.cpu.data();
-                        self.crtc.tick();
-                        self.crtc.pins.cs = false;
-                        println!("CRTC rs {:05} d ${:02X} rw {}", self.crtc.pins.rs, self.cpu.data(), self.crtc.pins.rw);
-                    }
-
-                    // Video ULA
-                    0x20..=0x2F => {
-                        if !self.cpu.rw {
-                            self.video_ula.pins.cs = true;
-                            self.video_ula.pins.a0 = (address & 1) == 1;
-                            self.video_ula.pins.data = self.cpu.data();
-                            self.video_ula.tick();
-                            self.video_ula.pins.cs = false;
-                            println!("Video ULA a0 {:05} d ${:02X}", self.video_ula.pins.a0, self.video_ula.pins.data);
-                        }
-                    }
-
-                    _ => {
-                       // println!("Unknown address {:04X} data {:02X}", address, cpu_pins.data);
-                    }
-                }
+            // SHEILA - 6845 CRTC
+            0xFE00..=0xFE07 => {
+                self.access_crtc(address, false, value);
             }
 
-            // Paged ROM
-            0x8000..=0xBFFF => {
-                if self.cpu.rw {
-                    self.cpu.set_data(self.basic_rom[(address - 0x8000) as usize]);
-                }
+            // SHEILA - Video ULA
+            0xFE20..=0xFE2F => {
+                self.video_ula.pins.cs = true;
+                self.video_ula.pins.a0 = (address & 1) == 1;
+                self.video_ula.pins.data = value;
+                self.video_ula.tick();
+                self.video_ula.pins.cs = false;
+                println!("Video ULA a0 {:05} d ${:02X}", self.video_ula.pins.a0, self.video_ula.pins.data);
             }
 
-            // Operating System ROM
-            0xC000..=0xFFFF => {
-                if self.cpu.rw {
-                    self.cpu.set_data(self.os_rom[(address - 0xC000) as usize]);
-                }
+            // SHEILA - 6522 system VIA and user VIA
+            0xFE40..=0xFE7F => {
+                self.access_via(address, false, value);
+            }
+
+            _ => {
+                // println!("Unknown address {:04X} data {:02X}", address, value);
             }
         }
+    }
 
-        // if cpu_pins.rw {
-        //     println!("READ      ${:04X} => ${:02X}", address, cpu_pins.data);
-        // } else {
-        //     println!("WRITE     ${:04X} <= ${:02X}", address, cpu_pins.data);
-        // }
+    fn peek(&self, address: u16) -> u8 {
+        match address {
+            // RAM
+            0x0000..=0x7FFF => self.ram[address as usize],
+
+            // Paged ROM
+            0x8000..=0xBFFF => self.basic_rom[(address - 0x8000) as usize],
+
+            // SHEILA. Nothing drives the data bus for unmapped registers, so it floats.
+            0xFE00..=0xFEFF => self.data_bus,
+
+            // Operating System ROM
+            0xC000..=0xFFFF => self.os_rom[(address - 0xC000) as usize],
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use std::{fs, path::Path};
+    use test_case::test_case;
+    use crate::chips::m6502::{assemble, ExecutionMode, Journal, JournalOptions, Program};
     use super::BBCMicro;
 
-    #[test]
-    fn boot_rom() {
+    /// Sets up a system with a program in place of the OS ROM, starting at $C000.
+    fn setup_program(program: &str, mode: ExecutionMode) -> (BBCMicro, Program) {
+        let program = assemble(&format!("
+                .org $C000
+                SEI
+                {}
+            done:
+                JMP done
+
+                .org $FFFC
+                .word $C000
+        ", program)).unwrap();
+
+        let mut memory = vec![0; 0x10000];
+        program.load_into(&mut memory);
+
+        let mut bbc_micro = BBCMicro::new(memory[0xC000..].to_vec(), vec![0; 0x4000]);
+        bbc_micro.set_cpu_execution_mode(mode);
+        bbc_micro.cpu.set_res(false);
+        bbc_micro.cpu.set_res(true);
+        (bbc_micro, program)
+    }
+
+    /// Ticks until the CPU fetches the opcode at a label, and returns the number of CPU cycles so far.
+    fn run_to_label(bbc_micro: &mut BBCMicro, program: &Program, label: &str) -> u64 {
+        let address = program.label(label).unwrap();
+        for _ in 0..100_000 {
+            bbc_micro.tick();
+            if bbc_micro.cpu.sync() && bbc_micro.cpu.get_address() == address {
+                return bbc_micro.clock_counter;
+            }
+        }
+        panic!("Didn't reach {}", label);
+    }
+
+    #[test_case(ExecutionMode::Cycle ; "cycle")]
+    #[test_case(ExecutionMode::Instruction ; "instruction")]
+    #[allow(clippy::unused_unit)]
+    fn boot_rom(mode: ExecutionMode) {
         let os_rom_path = Path::new("assets/systems/bbc_micro/roms/os.rom");
         let os_rom = fs::read(os_rom_path).unwrap();
 
@@ -207,10 +549,84 @@ mod tests {
         let basic_rom = fs::read(basic_rom_path).unwrap();
 
         let mut bbc_micro = BBCMicro::new(os_rom, basic_rom);
+        bbc_micro.set_cpu_execution_mode(mode);
 
         // TODO: Don't fix loop count.
         for _ in 0..1000000 {
             bbc_micro.tick();
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn via_accesses_are_stretched_to_1mhz() {
+        let (mut bbc_micro, program) = setup_program("
+                LDA $FE60
+            second:
+                LDA $FE60
+                LDA $FE60
+                LDA $FE60
+        ", ExecutionMode::Cycle);
+
+        // After the first access, each LDA starts at the beginning of a 1MHz cycle. So its access
+        // is in the second half of one, and waits for the end of the next: 3 + 3 cycles.
+        let start = run_to_label(&mut bbc_micro, &program, "second");
+        let end = run_to_label(&mut bbc_micro, &program, "done");
+        assert_eq!(3 * 6, end - start);
+    }
+
+    // An LDA takes 3us, because its read waits for the end of the 1MHz cycle after the one it starts in,
+    // and an INC takes 5us, because it then reads and writes the VIA in three back-to-back 1MHz cycles.
+    // The timer is read 3us into the last LDY. execute_instruction leaves out INC's dummy write,
+    // so instruction mode is only tested with one access per instruction.
+    #[test_case(ExecutionMode::Cycle, "LDA $FE60", 27 ; "cycle_lda")]
+    #[test_case(ExecutionMode::Cycle, "INC $FE60", 43 ; "cycle_inc")]
+    #[test_case(ExecutionMode::Instruction, "LDA $FE60", 27 ; "instruction_lda")]
+    #[allow(clippy::unused_unit)]
+    fn via_timers_run_at_1mhz_during_accesses(mode: ExecutionMode, access: &str, microseconds: u8) {
+        let (mut bbc_micro, program) = setup_program(&format!("
+                LDA #$FF
+                STA $FE68       ; User VIA timer 2
+                STA $FE69
+                LDX $FE68
+                {}
+                LDY $FE68
+                STX $00
+                STY $01
+        ", [access; 8].join("\n")), mode);
+
+        run_to_label(&mut bbc_micro, &program, "done");
+
+        let counted = bbc_micro.ram[0x00] - bbc_micro.ram[0x01];
+        assert!(counted.abs_diff(microseconds) <= 1, "Timer counted {}us, not {}us", counted, microseconds);
+    }
+
+    #[test_case(ExecutionMode::Cycle ; "cycle")]
+    #[test_case(ExecutionMode::Instruction ; "instruction")]
+    #[allow(clippy::unused_unit)]
+    fn journal_rewinds_cpu_and_ram(mode: ExecutionMode) {
+        let os_rom = fs::read(Path::new("assets/systems/bbc_micro/roms/os.rom")).unwrap();
+        let basic_rom = fs::read(Path::new("assets/systems/bbc_micro/roms/basic.rom")).unwrap();
+
+        let mut bbc_micro = BBCMicro::new(os_rom, basic_rom);
+        bbc_micro.set_cpu_execution_mode(mode);
+        bbc_micro.set_journal(Some(Journal::new_with_options(JournalOptions {
+            ram: vec![0x0000..=0x7FFF],
+            ..Default::default()
+        })));
+
+        for _ in 0..1000000 {
+            bbc_micro.tick();
+        }
+
+        // The stack starts at $01FF, so that has been written.
+        let record = bbc_micro.journal().unwrap().last_write(0x01FF).unwrap();
+        assert_eq!(record.write.new_value, bbc_micro.ram[0x01FF]);
+
+        assert_eq!(Some(record), bbc_micro.run_back_to_write(0x01FF));
+        assert_eq!(record.write.old_value, bbc_micro.ram[0x01FF]);
+        assert_eq!(record.pc, bbc_micro.cpu.get_address());
+
+        assert!(bbc_micro.step_back());
+        assert_ne!(record.pc, bbc_micro.cpu.get_address());
+    }
+}